@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -14,6 +17,37 @@ pub enum Error {
     #[error("\"{0}\" are not supported")]
     UnsupportedUrl(String),
 
+    #[error("{url} responded with status {status}")]
+    HttpStatus { url: String, status: u16 },
+
+    #[error("Rate limited by {url}")]
+    RateLimited {
+        url: String,
+        retry_after: Option<Duration>,
+    },
+
+    #[error("{url} not found: {message}")]
+    NotFound { url: String, message: String },
+
+    #[error("Unexpected response from {url}: {message}")]
+    UnexpectedResponse { url: String, message: String },
+
+    #[error("{url} requires authentication: {message}")]
+    AuthRequired { url: String, message: String },
+
+    #[error("{module} did not finish {operation} in {timeout:?}")]
+    ModuleTimeout {
+        module: String,
+        operation: String,
+        timeout: std::time::Duration,
+    },
+
+    #[error("{module} was terminated: {reason}")]
+    ModuleTerminated { module: String, reason: String },
+
+    #[error("Cancelled")]
+    Cancelled,
+
     #[error(transparent)]
     IOError(#[from] std::io::Error),
 
@@ -49,6 +83,14 @@ impl Error {
             UrlParseError { .. },
             UnsupportedUrl { ..},
             RequestError { .. },
+            HttpStatus { .. },
+            RateLimited { .. },
+            NotFound { .. },
+            UnexpectedResponse { .. },
+            AuthRequired { .. },
+            ModuleTimeout { .. },
+            ModuleTerminated { .. },
+            Cancelled { .. },
             ExternalError(..),
             IOError(..),
             HttpClientError(..)
@@ -56,3 +98,240 @@ impl Error {
         .to_string()
     }
 }
+
+impl Error {
+    /// Error for a response with unsuccessful `status`.
+    pub fn from_status(
+        url: impl ToString,
+        status: http::StatusCode,
+        retry_after: Option<Duration>,
+    ) -> Self {
+        let url = url.to_string();
+
+        match status {
+            http::StatusCode::NOT_FOUND | http::StatusCode::GONE => Self::NotFound {
+                url,
+                message: status.to_string(),
+            },
+            http::StatusCode::UNAUTHORIZED | http::StatusCode::FORBIDDEN => Self::AuthRequired {
+                url,
+                message: status.to_string(),
+            },
+            http::StatusCode::TOO_MANY_REQUESTS => Self::RateLimited { url, retry_after },
+            _ => Self::HttpStatus {
+                url,
+                status: status.as_u16(),
+            },
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UrlParseError { .. } | Self::UnexpectedResponse { .. } => ErrorKind::Parse,
+            Self::RequestError { .. } => ErrorKind::Network,
+            Self::UnsupportedUrl(..) | Self::Cancelled => ErrorKind::Other,
+            Self::HttpStatus { status, .. } => ErrorKind::HttpStatus { status: *status },
+            Self::RateLimited { retry_after, .. } => ErrorKind::RateLimited {
+                retry_after: retry_after.map(|it| it.as_secs()),
+            },
+            Self::NotFound { .. } => ErrorKind::NotFound,
+            Self::AuthRequired { .. } => ErrorKind::AuthRequired,
+            Self::ModuleTimeout { .. } | Self::ModuleTerminated { .. } => ErrorKind::ModuleCrash,
+            Self::IOError(..) => ErrorKind::Io,
+            Self::HttpClientError(error) => error.kind(),
+            Self::ExternalError(error) => {
+                if let Some(error) = error.downcast_ref::<Self>() {
+                    error.kind()
+                } else if let Some(error) = error.downcast_ref::<crate::http::Error>() {
+                    error.kind()
+                } else if error.is::<std::io::Error>() {
+                    ErrorKind::Io
+                } else {
+                    ErrorKind::Other
+                }
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        match self {
+            Self::Cancelled => true,
+            Self::ExternalError(error) => error
+                .downcast_ref::<Self>()
+                .map_or(false, Self::is_cancelled),
+            _ => false,
+        }
+    }
+
+    pub fn classify(&self) -> ClassifiedError {
+        ClassifiedError::new(self.kind(), self.to_string())
+    }
+}
+
+/// What went wrong, independent of where the error came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ErrorKind {
+    /// Connection failed, timed out or broke mid-transfer.
+    Network,
+    HttpStatus {
+        status: u16,
+    },
+    /// Retry after this many seconds, if the server said so.
+    #[serde(rename_all = "camelCase")]
+    RateLimited {
+        retry_after: Option<u64>,
+    },
+    NotFound,
+    /// The response doesn't look like what the module expects.
+    Parse,
+    AuthRequired,
+    /// Module panicked, timed out or was terminated.
+    ModuleCrash,
+    Io,
+    #[default]
+    Other,
+}
+
+impl ErrorKind {
+    /// Whether trying again later may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Network | Self::RateLimited { .. } | Self::ModuleCrash => true,
+            Self::HttpStatus { status } => *status >= 500 || *status == 408,
+            Self::NotFound | Self::Parse | Self::AuthRequired | Self::Io | Self::Other => false,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after } => retry_after.map(Duration::from_secs),
+            _ => None,
+        }
+    }
+
+    /// What the user can do about it.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Self::Network => "Check your internet connection and retry.",
+            Self::HttpStatus { status } if *status >= 500 => {
+                "The site is having problems, retry later."
+            }
+            Self::HttpStatus { .. } => "The site rejected the request.",
+            Self::RateLimited { .. } => "The site is limiting requests, wait before retrying.",
+            Self::NotFound => "The manga or chapter was removed or the link is wrong.",
+            Self::Parse => "The site changed its layout, the module needs an update.",
+            Self::AuthRequired => "The site requires logging in.",
+            Self::ModuleCrash => "The module stopped responding, retry or update the module.",
+            Self::Io => "Check that the download folder is writable and has free space.",
+            Self::Other => "",
+        }
+    }
+}
+
+/// Classified error that can be stored and sent across threads, keeping
+/// only the kind and the message of the original error.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Error)]
+#[error("{message}")]
+pub struct ClassifiedError {
+    #[serde(flatten)]
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl ClassifiedError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<&Error> for ClassifiedError {
+    fn from(error: &Error) -> Self {
+        error.classify()
+    }
+}
+
+impl From<Error> for ClassifiedError {
+    fn from(error: Error) -> Self {
+        error.classify()
+    }
+}
+
+impl From<String> for ClassifiedError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorKind::Other, message)
+    }
+}
+
+impl From<&str> for ClassifiedError {
+    fn from(message: &str) -> Self {
+        Self::new(ErrorKind::Other, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_status() {
+        let kind = |status: u16, retry_after| {
+            Error::from_status(
+                "https://localhost",
+                http::StatusCode::from_u16(status).unwrap(),
+                retry_after,
+            )
+            .kind()
+        };
+
+        assert_eq!(kind(404, None), ErrorKind::NotFound);
+        assert_eq!(kind(403, None), ErrorKind::AuthRequired);
+        assert_eq!(
+            kind(429, Some(Duration::from_secs(5))),
+            ErrorKind::RateLimited {
+                retry_after: Some(5)
+            }
+        );
+        assert_eq!(kind(503, None), ErrorKind::HttpStatus { status: 503 });
+
+        assert!(kind(503, None).is_retryable());
+        assert!(kind(429, None).is_retryable());
+        assert!(!kind(404, None).is_retryable());
+        assert!(!kind(400, None).is_retryable());
+    }
+
+    #[test]
+    fn external_error() {
+        let error = Error::ExternalError(anyhow::Error::from(Error::NotFound {
+            url: "https://localhost".to_string(),
+            message: "gone".to_string(),
+        }));
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+
+        let error = Error::ExternalError(anyhow::anyhow!("unknown"));
+        assert_eq!(error.kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn serialize() {
+        let error = ClassifiedError::new(
+            ErrorKind::RateLimited {
+                retry_after: Some(10),
+            },
+            "slow down",
+        );
+        let json = serde_json::to_string(&error).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"kind":"rateLimited","retryAfter":10,"message":"slow down"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ClassifiedError>(&json).unwrap(),
+            error
+        );
+    }
+}
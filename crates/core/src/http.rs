@@ -1,21 +1,77 @@
 use futures_lite::io::AsyncReadExt;
 use isahc::AsyncReadResponseExt;
 use serde::de::DeserializeOwned;
+use std::sync::{Arc, RwLock};
+
+mod cache;
+mod replay;
+pub use cache::{CacheControl, HttpCache};
+pub use replay::{Replay, ReplayMode};
 
 #[derive(Debug, Clone)]
 pub struct Client {
-    client: isahc::HttpClient,
+    /// Shared by clones, so [`Self::set_proxy`] applies to every one of them.
+    client: Arc<RwLock<isahc::HttpClient>>,
+    cache: Option<Arc<HttpCache>>,
+    replay: Option<Arc<Replay>>,
 }
 
 impl Default for Client {
     fn default() -> Self {
         Self {
-            client: isahc::HttpClientBuilder::new().build().unwrap(),
+            client: Arc::new(RwLock::new(build_client(None).unwrap())),
+            cache: None,
+            replay: None,
         }
     }
 }
 
 impl Client {
+    /// Record or replay fixtures when `MADO_HTTP_REPLAY` is set, see
+    /// [`Replay::from_env`].
+    pub fn from_env() -> Self {
+        Self::default().with_replay(Replay::from_env())
+    }
+
+    /// Serve GET requests from `cache` when possible.
+    pub fn with_cache(mut self, cache: impl Into<Option<HttpCache>>) -> Self {
+        self.cache = cache.into().map(Arc::new);
+        self
+    }
+
+    /// Send requests through `proxy`, e.g. `http://localhost:8080`, instead
+    /// of the proxy from the environment.
+    pub fn with_proxy(mut self, proxy: &str) -> Result<Self, Error> {
+        self.client = Arc::new(RwLock::new(build_client(Some(proxy))?));
+
+        Ok(self)
+    }
+
+    /// Change the proxy of this client and every clone of it, including
+    /// ones already given to modules. `None` uses the proxy from the
+    /// environment.
+    pub fn set_proxy(&self, proxy: Option<&str>) -> Result<(), Error> {
+        let client = build_client(proxy)?;
+        *self.client.write().unwrap() = client;
+
+        Ok(())
+    }
+
+    pub fn cache(&self) -> Option<&HttpCache> {
+        self.cache.as_deref()
+    }
+
+    /// Record responses to or replay them from fixture files. Replay takes
+    /// precedence over the cache.
+    pub fn with_replay(mut self, replay: impl Into<Option<Replay>>) -> Self {
+        self.replay = replay.into().map(Arc::new);
+        self
+    }
+
+    pub fn replay(&self) -> Option<&Replay> {
+        self.replay.as_deref()
+    }
+
     pub fn get(&self, url: crate::Url) -> RequestBuilder {
         let builder = http::request::Request::get(url.as_str());
         self.builder(url, builder)
@@ -24,12 +80,27 @@ impl Client {
     pub fn builder(&self, url: crate::Url, request: http::request::Builder) -> RequestBuilder {
         RequestBuilder {
             request,
-            client: self.client.clone(),
+            client: self.client.read().unwrap().clone(),
+            cache: self.cache.clone(),
+            replay: self.replay.clone(),
             url,
+            body: Vec::new(),
         }
     }
 }
 
+fn build_client(proxy: Option<&str>) -> Result<isahc::HttpClient, Error> {
+    use isahc::config::Configurable;
+
+    let mut builder = isahc::HttpClientBuilder::new();
+    if let Some(proxy) = proxy {
+        let proxy = proxy.parse::<http::Uri>().map_err(http::Error::from)?;
+        builder = builder.proxy(Some(proxy));
+    }
+
+    Ok(builder.build()?)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
@@ -42,15 +113,32 @@ pub enum Error {
     #[error(transparent)]
     SerdeJsonError(#[from] serde_json::Error),
 
+    #[error("no recorded response for {method} {url}")]
+    MissingFixture { method: String, url: String },
+
     #[error(transparent)]
     ExternalError(#[from] anyhow::Error),
 }
 
+impl Error {
+    pub fn kind(&self) -> crate::ErrorKind {
+        match self {
+            Self::IsahcError(..) | Self::IOError(..) => crate::ErrorKind::Network,
+            Self::SerdeJsonError(..) => crate::ErrorKind::Parse,
+            Self::MissingFixture { .. } => crate::ErrorKind::NotFound,
+            Self::HttpError(..) | Self::ExternalError(..) => crate::ErrorKind::Other,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RequestBuilder {
     request: http::request::Builder,
     client: isahc::HttpClient,
+    cache: Option<Arc<HttpCache>>,
+    replay: Option<Arc<Replay>>,
     url: crate::Url,
+    body: Vec<u8>,
 }
 
 impl RequestBuilder {
@@ -58,10 +146,34 @@ impl RequestBuilder {
         self.request = self.request.header(key, value);
         self
     }
-    pub async fn send(self) -> Result<Response, Error> {
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Bypass the client cache for this request.
+    pub fn no_cache(mut self) -> Self {
+        self.cache = None;
+        self
+    }
+
+    pub async fn send(mut self) -> Result<Response, Error> {
+        if let Some(replay) = self.replay.take() {
+            return replay.send(self).await;
+        }
+
+        let is_get = self.request.method_ref() == Some(&http::Method::GET);
+
+        match self.cache.take() {
+            Some(cache) if is_get => cache.send(self).await,
+            _ => self.send_without_cache().await,
+        }
+    }
+
+    async fn send_without_cache(self) -> Result<Response, Error> {
         let response = self
             .client
-            .send_async(self.request.body(isahc::AsyncBody::empty())?)
+            .send_async(self.request.body(isahc::AsyncBody::from(self.body))?)
             .await?;
 
         Ok(Response {
@@ -93,6 +205,22 @@ impl Response {
             .and_then(|it| it.to_str().ok())
     }
 
+    /// Turn unsuccessful status into a classified error.
+    pub fn error_for_status(self) -> Result<Self, crate::Error> {
+        let status = self.status();
+        if status.is_success() {
+            return Ok(self);
+        }
+
+        // only the delay form of Retry-After is used by the sites we support.
+        let retry_after = self
+            .header_str("Retry-After")
+            .and_then(|it| it.trim().parse().ok())
+            .map(std::time::Duration::from_secs);
+
+        Err(crate::Error::from_status(&self.url, status, retry_after))
+    }
+
     pub async fn text(mut self) -> Result<String, Error> {
         self.response.text().await.map_err(Into::into)
     }
@@ -101,6 +229,10 @@ impl Response {
         self.response.json().await.map_err(Into::into)
     }
 
+    pub async fn bytes(mut self) -> Result<Vec<u8>, Error> {
+        self.response.bytes().await.map_err(Into::into)
+    }
+
     pub fn stream(self) -> ResponseStream {
         ResponseStream {
             body: self.response.into_body(),
@@ -0,0 +1,580 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures_lite::io::AsyncReadExt;
+use serde::{Deserialize, Serialize};
+
+use super::{Error, RequestBuilder, Response};
+
+/// On-disk cache for textual GET responses.
+///
+/// Responses are stored when the server allows it through `Cache-Control`,
+/// `ETag` or `Last-Modified`. Stale entries are revalidated with
+/// `If-None-Match`/`If-Modified-Since` so a `304 Not Modified` is served
+/// from disk. `force_ttl` makes every cacheable response fresh for the
+/// given duration regardless of what the server says.
+///
+/// Responses are stored per URL and per value of the request headers the
+/// server names in `Vary`, along with headers like `Accept-Language`.
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    directory: PathBuf,
+    force_ttl: Option<Duration>,
+}
+
+impl HttpCache {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            force_ttl: None,
+        }
+    }
+
+    pub fn with_force_ttl(mut self, force_ttl: Option<Duration>) -> Self {
+        self.force_ttl = force_ttl;
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn force_ttl(&self) -> Option<Duration> {
+        self.force_ttl
+    }
+
+    /// Remove every stored response.
+    pub fn clear(&self) -> Result<(), std::io::Error> {
+        match std::fs::remove_dir_all(&self.directory) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) async fn send(&self, mut request: RequestBuilder) -> Result<Response, Error> {
+        let base = cache_key(request.url.as_str());
+        // validators added below don't select the stored variant.
+        let headers = request.request.headers_ref().cloned().unwrap_or_default();
+        let vary = self.load_vary(&base).await;
+        let key = variant_key(request.url.as_str(), &request_headers(&headers, &vary));
+        let now = now();
+
+        let cached = match self.load(&key).await {
+            Ok(cached) => cached,
+            Err(err) => {
+                tracing::warn!("cannot read http cache for {}: {}", request.url, err);
+                None
+            }
+        };
+
+        if let Some((entry, body)) = &cached {
+            if entry.is_fresh(now, self.force_ttl) {
+                return entry.to_response(body.clone(), request.url);
+            }
+
+            if let Some(etag) = &entry.etag {
+                request = request.header("If-None-Match".to_string(), etag.clone());
+            }
+
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header("If-Modified-Since".to_string(), last_modified.clone());
+            }
+        }
+
+        let url = request.url.clone();
+        let response = request.send_without_cache().await?;
+
+        if response.status() == http::StatusCode::NOT_MODIFIED {
+            if let Some((mut entry, body)) = cached {
+                entry.revalidated(&response, now);
+                self.store_entry(&key, &entry).await;
+                return entry.to_response(body, url);
+            }
+        }
+
+        let entry = match CacheEntry::from_response(&response, now) {
+            Some(entry) if entry.is_storable(self.force_ttl) => entry,
+            _ => return Ok(response),
+        };
+
+        // `Vary: *` means no request can be answered from the cache.
+        let vary = match response_vary(&response) {
+            Some(vary) => vary,
+            None => return Ok(response),
+        };
+        let key = variant_key(url.as_str(), &request_headers(&headers, &vary));
+
+        let (parts, mut body) = response.response.into_parts();
+        let mut buffer = Vec::new();
+        body.read_to_end(&mut buffer).await?;
+
+        self.store_vary(&base, &vary).await;
+        self.store_entry(&key, &entry).await;
+        self.store_body(&key, &buffer).await;
+
+        Ok(Response {
+            response: http::Response::from_parts(parts, isahc::AsyncBody::from(buffer)),
+            url,
+        })
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", key))
+    }
+
+    fn body_path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{}.body", key))
+    }
+
+    /// Headers named in `Vary` of the last response for the url with
+    /// `base` key.
+    fn vary_path(&self, base: &str) -> PathBuf {
+        self.directory.join(format!("{}.vary", base))
+    }
+
+    async fn load(&self, key: &str) -> Result<Option<(CacheEntry, Vec<u8>)>, std::io::Error> {
+        let entry = match tokio::fs::read(self.entry_path(key)).await {
+            Ok(entry) => entry,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let entry = serde_json::from_slice(&entry)?;
+        let body = match tokio::fs::read(self.body_path(key)).await {
+            Ok(body) => body,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Some((entry, body)))
+    }
+
+    async fn load_vary(&self, base: &str) -> Vec<String> {
+        let result = match tokio::fs::read(self.vary_path(base)).await {
+            Ok(vary) => serde_json::from_slice(&vary).map_err(std::io::Error::from),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err),
+        };
+
+        result.unwrap_or_else(|err| {
+            tracing::warn!("cannot read http cache vary: {}", err);
+            Vec::new()
+        })
+    }
+
+    async fn write(&self, path: PathBuf, content: &[u8]) -> Result<(), std::io::Error> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        tokio::fs::write(path, content).await
+    }
+
+    async fn store_entry(&self, key: &str, entry: &CacheEntry) {
+        let result = match serde_json::to_vec(entry) {
+            Ok(it) => self.write(self.entry_path(key), &it).await,
+            Err(err) => Err(err.into()),
+        };
+
+        if let Err(err) = result {
+            tracing::warn!("cannot write http cache for {}: {}", entry.url, err);
+        }
+    }
+
+    async fn store_body(&self, key: &str, body: &[u8]) {
+        if let Err(err) = self.write(self.body_path(key), body).await {
+            tracing::warn!("cannot write http cache body: {}", err);
+        }
+    }
+
+    async fn store_vary(&self, base: &str, vary: &[String]) {
+        let path = self.vary_path(base);
+        let result = if vary.is_empty() {
+            match tokio::fs::remove_file(path).await {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            }
+        } else {
+            match serde_json::to_vec(vary) {
+                Ok(it) => self.write(path, &it).await,
+                Err(err) => Err(err.into()),
+            }
+        };
+
+        if let Err(err) = result {
+            tracing::warn!("cannot write http cache vary: {}", err);
+        }
+    }
+}
+
+/// Request headers that change the response whether or not the server
+/// says so in `Vary`.
+const KEYED_HEADERS: &[&str] = &["accept", "accept-language", "authorization", "cookie"];
+
+/// Lowercase header names in `Vary` of `response`, `None` for `Vary: *`.
+fn response_vary(response: &Response) -> Option<Vec<String>> {
+    let mut vary = Vec::new();
+
+    for value in response.response.headers().get_all(http::header::VARY) {
+        for name in value.to_str().unwrap_or_default().split(',') {
+            let name = name.trim().to_ascii_lowercase();
+            match name.as_str() {
+                "*" => return None,
+                "" => {}
+                _ => vary.push(name),
+            }
+        }
+    }
+
+    vary.sort();
+    vary.dedup();
+
+    Some(vary)
+}
+
+/// Values of [`KEYED_HEADERS`] and the `vary` headers in `headers`, sorted
+/// by name. Headers the request doesn't have are left out.
+fn request_headers(headers: &http::HeaderMap, vary: &[String]) -> Vec<(String, String)> {
+    let mut names: Vec<_> = KEYED_HEADERS
+        .iter()
+        .map(|it| it.to_string())
+        .chain(vary.iter().cloned())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let values: Vec<_> = headers
+                .get_all(name.as_str())
+                .iter()
+                .filter_map(|it| it.to_str().ok())
+                .collect();
+
+            if values.is_empty() {
+                None
+            } else {
+                Some((name, values.join(", ")))
+            }
+        })
+        .collect()
+}
+
+/// Subset of `Cache-Control` directives that matter to a private cache.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CacheControl {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub max_age: Option<u64>,
+}
+
+impl CacheControl {
+    pub fn parse(value: &str) -> Self {
+        let mut this = Self::default();
+
+        for directive in value.split(',') {
+            let mut directive = directive.trim().splitn(2, '=');
+            let name = directive.next().unwrap_or_default().trim();
+            let value = directive.next().map(|it| it.trim().trim_matches('"'));
+
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => this.no_store = true,
+                "no-cache" => this.no_cache = true,
+                "max-age" => this.max_age = value.and_then(|it| it.parse().ok()),
+                _ => {}
+            }
+        }
+
+        this
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    stored_at: u64,
+    no_cache: bool,
+    max_age: Option<u64>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl CacheEntry {
+    fn from_response(response: &Response, now: u64) -> Option<Self> {
+        if response.status() != http::StatusCode::OK || !is_metadata(response) {
+            return None;
+        }
+
+        let control = response
+            .header_str("Cache-Control")
+            .map(CacheControl::parse)
+            .unwrap_or_default();
+
+        if control.no_store {
+            return None;
+        }
+
+        let headers = response
+            .response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+
+        Some(Self {
+            url: response.url().to_string(),
+            status: response.status().as_u16(),
+            headers,
+            stored_at: now,
+            no_cache: control.no_cache,
+            max_age: control.max_age,
+            etag: response.header_str("ETag").map(ToString::to_string),
+            last_modified: response
+                .header_str("Last-Modified")
+                .map(ToString::to_string),
+        })
+    }
+
+    fn is_storable(&self, force_ttl: Option<Duration>) -> bool {
+        force_ttl.is_some()
+            || self.max_age.is_some()
+            || self.etag.is_some()
+            || self.last_modified.is_some()
+    }
+
+    fn is_fresh(&self, now: u64, force_ttl: Option<Duration>) -> bool {
+        let age = now.saturating_sub(self.stored_at);
+
+        match (force_ttl, self.max_age) {
+            (Some(ttl), _) => age < ttl.as_secs(),
+            (None, _) if self.no_cache => false,
+            (None, Some(max_age)) => age < max_age,
+            (None, None) => false,
+        }
+    }
+
+    fn revalidated(&mut self, response: &Response, now: u64) {
+        self.stored_at = now;
+
+        if let Some(control) = response.header_str("Cache-Control") {
+            let control = CacheControl::parse(control);
+            self.no_cache = control.no_cache;
+            self.max_age = control.max_age;
+        }
+
+        if let Some(etag) = response.header_str("ETag") {
+            self.etag = Some(etag.to_string());
+        }
+    }
+
+    fn to_response(&self, body: Vec<u8>, url: crate::Url) -> Result<Response, Error> {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+
+        Ok(Response {
+            response: builder.body(isahc::AsyncBody::from(body))?,
+            url,
+        })
+    }
+}
+
+/// Only textual responses are cached, images and other binaries are not.
+fn is_metadata(response: &Response) -> bool {
+    let content_type = match response.header_str("Content-Type") {
+        Some(content_type) => content_type.to_ascii_lowercase(),
+        None => return false,
+    };
+
+    content_type.starts_with("text/")
+        || content_type.contains("json")
+        || content_type.contains("xml")
+        || content_type.contains("javascript")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_secs())
+        .unwrap_or_default()
+}
+
+/// FNV-1a, stable across builds so the cache survives upgrades.
+fn cache_key(url: &str) -> String {
+    hash_key(url.as_bytes())
+}
+
+/// FNV-1a hash of `bytes` in hex.
+pub(super) fn hash_key(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+
+    format!("{:016x}", hash)
+}
+
+/// Key of the response to `url` selected by `headers`, the same as
+/// [`cache_key`] without them.
+fn variant_key(url: &str, headers: &[(String, String)]) -> String {
+    if headers.is_empty() {
+        return cache_key(url);
+    }
+
+    let mut key = url.to_string();
+    for (name, value) in headers {
+        key.push_str(&format!("\n{}: {}", name, value));
+    }
+
+    cache_key(&key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> CacheEntry {
+        CacheEntry {
+            url: "https://localhost/manga".to_string(),
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            stored_at: 100,
+            no_cache: false,
+            max_age: None,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn parse_cache_control() {
+        assert_eq!(
+            CacheControl::parse("public, max-age=60"),
+            CacheControl {
+                max_age: Some(60),
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            CacheControl::parse("No-Store, no-cache"),
+            CacheControl {
+                no_store: true,
+                no_cache: true,
+                max_age: None,
+            }
+        );
+
+        assert_eq!(CacheControl::parse("max-age=abc").max_age, None);
+    }
+
+    #[test]
+    fn freshness() {
+        let mut entry = entry();
+        assert!(!entry.is_fresh(100, None));
+        assert!(!entry.is_storable(None));
+        assert!(entry.is_storable(Some(Duration::from_secs(1))));
+
+        entry.max_age = Some(60);
+        assert!(entry.is_fresh(159, None));
+        assert!(!entry.is_fresh(160, None));
+
+        entry.no_cache = true;
+        assert!(!entry.is_fresh(101, None));
+
+        // forced ttl wins over what the server says.
+        assert!(entry.is_fresh(500, Some(Duration::from_secs(600))));
+        assert!(!entry.is_fresh(700, Some(Duration::from_secs(600))));
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn store_and_load() {
+        let dir = std::env::temp_dir().join(format!("mado-http-cache-{}", std::process::id()));
+        let cache = HttpCache::new(&dir);
+        let key = cache_key("https://localhost/manga");
+
+        assert!(block_on(cache.load(&key)).unwrap().is_none());
+
+        block_on(cache.store_entry(&key, &entry()));
+        block_on(cache.store_body(&key, b"{}"));
+
+        let (entry, body) = block_on(cache.load(&key)).unwrap().unwrap();
+        assert_eq!(entry.url, "https://localhost/manga");
+        assert_eq!(body, b"{}");
+
+        let response = entry
+            .to_response(body, crate::Url::parse("https://localhost/manga").unwrap())
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(
+            response.header_str("content-type"),
+            Some("application/json")
+        );
+
+        cache.clear().unwrap();
+        assert!(block_on(cache.load(&key)).unwrap().is_none());
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn store_and_load_vary() {
+        let dir = std::env::temp_dir().join(format!("mado-http-vary-{}", std::process::id()));
+        let cache = HttpCache::new(&dir);
+        let base = cache_key("https://localhost/manga");
+
+        assert!(block_on(cache.load_vary(&base)).is_empty());
+
+        let vary = vec!["x-lang".to_string()];
+        block_on(cache.store_vary(&base, &vary));
+        assert_eq!(block_on(cache.load_vary(&base)), vary);
+
+        block_on(cache.store_vary(&base, &[]));
+        assert!(block_on(cache.load_vary(&base)).is_empty());
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn variant_key_uses_headers() {
+        let url = "https://localhost/manga";
+        let mut headers = http::HeaderMap::new();
+        assert_eq!(
+            variant_key(url, &request_headers(&headers, &[])),
+            cache_key(url)
+        );
+
+        headers.insert("x-lang", "en".parse().unwrap());
+        assert_eq!(
+            variant_key(url, &request_headers(&headers, &[])),
+            cache_key(url)
+        );
+
+        let vary = ["x-lang".to_string()];
+        let en = variant_key(url, &request_headers(&headers, &vary));
+        assert_ne!(en, cache_key(url));
+
+        headers.insert("x-lang", "id".parse().unwrap());
+        assert_ne!(variant_key(url, &request_headers(&headers, &vary)), en);
+
+        headers.remove("x-lang");
+        headers.insert("accept-language", "en".parse().unwrap());
+        assert_eq!(
+            request_headers(&headers, &vary),
+            [("accept-language".to_string(), "en".to_string())]
+        );
+    }
+
+    #[test]
+    fn key_is_stable() {
+        assert_eq!(cache_key(""), "cbf29ce484222325");
+        assert_ne!(cache_key("https://a"), cache_key("https://b"));
+    }
+}
@@ -0,0 +1,330 @@
+use std::path::{Path, PathBuf};
+
+use futures_lite::io::AsyncReadExt;
+use serde::{Deserialize, Serialize};
+
+use super::{cache::hash_key, Error, RequestBuilder, Response};
+
+pub const REPLAY_ENV: &str = "MADO_HTTP_REPLAY";
+pub const FIXTURE_ENV: &str = "MADO_HTTP_FIXTURES";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// Send requests to the network and save every response as a fixture.
+    Record,
+    /// Serve responses from fixtures only, never touching the network.
+    Replay,
+}
+
+/// Record/playback layer for [`super::Client`].
+///
+/// Fixtures are JSON files named after the request method, url, headers
+/// and body, one per request, so they can be committed next to module
+/// tests.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    mode: ReplayMode,
+    directory: PathBuf,
+}
+
+impl Replay {
+    pub fn new(mode: ReplayMode, directory: impl Into<PathBuf>) -> Self {
+        Self {
+            mode,
+            directory: directory.into(),
+        }
+    }
+
+    /// Read the mode from `MADO_HTTP_REPLAY` (`record` or `replay`) and the
+    /// fixture directory from `MADO_HTTP_FIXTURES`, defaulting to `fixtures`.
+    pub fn from_env() -> Option<Self> {
+        let mode = match std::env::var(REPLAY_ENV)
+            .ok()?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "record" => ReplayMode::Record,
+            "replay" => ReplayMode::Replay,
+            other => {
+                tracing::warn!("unknown {} value: {}", REPLAY_ENV, other);
+                return None;
+            }
+        };
+
+        let directory = std::env::var(FIXTURE_ENV).unwrap_or_else(|_| "fixtures".to_string());
+
+        Some(Self::new(mode, directory))
+    }
+
+    pub fn mode(&self) -> ReplayMode {
+        self.mode
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub(super) async fn send(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let method = request
+            .request
+            .method_ref()
+            .cloned()
+            .unwrap_or(http::Method::GET);
+
+        let path = self.fixture_path(&method, &request.url, &request_key(&request));
+
+        match self.mode {
+            ReplayMode::Replay => self.replay(&method, request.url, path).await,
+            ReplayMode::Record => self.record(&method, request, path).await,
+        }
+    }
+
+    fn fixture_path(&self, method: &http::Method, url: &crate::Url, key: &str) -> PathBuf {
+        let host = url.host_str().unwrap_or("local").replace(':', "_");
+
+        self.directory.join(format!(
+            "{}-{}-{}.json",
+            method.as_str().to_ascii_lowercase(),
+            host,
+            key
+        ))
+    }
+
+    async fn replay(
+        &self,
+        method: &http::Method,
+        url: crate::Url,
+        path: PathBuf,
+    ) -> Result<Response, Error> {
+        let fixture = match tokio::fs::read(&path).await {
+            Ok(fixture) => fixture,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Error::MissingFixture {
+                    method: method.to_string(),
+                    url: url.to_string(),
+                });
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let fixture: Fixture = serde_json::from_slice(&fixture)?;
+        fixture.into_response(url)
+    }
+
+    async fn record(
+        &self,
+        method: &http::Method,
+        request: RequestBuilder,
+        path: PathBuf,
+    ) -> Result<Response, Error> {
+        let url = request.url.clone();
+        let response = request.send_without_cache().await?;
+
+        let (parts, mut body) = response.response.into_parts();
+        let mut buffer = Vec::new();
+        body.read_to_end(&mut buffer).await?;
+
+        let fixture = Fixture {
+            method: method.to_string(),
+            url: url.to_string(),
+            status: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body: FixtureBody::from(buffer.clone()),
+        };
+
+        tokio::fs::create_dir_all(&self.directory).await?;
+        tokio::fs::write(path, serde_json::to_vec_pretty(&fixture)?).await?;
+
+        Ok(Response {
+            response: http::Response::from_parts(parts, isahc::AsyncBody::from(buffer)),
+            url,
+        })
+    }
+}
+
+/// Hash of the url, headers and body of `request`. Requests without
+/// headers or a body are keyed by their url only.
+fn request_key(request: &RequestBuilder) -> String {
+    let mut headers: Vec<_> = request
+        .request
+        .headers_ref()
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| (name.as_str(), value.as_bytes()))
+        .collect();
+    headers.sort();
+
+    let mut key = request.url.as_str().as_bytes().to_vec();
+    for (name, value) in headers {
+        key.push(b'\n');
+        key.extend_from_slice(name.as_bytes());
+        key.extend_from_slice(b": ");
+        key.extend_from_slice(value);
+    }
+
+    if !request.body.is_empty() {
+        key.extend_from_slice(b"\n\n");
+        key.extend_from_slice(&request.body);
+    }
+
+    hash_key(&key)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Fixture {
+    method: String,
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: FixtureBody,
+}
+
+/// Text bodies are kept readable so fixtures can be reviewed and edited.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum FixtureBody {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl From<Vec<u8>> for FixtureBody {
+    fn from(body: Vec<u8>) -> Self {
+        match String::from_utf8(body) {
+            Ok(text) => Self::Text(text),
+            Err(err) => Self::Bytes(err.into_bytes()),
+        }
+    }
+}
+
+impl From<FixtureBody> for Vec<u8> {
+    fn from(body: FixtureBody) -> Self {
+        match body {
+            FixtureBody::Text(text) => text.into_bytes(),
+            FixtureBody::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+impl Fixture {
+    fn into_response(self, url: crate::Url) -> Result<Response, Error> {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+
+        let body: Vec<u8> = self.body.into();
+
+        Ok(Response {
+            response: builder.body(isahc::AsyncBody::from(body))?,
+            url,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mado-http-replay-{}-{}", name, std::process::id()))
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn replay_fixture() {
+        let replay = Replay::new(ReplayMode::Replay, directory("fixture"));
+        let url = crate::Url::parse("https://localhost:8080/manga/1").unwrap();
+
+        let fixture = Fixture {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            status: 200,
+            headers: vec![("content-type".to_string(), "text/plain".to_string())],
+            body: FixtureBody::from(b"hello".to_vec()),
+        };
+
+        let request = crate::http::Client::default()
+            .with_replay(replay.clone())
+            .get(url.clone());
+
+        std::fs::create_dir_all(replay.directory()).unwrap();
+        std::fs::write(
+            replay.fixture_path(&http::Method::GET, &url, &request_key(&request)),
+            serde_json::to_vec(&fixture).unwrap(),
+        )
+        .unwrap();
+
+        let response = block_on(request.send()).unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.header_str("content-type"), Some("text/plain"));
+        assert_eq!(block_on(response.text()).unwrap(), "hello");
+
+        std::fs::remove_dir_all(replay.directory()).unwrap();
+    }
+
+    #[test]
+    fn missing_fixture() {
+        let replay = Replay::new(ReplayMode::Replay, directory("missing"));
+        let request = crate::http::Client::default()
+            .with_replay(replay)
+            .get(crate::Url::parse("https://localhost/none").unwrap());
+
+        assert!(matches!(
+            block_on(request.send()),
+            Err(Error::MissingFixture { .. })
+        ));
+    }
+
+    #[test]
+    fn request_key_test() {
+        let url = crate::Url::parse("https://localhost/search").unwrap();
+        let client = crate::http::Client::default();
+        let key = |request: RequestBuilder| request_key(&request);
+
+        // fixtures recorded without headers keep their name.
+        assert_eq!(
+            key(client.get(url.clone())),
+            super::super::cache::hash_key(url.as_str().as_bytes())
+        );
+
+        let plain = key(client.get(url.clone()));
+        let header = key(client
+            .get(url.clone())
+            .header("Range".into(), "bytes=1-".into()));
+        let body = key(client.get(url.clone()).body("query=a"));
+        let other_body = key(client.get(url.clone()).body("query=b"));
+
+        assert_ne!(plain, header);
+        assert_ne!(plain, body);
+        assert_ne!(body, other_body);
+        assert_eq!(body, key(client.get(url).body("query=a")));
+    }
+
+    #[test]
+    fn fixture_body() {
+        assert!(matches!(
+            FixtureBody::from(b"text".to_vec()),
+            FixtureBody::Text(text) if text == "text"
+        ));
+        assert!(matches!(
+            FixtureBody::from(vec![0xff, 0xfe]),
+            FixtureBody::Bytes(bytes) if bytes == vec![0xff, 0xfe]
+        ));
+
+        let json = serde_json::to_string(&FixtureBody::Bytes(vec![1, 2])).unwrap();
+        let body: Vec<u8> = serde_json::from_str::<FixtureBody>(&json).unwrap().into();
+        assert_eq!(body, vec![1, 2]);
+    }
+}
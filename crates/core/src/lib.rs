@@ -5,7 +5,7 @@ mod http_error;
 #[allow(dead_code)]
 mod manga;
 
-pub use error::Error;
+pub use error::{ClassifiedError, Error, ErrorKind};
 pub use manga::*;
 
 pub mod url;
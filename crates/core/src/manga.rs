@@ -89,6 +89,8 @@ pub struct ChapterInfo {
     pub volume: Option<String>,
     pub scanlator: Vec<String>,
     pub language: String,
+    /// When the chapter was uploaded, as an RFC 3339 timestamp.
+    pub uploaded_at: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
@@ -45,6 +45,12 @@ pub trait MadoModule: Send + Sync + Debug + 'static {
         &self,
         image: ChapterImageInfo,
     ) -> Result<crate::RequestBuilder, crate::Error>;
+
+    /// Release resources held by the module, called when the engine
+    /// shuts down. The module shouldn't be used afterwards.
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 pub type ArcMadoModule = Arc<dyn MadoModule + Sync>;
@@ -65,6 +71,13 @@ pub trait MadoModuleMap: Send + 'static {
     ///
     /// This operation should preserve old module if Error happen.
     fn push(&mut self, module: ArcMadoModule) -> Result<(), MadoModuleMapError>;
+
+    /// Remove module with `uuid`, returning it if it exists.
+    ///
+    /// Maps that can't remove modules keep them and return `None`.
+    fn remove(&mut self, _uuid: Uuid) -> Option<ArcMadoModule> {
+        None
+    }
 }
 
 pub fn remove_domain(url: &mut crate::url::Url) {
@@ -122,6 +135,22 @@ impl MadoModuleMap for DefaultMadoModuleMap {
             }
         }
     }
+
+    fn remove(&mut self, uuid: Uuid) -> Option<ArcMadoModule> {
+        let module = self.uuids.remove(&uuid)?;
+
+        let mut url = module.domain().clone();
+        remove_domain(&mut url);
+
+        // the domain may already belong to another module.
+        if self.domains.get(&url).map(|it| it.uuid()) == Some(uuid) {
+            self.domains.remove(&url);
+        }
+
+        self.vec.retain(|it| it.uuid() != uuid);
+
+        Some(module)
+    }
 }
 
 #[derive(Default, Debug)]
@@ -153,17 +182,27 @@ impl<Map: MadoModuleMap> MadoModuleMap for MutexMadoModuleMap<Map> {
     fn get_by_uuid(&self, uuid: Uuid) -> Option<ArcMadoModule> {
         self.map.lock().unwrap().get_by_uuid(uuid)
     }
+
+    fn remove(&mut self, uuid: Uuid) -> Option<ArcMadoModule> {
+        self.remove_mut(uuid)
+    }
 }
 
 /// Interior Mutable [`MadoModuleMap`]
 pub trait MutMadoModuleMap: MadoModuleMap {
     fn push_mut(&self, module: ArcMadoModule) -> Result<(), MadoModuleMapError>;
+
+    fn remove_mut(&self, uuid: Uuid) -> Option<ArcMadoModule>;
 }
 
 impl<Map: MadoModuleMap> MutMadoModuleMap for MutexMadoModuleMap<Map> {
     fn push_mut(&self, module: ArcMadoModule) -> Result<(), MadoModuleMapError> {
         self.map.lock().unwrap().push(module)
     }
+
+    fn remove_mut(&self, uuid: Uuid) -> Option<ArcMadoModule> {
+        self.map.lock().unwrap().remove(uuid)
+    }
 }
 
 #[cfg(test)]
@@ -198,4 +237,31 @@ mod test {
 
         assert_eq!(map.get_by_uuid(uuid).unwrap().domain().to_owned(), domain);
     }
+
+    #[test]
+    fn remove_then_push() {
+        let mut map = MutexMadoModuleMap::new(DefaultMadoModuleMap::default());
+
+        let mut module = MockMadoModule::new();
+
+        let domain = Url::parse("https://localhost").unwrap();
+        let uuid = super::Uuid::from_u128(123);
+
+        module.expect_uuid().return_const(uuid);
+        module.expect_domain().return_const(domain.clone());
+
+        let mock = Arc::new(module);
+
+        map.push(mock.clone()).unwrap();
+        assert_eq!(map.remove(uuid).unwrap().uuid(), uuid);
+
+        assert!(map.get_by_uuid(uuid).is_none());
+        assert!(map.get_by_url(domain.clone()).is_none());
+        assert!(map.lock().unwrap().vec().is_empty());
+        assert!(map.remove(uuid).is_none());
+
+        // re-registering after removal is not a duplicate.
+        map.push(mock).unwrap();
+        assert_eq!(map.get_by_url(domain).unwrap().uuid(), uuid);
+    }
 }
@@ -3,6 +3,23 @@ import { nodeResolve } from "@rollup/plugin-node-resolve";
 import multiInputPkg from "rollup-plugin-multi-input";
 const multiInput = multiInputPkg.default;
 import ts from "rollup-plugin-typescript2";
+import fastglob from "fast-glob";
+import fs from "fs";
+import path from "path";
+
+// copy module permission manifests next to the bundled module.
+const permissionManifest = {
+  name: "permission-manifest",
+  async generateBundle() {
+    for (const file of await fastglob("script/module/*.permissions.json")) {
+      this.emitFile({
+        type: "asset",
+        fileName: `module/${path.basename(file)}`,
+        source: fs.readFileSync(file),
+      });
+    }
+  },
+};
 
 export default {
   input: ["script/deps/*.ts", "script/module/*.ts", "script/test/*.ts"],
@@ -23,6 +40,7 @@ export default {
     nodeResolve({ browser: true }),
     ts(),
     common(),
+    permissionManifest,
     // rename({
     //     ["**/*.js", "**/*.ts"],
     // })
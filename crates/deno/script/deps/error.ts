@@ -146,6 +146,11 @@ export class Errors extends Error {
     });
   }
 
+  /** Kind of the error, e.g. `notFound` or `rateLimited`. */
+  get kind(): string {
+    return (this.content && this.content.kind && this.content.kind.kind) || "other";
+  }
+
   intoString() {
     return Deno.core.ops.op_error_to_string(this);
   }
@@ -167,4 +172,18 @@ export class Errors extends Error {
   static invalid_url(url: string) {
     return fromRust(Deno.core.ops.op_error_invalid_url(url));
   }
+  static not_found(url: string, message: string) {
+    return fromRust(Deno.core.ops.op_error_not_found(url, message));
+  }
+  static auth_required(url: string, message: string) {
+    return fromRust(Deno.core.ops.op_error_auth_required(url, message));
+  }
+  static http_status(url: string, status: number) {
+    return fromRust(Deno.core.ops.op_error_http_status(url, status));
+  }
+  static rate_limited(url: string, retryAfter?: number) {
+    return fromRust(
+      Deno.core.ops.op_error_rate_limited(url, retryAfter === undefined ? null : retryAfter)
+    );
+  }
 }
@@ -0,0 +1,89 @@
+import { ResultFromJson } from "./error";
+
+const ROOT_HANDLE = 0;
+
+// Node of a document parsed in Rust, only usable until the document is closed.
+export class HtmlNode {
+  constructor(public document: HtmlDocument, public handle: number) {}
+
+  private nodes(handles: number[]): HtmlNode[] {
+    return handles.map((it) => new HtmlNode(this.document, it));
+  }
+
+  // descendant elements matching css `selector`.
+  select(selector: string): HtmlNode[] {
+    return this.nodes(
+      ResultFromJson(
+        Deno.core.ops.op_html_select(this.document.rid, this.handle, selector)
+      ).throw()
+    );
+  }
+
+  selectFirst(selector: string): HtmlNode | null {
+    return this.select(selector)[0] ?? null;
+  }
+
+  // nodes selected by xpath `expression`, attributes can only be read with
+  // `xpathString`.
+  xpath(expression: string): HtmlNode[] {
+    return this.nodes(
+      ResultFromJson(
+        Deno.core.ops.op_html_xpath(this.document.rid, this.handle, expression)
+      ).throw()
+    );
+  }
+
+  // string value of xpath `expression`, like xpath `string()`.
+  xpathString(expression: string): string {
+    return ResultFromJson(
+      Deno.core.ops.op_html_xpath_string(
+        this.document.rid,
+        this.handle,
+        expression
+      )
+    ).throw();
+  }
+
+  get text(): string {
+    return ResultFromJson(
+      Deno.core.ops.op_html_text(this.document.rid, this.handle)
+    ).throw();
+  }
+
+  get name(): string | null {
+    return ResultFromJson(
+      Deno.core.ops.op_html_name(this.document.rid, this.handle)
+    ).throw();
+  }
+
+  get outerHtml(): string {
+    return ResultFromJson(
+      Deno.core.ops.op_html_outer_html(this.document.rid, this.handle)
+    ).throw();
+  }
+
+  attr(name: string): string | null {
+    return ResultFromJson(
+      Deno.core.ops.op_html_attribute(this.document.rid, this.handle, name)
+    ).throw();
+  }
+}
+
+export class HtmlDocument extends HtmlNode {
+  rid: number;
+
+  // parse `text`, or use the already parsed document `rid`.
+  constructor(text: string, rid: number = null) {
+    super(null, ROOT_HANDLE);
+    this.document = this;
+
+    if (rid == null) {
+      rid = ResultFromJson(Deno.core.ops.op_html_parse(text)).throw();
+    }
+    this.rid = rid;
+  }
+
+  close() {
+    ResultFromJson(Deno.core.ops.op_html_close(this.rid)).throw();
+  }
+}
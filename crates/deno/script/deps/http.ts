@@ -1,5 +1,6 @@
 import { Result, ResultFromJson } from "./error";
 import { Resource } from "./resource";
+import { HtmlDocument } from "./html";
 
 type ResponseDecl = {
   status: number;
@@ -15,6 +16,8 @@ export interface HttpResponse {
   text_data(): Promise<string>;
   json(): Promise<Result<any>>;
   json_data(): Promise<any>;
+  // parse the body as html, close the document when done.
+  html(): Promise<HtmlDocument>;
 
   close(): Promise<void>;
 }
@@ -22,6 +22,7 @@ export {
   ResultFromJson,
 } from "./error";
 export { XHTMLPath } from "./xhtmlpath";
+export { HtmlDocument, HtmlNode } from "./html";
 export { Resource } from "./resource";
 
 export { rx as VRegex } from "verbose-regexp";
@@ -26,6 +26,7 @@ export interface Chapter {
   chapter?: string | null;
   language: string;
   scanlator: Array<string>;
+  uploaded_at?: string | null;
 }
 
 export interface ChapterImageInfo {
@@ -1,6 +1,7 @@
 import { Result, ResultFromJson } from "./error";
 import { HttpClient, HttpRequest, HttpResponse } from "./http";
 import { Resource } from "./resource";
+import { HtmlDocument } from "./html";
 
 type ResponseDecl = {
   status: number;
@@ -43,6 +44,14 @@ export class RustHttpResponse implements HttpResponse {
     return await this.json().then((it) => it.data);
   }
 
+  async html(): Promise<HtmlDocument> {
+    let rid = ResultFromJson(
+      await Deno.core.ops.op_http_response_html(this.rid)
+    ).throw();
+
+    return new HtmlDocument(null, rid);
+  }
+
   async close(): Promise<void> {
     //TODO
   }
@@ -51,7 +60,7 @@ export class RustHttpResponse implements HttpResponse {
 export class RustHttpClient extends Resource implements HttpClient {
   constructor(rid: number = null) {
     if (rid == null) {
-      rid = Deno.core.ops.op_http_client_new();
+      rid = ResultFromJson(Deno.core.ops.op_http_client_new()).throw();
     }
 
     super(rid, "op_http_client");
@@ -161,6 +161,7 @@ export class MangaDex implements HttpModule {
       chapter: attr.chapter,
       language: attr.translatedLanguage,
       scanlator: [],
+      uploaded_at: attr.publishAt,
     };
 
     info.scanlator = CommonFunction.query(
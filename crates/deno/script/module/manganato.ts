@@ -4,7 +4,8 @@ import {
   HttpClient,
   HttpModule,
   VRegex as rx,
-  XHTMLPath,
+  HtmlDocument,
+  HtmlNode,
   ModuleWrapper,
   Manga,
   ChapterTask,
@@ -34,41 +35,40 @@ class MangaNato implements HttpModule {
       url: url,
     });
 
-    let text = await response.text_data();
-    // let response = self.client.get(url.clone()).send().await.unwrap().text().await;
+    let doc = await response.html();
 
-    let doc = new XHTMLPath(text);
+    try {
+      let manga = this.parse_info(url, doc);
 
-    let manga = this.parse_info(url, doc);
+      let chapters = doc
+        .select("ul.row-content-chapter > li > a.chapter-name")
+        .map((it) => this.parse_chapter(it));
 
-    let chapters = doc
-      .select(
-        '//ul[@class="row-content-chapter"]/li/a[contains(@class, "chapter-name")]'
-      )
-      .map((it) => this.parse_chapter(it));
+      if (this.reverseChapter) {
+        chapters.reverse();
+      }
 
-    if (this.reverseChapter) {
-      chapters.reverse();
+      return {
+        manga,
+        chapters
+      };
+    } finally {
+      doc.close();
     }
-
-    return {
-      manga,
-      chapters
-    };
   }
 
-  parse_404(url: string, doc: XHTMLPath) {
-    if (doc.select('//p[contains(., "404 - PAGE NOT FOUND")]').length) {
-      throw Error.request_error(url, "404 PAGE NOT FOUND");
+  parse_404(url: string, doc: HtmlDocument) {
+    if (doc.select("p").some((it) => it.text.includes("404 - PAGE NOT FOUND"))) {
+      throw Error.not_found(url, "404 PAGE NOT FOUND");
     }
   }
 
-  parse_info(url: string, doc: XHTMLPath): Manga {
+  parse_info(url: string, doc: HtmlDocument): Manga {
     this.parse_404(url, doc);
 
     let info: Manga = {
       id: url,
-      title: doc.selectString("//h1"),
+      title: "",
       types: "Series",
       authors: [],
       artists: [],
@@ -79,33 +79,42 @@ class MangaNato implements HttpModule {
     };
     info.id = url;
 
-    info.title = doc.selectString("//h1");
-    info.cover_link = doc.selectString('//span[@class="info-image"]/img/@src');
+    info.title = doc.selectFirst("h1")?.text ?? "";
+    info.cover_link =
+      doc.selectFirst("span.info-image > img")?.attr("src") ?? "";
 
-    info.authors = doc.selectTextData(
-      '//td[contains(., "Author(s)")]/following-sibling::td/a/text()'
-    );
+    info.authors = this.parse_table_links(doc, "Author(s)");
     info.artists = info.authors;
-    info.genres = doc.selectTextData(
-      '//td[contains(., "Genres")]/following-sibling::td/a/text()'
-    );
-    // info.status    = doc.selectString('//td[contains(., "Status")]/following-sibling::td')
-    info.summary = doc
-      .selectString(
-        '//div[@class="panel-story-info-description"]/text()[last()]'
-      )
-      .trim();
+    info.genres = this.parse_table_links(doc, "Genres");
+    // info.status    = this.parse_table_links(doc, "Status")
+
+    // the description starts with its heading.
+    let description = doc.selectFirst("div.panel-story-info-description");
+    let heading = description?.selectFirst("h3")?.text ?? "";
+    info.summary = (description?.text ?? "").replace(heading, "").trim();
     info.types = "Series";
 
     return info;
   }
 
-  parse_chapter(node: Node): Chapter {
-    let doc = XHTMLPath.fromNode(node);
+  // text of links in the cell next to the one containing `label`.
+  parse_table_links(doc: HtmlDocument, label: string): string[] {
+    for (const row of doc.select("tr")) {
+      let cells = row.select("td");
+      let index = cells.findIndex((it) => it.text.includes(label));
 
+      if (index != -1 && index + 1 < cells.length) {
+        return cells[index + 1].select("a").map((it) => it.text);
+      }
+    }
+
+    return [];
+  }
+
+  parse_chapter(node: HtmlNode): Chapter {
     let info: Chapter = {
-      id: doc.selectString("@href"),
-      title: doc.selectString("text()"),
+      id: node.attr("href") ?? "",
+      title: node.text,
       volume: null,
       chapter: null,
       language: "en",
@@ -126,26 +135,34 @@ class MangaNato implements HttpModule {
   async getChapterImage(id: string, task: ChapterTask) {
     let response = await this.client.get({ url: id });
 
-    let doc = new XHTMLPath(await response.text_data());
-
-    this.parse_404(id, doc);
-
-    let queries = [
-      '//div[@id="vungdoc"]/img[@title]/@src',
-      '//div[@class="vung_doc"]/img[@title]/@src',
-      '//div[@class="container-chapter-reader"]/img[@title]/@src',
-      '//div[@id="vungdoc"]/img[@title]/@data-src',
-    ];
-
+    let doc = await response.html();
     let images = [];
-    for (const query of queries) {
-      images = doc.selectText(query, "value").map((it) => {
-        return { id: it, extension: CommonFunction.url_extension(it) };
-      });
 
-      if (images.length != 0) {
-        break;
+    try {
+      this.parse_404(id, doc);
+
+      let queries = [
+        ["div#vungdoc > img[title]", "src"],
+        ["div.vung_doc > img[title]", "src"],
+        ["div.container-chapter-reader > img[title]", "src"],
+        ["div#vungdoc > img[title]", "data-src"],
+      ];
+
+      for (const [selector, attribute] of queries) {
+        images = doc
+          .select(selector)
+          .map((it) => it.attr(attribute))
+          .filter((id) => id != null)
+          .map((id) => {
+            return { id, extension: CommonFunction.url_extension(id) };
+          });
+
+        if (images.length != 0) {
+          break;
+        }
       }
+    } finally {
+      doc.close();
     }
 
     images.forEach((it) => task.push(it));
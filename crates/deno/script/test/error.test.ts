@@ -98,3 +98,17 @@ export async function error__Ok__CloseCustom() {
 
   return error.close();
 }
+
+export async function error__Ok__Kind() {
+  assertEq(Errors.not_found("https://google.com", "gone").kind, "notFound");
+  assertEq(Errors.rate_limited("https://google.com", 10).kind, "rateLimited");
+  assertEq(Errors.http_status("https://google.com", 503).kind, "httpStatus");
+  assertEq(Errors.request_error("https://google.com", "reset").kind, "network");
+  assertEq(Errors.message("Custom Error").kind, "other");
+
+  return Ok({});
+}
+
+export async function error__Err_MadoError_NotFound() {
+  return Err(Errors.not_found("https://google.com", "gone"));
+}
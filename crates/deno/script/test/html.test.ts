@@ -0,0 +1,89 @@
+import { HtmlDocument } from "../deps/html";
+import { catchAndReturn, Ok } from "../deps/error";
+import { assertEq } from "../deps/test";
+
+const HTML = `
+<ul class="row-content-chapter">
+  <li><a class="chapter-name" href="/chapter-2">Chapter 2</a></li>
+  <li><a class="chapter-name" href="/chapter-1">Chapter 1<b></li>
+</ul>
+<p>unclosed
+`;
+
+export function html__Ok__Select() {
+  let doc = new HtmlDocument(HTML);
+  let links = doc.select("ul.row-content-chapter a.chapter-name");
+
+  assertEq(
+    links.map((it) => it.attr("href")),
+    ["/chapter-2", "/chapter-1"]
+  );
+  assertEq(links[0].text, "Chapter 2");
+  assertEq(links[0].name, "a");
+  assertEq(doc.selectFirst("table"), null);
+
+  doc.close();
+  return Ok({});
+}
+
+export function html__Ok__Unclosed() {
+  let doc = new HtmlDocument(HTML);
+  let items = doc.select("li");
+
+  assertEq(items.length, 2);
+  assertEq(items[1].selectFirst("a").attr("href"), "/chapter-1");
+  assertEq(doc.selectFirst("p").text.trim(), "unclosed");
+
+  doc.close();
+  return Ok({});
+}
+
+export function html__Ok__XPath() {
+  let doc = new HtmlDocument(HTML);
+  let items = doc.xpath("//li");
+
+  assertEq(items.length, 2);
+  assertEq(items[1].xpathString("a/@href"), "/chapter-1");
+  assertEq(items[0].xpath("a")[0].text, "Chapter 2");
+  // the same nodes as css give the same handles.
+  assertEq(
+    doc.xpath('//a[contains(@class, "chapter-name")]').map((it) => it.handle),
+    doc.select("a.chapter-name").map((it) => it.handle)
+  );
+  assertEq(doc.xpathString("count(//a)"), "2");
+  assertEq(doc.xpathString("normalize-space(//p)"), "unclosed");
+
+  doc.close();
+  return Ok({});
+}
+
+export async function html__Err_SelectorError__XPath() {
+  let doc = new HtmlDocument(HTML);
+  let result = await catchAndReturn(async () => doc.xpath("//li["));
+  doc.close();
+
+  return result;
+}
+
+export async function html__Err_SelectorError__XPathAttribute() {
+  let doc = new HtmlDocument(HTML);
+  let result = await catchAndReturn(async () => doc.xpath("//a/@href"));
+  doc.close();
+
+  return result;
+}
+
+export async function html__Err_SelectorError__Css() {
+  let doc = new HtmlDocument(HTML);
+  let result = await catchAndReturn(async () => doc.select("li >"));
+  doc.close();
+
+  return result;
+}
+
+export async function html__Err_ResourceError__Closed() {
+  let doc = new HtmlDocument(HTML);
+  doc.close();
+
+  return await catchAndReturn(async () => doc.text);
+}
@@ -15,7 +15,7 @@ export async function getInfo__Ok__2() {
   return await module.getInfo(url);
 }
 
-export async function getInfo__Err_MadoError_RequestError() {
+export async function getInfo__Err_MadoError_UnexpectedResponse() {
   let url = "https://mangadex.org/title/5ebe4265-da26-4a3f-a2e4-56c4af489ce5";
 
   let it = await module.getInfo(url);
@@ -20,7 +20,7 @@ export async function getInfo__Ok__2() {
   return Ok(manga);
 }
 
-export async function getInfo__Err_MadoError_RequestError__404() {
+export async function getInfo__Err_MadoError_NotFound__404() {
   let url = "https://chapmanganato.com/manga-yu176355";
   return await module.getInfo(url);
 }
@@ -35,7 +35,7 @@ export async function getChapterImage__Ok__1() {
   return task.toArray();
 }
 
-export async function getChapterImage__Err_MadoError_RequestError__404() {
+export async function getChapterImage__Err_MadoError_NotFound__404() {
   let id = "https://chapmanganato.com/manga-yu976355/chapter-1325";
   let task = RustChapterTask.fromRust();
   return await module
@@ -1,6 +1,7 @@
 use std::rc::Rc;
 
 use deno_core::{op, serde_v8, Extension, ExtensionBuilder, OpState};
+use mado_core::ErrorKind;
 use thiserror::Error;
 
 use crate::{ResultJson, ToResultJson};
@@ -44,6 +45,12 @@ pub enum Error {
     #[error("Bad resource ID ({1}): {0}")]
     ResourceError(u32, String),
 
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("Invalid selector {selector}: {message}")]
+    SelectorError { selector: String, message: String },
+
     #[error("{0}")]
     ModuleLoadError(#[from] crate::runtime::ModuleLoadError),
 
@@ -85,10 +92,28 @@ impl Error {
             SerdeError(..),
             ModuleLoadError(..),
             ResourceError(..),
+            PermissionDenied(..),
+            SelectorError { .. },
             MadoError(..)
         }
         .to_string()
     }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UrlParseError { .. }
+            | Self::UnexpectedError { .. }
+            | Self::SerdeError(..)
+            | Self::SelectorError { .. } => ErrorKind::Parse,
+            Self::RequestError { .. } => ErrorKind::Network,
+            Self::MadoError(error) => error.kind(),
+            Self::InvalidUrl { .. }
+            | Self::ExternalError(..)
+            | Self::ResourceError(..)
+            | Self::PermissionDenied(..)
+            | Self::ModuleLoadError(..) => ErrorKind::Other,
+        }
+    }
 }
 
 impl From<mado_core::http::Error> for Error {
@@ -105,12 +130,14 @@ impl From<Error> for mado_core::Error {
                 url,
                 message: "Invalid Link".into(),
             },
-            Error::UnexpectedError { url, message } => Self::RequestError { url, message },
+            Error::UnexpectedError { url, message } => Self::UnexpectedResponse { url, message },
             Error::RequestError { url, message } => Self::RequestError { url, message },
             Error::MadoError(err) => err,
-            Error::ModuleLoadError(..) | Error::ResourceError(..) | Error::SerdeError(..) => {
-                Self::ExternalError(err.into())
-            }
+            Error::ModuleLoadError(..)
+            | Error::ResourceError(..)
+            | Error::PermissionDenied(..)
+            | Error::SelectorError { .. }
+            | Error::SerdeError(..) => Self::ExternalError(err.into()),
             Error::ExternalError(err) => Self::ExternalError(err),
         }
     }
@@ -119,8 +146,15 @@ impl From<Error> for mado_core::Error {
 #[derive(serde::Deserialize, serde::Serialize, Debug)]
 #[serde(tag = "type", content = "content")]
 pub enum ErrorJson {
-    Resource { rid: u32, types: String },
-    Custom { message: String },
+    Resource {
+        rid: u32,
+        types: String,
+        #[serde(default)]
+        kind: ErrorKind,
+    },
+    Custom {
+        message: String,
+    },
 }
 
 impl ErrorJson {
@@ -172,6 +206,7 @@ impl ErrorJson {
     pub fn from_error(state: &mut OpState, error: Error) -> ErrorJson {
         ErrorJson::Resource {
             types: error.to_string_variant(),
+            kind: error.kind(),
             rid: state.resource_table.add(error),
         }
     }
@@ -206,6 +241,38 @@ pub fn op_error_unexpected_error(state: &mut OpState, url: String, message: Stri
     ErrorJson::from_error(state, Error::UnexpectedError { url, message })
 }
 
+#[op]
+pub fn op_error_not_found(state: &mut OpState, url: String, message: String) -> ErrorJson {
+    ErrorJson::from_error(state, mado_core::Error::NotFound { url, message }.into())
+}
+
+#[op]
+pub fn op_error_auth_required(state: &mut OpState, url: String, message: String) -> ErrorJson {
+    ErrorJson::from_error(
+        state,
+        mado_core::Error::AuthRequired { url, message }.into(),
+    )
+}
+
+#[op]
+pub fn op_error_http_status(state: &mut OpState, url: String, status: u16) -> ErrorJson {
+    ErrorJson::from_error(state, mado_core::Error::HttpStatus { url, status }.into())
+}
+
+#[op]
+pub fn op_error_rate_limited(
+    state: &mut OpState,
+    url: String,
+    retry_after: Option<u64>,
+) -> ErrorJson {
+    let error = mado_core::Error::RateLimited {
+        url,
+        retry_after: retry_after.map(std::time::Duration::from_secs),
+    };
+
+    ErrorJson::from_error(state, error.into())
+}
+
 #[op]
 pub fn op_error_close(state: &mut OpState, error: ErrorJson) -> ResultJson<()> {
     if let ErrorJson::Resource { rid, .. } = error {
@@ -226,6 +293,10 @@ pub fn init() -> Extension {
             op_error_request_error::decl(),
             op_error_close::decl(),
             op_error_unexpected_error::decl(),
+            op_error_not_found::decl(),
+            op_error_auth_required::decl(),
+            op_error_http_status::decl(),
+            op_error_rate_limited::decl(),
             op_error_to_string::decl(),
             op_error_to_debug::decl(),
         ])
@@ -0,0 +1,404 @@
+use std::{collections::HashMap, rc::Rc};
+
+use deno_core::{op, Extension, ExtensionBuilder, OpState, Resource};
+use ego_tree::{NodeId, NodeRef};
+use scraper::{ElementRef, Html, Node, Selector};
+
+use crate::error::Error;
+use crate::permission::{current_permissions, PermissionState};
+use crate::{try_json, ResultJson, ToResultJson};
+
+mod xpath;
+
+pub use xpath::{Value, XPath, XPathError, XPathNode};
+
+/// Handle of the document node, every document starts with it.
+pub const ROOT_HANDLE: u32 = 0;
+
+/// Parsed HTML document shared with JS through node handles.
+///
+/// Handles are positions of nodes in document order, so they don't need to
+/// be stored and stay valid until the document is closed.
+pub struct HtmlDocument {
+    html: Html,
+    nodes: Vec<NodeId>,
+    order: HashMap<NodeId, u32>,
+    permissions: Rc<PermissionState>,
+}
+
+impl Resource for HtmlDocument {}
+
+impl HtmlDocument {
+    pub fn parse(text: &str, permissions: Rc<PermissionState>) -> Self {
+        let html = Html::parse_document(text);
+        let nodes: Vec<_> = html.tree.root().descendants().map(|it| it.id()).collect();
+        let order = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, it)| (*it, index as u32))
+            .collect();
+
+        Self {
+            html,
+            nodes,
+            order,
+            permissions,
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.html.tree.root().id()
+    }
+
+    pub fn handle(&self, node: NodeId) -> u32 {
+        // ids never leave the document they come from.
+        self.order[&node]
+    }
+
+    pub fn node(&self, handle: u32) -> Option<NodeId> {
+        self.nodes.get(handle as usize).copied()
+    }
+
+    pub fn permissions(&self) -> &PermissionState {
+        &self.permissions
+    }
+
+    /// Descendant elements of `node` matching `selector`.
+    pub fn select(&self, node: NodeId, selector: &Selector) -> Vec<NodeId> {
+        self.node_ref(node)
+            .descendants()
+            .skip(1)
+            .filter_map(ElementRef::wrap)
+            .filter(|it| selector.matches(it))
+            .map(|it| it.id())
+            .collect()
+    }
+
+    pub fn xpath(&self, node: NodeId, xpath: &XPath) -> Result<Value, XPathError> {
+        xpath.evaluate(self, &XPathNode::Node(node))
+    }
+
+    /// Text content of the node.
+    pub fn text(&self, node: NodeId) -> String {
+        let node = self.node_ref(node);
+
+        match node.value() {
+            Node::Text(text) => text.to_string(),
+            Node::Comment(comment) => comment.to_string(),
+            _ => node
+                .descendants()
+                .filter_map(|it| it.value().as_text())
+                .map(|it| &**it)
+                .collect(),
+        }
+    }
+
+    pub fn attribute(&self, node: NodeId, name: &str) -> Option<String> {
+        self.node_ref(node)
+            .value()
+            .as_element()?
+            .attrs()
+            .find(|(it, _)| it.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.to_string())
+    }
+
+    /// Element name.
+    pub fn name(&self, node: NodeId) -> Option<String> {
+        self.node_ref(node)
+            .value()
+            .as_element()
+            .map(|it| it.name().to_string())
+    }
+
+    pub fn outer_html(&self, node: NodeId) -> String {
+        match ElementRef::wrap(self.node_ref(node)) {
+            Some(element) => element.html(),
+            None if node == self.root() => self.html.root_element().html(),
+            None => self.text(node),
+        }
+    }
+
+    fn node_ref(&self, id: NodeId) -> NodeRef<'_, Node> {
+        // ids never leave the document they come from.
+        self.html.tree.get(id).unwrap()
+    }
+}
+
+fn with_node<T>(
+    state: &mut OpState,
+    op: &str,
+    rid: u32,
+    handle: u32,
+    f: impl FnOnce(&HtmlDocument, NodeId) -> Result<T, Error>,
+) -> ResultJson<T> {
+    let result = state
+        .resource_table
+        .get::<HtmlDocument>(rid)
+        .map_err(|_| Error::resource_error(rid, "Html document already closed"))
+        .and_then(|document| {
+            document.permissions.check_op(op)?;
+
+            let node = document.node(handle).ok_or_else(|| {
+                Error::resource_error(rid, format!("Html node {} doesn't exist", handle))
+            })?;
+
+            f(&document, node)
+        });
+
+    result.to_result_json(state)
+}
+
+fn xpath_error(expression: &str, message: impl ToString) -> Error {
+    Error::SelectorError {
+        selector: expression.to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn evaluate_xpath(document: &HtmlDocument, node: NodeId, expression: &str) -> Result<Value, Error> {
+    let xpath = XPath::parse(expression).map_err(|err| xpath_error(expression, err))?;
+
+    document
+        .xpath(node, &xpath)
+        .map_err(|err| xpath_error(expression, err))
+}
+
+#[op]
+pub fn op_html_parse(state: &mut OpState, text: String) -> ResultJson<u32> {
+    // documents keep the permissions of the module that parsed them, parse
+    // a response with `op_http_response_html` after an `await`.
+    let permissions = try_json!(current_permissions(state)
+        .ok_or_else(|| Error::PermissionDenied(
+            "html can only be parsed while a module is loading or running a call".to_string()
+        ))
+        .to_result_json(state));
+    try_json!(permissions.check_op("op_html_parse").to_result_json(state));
+
+    ResultJson::Ok(
+        state
+            .resource_table
+            .add(HtmlDocument::parse(&text, permissions)),
+    )
+}
+
+#[op]
+pub fn op_html_close(state: &mut OpState, rid: u32) -> ResultJson<()> {
+    state
+        .resource_table
+        .close(rid)
+        .map_err(|_| Error::resource_error(rid, "Html document already closed"))
+        .to_result_json(state)
+}
+
+#[op]
+pub fn op_html_select(
+    state: &mut OpState,
+    rid: u32,
+    node: u32,
+    selector: String,
+) -> ResultJson<Vec<u32>> {
+    with_node(state, "op_html_select", rid, node, |document, node| {
+        let parsed = Selector::parse(&selector).map_err(|err| Error::SelectorError {
+            selector: selector.clone(),
+            message: format!("{:?}", err),
+        })?;
+
+        Ok(document
+            .select(node, &parsed)
+            .into_iter()
+            .map(|it| document.handle(it))
+            .collect())
+    })
+}
+
+#[op]
+pub fn op_html_xpath(
+    state: &mut OpState,
+    rid: u32,
+    node: u32,
+    expression: String,
+) -> ResultJson<Vec<u32>> {
+    with_node(state, "op_html_xpath", rid, node, |document, node| {
+        let nodes = match evaluate_xpath(document, node, &expression)? {
+            Value::Nodes(nodes) => nodes,
+            _ => return Err(xpath_error(&expression, "expression doesn't return nodes")),
+        };
+
+        nodes
+            .into_iter()
+            .map(|it| match it {
+                XPathNode::Node(id) => Ok(document.handle(id)),
+                // handles only point to nodes of the tree.
+                XPathNode::Attribute(..) => Err(xpath_error(
+                    &expression,
+                    "attributes aren't nodes, use xpathString for their value",
+                )),
+            })
+            .collect()
+    })
+}
+
+#[op]
+pub fn op_html_xpath_string(
+    state: &mut OpState,
+    rid: u32,
+    node: u32,
+    expression: String,
+) -> ResultJson<String> {
+    with_node(
+        state,
+        "op_html_xpath_string",
+        rid,
+        node,
+        |document, node| Ok(evaluate_xpath(document, node, &expression)?.string(document)),
+    )
+}
+
+#[op]
+pub fn op_html_text(state: &mut OpState, rid: u32, node: u32) -> ResultJson<String> {
+    with_node(state, "op_html_text", rid, node, |document, node| {
+        Ok(document.text(node))
+    })
+}
+
+#[op]
+pub fn op_html_attribute(
+    state: &mut OpState,
+    rid: u32,
+    node: u32,
+    name: String,
+) -> ResultJson<Option<String>> {
+    with_node(state, "op_html_attribute", rid, node, |document, node| {
+        Ok(document.attribute(node, &name))
+    })
+}
+
+#[op]
+pub fn op_html_name(state: &mut OpState, rid: u32, node: u32) -> ResultJson<Option<String>> {
+    with_node(state, "op_html_name", rid, node, |document, node| {
+        Ok(document.name(node))
+    })
+}
+
+#[op]
+pub fn op_html_outer_html(state: &mut OpState, rid: u32, node: u32) -> ResultJson<String> {
+    with_node(state, "op_html_outer_html", rid, node, |document, node| {
+        Ok(document.outer_html(node))
+    })
+}
+
+pub fn init() -> Extension {
+    ExtensionBuilder::default()
+        .ops(vec![
+            op_html_parse::decl(),
+            op_html_close::decl(),
+            op_html_select::decl(),
+            op_html_xpath::decl(),
+            op_html_xpath_string::decl(),
+            op_html_text::decl(),
+            op_html_attribute::decl(),
+            op_html_name::decl(),
+            op_html_outer_html::decl(),
+        ])
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permission::ModulePermissions;
+
+    const HTML: &str = r#"
+        <html>
+          <body>
+            <ul class="row-content-chapter">
+              <li><a class="chapter-name text-nowrap" href="/chapter-2">Chapter 2</a></li>
+              <li><a class="chapter-name" href="/chapter-1">Chapter 1<b>
+            </ul>
+          </body>
+        </html>
+    "#;
+
+    fn parse() -> HtmlDocument {
+        HtmlDocument::parse(
+            HTML,
+            PermissionState::new(ModulePermissions::unrestricted()),
+        )
+    }
+
+    #[test]
+    fn select() {
+        let document = parse();
+        let selector = Selector::parse("ul.row-content-chapter a.chapter-name").unwrap();
+        let nodes = document.select(document.root(), &selector);
+
+        // unclosed tags don't stop the parser.
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(document.text(nodes[0]), "Chapter 2");
+        assert_eq!(document.name(nodes[1]).as_deref(), Some("a"));
+        assert_eq!(
+            document.attribute(nodes[1], "href").as_deref(),
+            Some("/chapter-1")
+        );
+    }
+
+    #[test]
+    fn handle() {
+        let document = parse();
+        assert_eq!(document.node(ROOT_HANDLE), Some(document.root()));
+
+        let selector = Selector::parse("a").unwrap();
+        let nodes = document.select(document.root(), &selector);
+
+        // selecting again gives the same handles without storing anything.
+        let handles: Vec<_> = nodes.iter().map(|it| document.handle(*it)).collect();
+        let again = document.select(document.root(), &selector);
+        assert_eq!(
+            again
+                .iter()
+                .map(|it| document.handle(*it))
+                .collect::<Vec<_>>(),
+            handles
+        );
+        assert_eq!(document.node(handles[0]), Some(nodes[0]));
+        assert_eq!(document.node(u32::MAX), None);
+    }
+
+    #[test]
+    fn xpath() {
+        let document = parse();
+        let xpath = XPath::parse("//a[contains(@class, 'chapter-name')]").unwrap();
+        let selector = Selector::parse("a.chapter-name").unwrap();
+        let selected = document.select(document.root(), &selector);
+
+        // xpath gives the same nodes, so the same handles, as css.
+        assert_eq!(
+            document.xpath(document.root(), &xpath).unwrap(),
+            Value::Nodes(selected.iter().map(|it| XPathNode::Node(*it)).collect())
+        );
+
+        let href = XPath::parse("@href").unwrap();
+        let value = document.xpath(selected[1], &href).unwrap();
+        assert_eq!(value.string(&document), "/chapter-1");
+    }
+
+    #[test]
+    fn permission() {
+        let permissions = ModulePermissions {
+            ops: Some(vec!["op_html_select".to_string()]),
+            ..Default::default()
+        };
+        let document = HtmlDocument::parse(HTML, PermissionState::new(permissions));
+
+        assert!(document.permissions().check_op("op_html_select").is_ok());
+        assert!(matches!(
+            document.permissions().check_op("op_html_text"),
+            Err(Error::PermissionDenied(..))
+        ));
+        // xpath is allowed separately from css selectors.
+        assert!(matches!(
+            document.permissions().check_op("op_html_xpath"),
+            Err(Error::PermissionDenied(..))
+        ));
+    }
+}
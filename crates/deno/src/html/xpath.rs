@@ -0,0 +1,1258 @@
+//! XPath 1.0 over the parsed HTML tree.
+//!
+//! Covers location paths with every axis but `namespace`, predicates,
+//! operators and the core function library without `id`, `lang` and the
+//! namespace functions. Names are matched case-insensitively like HTML.
+
+use std::iter::once;
+
+use ego_tree::{NodeId, NodeRef};
+use scraper::Node;
+use thiserror::Error;
+
+use super::HtmlDocument;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{0}")]
+pub struct XPathError(String);
+
+fn error<T>(message: impl ToString) -> Result<T, XPathError> {
+    Err(XPathError(message.to_string()))
+}
+
+/// Node selected by an expression, attributes aren't nodes of the tree so
+/// they don't have a handle.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum XPathNode {
+    Node(NodeId),
+    Attribute(NodeId, String),
+}
+
+impl XPathNode {
+    /// Text content of the node, the value for attributes.
+    pub fn text(&self, document: &HtmlDocument) -> String {
+        match self {
+            Self::Node(id) => document.text(*id),
+            Self::Attribute(id, name) => document.attribute(*id, name).unwrap_or_default(),
+        }
+    }
+
+    /// Element or attribute name.
+    pub fn name(&self, document: &HtmlDocument) -> Option<String> {
+        match self {
+            Self::Node(id) => document.name(*id),
+            Self::Attribute(_, name) => Some(name.clone()),
+        }
+    }
+
+    fn order_key<'a>(&'a self, document: &HtmlDocument) -> (u32, Option<&'a str>) {
+        // handles are positions in document order.
+        match self {
+            Self::Node(id) => (document.handle(*id), None),
+            Self::Attribute(id, name) => (document.handle(*id), Some(name)),
+        }
+    }
+}
+
+/// Sort nodes in document order, removing duplicates.
+fn sort(document: &HtmlDocument, nodes: &mut Vec<XPathNode>) {
+    nodes.sort_by(|a, b| a.order_key(document).cmp(&b.order_key(document)));
+    nodes.dedup();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nodes(Vec<XPathNode>),
+    String(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+impl Value {
+    /// Same as XPath `string()`, the first node is used for node-sets.
+    pub fn string(&self, document: &HtmlDocument) -> String {
+        match self {
+            Self::Nodes(nodes) => nodes
+                .first()
+                .map(|it| it.text(document))
+                .unwrap_or_default(),
+            Self::String(string) => string.clone(),
+            Self::Number(number) => number_to_string(*number),
+            Self::Boolean(boolean) => boolean.to_string(),
+        }
+    }
+
+    pub fn number(&self, document: &HtmlDocument) -> f64 {
+        match self {
+            Self::Number(number) => *number,
+            Self::Boolean(boolean) => *boolean as u8 as f64,
+            _ => string_to_number(&self.string(document)),
+        }
+    }
+
+    pub fn boolean(&self) -> bool {
+        match self {
+            Self::Nodes(nodes) => !nodes.is_empty(),
+            Self::String(string) => !string.is_empty(),
+            Self::Number(number) => *number != 0.0 && !number.is_nan(),
+            Self::Boolean(boolean) => *boolean,
+        }
+    }
+}
+
+fn number_to_string(number: f64) -> String {
+    if number.is_nan() {
+        "NaN".to_string()
+    } else if number.is_infinite() && number > 0.0 {
+        "Infinity".to_string()
+    } else if number.is_infinite() {
+        "-Infinity".to_string()
+    } else if number.fract() == 0.0 && number.abs() < 1e15 {
+        (number as i64).to_string()
+    } else {
+        number.to_string()
+    }
+}
+
+fn string_to_number(string: &str) -> f64 {
+    let string = string.trim();
+    let valid = string
+        .strip_prefix('-')
+        .unwrap_or(string)
+        .chars()
+        .all(|it| it.is_ascii_digit() || it == '.');
+
+    if valid {
+        string.parse().unwrap_or(f64::NAN)
+    } else {
+        f64::NAN
+    }
+}
+
+/// A parsed XPath expression.
+#[derive(Debug, Clone)]
+pub struct XPath {
+    expr: Expr,
+}
+
+impl XPath {
+    pub fn parse(expression: &str) -> Result<Self, XPathError> {
+        let mut parser = Parser {
+            tokens: tokenize(expression)?,
+            position: 0,
+        };
+
+        let expr = parser.expr()?;
+        match parser.peek() {
+            None => Ok(Self { expr }),
+            Some(token) => error(format!("unexpected {:?}", token)),
+        }
+    }
+
+    pub fn evaluate(&self, document: &HtmlDocument, node: &XPathNode) -> Result<Value, XPathError> {
+        Context {
+            document,
+            node: node.clone(),
+            position: 1,
+            size: 1,
+        }
+        .evaluate(&self.expr)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Slash,
+    DoubleSlash,
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    At,
+    Comma,
+    Dot,
+    DotDot,
+    ColonColon,
+    Star,
+    Name(String),
+    Literal(String),
+    Number(f64),
+    Operator(Operator),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Union,
+}
+
+impl Operator {
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Eq | Self::Ne => 3,
+            Self::Lt | Self::Le | Self::Gt | Self::Ge => 4,
+            Self::Add | Self::Sub => 5,
+            Self::Mul | Self::Div | Self::Mod => 6,
+            Self::Union => 7,
+        }
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, XPathError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        let next = chars.get(index + 1).copied();
+
+        if c.is_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        // `*` and `and`, `or`, `div`, `mod` are operators only when they
+        // follow something that can end an operand.
+        let operator_allowed = matches!(
+            tokens.last(),
+            Some(last) if !matches!(
+                last,
+                Token::At
+                    | Token::ColonColon
+                    | Token::LParen
+                    | Token::LBracket
+                    | Token::Comma
+                    | Token::Slash
+                    | Token::DoubleSlash
+                    | Token::Operator(_)
+            )
+        );
+
+        let (token, length) = match c {
+            '/' if next == Some('/') => (Token::DoubleSlash, 2),
+            '/' => (Token::Slash, 1),
+            '[' => (Token::LBracket, 1),
+            ']' => (Token::RBracket, 1),
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '@' => (Token::At, 1),
+            ',' => (Token::Comma, 1),
+            '.' if next == Some('.') => (Token::DotDot, 2),
+            '.' if !next.map_or(false, |it| it.is_ascii_digit()) => (Token::Dot, 1),
+            ':' if next == Some(':') => (Token::ColonColon, 2),
+            '|' => (Token::Operator(Operator::Union), 1),
+            '=' => (Token::Operator(Operator::Eq), 1),
+            '!' if next == Some('=') => (Token::Operator(Operator::Ne), 2),
+            '<' if next == Some('=') => (Token::Operator(Operator::Le), 2),
+            '<' => (Token::Operator(Operator::Lt), 1),
+            '>' if next == Some('=') => (Token::Operator(Operator::Ge), 2),
+            '>' => (Token::Operator(Operator::Gt), 1),
+            '+' => (Token::Operator(Operator::Add), 1),
+            '-' => (Token::Operator(Operator::Sub), 1),
+            '*' if operator_allowed => (Token::Operator(Operator::Mul), 1),
+            '*' => (Token::Star, 1),
+            '"' | '\'' => {
+                let end = chars[index + 1..]
+                    .iter()
+                    .position(|it| *it == c)
+                    .map(|it| index + 1 + it);
+
+                match end {
+                    Some(end) => (
+                        Token::Literal(chars[index + 1..end].iter().collect()),
+                        end - index + 1,
+                    ),
+                    None => return error(format!("unterminated string at {}", index)),
+                }
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let length = chars[index..]
+                    .iter()
+                    .take_while(|it| it.is_ascii_digit() || **it == '.')
+                    .count();
+                let number: String = chars[index..index + length].iter().collect();
+
+                match number.parse() {
+                    Ok(number) => (Token::Number(number), length),
+                    Err(_) => return error(format!("invalid number {}", number)),
+                }
+            }
+            c if is_name_start(c) => {
+                let mut end = index;
+                while end < chars.len() {
+                    let prefixed = chars[end] == ':'
+                        && chars.get(end + 1).map_or(false, |it| is_name_start(*it));
+
+                    if is_name_char(chars[end]) || prefixed {
+                        end += 1;
+                    } else {
+                        break;
+                    }
+                }
+
+                let name: String = chars[index..end].iter().collect();
+                let token = match name.as_str() {
+                    "and" if operator_allowed => Token::Operator(Operator::And),
+                    "or" if operator_allowed => Token::Operator(Operator::Or),
+                    "div" if operator_allowed => Token::Operator(Operator::Div),
+                    "mod" if operator_allowed => Token::Operator(Operator::Mod),
+                    _ => Token::Name(name),
+                };
+
+                (token, end - index)
+            }
+            c => return error(format!("unexpected character {:?} at {}", c, index)),
+        };
+
+        tokens.push(token);
+        index += length;
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Path(Path),
+    /// Primary expression followed by predicates or a relative path,
+    /// e.g. `(//a)[1]/@href`.
+    Filter {
+        primary: Box<Expr>,
+        predicates: Vec<Expr>,
+        steps: Vec<Step>,
+    },
+    Literal(String),
+    Number(f64),
+    Function(String, Vec<Expr>),
+    Binary(Box<Expr>, Operator, Box<Expr>),
+    Negate(Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+struct Path {
+    absolute: bool,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    axis: Axis,
+    test: NodeTest,
+    predicates: Vec<Expr>,
+}
+
+impl Step {
+    fn new(axis: Axis, test: NodeTest) -> Self {
+        Self {
+            axis,
+            test,
+            predicates: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    Ancestor,
+    AncestorOrSelf,
+    Attribute,
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Following,
+    FollowingSibling,
+    Parent,
+    Preceding,
+    PrecedingSibling,
+    SelfNode,
+}
+
+impl Axis {
+    fn parse(name: &str) -> Result<Self, XPathError> {
+        Ok(match name {
+            "ancestor" => Self::Ancestor,
+            "ancestor-or-self" => Self::AncestorOrSelf,
+            "attribute" => Self::Attribute,
+            "child" => Self::Child,
+            "descendant" => Self::Descendant,
+            "descendant-or-self" => Self::DescendantOrSelf,
+            "following" => Self::Following,
+            "following-sibling" => Self::FollowingSibling,
+            "parent" => Self::Parent,
+            "preceding" => Self::Preceding,
+            "preceding-sibling" => Self::PrecedingSibling,
+            "self" => Self::SelfNode,
+            _ => return error(format!("unsupported axis {}", name)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NodeTest {
+    Name(String),
+    /// `*`
+    Any,
+    Text,
+    Comment,
+    Node,
+}
+
+fn is_node_type(name: &str) -> bool {
+    matches!(name, "text" | "node" | "comment" | "processing-instruction")
+}
+
+/// Minimum and maximum number of arguments, `None` for unknown functions.
+fn function_arity(name: &str) -> Option<(usize, usize)> {
+    Some(match name {
+        "last" | "position" | "true" | "false" => (0, 0),
+        "name" | "local-name" | "string" | "string-length" | "normalize-space" | "number" => (0, 1),
+        "count" | "boolean" | "not" | "sum" | "floor" | "ceiling" | "round" => (1, 1),
+        "starts-with" | "contains" | "substring-before" | "substring-after" => (2, 2),
+        "substring" => (2, 3),
+        "translate" => (3, 3),
+        "concat" => (2, usize::MAX),
+        _ => return None,
+    })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.position + offset)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), XPathError> {
+        match self.next() {
+            Some(it) if it == token => Ok(()),
+            Some(it) => error(format!("expected {:?}, found {:?}", token, it)),
+            None => error(format!("expected {:?}, found end of expression", token)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, XPathError> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, min_precedence: u8) -> Result<Expr, XPathError> {
+        let mut left = self.unary()?;
+
+        while let Some(Token::Operator(operator)) = self.peek() {
+            let operator = *operator;
+            let precedence = operator.precedence();
+            if operator == Operator::Union || precedence < min_precedence {
+                break;
+            }
+
+            self.position += 1;
+            let right = self.binary(precedence + 1)?;
+            left = Expr::Binary(Box::new(left), operator, Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, XPathError> {
+        if self.eat(&Token::Operator(Operator::Sub)) {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+
+        let mut left = self.path_expr()?;
+        while self.eat(&Token::Operator(Operator::Union)) {
+            let right = self.path_expr()?;
+            left = Expr::Binary(Box::new(left), Operator::Union, Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn path_expr(&mut self) -> Result<Expr, XPathError> {
+        let is_filter = match self.peek() {
+            Some(Token::Literal(_) | Token::Number(_) | Token::LParen) => true,
+            Some(Token::Name(name)) => {
+                !is_node_type(name) && self.peek_at(1) == Some(&Token::LParen)
+            }
+            _ => false,
+        };
+
+        if !is_filter {
+            return Ok(Expr::Path(self.location_path()?));
+        }
+
+        let primary = self.primary()?;
+        let predicates = self.predicates()?;
+        let steps = self.relative_steps(Vec::new())?;
+
+        if predicates.is_empty() && steps.is_empty() {
+            Ok(primary)
+        } else {
+            Ok(Expr::Filter {
+                primary: Box::new(primary),
+                predicates,
+                steps,
+            })
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, XPathError> {
+        match self.next() {
+            Some(Token::Literal(literal)) => Ok(Expr::Literal(literal)),
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::LParen) => {
+                let expr = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Name(name)) => {
+                self.expect(Token::LParen)?;
+
+                let mut args = Vec::new();
+                if !self.eat(&Token::RParen) {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(&Token::RParen) {
+                            break;
+                        }
+                        self.expect(Token::Comma)?;
+                    }
+                }
+
+                match function_arity(&name) {
+                    Some((min, max)) if (min..=max).contains(&args.len()) => {
+                        Ok(Expr::Function(name, args))
+                    }
+                    Some(_) => error(format!("wrong number of arguments for {}()", name)),
+                    None => error(format!("unknown function {}()", name)),
+                }
+            }
+            Some(token) => error(format!("unexpected {:?}", token)),
+            None => error("unexpected end of expression"),
+        }
+    }
+
+    fn location_path(&mut self) -> Result<Path, XPathError> {
+        let mut steps = Vec::new();
+
+        let absolute = match self.peek() {
+            Some(Token::Slash) => {
+                self.position += 1;
+
+                // `/` alone selects the document.
+                if !self.starts_step() {
+                    return Ok(Path {
+                        absolute: true,
+                        steps,
+                    });
+                }
+
+                true
+            }
+            Some(Token::DoubleSlash) => {
+                self.position += 1;
+                steps.push(Step::new(Axis::DescendantOrSelf, NodeTest::Node));
+                true
+            }
+            _ => false,
+        };
+
+        steps.push(self.step()?);
+        let steps = self.relative_steps(steps)?;
+
+        Ok(Path { absolute, steps })
+    }
+
+    fn relative_steps(&mut self, mut steps: Vec<Step>) -> Result<Vec<Step>, XPathError> {
+        loop {
+            if self.eat(&Token::Slash) {
+                steps.push(self.step()?);
+            } else if self.eat(&Token::DoubleSlash) {
+                steps.push(Step::new(Axis::DescendantOrSelf, NodeTest::Node));
+                steps.push(self.step()?);
+            } else {
+                return Ok(steps);
+            }
+        }
+    }
+
+    fn starts_step(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token::Dot | Token::DotDot | Token::At | Token::Star | Token::Name(_))
+        )
+    }
+
+    fn step(&mut self) -> Result<Step, XPathError> {
+        if self.eat(&Token::Dot) {
+            return Ok(Step::new(Axis::SelfNode, NodeTest::Node));
+        }
+
+        if self.eat(&Token::DotDot) {
+            return Ok(Step::new(Axis::Parent, NodeTest::Node));
+        }
+
+        let axis = if self.eat(&Token::At) {
+            Axis::Attribute
+        } else if let (Some(Token::Name(name)), Some(Token::ColonColon)) =
+            (self.peek(), self.peek_at(1))
+        {
+            let axis = Axis::parse(name)?;
+            self.position += 2;
+            axis
+        } else {
+            Axis::Child
+        };
+
+        let test = match self.next() {
+            Some(Token::Star) => NodeTest::Any,
+            Some(Token::Name(name))
+                if is_node_type(&name) && self.peek() == Some(&Token::LParen) =>
+            {
+                self.position += 1;
+                self.expect(Token::RParen)?;
+
+                match name.as_str() {
+                    "text" => NodeTest::Text,
+                    "comment" => NodeTest::Comment,
+                    "node" => NodeTest::Node,
+                    _ => return error(format!("unsupported node test {}()", name)),
+                }
+            }
+            Some(Token::Name(name)) => NodeTest::Name(name),
+            Some(token) => return error(format!("unexpected {:?}", token)),
+            None => return error("unexpected end of expression"),
+        };
+
+        Ok(Step {
+            axis,
+            test,
+            predicates: self.predicates()?,
+        })
+    }
+
+    fn predicates(&mut self) -> Result<Vec<Expr>, XPathError> {
+        let mut predicates = Vec::new();
+
+        while self.eat(&Token::LBracket) {
+            predicates.push(self.expr()?);
+            self.expect(Token::RBracket)?;
+        }
+
+        Ok(predicates)
+    }
+}
+
+struct Context<'a> {
+    document: &'a HtmlDocument,
+    node: XPathNode,
+    position: usize,
+    size: usize,
+}
+
+impl<'a> Context<'a> {
+    fn with_node(&self, node: XPathNode, position: usize, size: usize) -> Self {
+        Self {
+            document: self.document,
+            node,
+            position,
+            size,
+        }
+    }
+
+    fn evaluate(&self, expr: &Expr) -> Result<Value, XPathError> {
+        match expr {
+            Expr::Literal(literal) => Ok(Value::String(literal.clone())),
+            Expr::Number(number) => Ok(Value::Number(*number)),
+            Expr::Path(path) => {
+                let start = if path.absolute {
+                    XPathNode::Node(self.document.root())
+                } else {
+                    self.node.clone()
+                };
+
+                Ok(Value::Nodes(self.steps(vec![start], &path.steps)?))
+            }
+            Expr::Filter {
+                primary,
+                predicates,
+                steps,
+            } => {
+                let mut nodes = self.nodes(primary)?;
+                sort(self.document, &mut nodes);
+
+                let nodes = self.filter(nodes, predicates)?;
+                Ok(Value::Nodes(self.steps(nodes, steps)?))
+            }
+            Expr::Negate(expr) => Ok(Value::Number(-self.evaluate(expr)?.number(self.document))),
+            Expr::Binary(left, operator, right) => self.binary(left, *operator, right),
+            Expr::Function(name, args) => self.function(name, args),
+        }
+    }
+
+    fn nodes(&self, expr: &Expr) -> Result<Vec<XPathNode>, XPathError> {
+        match self.evaluate(expr)? {
+            Value::Nodes(nodes) => Ok(nodes),
+            other => error(format!("expected node-set, found {:?}", other)),
+        }
+    }
+
+    fn steps(
+        &self,
+        mut nodes: Vec<XPathNode>,
+        steps: &[Step],
+    ) -> Result<Vec<XPathNode>, XPathError> {
+        for step in steps {
+            let mut result = Vec::new();
+
+            for node in &nodes {
+                let candidates = self
+                    .axis(node, step.axis)
+                    .into_iter()
+                    .filter(|it| self.matches(it, step))
+                    .collect();
+
+                result.extend(self.filter(candidates, &step.predicates)?);
+            }
+
+            sort(self.document, &mut result);
+            nodes = result;
+        }
+
+        Ok(nodes)
+    }
+
+    fn filter(
+        &self,
+        mut nodes: Vec<XPathNode>,
+        predicates: &[Expr],
+    ) -> Result<Vec<XPathNode>, XPathError> {
+        for predicate in predicates {
+            let size = nodes.len();
+            let mut kept = Vec::new();
+
+            for (index, node) in nodes.into_iter().enumerate() {
+                let position = index + 1;
+                let keep = match self
+                    .with_node(node.clone(), position, size)
+                    .evaluate(predicate)?
+                {
+                    Value::Number(number) => number == position as f64,
+                    value => value.boolean(),
+                };
+
+                if keep {
+                    kept.push(node);
+                }
+            }
+
+            nodes = kept;
+        }
+
+        Ok(nodes)
+    }
+
+    /// Nodes of `axis` in axis order, reverse axes start from the nearest
+    /// node.
+    fn axis(&self, node: &XPathNode, axis: Axis) -> Vec<XPathNode> {
+        let (id, is_attribute) = match node {
+            XPathNode::Node(id) => (*id, false),
+            XPathNode::Attribute(id, _) => (*id, true),
+        };
+        let node_ref = self.document.node_ref(id);
+
+        match axis {
+            Axis::SelfNode => vec![node.clone()],
+            Axis::Parent if is_attribute => vec![XPathNode::Node(id)],
+            Axis::Ancestor | Axis::AncestorOrSelf => {
+                let mut nodes = Vec::new();
+                if axis == Axis::AncestorOrSelf {
+                    nodes.push(node.clone());
+                }
+                if is_attribute {
+                    nodes.push(XPathNode::Node(id));
+                }
+                nodes.extend(to_nodes(node_ref.ancestors()));
+                nodes
+            }
+            // attributes don't have children or siblings.
+            _ if is_attribute => Vec::new(),
+            Axis::Parent => to_nodes(node_ref.parent().into_iter()),
+            Axis::Attribute => match node_ref.value().as_element() {
+                Some(element) => element
+                    .attrs()
+                    .map(|(name, _)| XPathNode::Attribute(id, name.to_string()))
+                    .collect(),
+                None => Vec::new(),
+            },
+            Axis::Child => to_nodes(node_ref.children()),
+            Axis::Descendant => to_nodes(node_ref.descendants().skip(1)),
+            Axis::DescendantOrSelf => to_nodes(node_ref.descendants()),
+            Axis::FollowingSibling => to_nodes(node_ref.next_siblings()),
+            Axis::PrecedingSibling => to_nodes(node_ref.prev_siblings()),
+            Axis::Following => to_nodes(
+                &mut once(node_ref)
+                    .chain(node_ref.ancestors())
+                    .flat_map(|it| it.next_siblings())
+                    .flat_map(|it| it.descendants()),
+            ),
+            Axis::Preceding => to_nodes(
+                &mut once(node_ref)
+                    .chain(node_ref.ancestors())
+                    .flat_map(|it| it.prev_siblings())
+                    .flat_map(|it| it.descendants().collect::<Vec<_>>().into_iter().rev()),
+            ),
+        }
+    }
+
+    fn matches(&self, node: &XPathNode, step: &Step) -> bool {
+        match (node, &step.test) {
+            (_, NodeTest::Node) => true,
+            (XPathNode::Attribute(_, name), NodeTest::Name(test)) => {
+                step.axis == Axis::Attribute && name.eq_ignore_ascii_case(test)
+            }
+            (XPathNode::Attribute(..), NodeTest::Any) => step.axis == Axis::Attribute,
+            (XPathNode::Attribute(..), _) => false,
+            (XPathNode::Node(id), test) => {
+                let value = self.document.node_ref(*id).value();
+
+                match test {
+                    NodeTest::Name(name) => value
+                        .as_element()
+                        .map_or(false, |it| it.name().eq_ignore_ascii_case(name)),
+                    NodeTest::Any => value.is_element(),
+                    NodeTest::Text => value.is_text(),
+                    NodeTest::Comment => value.is_comment(),
+                    NodeTest::Node => true,
+                }
+            }
+        }
+    }
+
+    fn binary(&self, left: &Expr, operator: Operator, right: &Expr) -> Result<Value, XPathError> {
+        let document = self.document;
+
+        match operator {
+            Operator::Or => Ok(Value::Boolean(
+                self.evaluate(left)?.boolean() || self.evaluate(right)?.boolean(),
+            )),
+            Operator::And => Ok(Value::Boolean(
+                self.evaluate(left)?.boolean() && self.evaluate(right)?.boolean(),
+            )),
+            Operator::Union => {
+                let mut nodes = self.nodes(left)?;
+                nodes.extend(self.nodes(right)?);
+                sort(document, &mut nodes);
+
+                Ok(Value::Nodes(nodes))
+            }
+            Operator::Eq
+            | Operator::Ne
+            | Operator::Lt
+            | Operator::Le
+            | Operator::Gt
+            | Operator::Ge => {
+                let (left, right) = (self.evaluate(left)?, self.evaluate(right)?);
+                Ok(Value::Boolean(self.compare(&left, operator, &right)))
+            }
+            Operator::Add | Operator::Sub | Operator::Mul | Operator::Div | Operator::Mod => {
+                let left = self.evaluate(left)?.number(document);
+                let right = self.evaluate(right)?.number(document);
+
+                Ok(Value::Number(match operator {
+                    Operator::Add => left + right,
+                    Operator::Sub => left - right,
+                    Operator::Mul => left * right,
+                    Operator::Div => left / right,
+                    _ => left % right,
+                }))
+            }
+        }
+    }
+
+    fn compare(&self, left: &Value, operator: Operator, right: &Value) -> bool {
+        let document = self.document;
+        let text = |node: &XPathNode| Value::String(node.text(document));
+
+        match (left, right) {
+            (Value::Nodes(left), Value::Nodes(right)) => left.iter().any(|left| {
+                let left = text(left);
+                right
+                    .iter()
+                    .any(|right| self.compare_atoms(&left, operator, &text(right)))
+            }),
+            (Value::Nodes(nodes), Value::Boolean(_)) => {
+                self.compare_atoms(&Value::Boolean(!nodes.is_empty()), operator, right)
+            }
+            (Value::Boolean(_), Value::Nodes(nodes)) => {
+                self.compare_atoms(left, operator, &Value::Boolean(!nodes.is_empty()))
+            }
+            (Value::Nodes(nodes), _) => nodes
+                .iter()
+                .any(|it| self.compare_atoms(&text(it), operator, right)),
+            (_, Value::Nodes(nodes)) => nodes
+                .iter()
+                .any(|it| self.compare_atoms(left, operator, &text(it))),
+            _ => self.compare_atoms(left, operator, right),
+        }
+    }
+
+    fn compare_atoms(&self, left: &Value, operator: Operator, right: &Value) -> bool {
+        let document = self.document;
+
+        match operator {
+            Operator::Eq | Operator::Ne => {
+                let equal = match (left, right) {
+                    (Value::Boolean(_), _) | (_, Value::Boolean(_)) => {
+                        left.boolean() == right.boolean()
+                    }
+                    (Value::Number(_), _) | (_, Value::Number(_)) => {
+                        left.number(document) == right.number(document)
+                    }
+                    _ => left.string(document) == right.string(document),
+                };
+
+                equal == (operator == Operator::Eq)
+            }
+            _ => {
+                let (left, right) = (left.number(document), right.number(document));
+
+                match operator {
+                    Operator::Lt => left < right,
+                    Operator::Le => left <= right,
+                    Operator::Gt => left > right,
+                    _ => left >= right,
+                }
+            }
+        }
+    }
+
+    fn function(&self, name: &str, args: &[Expr]) -> Result<Value, XPathError> {
+        let document = self.document;
+
+        let string = |index: usize| -> Result<String, XPathError> {
+            match args.get(index) {
+                Some(arg) => Ok(self.evaluate(arg)?.string(document)),
+                None => Ok(self.node.text(document)),
+            }
+        };
+        let number = |index: usize| -> Result<f64, XPathError> {
+            match args.get(index) {
+                Some(arg) => Ok(self.evaluate(arg)?.number(document)),
+                None => Ok(string_to_number(&self.node.text(document))),
+            }
+        };
+        let first_node = || -> Result<Option<XPathNode>, XPathError> {
+            match args.first() {
+                Some(arg) => {
+                    let mut nodes = self.nodes(arg)?;
+                    sort(document, &mut nodes);
+                    Ok(nodes.into_iter().next())
+                }
+                None => Ok(Some(self.node.clone())),
+            }
+        };
+
+        Ok(match name {
+            "last" => Value::Number(self.size as f64),
+            "position" => Value::Number(self.position as f64),
+            "count" => Value::Number(self.nodes(&args[0])?.len() as f64),
+            "name" | "local-name" => Value::String(
+                first_node()?
+                    .and_then(|it| it.name(document))
+                    .unwrap_or_default(),
+            ),
+            "string" => Value::String(string(0)?),
+            "concat" => Value::String((0..args.len()).map(string).collect::<Result<String, _>>()?),
+            "starts-with" => Value::Boolean(string(0)?.starts_with(&string(1)?)),
+            "contains" => Value::Boolean(string(0)?.contains(&string(1)?)),
+            "substring-before" => {
+                let (haystack, needle) = (string(0)?, string(1)?);
+                Value::String(
+                    haystack
+                        .split_once(&needle)
+                        .map(|(before, _)| before.to_string())
+                        .unwrap_or_default(),
+                )
+            }
+            "substring-after" => {
+                let (haystack, needle) = (string(0)?, string(1)?);
+                Value::String(
+                    haystack
+                        .split_once(&needle)
+                        .map(|(_, after)| after.to_string())
+                        .unwrap_or_default(),
+                )
+            }
+            "substring" => {
+                let haystack = string(0)?;
+                let start = round(number(1)?);
+                let end = match args.len() {
+                    3 => start + round(number(2)?),
+                    _ => f64::INFINITY,
+                };
+
+                Value::String(
+                    haystack
+                        .chars()
+                        .enumerate()
+                        .filter(|(index, _)| {
+                            let position = (index + 1) as f64;
+                            position >= start && position < end
+                        })
+                        .map(|(_, it)| it)
+                        .collect(),
+                )
+            }
+            "string-length" => Value::Number(string(0)?.chars().count() as f64),
+            "normalize-space" => {
+                Value::String(string(0)?.split_whitespace().collect::<Vec<_>>().join(" "))
+            }
+            "translate" => {
+                let (haystack, from, to) = (string(0)?, string(1)?, string(2)?);
+                let to: Vec<char> = to.chars().collect();
+
+                Value::String(
+                    haystack
+                        .chars()
+                        .filter_map(|it| match from.chars().position(|from| from == it) {
+                            Some(index) => to.get(index).copied(),
+                            None => Some(it),
+                        })
+                        .collect(),
+                )
+            }
+            "boolean" => Value::Boolean(self.evaluate(&args[0])?.boolean()),
+            "not" => Value::Boolean(!self.evaluate(&args[0])?.boolean()),
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            "number" => Value::Number(number(0)?),
+            "sum" => Value::Number(
+                self.nodes(&args[0])?
+                    .iter()
+                    .map(|it| string_to_number(&it.text(document)))
+                    .sum(),
+            ),
+            "floor" => Value::Number(number(0)?.floor()),
+            "ceiling" => Value::Number(number(0)?.ceil()),
+            "round" => Value::Number(round(number(0)?)),
+            _ => return error(format!("unknown function {}()", name)),
+        })
+    }
+}
+
+fn to_nodes<'a>(nodes: impl Iterator<Item = NodeRef<'a, Node>>) -> Vec<XPathNode> {
+    nodes.map(|it| XPathNode::Node(it.id())).collect()
+}
+
+/// XPath rounds halves towards positive infinity.
+fn round(number: f64) -> f64 {
+    (number + 0.5).floor()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permission::{ModulePermissions, PermissionState};
+
+    const HTML: &str = r#"
+        <html>
+          <body>
+            <h1>Manga Title</h1>
+            <span class="info-image"><img src="/cover.jpg"></span>
+            <table>
+              <tr><td>Author(s) :</td><td><a>First</a> - <a>Second</a></td></tr>
+              <tr><td>Genres :</td><td><a>Action</a></td></tr>
+            </table>
+            <div class="panel-story-info-description">
+              <h3>Description :</h3>
+              Summary   text
+            </div>
+            <ul class="row-content-chapter">
+              <li><a class="chapter-name text-nowrap" href="/chapter-2">Chapter 2</a></li>
+              <li><a class="chapter-name" href="/chapter-1">Chapter 1</a></li>
+            </ul>
+          </body>
+        </html>
+    "#;
+
+    fn parse() -> HtmlDocument {
+        HtmlDocument::parse(
+            HTML,
+            PermissionState::new(ModulePermissions::unrestricted()),
+        )
+    }
+
+    fn evaluate(document: &HtmlDocument, expression: &str) -> Value {
+        XPath::parse(expression)
+            .unwrap()
+            .evaluate(document, &XPathNode::Node(document.root()))
+            .unwrap()
+    }
+
+    fn string(document: &HtmlDocument, expression: &str) -> String {
+        evaluate(document, expression).string(document)
+    }
+
+    fn strings(document: &HtmlDocument, expression: &str) -> Vec<String> {
+        match evaluate(document, expression) {
+            Value::Nodes(nodes) => nodes.iter().map(|it| it.text(document)).collect(),
+            other => panic!("expected nodes, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn location_path() {
+        let document = parse();
+
+        assert_eq!(string(&document, "//h1"), "Manga Title");
+        assert_eq!(
+            string(&document, r#"//span[@class="info-image"]/img/@src"#),
+            "/cover.jpg"
+        );
+        assert_eq!(
+            strings(
+                &document,
+                r#"//td[contains(., "Author(s)")]/following-sibling::td/a/text()"#
+            ),
+            vec!["First", "Second"]
+        );
+        assert_eq!(
+            strings(
+                &document,
+                r#"//ul[@class="row-content-chapter"]/li/a[contains(@class, "chapter-name")]/@href"#
+            ),
+            vec!["/chapter-2", "/chapter-1"]
+        );
+        assert_eq!(
+            string(
+                &document,
+                r#"//div[@class="panel-story-info-description"]/text()[last()]"#
+            )
+            .trim(),
+            "Summary   text"
+        );
+        assert_eq!(
+            strings(&document, "(//a)[last()]/../../li[1]/a"),
+            vec!["Chapter 2"]
+        );
+        assert_eq!(
+            strings(&document, "//li[2]/preceding::a[1]"),
+            vec!["Chapter 2"]
+        );
+        assert_eq!(
+            strings(&document, "//LI/A[@HREF='/chapter-1']"),
+            vec!["Chapter 1"]
+        );
+        assert_eq!(strings(&document, "//h1 | //h3").len(), 2);
+        assert_eq!(strings(&document, "//nothing"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn relative_to_node() {
+        let document = parse();
+        let anchor = match evaluate(&document, "//li/a") {
+            Value::Nodes(nodes) => nodes[1].clone(),
+            other => panic!("expected nodes, found {:?}", other),
+        };
+
+        let href = XPath::parse("@href").unwrap();
+        assert_eq!(
+            href.evaluate(&document, &anchor).unwrap().string(&document),
+            "/chapter-1"
+        );
+
+        let name = XPath::parse("name(..)").unwrap();
+        assert_eq!(
+            name.evaluate(&document, &anchor).unwrap(),
+            Value::String("li".to_string())
+        );
+    }
+
+    #[test]
+    fn functions_and_operators() {
+        let document = parse();
+
+        assert_eq!(evaluate(&document, "count(//a)"), Value::Number(5.0));
+        assert_eq!(
+            evaluate(&document, "1 + 2 * 3 - 4 div 2"),
+            Value::Number(5.0)
+        );
+        assert_eq!(evaluate(&document, "7 mod 4 = 3"), Value::Boolean(true));
+        assert_eq!(evaluate(&document, "-(1)"), Value::Number(-1.0));
+        assert_eq!(string(&document, "concat('a', 1, true())"), "a1true");
+        assert_eq!(string(&document, "substring('12345', 1.5, 2.6)"), "234");
+        assert_eq!(
+            string(&document, "substring-after('Chapter 12', ' ')"),
+            "12"
+        );
+        assert_eq!(string(&document, "normalize-space('  a   b ')"), "a b");
+        assert_eq!(string(&document, "translate('bar', 'abc', 'AB')"), "BAr");
+        assert_eq!(string(&document, "round(2.5)"), "3");
+        assert_eq!(string(&document, "1 div 0"), "Infinity");
+        assert_eq!(string(&document, "number('abc')"), "NaN");
+        assert_eq!(evaluate(&document, "//a = 'Action'"), Value::Boolean(true));
+        assert_eq!(evaluate(&document, "//a != 'Action'"), Value::Boolean(true));
+        assert_eq!(
+            evaluate(&document, "not(//a = 'None')"),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            evaluate(&document, "//h1 and //nothing"),
+            Value::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn parse_error() {
+        assert!(XPath::parse("//a[").is_err());
+        assert!(XPath::parse("//a[@href='x]").is_err());
+        assert!(XPath::parse("unknown()").is_err());
+        assert!(XPath::parse("contains('a')").is_err());
+        assert!(XPath::parse("namespace::a").is_err());
+        assert!(XPath::parse("//a)").is_err());
+    }
+}
@@ -6,11 +6,12 @@ use deno_core::{op, Extension, ExtensionBuilder, OpState, Resource};
 use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
+use crate::permission::{current_permissions, PermissionState};
 use crate::{try_json, ResultJson, ToResultJson};
 
-#[derive(Default)]
 pub struct Client {
     pub client: mado_core::http::Client,
+    pub permissions: Rc<PermissionState>,
 }
 impl Resource for Client {}
 
@@ -22,6 +23,10 @@ pub struct RequestBuilder {
 }
 
 impl RequestBuilder {
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
     pub fn to_request(self, client: &mado_core::http::Client) -> mado_core::http::RequestBuilder {
         let mut builder = client.get(self.url.clone());
 
@@ -40,7 +45,10 @@ pub struct ResponseJson {
     rid: u32,
 }
 
-pub struct ResponseResource(mado_core::http::Response);
+pub struct ResponseResource {
+    response: mado_core::http::Response,
+    permissions: Rc<PermissionState>,
+}
 impl Resource for ResponseResource {}
 
 #[derive(Deserialize, Serialize)]
@@ -55,13 +63,37 @@ fn get_http(state: &mut OpState, rid: u32) -> ResultJson<Rc<Client>> {
 }
 
 #[op]
-pub fn op_http_client_new(state: &mut OpState) -> u32 {
-    state.resource_table.add(Client::default())
+pub fn op_http_client_new(state: &mut OpState) -> ResultJson<u32> {
+    // clients keep the permissions of the module that created them, so
+    // they can't be created where the module isn't known.
+    let permissions = try_json!(current_permissions(state)
+        .ok_or_else(|| Error::PermissionDenied(
+            "http clients can only be created while a module is loading or running a call"
+                .to_string()
+        ))
+        .to_result_json(state));
+    try_json!(permissions
+        .check_op("op_http_client_new")
+        .to_result_json(state));
+
+    let client = state
+        .try_borrow::<mado_core::http::Client>()
+        .cloned()
+        .unwrap_or_default();
+
+    ResultJson::Ok(state.resource_table.add(Client {
+        client,
+        permissions,
+    }))
 }
 
 #[op]
 pub fn op_http_client_clone(state: &mut OpState, rid: u32) -> ResultJson<u32> {
     let http = try_json!(get_http(state, rid));
+    try_json!(http
+        .permissions
+        .check_op("op_http_client_clone")
+        .to_result_json(state));
     let rid = state.resource_table.add_rc(http);
 
     ResultJson::Ok(rid)
@@ -83,8 +115,15 @@ pub async fn op_http_client_get<'a>(
     request: RequestBuilder,
 ) -> ResultJson<ResponseJson> {
     let client = try_json!(get_http(&mut state.borrow_mut(), rid));
+    try_json!(client
+        .permissions
+        .check_url("op_http_client_get", request.url())
+        .to_result_json_borrow(state.clone()));
 
-    let response = request.to_request(&client.client).send().await;
+    let response = {
+        let _permit = client.permissions.acquire().await;
+        request.to_request(&client.client).send().await
+    };
 
     let response = try_json!(response
         .map_err(Error::from)
@@ -93,15 +132,15 @@ pub async fn op_http_client_get<'a>(
     ResultJson::Ok(ResponseJson {
         status: response.status().as_u16(),
         url: response.url().clone(),
-        rid: state
-            .borrow_mut()
-            .resource_table
-            .add(ResponseResource(response)),
+        rid: state.borrow_mut().resource_table.add(ResponseResource {
+            response,
+            permissions: client.permissions.clone(),
+        }),
     })
 }
 
-#[op]
-pub async fn op_http_response_text(state: Rc<RefCell<OpState>>, rid: u32) -> ResultJson<String> {
+/// Take the response `rid` out of the resource table for `op`.
+fn take_response(state: &Rc<RefCell<OpState>>, op: &str, rid: u32) -> ResultJson<ResponseResource> {
     let response = {
         let state = &mut state.borrow_mut();
 
@@ -116,15 +155,45 @@ pub async fn op_http_response_text(state: Rc<RefCell<OpState>>, rid: u32) -> Res
     };
 
     let response = try_json!(response);
+    try_json!(response
+        .permissions
+        .check_op(op)
+        .to_result_json_borrow(state.clone()));
+
+    ResultJson::Ok(response)
+}
+
+#[op]
+pub async fn op_http_response_text(state: Rc<RefCell<OpState>>, rid: u32) -> ResultJson<String> {
+    let response = try_json!(take_response(&state, "op_http_response_text", rid));
 
     response
-        .0
+        .response
         .text()
         .await
         .map_err(Error::from)
         .to_result_json_borrow(state)
 }
 
+/// Parse the response as a [`crate::html::HtmlDocument`], which keeps the
+/// permissions of the response.
+#[op]
+pub async fn op_http_response_html(state: Rc<RefCell<OpState>>, rid: u32) -> ResultJson<u32> {
+    let ResponseResource {
+        response,
+        permissions,
+    } = try_json!(take_response(&state, "op_http_response_html", rid));
+
+    let text = try_json!(response
+        .text()
+        .await
+        .map_err(Error::from)
+        .to_result_json_borrow(state.clone()));
+    let document = crate::html::HtmlDocument::parse(&text, permissions);
+
+    ResultJson::Ok(state.borrow_mut().resource_table.add(document))
+}
+
 pub fn init() -> Extension {
     ExtensionBuilder::default()
         .ops(vec![
@@ -133,6 +202,7 @@ pub fn init() -> Extension {
             op_http_client_clone::decl(),
             op_http_client_get::decl(),
             op_http_response_text::decl(),
+            op_http_response_html::decl(),
         ])
         .build()
 }
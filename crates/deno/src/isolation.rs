@@ -0,0 +1,235 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::FutureExt;
+
+use crate::{permission::ModulePermissions, DenoMadoModule, ModuleLimits, ModuleLoader, Runtime};
+
+/// Option for module files running in their own runtime thread.
+#[derive(Debug, Clone)]
+pub struct IsolationOption {
+    pub limits: ModuleLimits,
+    /// Permissions for module files without a manifest.
+    pub default_permissions: ModulePermissions,
+    pub http_client: Option<mado_core::http::Client>,
+    /// Delay before restarting a crashed runtime.
+    pub restart_delay: Duration,
+    /// Give up after this many restarts, `None` restarts forever.
+    pub max_restarts: Option<usize>,
+    /// Runtimes running this long before crashing start counting restarts
+    /// from zero again.
+    pub healthy_after: Duration,
+}
+
+impl Default for IsolationOption {
+    fn default() -> Self {
+        Self {
+            limits: Default::default(),
+            default_permissions: ModulePermissions::deny_all(),
+            http_client: None,
+            restart_delay: Duration::from_secs(1),
+            max_restarts: Some(5),
+            healthy_after: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum IsolatedModuleEvent {
+    /// Modules of the file are ready, sent after the first load and after
+    /// every restart.
+    Loaded(Vec<DenoMadoModule>),
+    /// The file cannot be loaded, the runtime won't be restarted.
+    LoadError(anyhow::Error),
+    /// The runtime stopped, previously loaded modules are unusable.
+    /// `restart` tells whether it will be loaded again.
+    Crashed { reason: String, restart: bool },
+}
+
+/// Handle to a module file running in its own runtime thread.
+pub struct IsolatedModuleHandle {
+    stopped: Arc<AtomicBool>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl IsolatedModuleHandle {
+    /// Don't restart the runtime after its next crash.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+}
+
+/// Run module file at `path` in a new runtime thread, restarting the
+/// runtime when a module panics or gets terminated.
+///
+/// `handler` is called from the runtime thread.
+pub fn spawn_isolated(
+    path: PathBuf,
+    option: IsolationOption,
+    mut handler: impl FnMut(IsolatedModuleEvent) + Send + 'static,
+) -> std::io::Result<IsolatedModuleHandle> {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let name = path
+        .file_name()
+        .map(|it| it.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let thread = std::thread::Builder::new()
+        .name(format!("deno-{}", name))
+        .spawn({
+            let stopped = stopped.clone();
+            move || {
+                let mut restarts = 0;
+
+                loop {
+                    let started = Instant::now();
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        run_runtime(&path, &option, &mut handler)
+                    }));
+
+                    let reason = match result {
+                        Ok(Ok(reason)) => reason,
+                        Ok(Err(err)) => {
+                            handler(IsolatedModuleEvent::LoadError(err));
+                            return;
+                        }
+                        Err(panic) => panic_message(panic),
+                    };
+
+                    restarts = count_restart(restarts, started.elapsed(), &option);
+                    let restart = !stopped.load(Ordering::SeqCst)
+                        && option.max_restarts.map_or(true, |max| restarts <= max);
+
+                    tracing::error!("runtime of {} crashed: {}", path.display(), reason);
+                    handler(IsolatedModuleEvent::Crashed { reason, restart });
+
+                    if !restart {
+                        return;
+                    }
+
+                    std::thread::sleep(option.restart_delay);
+                }
+            }
+        })?;
+
+    Ok(IsolatedModuleHandle { stopped, thread })
+}
+
+/// Load the file and run its modules until the runtime crashes, returning
+/// the reason.
+fn run_runtime(
+    path: &std::path::Path,
+    option: &IsolationOption,
+    handler: &mut impl FnMut(IsolatedModuleEvent),
+) -> Result<String, anyhow::Error> {
+    let tokio = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let local = tokio::task::LocalSet::new();
+
+    local.block_on(&tokio, async {
+        let mut runtime = Runtime::with_limits(option.limits.clone());
+        if let Some(client) = &option.http_client {
+            runtime.set_http_client(client.clone());
+        }
+
+        let mut loader = ModuleLoader::from_runtime(runtime.clone());
+        loader.set_default_permissions(option.default_permissions.clone());
+
+        let module = loader.load_file(path).await?;
+        let modules = loader.init_module(module).await?;
+
+        let mut senders = Vec::new();
+        for (sender, looper) in modules.into_iter().flatten() {
+            let runtime = runtime.clone();
+
+            tokio::task::spawn_local(async move {
+                let result = std::panic::AssertUnwindSafe(looper.start())
+                    .catch_unwind()
+                    .await;
+
+                if let Err(panic) = result {
+                    runtime.mark_crashed(panic_message(panic));
+                }
+            });
+
+            senders.push(sender);
+        }
+
+        handler(IsolatedModuleEvent::Loaded(senders));
+
+        Ok(runtime.crashed().await)
+    })
+}
+
+/// Restarts after a crash following `uptime` of running.
+fn count_restart(restarts: usize, uptime: Duration, option: &IsolationOption) -> usize {
+    // only crashes in a row count against the limit.
+    if uptime >= option.healthy_after {
+        1
+    } else {
+        restarts + 1
+    }
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "module panicked".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panic_message_test() {
+        assert_eq!(panic_message(Box::new("static")), "static");
+        assert_eq!(panic_message(Box::new("owned".to_string())), "owned");
+        assert_eq!(panic_message(Box::new(1)), "module panicked");
+    }
+
+    #[test]
+    fn count_restart_test() {
+        let option = IsolationOption {
+            healthy_after: Duration::from_secs(60),
+            ..Default::default()
+        };
+
+        assert_eq!(count_restart(0, Duration::from_secs(1), &option), 1);
+        assert_eq!(count_restart(4, Duration::from_secs(59), &option), 5);
+        assert_eq!(count_restart(4, Duration::from_secs(60), &option), 1);
+    }
+
+    #[test]
+    fn load_error() {
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let handle = spawn_isolated(
+            PathBuf::from("./nonexistent/module.js"),
+            IsolationOption::default(),
+            move |event| {
+                tx.send(matches!(event, IsolatedModuleEvent::LoadError(..)))
+                    .unwrap();
+            },
+        )
+        .unwrap();
+
+        assert!(rx.recv().unwrap());
+        handle.thread.join().unwrap();
+    }
+}
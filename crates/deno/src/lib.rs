@@ -1,10 +1,16 @@
 pub mod error;
+pub mod html;
 pub mod http;
+mod isolation;
+mod limit;
 mod module;
+pub mod permission;
 mod runtime;
 pub mod task;
 
 pub use error::{Error, ErrorJson};
+pub use isolation::{spawn_isolated, IsolatedModuleEvent, IsolatedModuleHandle, IsolationOption};
+pub use limit::ModuleLimits;
 pub use module::*;
 pub use runtime::*;
 
@@ -38,6 +44,7 @@ pub fn extensions() -> Vec<deno_core::Extension> {
     vec![
         deno_console::init(),
         crate::http::init(),
+        crate::html::init(),
         crate::error::init(),
         crate::module::init(),
         crate::task::init(),
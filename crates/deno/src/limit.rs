@@ -0,0 +1,258 @@
+use std::{
+    cell::Cell,
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use deno_core::{
+    parking_lot::{Condvar, Mutex},
+    v8,
+};
+
+/// Deadlines for module calls and the heap size of the runtime.
+#[derive(Debug, Clone)]
+pub struct ModuleLimits {
+    pub get_info: Duration,
+    pub get_chapter_images: Duration,
+    pub download_image: Duration,
+    /// Maximum V8 heap size in bytes, `None` uses V8's default.
+    pub max_heap_size: Option<usize>,
+}
+
+impl Default for ModuleLimits {
+    fn default() -> Self {
+        Self {
+            get_info: Duration::from_secs(60),
+            get_chapter_images: Duration::from_secs(60),
+            download_image: Duration::from_secs(30),
+            max_heap_size: None,
+        }
+    }
+}
+
+/// Heap V8 is given past its limit to unwind a terminated call.
+pub(crate) const HEAP_LIMIT_HEADROOM: usize = 32 * 1024 * 1024;
+
+/// How long a call may block the runtime thread past its deadline before
+/// the watchdog terminates it.
+const WATCHDOG_GRACE: Duration = Duration::from_secs(1);
+
+/// Identifies a call watched by a [`Watchdog`].
+pub(crate) type CallId = u64;
+
+/// Terminate JS execution from another thread when a call is stuck in
+/// synchronous code, where an async timeout can never fire.
+///
+/// One thread serves every call of a runtime. A call is only terminated
+/// while its own code, or code not known to belong to any call such as a
+/// promise continuation, is blocking the runtime; a call that is just
+/// waiting is left to its async timeout.
+pub(crate) struct Watchdog {
+    shared: Arc<Shared>,
+    isolate: v8::IsolateHandle,
+    next_id: Cell<CallId>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    deadlines: Vec<(Instant, CallId)>,
+    running: Option<Running>,
+    fired: HashSet<CallId>,
+    started: bool,
+    stopped: bool,
+}
+
+/// JS the runtime thread is executing, `owner` is `None` when it can't be
+/// attributed to a call.
+#[derive(Clone, Copy)]
+struct Running {
+    owner: Option<CallId>,
+    since: Instant,
+}
+
+impl Watchdog {
+    pub fn new(isolate: v8::IsolateHandle) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Default::default(),
+                wake: Condvar::new(),
+            }),
+            isolate,
+            next_id: Cell::new(0),
+        }
+    }
+
+    /// Watch a new call that may run for `deadline`.
+    pub fn watch(&self, deadline: Duration) -> CallId {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+
+        let mut state = self.shared.state.lock();
+        state
+            .deadlines
+            .push((Instant::now() + deadline + WATCHDOG_GRACE, id));
+
+        if !state.started {
+            state.started = true;
+            self.spawn();
+        }
+
+        self.shared.wake.notify_one();
+        id
+    }
+
+    fn spawn(&self) {
+        let shared = self.shared.clone();
+        let isolate = self.isolate.clone();
+
+        let spawned = std::thread::Builder::new()
+            .name("module-watchdog".to_string())
+            .spawn(move || shared.run(isolate));
+
+        if let Err(err) = spawned {
+            tracing::error!("cannot spawn module watchdog: {}", err);
+        }
+    }
+
+    /// Stop watching `call`, returning whether it was terminated.
+    pub fn finish(&self, call: CallId) -> bool {
+        let mut state = self.shared.state.lock();
+        state.deadlines.retain(|(_, id)| *id != call);
+
+        state.fired.remove(&call)
+    }
+
+    /// Mark the runtime as executing JS of `owner` until the guard is
+    /// dropped.
+    pub fn enter(&self, owner: Option<CallId>) -> RunningGuard<'_> {
+        let running = Running {
+            owner,
+            since: Instant::now(),
+        };
+        let previous = self.shared.state.lock().running.replace(running);
+
+        RunningGuard {
+            shared: &self.shared,
+            previous,
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.state.lock().stopped = true;
+        self.shared.wake.notify_one();
+    }
+}
+
+pub(crate) struct RunningGuard<'a> {
+    shared: &'a Shared,
+    previous: Option<Running>,
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.shared.state.lock().running = self.previous;
+    }
+}
+
+impl Shared {
+    fn run(&self, isolate: v8::IsolateHandle) {
+        let mut state = self.state.lock();
+
+        while !state.stopped {
+            let now = Instant::now();
+            let running = state.running;
+            let mut fired = Vec::new();
+
+            state.deadlines.retain_mut(|(at, id)| {
+                if *at > now {
+                    return true;
+                }
+
+                match running {
+                    Some(Running {
+                        owner: Some(owner), ..
+                    }) if owner == *id => {
+                        fired.push(*id);
+                        false
+                    }
+                    Some(Running { owner: None, since }) => {
+                        // only terminate once it's clear the code is stuck.
+                        if now < since + WATCHDOG_GRACE {
+                            *at = since + WATCHDOG_GRACE;
+                            true
+                        } else {
+                            fired.push(*id);
+                            false
+                        }
+                    }
+                    // idle or running another call, this one is only waiting.
+                    _ => false,
+                }
+            });
+
+            if !fired.is_empty() {
+                state.fired.extend(fired);
+                isolate.terminate_execution();
+            }
+
+            match state.deadlines.iter().map(|(at, _)| *at).min() {
+                Some(at) => {
+                    let timeout = at.saturating_duration_since(Instant::now());
+                    self.wake.wait_for(&mut state, timeout);
+                }
+                None => self.wake.wait(&mut state),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog() {
+        let mut runtime = deno_core::JsRuntime::new(Default::default());
+        let watchdog = Watchdog::new(runtime.v8_isolate().thread_safe_handle());
+
+        let call = watchdog.watch(Duration::ZERO);
+        let result = {
+            let _running = watchdog.enter(Some(call));
+            runtime.execute_script("watchdog", "while (true) {}")
+        };
+
+        assert!(result.is_err());
+        assert!(watchdog.finish(call));
+        runtime.v8_isolate().cancel_terminate_execution();
+
+        let call = watchdog.watch(Duration::from_secs(60));
+        runtime.execute_script("watchdog", "1 + 1").unwrap();
+        assert!(!watchdog.finish(call));
+    }
+
+    #[test]
+    fn watchdog_other_call() {
+        let mut runtime = deno_core::JsRuntime::new(Default::default());
+        let watchdog = Watchdog::new(runtime.v8_isolate().thread_safe_handle());
+
+        let call = watchdog.watch(Duration::ZERO);
+        let other = watchdog.watch(Duration::from_secs(60));
+        {
+            // the deadline passes while another call is running.
+            let _running = watchdog.enter(Some(other));
+            std::thread::sleep(WATCHDOG_GRACE * 2);
+            runtime.execute_script("watchdog", "1 + 1").unwrap();
+        }
+
+        assert!(!watchdog.finish(call));
+        assert!(!watchdog.finish(other));
+    }
+}
@@ -1,4 +1,9 @@
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::Context;
 use deno_core::{
@@ -16,6 +21,8 @@ use url::Url;
 
 use crate::{
     error::Error as DenoError,
+    limit::{CallId, ModuleLimits},
+    permission::PermissionState,
     task::{DenoChapterTask, JsChapterTask},
     try_json, ResultJson, ToResultJson,
 };
@@ -61,10 +68,6 @@ impl DenoMadoModule {
 
         rx.await.context("cannot await request")?
     }
-
-    async fn close(self) -> Result<(), Error> {
-        self.send_message(ModuleMessage::Close).await
-    }
 }
 
 #[async_trait::async_trait]
@@ -102,6 +105,10 @@ impl MadoModule for DenoMadoModule {
         self.send_message(|cx| ModuleMessage::DownloadImage(image, cx))
             .await
     }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.send_message(ModuleMessage::Close).await
+    }
 }
 
 pub enum ModuleMessage {
@@ -125,9 +132,16 @@ pub struct ModuleLoop {
 
 #[derive(Clone)]
 struct ModuleMessageHandler {
+    name: String,
+    uuid: Uuid,
+    /// Reason the module was terminated, every later call fails with it.
+    terminated: Rc<RefCell<Option<String>>>,
+    /// Set after [`ModuleMessage::Close`], the loop then stops.
+    closed: Rc<Cell<bool>>,
     runtime: crate::Runtime,
     object: Global<Object>,
     client: mado_core::http::Client,
+    permissions: Rc<PermissionState>,
 }
 
 struct FunctionCaller<'a> {
@@ -146,24 +160,42 @@ impl<'a> FunctionCaller<'a> {
 }
 
 impl ModuleLoop {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        uuid: Uuid,
+        name: String,
         receiver: mpsc::Receiver<ModuleMessage>,
         runtime: crate::Runtime,
         object: Global<Object>,
         client: mado_core::http::Client,
+        permissions: Rc<PermissionState>,
     ) -> Self {
         Self {
             receiver,
             handler: ModuleMessageHandler {
+                name,
+                uuid,
+                terminated: Default::default(),
+                closed: Default::default(),
                 runtime,
                 object,
                 client,
+                permissions,
             },
         }
     }
 
+    /// Handle messages until the module is closed or every sender is dropped.
     pub async fn start(mut self) {
-        let stream = futures::stream::poll_fn(move |cx| self.receiver.poll_recv(cx));
+        let closed = self.handler.closed.clone();
+        let stream = futures::stream::poll_fn(move |cx| {
+            // messages already sent are still handled.
+            if closed.get() {
+                self.receiver.close();
+            }
+
+            self.receiver.poll_recv(cx)
+        });
 
         stream
             .for_each_concurrent(None, |msg| async {
@@ -206,9 +238,72 @@ impl ModuleMessageHandler {
         self.runtime.clone().with_scope_state(fun)
     }
 
+    fn limits(&self) -> ModuleLimits {
+        self.runtime.module_limits()
+    }
+
+    fn terminated_error(&self, reason: String) -> DenoError {
+        DenoError::MadoError(Error::ModuleTerminated {
+            module: self.name.clone(),
+            reason,
+        })
+    }
+
+    /// Mark the module as terminated and let the runtime run other modules.
+    fn terminate(&self, reason: &str) -> DenoError {
+        tracing::error!(
+            "terminating module {} ({}): {}",
+            self.name,
+            self.uuid,
+            reason
+        );
+
+        self.runtime.cancel_terminate_execution();
+        self.runtime
+            .mark_crashed(format!("module {} was terminated: {}", self.name, reason));
+        *self.terminated.borrow_mut() = Some(reason.to_string());
+
+        self.terminated_error(reason.to_string())
+    }
+
     async fn call_async_function<F, Resource, Ref>(
         &self,
         name: &str,
+        timeout: Option<Duration>,
+        resource: Resource,
+        args: F,
+    ) -> Result<Global<Value>, DenoError>
+    where
+        Resource: FnOnce(&mut OpState) -> Ref,
+        Ref: AsRef<[u32]>,
+        F: for<'b> FnOnce(&mut HandleScope<'b>, &[u32], FunctionCaller) -> Option<Local<'b, Value>>,
+    {
+        if let Some(reason) = self.terminated.borrow().clone() {
+            return Err(self.terminated_error(reason));
+        }
+
+        let watchdog = self.runtime.watchdog();
+        let call = timeout.map(|timeout| watchdog.watch(timeout));
+        let value = self.call_function(name, call, timeout, resource, args).await;
+        let fired = call.map(|call| watchdog.finish(call)).unwrap_or(false);
+
+        if self.runtime.take_heap_limit_reached() {
+            return Err(self.terminate(&format!("{} reached the heap limit", name)));
+        }
+
+        if fired {
+            let reason = format!("{} blocked the runtime past its deadline", name);
+            return Err(self.terminate(&reason));
+        }
+
+        value
+    }
+
+    async fn call_function<F, Resource, Ref>(
+        &self,
+        name: &str,
+        call: Option<CallId>,
+        timeout: Option<Duration>,
         resource: Resource,
         args: F,
     ) -> Result<Global<Value>, DenoError>
@@ -235,6 +330,10 @@ impl ModuleMessageHandler {
 
             let it = args(scope, resource.as_ref(), FunctionCaller { recv, function });
 
+            if scope.has_terminated() {
+                return Err(anyhow::anyhow!("{} was terminated", name).into());
+            }
+
             match (scope.exception(), it) {
                 (Some(ex), Some(it)) => {
                     tracing::error!("exception: {:?}", ex);
@@ -247,10 +346,28 @@ impl ModuleMessageHandler {
             }
         };
 
-        let value = self.with_scope(value);
-        let value = match value {
-            Ok(val) => self.runtime.resolve_value(val).await.map_err(Into::into),
-            Err(err) => Err(err),
+        // only the synchronous part of the call is known to be this module's.
+        let mut runtime = self.runtime.clone();
+        runtime.bind_permissions(Some(self.permissions.clone()));
+        let value = {
+            let _running = self.runtime.watchdog().enter(call);
+            self.with_scope(value)
+        };
+        runtime.bind_permissions(None);
+
+        let value = match (value, timeout) {
+            (Ok(val), Some(timeout)) => {
+                match tokio::time::timeout(timeout, self.runtime.resolve_value(val)).await {
+                    Ok(it) => it.map_err(Into::into),
+                    Err(_) => Err(DenoError::MadoError(Error::ModuleTimeout {
+                        module: self.name.clone(),
+                        operation: name.to_string(),
+                        timeout,
+                    })),
+                }
+            }
+            (Ok(val), None) => self.runtime.resolve_value(val).await.map_err(Into::into),
+            (Err(err), _) => Err(err),
         };
 
         self.with_state(|op_state| {
@@ -280,6 +397,7 @@ impl ModuleMessageHandler {
     async fn call_async_serialize<T, F, Resource, A>(
         &self,
         name: &str,
+        timeout: Option<Duration>,
         resource: Resource,
         args: F,
     ) -> Result<T, DenoError>
@@ -289,7 +407,7 @@ impl ModuleMessageHandler {
         A: AsRef<[u32]>,
         T: DeserializeOwned + serde::Serialize,
     {
-        self.call_async_function(name, resource, args)
+        self.call_async_function(name, timeout, resource, args)
             .await
             .and_then(|it| self.serialize_result(it))
     }
@@ -297,6 +415,7 @@ impl ModuleMessageHandler {
     async fn call_async_void<F, Resource, A>(
         &self,
         name: &str,
+        timeout: Option<Duration>,
         resource: Resource,
         args: F,
     ) -> Result<(), DenoError>
@@ -305,7 +424,9 @@ impl ModuleMessageHandler {
         Resource: FnOnce(&mut OpState) -> A,
         A: AsRef<[u32]>,
     {
-        let it: Result<Option<()>, _> = self.call_async_serialize(name, resource, args).await;
+        let it: Result<Option<()>, _> = self
+            .call_async_serialize(name, timeout, resource, args)
+            .await;
 
         it.map(|_| ())
     }
@@ -318,6 +439,7 @@ impl ModuleMessageHandler {
         let it = self
             .call_async_serialize(
                 "getInfo",
+                Some(self.limits().get_info),
                 |_| [],
                 |scope, _, call| {
                     let args = &[v8::String::new(scope, url.as_str()).unwrap().into()];
@@ -338,6 +460,7 @@ impl ModuleMessageHandler {
         let it = self
             .call_async_void(
                 "getChapterImageRust",
+                Some(self.limits().get_chapter_images),
                 |state| [DenoChapterTask::new_to_state(task, state)],
                 |scope, state, call| {
                     let args = &[
@@ -361,6 +484,7 @@ impl ModuleMessageHandler {
         let it: Result<crate::http::RequestBuilder, _> = self
             .call_async_serialize(
                 "downloadImage",
+                Some(self.limits().download_image),
                 |_| &[],
                 |scope, _, call| {
                     let args = &[serde_v8::to_v8(scope, info).unwrap()];
@@ -369,7 +493,11 @@ impl ModuleMessageHandler {
             )
             .await;
 
-        let it = it.map(|it| it.to_request(&self.client));
+        let it = it.and_then(|it| {
+            self.permissions
+                .check_url("op_http_client_get", it.url())
+                .map(|_| it.to_request(&self.client))
+        });
 
         let _ = cx.send(it.map(Into::into).map_err(Into::into));
     }
@@ -378,6 +506,7 @@ impl ModuleMessageHandler {
         let it: Result<_, _> = self
             .call_async_function(
                 "close",
+                None,
                 |_| &[],
                 |scope, _, call| {
                     let args = &[];
@@ -386,6 +515,7 @@ impl ModuleMessageHandler {
             )
             .await;
 
+        self.closed.set(true);
         let _ = cx.send(it.map(|_| ()).map_err(Into::into));
     }
 }
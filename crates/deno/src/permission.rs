@@ -0,0 +1,246 @@
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::error::Error;
+
+/// What a module is allowed to do, read from `<module>.permissions.json`
+/// next to the module file.
+///
+/// Every field left out of a manifest is unrestricted, module files
+/// without a manifest aren't allowed anything by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ModulePermissions {
+    /// Hosts the module may request. `*.example.com` also matches every
+    /// subdomain of `example.com`.
+    pub hosts: Option<Vec<String>>,
+    pub max_concurrent_requests: Option<usize>,
+    /// Op names the module may call, e.g. `op_http_client_get`.
+    pub ops: Option<Vec<String>>,
+}
+
+impl ModulePermissions {
+    pub fn unrestricted() -> Self {
+        Self::default()
+    }
+
+    pub fn deny_all() -> Self {
+        Self {
+            hosts: Some(Vec::new()),
+            max_concurrent_requests: None,
+            ops: Some(Vec::new()),
+        }
+    }
+
+    pub fn manifest_path(module: &Path) -> PathBuf {
+        let stem = module.file_stem().unwrap_or_default().to_string_lossy();
+        module.with_file_name(format!("{}.permissions.json", stem))
+    }
+
+    /// Read the manifest for `module`, `None` if the module doesn't have one.
+    pub fn from_manifest(module: &Path) -> Result<Option<Self>, anyhow::Error> {
+        let path = Self::manifest_path(module);
+
+        match std::fs::read(&path) {
+            Ok(content) => Ok(Some(serde_json::from_slice(&content).map_err(|err| {
+                anyhow::anyhow!("invalid permission manifest {}: {}", path.display(), err)
+            })?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn is_host_allowed(&self, url: &url::Url) -> bool {
+        let hosts = match &self.hosts {
+            Some(hosts) => hosts,
+            None => return true,
+        };
+
+        let host = match url.host_str() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+
+        hosts.iter().any(|allowed| {
+            let allowed = allowed.to_ascii_lowercase();
+
+            match allowed.strip_prefix("*.") {
+                Some(domain) => host == domain || host.ends_with(&format!(".{}", domain)),
+                None => host == allowed,
+            }
+        })
+    }
+
+    pub fn is_op_allowed(&self, op: &str) -> bool {
+        match &self.ops {
+            Some(ops) => ops.iter().any(|it| it == op),
+            None => true,
+        }
+    }
+}
+
+/// Permissions of one loaded module file, shared by every http client the
+/// module creates.
+#[derive(Debug)]
+pub struct PermissionState {
+    permissions: ModulePermissions,
+    semaphore: Option<Semaphore>,
+}
+
+impl PermissionState {
+    pub fn new(permissions: ModulePermissions) -> Rc<Self> {
+        Rc::new(Self {
+            semaphore: permissions.max_concurrent_requests.map(Semaphore::new),
+            permissions,
+        })
+    }
+
+    pub fn permissions(&self) -> &ModulePermissions {
+        &self.permissions
+    }
+
+    pub fn check_op(&self, op: &str) -> Result<(), Error> {
+        if self.permissions.is_op_allowed(op) {
+            Ok(())
+        } else {
+            Err(Error::PermissionDenied(format!(
+                "module is not allowed to call {}",
+                op
+            )))
+        }
+    }
+
+    pub fn check_url(&self, op: &str, url: &url::Url) -> Result<(), Error> {
+        self.check_op(op)?;
+
+        if self.permissions.is_host_allowed(url) {
+            Ok(())
+        } else {
+            Err(Error::PermissionDenied(format!(
+                "module is not allowed to request {}, host {} is not in the manifest",
+                url,
+                url.host_str().unwrap_or_default()
+            )))
+        }
+    }
+
+    /// Wait until the module is below its concurrent request limit.
+    pub async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.semaphore {
+            Some(semaphore) => semaphore.acquire().await.ok(),
+            None => None,
+        }
+    }
+}
+
+/// Permissions of the module whose code is running synchronously, bound
+/// while a module file is evaluated or initialized and for every call into
+/// one of its modules.
+pub(crate) struct BoundPermissions(pub Option<Rc<PermissionState>>);
+
+/// Permissions for code running outside of a module load or call.
+pub(crate) struct UnboundPermissions(pub Rc<PermissionState>);
+
+/// Permissions of the module running now, `None` when no module is, e.g. in
+/// a callback after an `await`.
+pub(crate) fn current_permissions(state: &deno_core::OpState) -> Option<Rc<PermissionState>> {
+    state
+        .try_borrow::<BoundPermissions>()
+        .and_then(|it| it.0.clone())
+        .or_else(|| {
+            state
+                .try_borrow::<UnboundPermissions>()
+                .map(|it| it.0.clone())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> url::Url {
+        url::Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn host() {
+        let permissions = ModulePermissions {
+            hosts: Some(vec![
+                "mangadex.org".to_string(),
+                "*.mangadex.network".to_string(),
+            ]),
+            ..Default::default()
+        };
+
+        assert!(permissions.is_host_allowed(&url("https://mangadex.org/title/1")));
+        assert!(permissions.is_host_allowed(&url("https://MangaDex.org:443/")));
+        assert!(permissions.is_host_allowed(&url("https://a.b.mangadex.network/data")));
+        assert!(permissions.is_host_allowed(&url("https://mangadex.network/data")));
+
+        assert!(!permissions.is_host_allowed(&url("https://api.mangadex.org/")));
+        assert!(!permissions.is_host_allowed(&url("https://evilmangadex.network/")));
+        assert!(!permissions.is_host_allowed(&url("https://example.com/")));
+
+        assert!(ModulePermissions::unrestricted().is_host_allowed(&url("https://example.com")));
+        assert!(!ModulePermissions::deny_all().is_host_allowed(&url("https://example.com")));
+    }
+
+    #[test]
+    fn op() {
+        let state = PermissionState::new(ModulePermissions {
+            ops: Some(vec!["op_http_client_get".to_string()]),
+            ..Default::default()
+        });
+
+        assert!(state.check_op("op_http_client_get").is_ok());
+        assert!(matches!(
+            state.check_op("op_http_client_clone"),
+            Err(Error::PermissionDenied(..))
+        ));
+        assert!(matches!(
+            state.check_url("op_http_client_clone", &url("https://example.com")),
+            Err(Error::PermissionDenied(..))
+        ));
+    }
+
+    #[test]
+    fn manifest() {
+        assert_eq!(
+            ModulePermissions::manifest_path(Path::new("/module/mangadex.js")),
+            PathBuf::from("/module/mangadex.permissions.json")
+        );
+
+        let permissions: ModulePermissions =
+            serde_json::from_str(r#"{ "hosts": ["a.com"], "maxConcurrentRequests": 2 }"#).unwrap();
+
+        assert_eq!(
+            permissions,
+            ModulePermissions {
+                hosts: Some(vec!["a.com".to_string()]),
+                max_concurrent_requests: Some(2),
+                ops: None,
+            }
+        );
+
+        assert_eq!(
+            ModulePermissions::from_manifest(Path::new("/nonexistent/module.js")).unwrap(),
+            None
+        );
+    }
+    #[test]
+    fn bundled_manifest() {
+        for manifest in [
+            include_str!("../script/module/mangadex.permissions.json"),
+            include_str!("../script/module/manganato.permissions.json"),
+        ] {
+            let permissions: ModulePermissions = serde_json::from_str(manifest).unwrap();
+            assert!(permissions.hosts.is_some());
+            assert!(!permissions.is_host_allowed(&url("https://example.com")));
+        }
+    }
+}
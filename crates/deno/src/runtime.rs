@@ -1,4 +1,11 @@
-use std::{cell::RefCell, collections::HashMap, num::NonZeroI32, path::Path, rc::Rc, sync::Arc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    num::NonZeroI32,
+    path::Path,
+    rc::Rc,
+    sync::Arc,
+};
 
 use anyhow::Context;
 use deno_core::{
@@ -10,12 +17,23 @@ use futures::FutureExt;
 use mado_core::Uuid;
 use tokio::sync::mpsc;
 
-use crate::{DenoMadoModule, ModuleLoop};
+use crate::{
+    limit::{ModuleLimits, Watchdog, HEAP_LIMIT_HEADROOM},
+    permission::{BoundPermissions, ModulePermissions, PermissionState, UnboundPermissions},
+    DenoMadoModule, ModuleLoop,
+};
 
-#[derive(Default)]
 pub struct ModuleLoader {
     runtime: Runtime,
     max_module: i32,
+    default_permissions: ModulePermissions,
+    permissions: HashMap<i32, Rc<PermissionState>>,
+}
+
+impl Default for ModuleLoader {
+    fn default() -> Self {
+        Self::from_runtime(Runtime::default())
+    }
 }
 
 impl ModuleLoader {
@@ -23,6 +41,8 @@ impl ModuleLoader {
         Self {
             runtime,
             max_module: 0,
+            default_permissions: ModulePermissions::deny_all(),
+            permissions: HashMap::new(),
         }
     }
 
@@ -30,9 +50,39 @@ impl ModuleLoader {
         self.max_module
     }
 
+    /// Permissions for module files without a manifest, nothing is allowed
+    /// by default.
+    pub fn set_default_permissions(&mut self, permissions: ModulePermissions) {
+        self.default_permissions = permissions;
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn load_file(&mut self, path: &Path) -> Result<i32, anyhow::Error> {
-        let path = path.canonicalize().unwrap();
+        let permissions = match ModulePermissions::from_manifest(path)? {
+            Some(permissions) => permissions,
+            None => {
+                tracing::warn!(
+                    "{} has no permission manifest, using default permissions {:?}",
+                    path.display(),
+                    self.default_permissions
+                );
+                self.default_permissions.clone()
+            }
+        };
+        let permissions = PermissionState::new(permissions);
+
+        self.runtime.bind_permissions(Some(permissions.clone()));
+        let module = self.evaluate_file(path).await;
+        self.runtime.bind_permissions(None);
+
+        let module = module?;
+        self.permissions.entry(module).or_insert(permissions);
+
+        Ok(module)
+    }
+
+    async fn evaluate_file(&mut self, path: &Path) -> Result<i32, anyhow::Error> {
+        let path = path.canonicalize()?;
         let path = format!("file://{}", path.to_string_lossy());
         tracing::trace!("loading {}", path);
         let url = url::Url::parse(&path)?;
@@ -57,6 +107,18 @@ impl ModuleLoader {
     pub async fn init_module(
         &mut self,
         module: i32,
+    ) -> Result<Vec<Result<(DenoMadoModule, ModuleLoop), ModuleLoadError>>, ModuleLoadError> {
+        self.runtime
+            .bind_permissions(self.permissions.get(&module).cloned());
+        let result = self.init_module_object(module);
+        self.runtime.bind_permissions(None);
+
+        result
+    }
+
+    fn init_module_object(
+        &mut self,
+        module: i32,
     ) -> Result<Vec<Result<(DenoMadoModule, ModuleLoop), ModuleLoadError>>, ModuleLoadError> {
         let (array, length) = {
             let namespace = self.runtime.js.borrow_mut().get_module_namespace(module);
@@ -129,6 +191,16 @@ impl ModuleLoader {
 pub struct Runtime {
     js: Rc<RefCell<JsRuntime>>,
     event: Arc<event_listener::Event>,
+    isolate: v8::IsolateHandle,
+    heap_limit_reached: Rc<Cell<bool>>,
+    crash: Rc<CrashState>,
+    watchdog: Rc<Watchdog>,
+}
+
+#[derive(Default)]
+struct CrashState {
+    reason: RefCell<Option<String>>,
+    notify: tokio::sync::Notify,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -155,9 +227,38 @@ impl Runtime {
 
         js.handle_scope().set_promise_hook(promise_hook);
 
+        let isolate = js.v8_isolate().thread_safe_handle();
+        let heap_limit_reached = Rc::new(Cell::new(false));
+
+        {
+            let isolate = isolate.clone();
+            let heap_limit_reached = heap_limit_reached.clone();
+
+            let initial = Cell::new(None);
+
+            js.add_near_heap_limit_callback(move |current, _| {
+                tracing::error!("module runtime reached heap limit of {} bytes", current);
+                heap_limit_reached.set(true);
+                isolate.terminate_execution();
+
+                // give V8 room to unwind the terminated call instead of aborting,
+                // but only once, a runtime that keeps growing is still aborted.
+                let limit = initial.get().unwrap_or(current);
+                initial.set(Some(limit));
+
+                current.max(limit + HEAP_LIMIT_HEADROOM)
+            });
+        }
+
+        let watchdog = Rc::new(Watchdog::new(isolate.clone()));
+
         let this = Self {
             js: Rc::new(RefCell::new(js)),
             event,
+            isolate,
+            heap_limit_reached,
+            crash: Default::default(),
+            watchdog,
         };
 
         this.js
@@ -185,6 +286,92 @@ impl Runtime {
         Self::new(options)
     }
 
+    /// Runtime with `limits` applied to its heap and module calls.
+    pub fn with_limits(limits: ModuleLimits) -> Self {
+        let max_heap_size = limits.max_heap_size;
+
+        let mut this = Self::new_with_option(|option| {
+            if let Some(max_heap_size) = max_heap_size {
+                option.create_params =
+                    Some(v8::CreateParams::default().heap_limits(0, max_heap_size));
+            }
+        });
+
+        this.set_module_limits(limits);
+        this
+    }
+
+    pub fn set_module_limits(&mut self, limits: ModuleLimits) {
+        self.with_state(|state| state.put(limits));
+    }
+
+    pub fn module_limits(&self) -> ModuleLimits {
+        self.clone()
+            .with_state(|state| state.try_borrow::<ModuleLimits>().cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn isolate_handle(&self) -> v8::IsolateHandle {
+        self.isolate.clone()
+    }
+
+    pub(crate) fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+
+    /// Whether the heap limit was reached since the last call.
+    pub fn take_heap_limit_reached(&self) -> bool {
+        self.heap_limit_reached.replace(false)
+    }
+
+    /// Mark the runtime as unusable, the first reason is kept.
+    pub fn mark_crashed(&self, reason: impl Into<String>) {
+        let mut current = self.crash.reason.borrow_mut();
+
+        if current.is_none() {
+            *current = Some(reason.into());
+            self.crash.notify.notify_one();
+        }
+    }
+
+    pub fn crash_reason(&self) -> Option<String> {
+        self.crash.reason.borrow().clone()
+    }
+
+    /// Resolve with the reason once the runtime is marked as crashed.
+    pub async fn crashed(&self) -> String {
+        loop {
+            if let Some(reason) = self.crash_reason() {
+                return reason;
+            }
+
+            self.crash.notify.notified().await;
+        }
+    }
+
+    /// Let the runtime execute JS again after a terminated call.
+    pub fn cancel_terminate_execution(&self) {
+        self.isolate.cancel_terminate_execution();
+    }
+
+    /// Bind `permissions` to ops called until they are unbound with `None`.
+    pub(crate) fn bind_permissions(&mut self, permissions: Option<Rc<PermissionState>>) {
+        self.with_state(|state| state.put(BoundPermissions(permissions)));
+    }
+
+    /// Permissions for code running outside of a module load or call, such
+    /// as script tests. Nothing is allowed there by default.
+    pub fn set_unbound_permissions(&mut self, permissions: ModulePermissions) {
+        let permissions = PermissionState::new(permissions);
+        self.with_state(|state| state.put(UnboundPermissions(permissions)));
+    }
+
+    /// Client that new JS http clients are cloned from, so modules share
+    /// its configuration such as the response cache.
+    pub fn set_http_client(&mut self, client: mado_core::http::Client) {
+        self.with_state(|state| state.put(client));
+    }
+
     fn with_runtime<F, R>(&mut self, fun: F) -> R
     where
         F: FnOnce(&Self, &mut JsRuntime) -> R,
@@ -275,16 +462,17 @@ impl Runtime {
             crate::from_v8::<ObjectSerde>(scope, object.into()).map_err(ModuleLoadError::SerdeError)
         }?;
 
-        let client = {
+        let (client, permissions) = {
             state
                 .resource_table
                 .get::<crate::http::Client>(value.client.rid)
                 .map_err(ModuleLoadError::WrongTypeError)
-                .map(|it| it.client.clone())
+                .map(|it| (it.client.clone(), it.permissions.clone()))
         }?;
 
         let (cx, rx) = mpsc::channel(5);
 
+        let name = value.name.clone();
         let sender = crate::DenoMadoModule::new(
             value.name,
             value.uuid,
@@ -293,7 +481,15 @@ impl Runtime {
             cx,
         );
 
-        let looper = crate::ModuleLoop::new(rx, self.clone(), object, client);
+        let looper = crate::ModuleLoop::new(
+            value.uuid,
+            name,
+            rx,
+            self.clone(),
+            object,
+            client,
+            permissions,
+        );
 
         Ok((sender, looper))
     }
@@ -333,7 +529,10 @@ impl Runtime {
 
         loop {
             let it = futures::future::poll_fn(|cx| {
-                self.with_runtime(|_, js| js.poll_event_loop(cx, false))
+                self.with_runtime(|this, js| {
+                    let _running = this.watchdog.enter(None);
+                    js.poll_event_loop(cx, false)
+                })
             });
 
             tokio::select! {
@@ -366,6 +565,7 @@ struct ValueResolver {
     event: Arc<Event>,
     prev_err: Option<anyhow::Error>,
     js: Rc<RefCell<JsRuntime>>,
+    watchdog: Rc<Watchdog>,
     timer: async_io::Timer,
 }
 
@@ -377,6 +577,7 @@ impl ValueResolver {
             event: runtime.event.clone(),
             prev_err: None,
             js: runtime.js.clone(),
+            watchdog: runtime.watchdog.clone(),
             timer: async_io::Timer::interval(std::time::Duration::from_millis(250)),
         }
     }
@@ -427,7 +628,11 @@ impl std::future::Future for ValueResolver {
             }
         };
 
-        let poll = js.poll_value(&this.value, cx);
+        let poll = {
+            // continuations can't be attributed to the call awaiting them.
+            let _running = this.watchdog.enter(None);
+            js.poll_value(&this.value, cx)
+        };
 
         // notify event when poll is ready so another poller will wait instead.
         if poll.is_ready() {
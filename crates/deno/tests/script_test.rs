@@ -10,6 +10,7 @@ use deno_core::{
     serde_v8,
     v8::{self, Local},
 };
+use mado_core::http::{Replay, ReplayMode};
 use mado_deno::Runtime;
 use serde::de::DeserializeOwned;
 use tokio::task::LocalSet;
@@ -54,7 +55,17 @@ pub fn script_test() -> Result<(), Box<dyn std::error::Error>> {
             .with_event_loop(coverage_collector.start_collecting()),
     )?;
 
+    // test files have no manifest and call modules directly.
+    let unrestricted = mado_deno::permission::ModulePermissions::unrestricted;
+    runtime.clone().set_unbound_permissions(unrestricted());
+    // modules replay the fixtures in ./fixtures unless MADO_HTTP_REPLAY
+    // says otherwise, so the tests don't need network access.
+    let replay = Replay::from_env().unwrap_or_else(|| Replay::new(ReplayMode::Replay, "fixtures"));
+    runtime
+        .clone()
+        .set_http_client(mado_core::http::Client::default().with_replay(replay));
     let mut runtime = mado_deno::ModuleLoader::from_runtime(runtime);
+    runtime.set_default_permissions(unrestricted());
 
     let mut module_to_path = HashMap::new();
 
@@ -0,0 +1,162 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+};
+
+use event_listener::Event;
+use parking_lot::Mutex;
+
+/// Cooperative cancellation of running downloads.
+///
+/// Cloned tokens share the same state, tokens created with [`Self::child`]
+/// are cancelled with their parent but can also be cancelled on their own.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    event: Event,
+    children: Mutex<Vec<Weak<Inner>>>,
+}
+
+impl Inner {
+    fn cancel(&self) {
+        if self.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        self.event.notify(usize::MAX);
+
+        for child in std::mem::take(&mut *self.children.lock()) {
+            if let Some(child) = child.upgrade() {
+                child.cancel();
+            }
+        }
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn child(&self) -> Self {
+        let child = Self::new();
+
+        let mut children = self.inner.children.lock();
+        // checked while holding the lock so a concurrent cancel can't miss it.
+        if self.is_cancelled() {
+            child.cancel();
+        } else {
+            children.retain(|it| it.strong_count() > 0);
+            children.push(Arc::downgrade(&child.inner));
+        }
+
+        child
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolve once the token is cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            if self.is_cancelled() {
+                return;
+            }
+
+            let listener = self.inner.event.listen();
+
+            if self.is_cancelled() {
+                return;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// Return [`mado_core::Error::Cancelled`] if the token is cancelled.
+    pub fn check(&self) -> Result<(), mado_core::Error> {
+        if self.is_cancelled() {
+            Err(mado_core::Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Run `future` until it finishes or the token is cancelled, whichever
+    /// comes first.
+    pub async fn run<F, T>(&self, future: F) -> Result<T, mado_core::Error>
+    where
+        F: Future<Output = Result<T, mado_core::Error>>,
+    {
+        self.check()?;
+
+        let cancelled = self.cancelled();
+        futures::pin_mut!(future, cancelled);
+
+        match futures::future::select(future, cancelled).await {
+            futures::future::Either::Left((result, _)) => result,
+            futures::future::Either::Right(_) => Err(mado_core::Error::Cancelled),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn cancel_test() {
+        let token = CancellationToken::new();
+        let child = token.child();
+        let clone = child.clone();
+
+        assert!(!token.is_cancelled());
+        child.cancel();
+        assert!(clone.is_cancelled());
+        assert!(!token.is_cancelled());
+
+        let child = token.child();
+        token.cancel();
+        assert!(child.is_cancelled());
+        assert!(token.child().is_cancelled());
+    }
+
+    #[test]
+    fn run_test() {
+        let token = CancellationToken::new();
+
+        futures::executor::block_on(async {
+            let result = token.run(async { Ok(1) }).await;
+            assert_eq!(result.unwrap(), 1);
+
+            let cancel = async {
+                crate::timer::sleep(Duration::from_millis(10)).await;
+                token.cancel();
+            };
+            let run = token.run(async {
+                crate::timer::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            });
+
+            let (result, _) = futures::join!(run, cancel);
+            assert!(result.unwrap_err().is_cancelled());
+
+            token.cancelled().await;
+        });
+    }
+}
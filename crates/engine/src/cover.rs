@@ -0,0 +1,139 @@
+use crate::{
+    core::{ArcMadoModule, Error, Url},
+    path::Utf8PathBuf,
+};
+
+/// Name of the cover saved in a manga's download directory.
+pub const COVER_FILENAME: &str = "cover.jpg";
+
+/// Download the cover at `url` with the client of `module`, so its proxy
+/// and cache apply. Relative urls are joined with the module's domain.
+pub async fn fetch_cover(module: &ArcMadoModule, url: &str) -> Result<Vec<u8>, Error> {
+    let url = Url::parse(url)
+        .or_else(|_| module.domain().join(url))
+        .map_err(|source| Error::UrlParseError {
+            input: url.to_string(),
+            source,
+        })?;
+
+    let mado_core::Client::Http(client) = module.client();
+
+    // image hosts commonly reject requests from other sites.
+    let response = client
+        .get(url)
+        .header("Referer".to_string(), module.domain().to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(response.bytes().await?)
+}
+
+/// Write `data` to `path` through a `.part` file, creating its directory.
+pub(crate) async fn write_file(path: &Utf8PathBuf, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let part = format!("{}.part", path);
+    tokio::fs::write(&part, data).await?;
+    tokio::fs::rename(&part, path).await
+}
+
+/// Whether `path` exists, without blocking the runtime.
+pub(crate) async fn exists(path: &Utf8PathBuf) -> bool {
+    tokio::fs::metadata(path).await.is_ok()
+}
+
+/// Covers fetched by [`fetch_cover`], stored in a directory by url.
+#[derive(Debug, Clone)]
+pub struct CoverCache {
+    dir: Utf8PathBuf,
+}
+
+impl CoverCache {
+    pub fn new(dir: impl Into<Utf8PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn key(url: &str) -> String {
+        crate::repository::sha256(url.as_bytes())
+    }
+
+    pub fn path(&self, url: &str) -> Utf8PathBuf {
+        self.dir.join(Self::key(url))
+    }
+
+    /// Where a thumbnail of the cover at `url` scaled to `size` pixels is
+    /// kept, thumbnails are made by the caller.
+    pub fn thumbnail_path(&self, url: &str, size: u32) -> Utf8PathBuf {
+        self.dir.join(format!("{}-{}.png", Self::key(url), size))
+    }
+
+    /// Path of the cover at `url`, fetched first if it isn't cached.
+    pub async fn get(&self, module: &ArcMadoModule, url: &str) -> Result<Utf8PathBuf, Error> {
+        let path = self.path(url);
+
+        if !exists(&path).await {
+            let data = fetch_cover(module, url).await?;
+            write_file(&path, &data).await?;
+        }
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use httpmock::Method::GET;
+    use mado_core::{http::Client, MockMadoModule};
+
+    use super::*;
+    use crate::tests::server_url;
+
+    #[test]
+    fn cache_test() {
+        let server = httpmock::MockServer::start();
+        let domain = server_url(server.address());
+        let cover = server.mock(|when, then| {
+            when.path("/cover.jpg")
+                .method(GET)
+                .header("Referer", domain.as_str());
+            then.body("cover");
+        });
+
+        let mut module = MockMadoModule::new();
+        module.expect_domain().return_const(domain);
+        module
+            .expect_client()
+            .return_const(mado_core::Client::from(Client::default()));
+        let module = Arc::new(module) as ArcMadoModule;
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = Utf8PathBuf::from_path_buf(temp.path().to_path_buf()).unwrap();
+        let cache = CoverCache::new(dir.join("covers"));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let path = cache.get(&module, "/cover.jpg").await.unwrap();
+            assert_eq!(path, cache.path("/cover.jpg"));
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "cover");
+
+            // served from the cache.
+            cache.get(&module, "/cover.jpg").await.unwrap();
+            cover.assert_hits(1);
+
+            assert!(cache.get(&module, "/missing.jpg").await.is_err());
+        });
+
+        assert_ne!(
+            cache.thumbnail_path("/cover.jpg", 100),
+            cache.thumbnail_path("/cover.jpg", 200)
+        );
+    }
+}
@@ -1,14 +1,73 @@
 use std::sync::Arc;
 
-use futures::{channel::mpsc, FutureExt, StreamExt};
+use futures::{channel::mpsc, StreamExt};
 
 use crate::{
-    DownloadStatus, MadoEngineState, MadoEngineStateMsg, MadoModuleLoader,
-    {TaskRunner, TaskScheduler},
+    path::Utf8PathBuf,
+    CancellationToken, MadoEngineState, MadoEngineStateMsg, MadoModuleLoader, ModuleFile,
+    ModuleFileStatus, {TaskRunner, TaskScheduler},
 };
 
 pub struct MadoEngine {
     state: Arc<MadoEngineState>,
+    shutdown: ShutdownHandle,
+}
+
+/// Handle to stop a running [`MadoEngine`].
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHandle {
+    token: CancellationToken,
+    /// Cancelled once [`MadoEngine::run`] returns.
+    finished: CancellationToken,
+}
+
+impl ShutdownHandle {
+    /// Stop the engine and wait until downloads and modules are stopped.
+    pub async fn shutdown(&self) {
+        self.token.cancel();
+        self.finished.cancelled().await;
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.is_cancelled()
+    }
+}
+
+/// Handle to load more module files, see [`MadoEngine::module_loader`].
+#[derive(Debug, Clone)]
+pub struct ModuleLoaderHandle {
+    sender: mpsc::UnboundedSender<LoaderRequest>,
+}
+
+#[derive(Debug)]
+enum LoaderRequest {
+    Load(Utf8PathBuf),
+    Scan,
+}
+
+impl ModuleLoaderHandle {
+    /// Load module file at `path`, modules previously loaded from it are
+    /// closed and replaced.
+    ///
+    /// Returns `false` if the loader has stopped.
+    pub fn load(&self, path: Utf8PathBuf) -> bool {
+        self.sender
+            .unbounded_send(LoaderRequest::Load(path))
+            .is_ok()
+    }
+
+    /// Load files from [`MadoModuleLoader::get_paths`] that weren't loaded
+    /// yet, e.g. after the module folders changed. Files already loaded are
+    /// kept.
+    ///
+    /// Returns `false` if the loader has stopped.
+    pub fn scan(&self) -> bool {
+        self.sender.unbounded_send(LoaderRequest::Scan).is_ok()
+    }
 }
 
 const _: () = {
@@ -28,13 +87,21 @@ impl MadoEngine {
     pub fn new(state: MadoEngineState) -> Self {
         let state = Arc::new(state);
 
-        Self { state }
+        Self {
+            state,
+            shutdown: Default::default(),
+        }
     }
 
     pub fn state(&self) -> Arc<MadoEngineState> {
         self.state.clone()
     }
 
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Run downloads until [`ShutdownHandle::shutdown`] is called.
     pub async fn run(self) {
         let mut rx = self.connect_state();
 
@@ -42,20 +109,46 @@ impl MadoEngine {
         let (scheduler, runner) = TaskScheduler::connect(self.state.clone(), option);
 
         let runner = Arc::new(runner);
+        let mut tasks: Vec<tokio::task::JoinHandle<_>> = Vec::new();
 
-        let scheduler = scheduler.run();
+        {
+            let scheduler = scheduler.run();
 
-        let rx = async move {
-            while let Some(msg) = rx.next().await {
-                match msg {
-                    MadoEngineMsg::Download(info) => {
-                        tokio::spawn(self.download(runner.clone(), info));
+            let rx = async {
+                while let Some(msg) = rx.next().await {
+                    match msg {
+                        MadoEngineMsg::Download(info) => {
+                            tasks.retain(|it| !it.is_finished());
+                            tasks.push(tokio::spawn(self.download(runner.clone(), info)));
+                        }
                     }
                 }
+            };
+
+            let run = futures::future::join(rx, scheduler);
+            let shutdown = self.shutdown.token.cancelled();
+
+            futures::pin_mut!(run, shutdown);
+            futures::future::select(run, shutdown).await;
+        }
+
+        tracing::trace!("shutting down engine");
+        runner.cancel_token().cancel();
+
+        for task in tasks {
+            if let Err(err) = task.await {
+                tracing::error!("error stopping download: {}", err);
             }
-        };
+        }
 
-        let _ = futures::future::join(rx, scheduler).await;
+        for module in self.state.module_list() {
+            if let Err(err) = module.close().await {
+                tracing::error!("error closing module {}: {}", module.name(), err);
+            }
+        }
+
+        tracing::trace!("engine stopped");
+        self.shutdown.finished.cancel();
     }
 
     pub fn connect_state(&self) -> mpsc::UnboundedReceiver<MadoEngineMsg> {
@@ -67,37 +160,61 @@ impl MadoEngine {
                     tx.unbounded_send(MadoEngineMsg::Download(info.clone()))
                         .ok();
                 }
-                MadoEngineStateMsg::PushModule(_) => {}
+                // the download task notices removal by itself.
+                MadoEngineStateMsg::RemoveDownload(_)
+                | MadoEngineStateMsg::PushModule(_)
+                | MadoEngineStateMsg::RemoveModule(_)
+                | MadoEngineStateMsg::ModuleFile(_) => {}
             }
         });
         rx
     }
 
+    /// Load every module file from `loader`.
     pub fn load_module(
         &self,
         loader: impl MadoModuleLoader + 'static,
     ) -> impl std::future::Future<Output = impl Send> + Send + 'static {
+        let (_, run) = self.module_loader(loader);
+        run
+    }
+
+    /// Load every module file from `loader`, then keep loading files
+    /// requested through the returned handle until every handle is dropped.
+    ///
+    /// Status of each file is kept in [`MadoEngineState::module_files`].
+    pub fn module_loader(
+        &self,
+        loader: impl MadoModuleLoader + 'static,
+    ) -> (
+        ModuleLoaderHandle,
+        impl std::future::Future<Output = impl Send> + Send + 'static,
+    ) {
+        let (sender, mut rx) = mpsc::unbounded();
         let state = self.state.clone();
-        async move {
-            let paths = loader.get_paths().await;
-
-            for path in paths {
-                match loader.load(path.clone()).await {
-                    Ok(modules) => {
-                        for it in modules {
-                            if let Err(err) = state.push_module(it) {
-                                tracing::error!("error pushing {}: {}", path, err);
+
+        let run = async move {
+            for path in loader.get_paths().await {
+                load_module_file(&state, &loader, path).await;
+            }
+
+            while let Some(request) = rx.next().await {
+                match request {
+                    LoaderRequest::Load(path) => load_module_file(&state, &loader, path).await,
+                    LoaderRequest::Scan => {
+                        for path in loader.get_paths().await {
+                            if state.module_file(&path).is_none() {
+                                load_module_file(&state, &loader, path).await;
                             }
                         }
                     }
-                    Err(err) => {
-                        tracing::error!("error loading {}: {}", path, err);
-                    }
                 }
             }
 
             Some(())
-        }
+        };
+
+        (ModuleLoaderHandle { sender }, run)
     }
 
     fn download(
@@ -107,24 +224,243 @@ impl MadoEngine {
     ) -> impl std::future::Future<Output = impl Send> + Send + 'static {
         let state = self.state();
         async move {
-            loop {
-                let option = state.option();
-                let it = std::panic::AssertUnwindSafe(scheduler.run(info.clone(), |info| {
-                    crate::TaskDownloader::new(info.clone(), option.clone())
-                }))
-                .catch_unwind()
-                .await;
-
-                if let Err(e) = it {
-                    if let Some(e) = e.downcast_ref::<&str>() {
-                        info.set_status(DownloadStatus::error(e));
-                    } else if let Some(e) = e.downcast_ref::<String>() {
-                        info.set_status(DownloadStatus::error(e));
-                    } else {
-                        info.set_status(DownloadStatus::error("Cannot dechiper panic error!"));
+            let watcher = crate::watcher::DownloadInfoWatcher::connect(info.clone());
+
+            // errors end up in the download's status, the runner then waits
+            // until the user resumes it.
+            let run = scheduler.run(info.clone(), |info| {
+                crate::TaskDownloader::new(info.clone(), state.option())
+            });
+            let removed = watcher.wait_removed();
+
+            futures::pin_mut!(run, removed);
+            futures::future::select(run, removed).await;
+
+            tracing::trace!("download {:?} removed", info.id());
+        }
+    }
+}
+
+async fn load_module_file(
+    state: &MadoEngineState,
+    loader: &impl MadoModuleLoader,
+    path: Utf8PathBuf,
+) {
+    if let Some(file) = state.module_file(&path) {
+        for uuid in file.modules() {
+            if let Some(module) = state.take_module(*uuid) {
+                if let Err(err) = module.close().await {
+                    tracing::error!("error closing module {}: {}", module.name(), err);
+                }
+            }
+        }
+    }
+
+    state.set_module_file(ModuleFile::new(path.clone()));
+
+    let file = match loader.load(path.clone()).await {
+        Ok(modules) => {
+            let mut uuids = Vec::new();
+            let mut errors = Vec::new();
+
+            for it in modules {
+                let uuid = it.uuid();
+                match state.push_module(it) {
+                    Ok(()) => uuids.push(uuid),
+                    Err(err) => {
+                        tracing::error!("error pushing {}: {}", path, err);
+                        errors.push(err.to_string());
                     }
                 }
             }
+
+            let status = match errors.is_empty() {
+                true => ModuleFileStatus::Loaded,
+                false => ModuleFileStatus::Failed(errors.join("\n")),
+            };
+
+            ModuleFile::new(path)
+                .with_status(status)
+                .with_modules(uuids)
+        }
+        Err(err) => {
+            tracing::error!("error loading {}: {}", path, err);
+            ModuleFile::new(path).with_status(ModuleFileStatus::Failed(err.to_string()))
+        }
+    };
+
+    state.set_module_file(file);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use mado_core::{
+        DefaultMadoModuleMap, MadoModuleMap, MockMadoModule, MutexMadoModuleMap, Uuid,
+    };
+
+    use super::*;
+
+    #[test]
+    fn shutdown_test() {
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+        module
+            .expect_domain()
+            .return_const(mado_core::Url::try_from("http://localhost").unwrap());
+        module.expect_close().times(1).returning(|| Ok(()));
+
+        let map = Arc::new(MutexMadoModuleMap::new(DefaultMadoModuleMap::new()));
+        let state = MadoEngineState::new(map, Default::default(), Default::default());
+        state.push_module(Arc::new(module)).unwrap();
+
+        let engine = MadoEngine::new(state);
+        let handle = engine.shutdown_handle();
+
+        futures::executor::block_on(async {
+            let shutdown = async {
+                crate::timer::sleep(Duration::from_millis(10)).await;
+                assert!(!handle.is_finished());
+                handle.shutdown().await;
+            };
+
+            futures::join!(engine.run(), shutdown);
+        });
+
+        assert!(handle.is_shutdown());
+        assert!(handle.is_finished());
+    }
+
+    struct Loader;
+
+    #[async_trait::async_trait]
+    impl MadoModuleLoader for Loader {
+        async fn get_paths(&self) -> Vec<Utf8PathBuf> {
+            vec!["a.js".into(), "b.js".into()]
+        }
+
+        async fn load(
+            &self,
+            path: Utf8PathBuf,
+        ) -> Result<Vec<mado_core::ArcMadoModule>, crate::ModuleLoadError> {
+            if path == "b.js" {
+                return Err(std::io::Error::from(std::io::ErrorKind::NotFound).into());
+            }
+
+            let mut module = MockMadoModule::new();
+            module.expect_uuid().return_const(Uuid::from_u128(1));
+            module
+                .expect_domain()
+                .return_const(mado_core::Url::try_from("http://localhost").unwrap());
+            module.expect_close().returning(|| Ok(()));
+
+            Ok(vec![Arc::new(module)])
         }
     }
+
+    #[test]
+    fn module_loader_test() {
+        let engine = MadoEngine::new(MadoEngineState::default());
+        let state = engine.state();
+        let (handle, run) = engine.module_loader(Loader);
+
+        // a.js is reloaded, c.js has the same uuid as a.js.
+        assert!(handle.load("a.js".into()));
+        assert!(handle.load("c.js".into()));
+        // every file is already loaded.
+        assert!(handle.scan());
+        drop(handle);
+
+        futures::executor::block_on(run);
+
+        let files = state.module_files();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].status(), &ModuleFileStatus::Loaded);
+        assert_eq!(files[0].modules(), [Uuid::from_u128(1)]);
+        assert!(matches!(files[1].status(), ModuleFileStatus::Failed(_)));
+        assert!(matches!(files[2].status(), ModuleFileStatus::Failed(_)));
+        assert!(files[2].modules().is_empty());
+        assert!(state.modules().get_by_uuid(Uuid::from_u128(1)).is_some());
+    }
+
+    #[test]
+    fn remove_streaming_download_test() {
+        use std::io::{Read, Write};
+
+        // sends half of the image, then stalls before sending the rest.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.read(&mut [0; 1024]).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\ntest")
+                .unwrap();
+            stream.flush().unwrap();
+            std::thread::sleep(Duration::from_millis(300));
+            stream.write_all(b"rest").ok();
+        });
+
+        let url = crate::tests::server_url(&address).join("/image").unwrap();
+        let client = mado_core::http::Client::default();
+
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+        module
+            .expect_domain()
+            .return_const(mado_core::Url::try_from("http://localhost").unwrap());
+        module.expect_get_chapter_images().returning(|_, mut task| {
+            task.add(mado_core::ChapterImageInfo {
+                id: "1".to_string(),
+                extension: "png".to_string(),
+                name: None,
+            });
+            Ok(())
+        });
+        module
+            .expect_download_image()
+            .returning(move |_| Ok(client.get(url.clone()).into()));
+        module.expect_close().returning(|| Ok(()));
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::from_path_buf(temp.path().join("manga")).unwrap();
+
+        let engine = MadoEngine::new(MadoEngineState::default());
+        let state = engine.state();
+        let handle = engine.shutdown_handle();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let run = tokio::spawn(engine.run());
+
+            state.download_request(crate::DownloadRequest::new(
+                Arc::new(module),
+                Default::default(),
+                vec![Default::default()],
+                path.clone(),
+                None,
+                crate::DownloadRequestStatus::Resume,
+            ));
+            let info = state.tasks()[0].clone();
+            let part = info.chapters()[0].path().join("0001.png.part");
+
+            while std::fs::read(&part).map_or(true, |it| it != b"test") {
+                crate::timer::sleep(Duration::from_millis(5)).await;
+            }
+
+            state.remove_download(info.id(), true).await.unwrap();
+            assert!(!path.exists());
+
+            // the rest of the image must not bring the files back.
+            crate::timer::sleep(Duration::from_millis(500)).await;
+            assert!(!path.exists());
+
+            handle.shutdown().await;
+            run.await.unwrap();
+        });
+    }
 }
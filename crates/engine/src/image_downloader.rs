@@ -3,14 +3,39 @@ use std::{future::Future, time::Duration};
 use futures::{AsyncWrite, AsyncWriteExt};
 use mado_core::{ArcMadoModule, ChapterImageInfo};
 
+use crate::CancellationToken;
+
 #[cfg_attr(any(test), mockall::automock(type Buffer=MutexVec;))]
 pub trait ImageDownloaderConfig {
-    type Buffer: AsyncWrite + Unpin;
+    type Buffer: ImageBuffer;
 
     fn should_retry(&self, retry_count: usize) -> bool;
+    /// How long to wait before the next try.
+    fn retry_delay(&self, retry_count: usize) -> Duration;
     fn timeout(&self) -> Duration;
 
-    fn buffer(&self) -> Self::Buffer;
+    /// Buffer for a try, it may still hold bytes from an earlier one.
+    fn buffer(&self) -> std::io::Result<Self::Buffer>;
+}
+
+/// Where an image is written while it downloads.
+pub trait ImageBuffer: AsyncWrite + Unpin {
+    /// Bytes kept from an earlier try, the download continues after them.
+    fn written(&self) -> u64;
+
+    /// Discard kept bytes, the server is sending the whole image again.
+    fn clear(&mut self) -> std::io::Result<()>;
+}
+
+/// Files are opened in append mode, so writes follow the kept bytes.
+impl ImageBuffer for futures::io::AllowStdIo<std::fs::File> {
+    fn written(&self) -> u64 {
+        self.get_ref().metadata().map(|it| it.len()).unwrap_or(0)
+    }
+
+    fn clear(&mut self) -> std::io::Result<()> {
+        self.get_mut().set_len(0)
+    }
 }
 
 #[cfg(test)]
@@ -22,8 +47,25 @@ mod mutexvec {
 
     use parking_lot::Mutex;
 
-    #[derive(Default, Clone)]
+    #[derive(Debug, Default, Clone)]
     pub struct MutexVec(Arc<Mutex<Vec<u8>>>);
+
+    impl MutexVec {
+        pub fn new(data: impl Into<Vec<u8>>) -> Self {
+            Self(Arc::new(Mutex::new(data.into())))
+        }
+    }
+
+    impl super::ImageBuffer for MutexVec {
+        fn written(&self) -> u64 {
+            self.0.lock().len() as u64
+        }
+
+        fn clear(&mut self) -> std::io::Result<()> {
+            self.0.lock().clear();
+            Ok(())
+        }
+    }
     impl futures::io::AsyncWrite for MutexVec {
         fn poll_write(
             self: std::pin::Pin<&mut Self>,
@@ -56,14 +98,14 @@ mod mutexvec {
 }
 
 /// Run future returned by fun until the future return Ok or should_retry return false.
-/// limit will be called with retry count and after fun is awaited
+/// should_retry will be called with retry count and the error after fun is awaited
 #[inline]
 pub async fn do_while_err_or<F, R, O, E, L>(mut fun: F, mut should_retry: L) -> Result<O, E>
 where
     F: FnMut() -> R,
     R: Future<Output = Result<O, E>>,
     E: std::fmt::Display,
-    L: FnMut(usize) -> bool,
+    L: FnMut(usize, &E) -> bool,
 {
     let mut retry = 0;
     let mut error;
@@ -79,7 +121,7 @@ where
 
         retry += 1;
 
-        let stop = !should_retry(retry);
+        let stop = !should_retry(retry, &error);
 
         tracing::error!(
             "{}, {}",
@@ -93,40 +135,90 @@ where
     }
 }
 
-async fn wait_timeout<F>(future: F, duration: Duration) -> Result<F::Output, mado_core::Error>
+/// Wait for `future` from the response of `url`, a stalled transfer is a
+/// network error so it can be retried.
+async fn wait_timeout<F>(
+    future: F,
+    duration: Duration,
+    url: &str,
+) -> Result<F::Output, mado_core::Error>
 where
     F: Future,
 {
     let timeout = crate::timer::timeout(duration, future);
 
-    let result = timeout
-        .await
-        .map_err(|elapsed| mado_core::Error::ExternalError(elapsed.into()))?;
+    let result = timeout.await.map_err(|_| mado_core::Error::RequestError {
+        url: url.to_string(),
+        message: format!("no data received in {:?}", duration),
+    })?;
 
     Ok(result)
 }
 
+/// Write the response of `request` to `buffer`, stopping with
+/// [`mado_core::Error::Cancelled`] as soon as `cancel` is cancelled.
+///
+/// Bytes already in `buffer` are kept and only the rest of the image is
+/// requested, unless the server doesn't support ranges.
 pub async fn download_http<Buffer>(
-    request: mado_core::http::RequestBuilder,
+    mut request: mado_core::http::RequestBuilder,
     buffer: &mut Buffer,
     mut timeout: impl FnMut() -> Duration,
+    cancel: &CancellationToken,
 ) -> Result<(), mado_core::Error>
 where
-    Buffer: AsyncWrite + Unpin,
+    Buffer: ImageBuffer,
 {
     const BUFFER_SIZE: usize = 1024;
-    let mut total = 0;
+    const PARTIAL_CONTENT: u16 = 206;
+    const RANGE_NOT_SATISFIABLE: u16 = 416;
+
+    let written = buffer.written();
+    if written > 0 {
+        // the cache only knows whole responses.
+        request = request
+            .no_cache()
+            .header("Range".to_string(), format!("bytes={}-", written));
+    }
+
+    let response = cancel.run(async { Ok(request.send().await?) }).await?;
+
+    if written > 0 {
+        match response.status().as_u16() {
+            PARTIAL_CONTENT => {}
+            RANGE_NOT_SATISFIABLE => {
+                // kept bytes don't belong to this image anymore.
+                buffer.clear()?;
+                return Err(mado_core::Error::RequestError {
+                    url: response.url().to_string(),
+                    message: format!("cannot continue after {} bytes", written),
+                });
+            }
+            // the server ignored the range and sends the whole image.
+            status if (200..300).contains(&status) => buffer.clear()?,
+            // kept for the next try, which asks for the same range.
+            _ => {}
+        }
+    }
 
-    let response = request.send().await?;
+    let response = response.error_for_status()?;
+    let mut total = buffer.written() as usize;
     let length = response
         .header_str("Content-Length")
         .map(|it| it.to_string());
+    let url = response.url().to_string();
 
     let mut stream = response.stream();
 
     loop {
         let mut buf = vec![0u8; BUFFER_SIZE];
-        let size = wait_timeout(stream.read(&mut buf), timeout()).await??;
+        // reading fails when the connection drops, unlike writing to `buffer`.
+        let size = cancel
+            .run(async {
+                let read = wait_timeout(stream.read(&mut buf), timeout(), &url).await?;
+                Ok(read.map_err(mado_core::http::Error::from)?)
+            })
+            .await?;
 
         let (buf, _) = buf.split_at(size);
 
@@ -150,6 +242,7 @@ pub struct ImageDownloader<C> {
     module: ArcMadoModule,
     image: ChapterImageInfo,
     config: C,
+    cancel: CancellationToken,
 }
 
 impl<C> ImageDownloader<C>
@@ -161,20 +254,47 @@ where
             module,
             image,
             config,
+            cancel: CancellationToken::new(),
         }
     }
 
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
     pub async fn download(self) -> Result<C::Buffer, mado_core::Error> {
+        let delay = parking_lot::Mutex::new(Duration::ZERO);
+
         do_while_err_or(
             || async {
+                let delay = std::mem::take(&mut *delay.lock());
+                if !delay.is_zero() {
+                    tracing::trace!("waiting {:?} before retrying", delay);
+                    self.cancel
+                        .run(async {
+                            crate::timer::sleep(delay).await;
+                            Ok(())
+                        })
+                        .await?;
+                }
+
                 tracing::trace!("trying...");
-                let mut buffer = self.config.buffer();
+                let mut buffer = self.config.buffer()?;
 
                 self.download_without_retry(&mut buffer).await?;
 
                 Ok(buffer)
             },
-            |retry| self.config.should_retry(retry),
+            // retrying won't fix missing pages or broken modules.
+            |retry, error: &mado_core::Error| {
+                let kind = error.kind();
+                *delay.lock() = kind
+                    .retry_after()
+                    .unwrap_or_else(|| self.config.retry_delay(retry));
+
+                kind.is_retryable() && self.config.should_retry(retry)
+            },
         )
         .await
     }
@@ -184,14 +304,13 @@ where
         buffer: &mut C::Buffer,
     ) -> Result<(), mado_core::Error> {
         let request = self
-            .module
-            .download_image(self.image.clone())
-            .await
-            .unwrap();
+            .cancel
+            .run(self.module.download_image(self.image.clone()))
+            .await?;
 
         match request {
             mado_core::RequestBuilder::Http(request) => {
-                download_http(request, buffer, || self.config.timeout()).await
+                download_http(request, buffer, || self.config.timeout(), &self.cancel).await
             }
         }
     }
@@ -230,7 +349,7 @@ mod tests {
                         Err("")
                     }
                 },
-                |retry| retry <= 1,
+                |retry, _| retry <= 1,
             )
             .await
             .unwrap();
@@ -244,14 +363,14 @@ mod tests {
                     set(get() + 1);
                     Result::<(), &str>::Err("")
                 },
-                |retry| retry < RETRY,
+                |retry, _| retry < RETRY,
             )
             .await
             .unwrap_err();
 
             assert_eq!(get(), RETRY);
 
-            do_while_err_or(|| async { Ok::<_, &str>(()) }, |_| unreachable!())
+            do_while_err_or(|| async { Ok::<_, &str>(()) }, |_, _| unreachable!())
                 .await
                 .unwrap();
         });
@@ -286,13 +405,119 @@ mod tests {
 
         futures::executor::block_on(async {
             let request = client.get(server_url.join("/test").unwrap());
-            download_http(request, &mut buffer, || Duration::from_millis(50))
-                .await
-                .unwrap();
+            download_http(
+                request,
+                &mut buffer,
+                || Duration::from_millis(50),
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
             assert_eq!(buffer.to_string(), "testtest");
         });
     }
 
+    #[test]
+    fn resume_test() {
+        let server = MockServer::start();
+
+        let _m = server.mock(|when, then| {
+            when.path("/resume").header("Range", "bytes=4-");
+            then.status(206).body("rest");
+        });
+
+        let server_url = server_url(_m.server_address());
+        let client = mado_core::http::Client::default();
+
+        futures::executor::block_on(async {
+            let mut buffer = MutexVec::new("test");
+            let request = client.get(server_url.join("/resume").unwrap());
+            download_http(
+                request,
+                &mut buffer,
+                || Duration::from_millis(50),
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(buffer.to_string(), "testrest");
+        });
+    }
+
+    #[test]
+    fn resume_without_range_test() {
+        let server = MockServer::start();
+
+        // the server ignores the range and sends everything again.
+        let _m = server.mock(|when, then| {
+            when.path("/whole");
+            then.status(200).body("testrest");
+        });
+
+        let server_url = server_url(_m.server_address());
+        let client = mado_core::http::Client::default();
+
+        futures::executor::block_on(async {
+            let mut buffer = MutexVec::new("test");
+            let request = client.get(server_url.join("/whole").unwrap());
+            download_http(
+                request,
+                &mut buffer,
+                || Duration::from_millis(50),
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(buffer.to_string(), "testrest");
+        });
+    }
+
+    #[test]
+    fn resume_after_error_test() {
+        let server = MockServer::start();
+
+        let mut unavailable = server.mock(|when, then| {
+            when.path("/retry").header("Range", "bytes=4-");
+            then.status(503);
+        });
+
+        let server_url = server_url(unavailable.server_address());
+        let client = mado_core::http::Client::default();
+
+        futures::executor::block_on(async {
+            let mut buffer = MutexVec::new("test");
+            let request = client.get(server_url.join("/retry").unwrap());
+            download_http(
+                request,
+                &mut buffer,
+                || Duration::from_millis(50),
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(buffer.to_string(), "test");
+
+            unavailable.delete();
+            server.mock(|when, then| {
+                when.path("/retry").header("Range", "bytes=4-");
+                then.status(206).body("rest");
+            });
+
+            let request = client.get(server_url.join("/retry").unwrap());
+            download_http(
+                request,
+                &mut buffer,
+                || Duration::from_millis(50),
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(buffer.to_string(), "testrest");
+        });
+    }
+
     #[test]
     fn timeout_test() {
         let mut buffer = MutexVec::default();
@@ -315,9 +540,81 @@ mod tests {
 
         futures::executor::block_on(async {
             let request = client.get(server_url.join("/timeout").unwrap());
-            download_http(request, &mut buffer, || Duration::from_millis(2))
-                .await
-                .unwrap_err();
+            let error = download_http(
+                request,
+                &mut buffer,
+                || Duration::from_millis(2),
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap_err();
+
+            assert_eq!(error.kind(), mado_core::ErrorKind::Network);
+        });
+    }
+
+    #[test]
+    fn status_test() {
+        let mut buffer = MutexVec::default();
+
+        let server = MockServer::start();
+
+        let _m = server.mock(|when, then| {
+            when.path("/missing");
+            then.status(404).body("not found");
+        });
+
+        let server_url = server_url(_m.server_address());
+        let client = mado_core::http::Client::default();
+
+        futures::executor::block_on(async {
+            let request = client.get(server_url.join("/missing").unwrap());
+            let error = download_http(
+                request,
+                &mut buffer,
+                || Duration::from_millis(50),
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap_err();
+
+            assert_eq!(error.kind(), mado_core::ErrorKind::NotFound);
+            assert_eq!(buffer.to_string(), "");
+        });
+    }
+
+    #[test]
+    fn cancel_test() {
+        let mut buffer = MutexVec::default();
+
+        let server = MockServer::start();
+
+        let _m = server.mock(|when, then| {
+            when.path("/slow");
+
+            then.body_stream(|| {
+                StreamBuilder::new()
+                    .body("t")
+                    .delay(Duration::from_secs(5))
+                    .body("t")
+                    .build()
+            });
+        });
+
+        let server_url = server_url(_m.server_address());
+        let client = mado_core::http::Client::default();
+        let cancel = CancellationToken::new();
+
+        futures::executor::block_on(async {
+            let request = client.get(server_url.join("/slow").unwrap());
+            let download = download_http(request, &mut buffer, || Duration::from_secs(10), &cancel);
+            let stop = async {
+                crate::timer::sleep(Duration::from_millis(50)).await;
+                cancel.cancel();
+            };
+
+            let (result, _) = futures::join!(download, stop);
+            assert!(result.unwrap_err().is_cancelled());
         });
     }
 
@@ -423,7 +720,7 @@ mod tests {
             .return_once(|_| Ok(mado_core::RequestBuilder::Http(request)));
 
         let mut config = MockImageDownloaderConfig::new();
-        config.expect_buffer().return_const(buffer);
+        config.expect_buffer().returning(move || Ok(buffer.clone()));
         config.expect_should_retry().return_once(|_| true);
         config
             .expect_timeout()
@@ -438,4 +735,106 @@ mod tests {
             assert_eq!(vec.to_string(), "test");
         });
     }
+
+    #[test]
+    fn image_downloader_timeout_retry_test() {
+        let server = MockServer::start();
+        let slow = server.mock(|when, then| {
+            when.path("/slow");
+            then.body_stream(|| {
+                StreamBuilder::new()
+                    .delay(Duration::from_millis(100))
+                    .body("slow")
+                    .build()
+            });
+        });
+        let fast = server.mock(|when, then| {
+            when.path("/fast");
+            then.body_stream(|| StreamBuilder::new().body("fast").build());
+        });
+        let server_url = server_url(server.address());
+
+        let image = ChapterImageInfo {
+            id: "1".to_string(),
+            extension: "png".to_string(),
+            ..Default::default()
+        };
+
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+
+        let client = mado_core::http::Client::default();
+        let calls = AtomicUsize::new(0);
+        module.expect_download_image().returning(move |_| {
+            let path = match calls.fetch_add(1, atomic::Ordering::Relaxed) {
+                0 => "/slow",
+                _ => "/fast",
+            };
+            let request = client.get(server_url.join(path).unwrap());
+            Ok(mado_core::RequestBuilder::Http(request))
+        });
+
+        let mut config = MockImageDownloaderConfig::new();
+        config.expect_buffer().returning(|| Ok(MutexVec::default()));
+        config.expect_should_retry().returning(|_| true);
+        config.expect_retry_delay().return_const(Duration::ZERO);
+        config
+            .expect_timeout()
+            .return_const(Duration::from_millis(20));
+
+        let downloader = ImageDownloader::new(Arc::new(module), image, config);
+
+        futures::executor::block_on(async {
+            let vec = downloader.download().await.unwrap();
+            assert_eq!(vec.to_string(), "fast");
+        });
+        slow.assert_hits(1);
+        fast.assert_hits(1);
+    }
+
+    #[test]
+    fn image_downloader_module_error_test() {
+        let image = ChapterImageInfo {
+            id: "1".to_string(),
+            extension: "png".to_string(),
+            ..Default::default()
+        };
+
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let count = calls.clone();
+        module.expect_download_image().returning(move |_| {
+            if count.fetch_add(1, atomic::Ordering::Relaxed) == 0 {
+                Err(mado_core::Error::RequestError {
+                    url: "1".to_string(),
+                    message: "connection reset".to_string(),
+                })
+            } else {
+                Err(mado_core::Error::NotFound {
+                    url: "1".to_string(),
+                    message: "removed".to_string(),
+                })
+            }
+        });
+
+        let mut config = MockImageDownloaderConfig::new();
+        config.expect_buffer().returning(|| Ok(MutexVec::default()));
+        config.expect_should_retry().returning(|_| true);
+        config.expect_retry_delay().return_const(Duration::ZERO);
+        config
+            .expect_timeout()
+            .return_const(Duration::from_millis(10));
+
+        let downloader = ImageDownloader::new(Arc::new(module), image, config);
+
+        futures::executor::block_on(async {
+            let error = downloader.download().await.unwrap_err();
+
+            // network error is retried, the missing image is not.
+            assert_eq!(error.kind(), mado_core::ErrorKind::NotFound);
+            assert_eq!(calls.load(atomic::Ordering::Relaxed), 2);
+        });
+    }
 }
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::SystemTime};
 
 use crate::{
     path::Utf8PathBuf, DownloadChapterImageInfo, DownloadStatus, LateBindingModule, ObserverHandle,
@@ -13,9 +13,20 @@ pub struct DownloadChapterInfo {
     chapter_id: String,
     path: Utf8PathBuf,
     status: Mutex<DownloadStatus>,
+    progress: Mutex<ReadProgress>,
     observers: Observers<BoxObserver>,
     images: Mutex<Vec<Arc<DownloadChapterImageInfo>>>,
 }
+
+/// How far a downloaded chapter has been read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadProgress {
+    pub read: bool,
+    /// Index of the last page shown.
+    pub last_page: usize,
+    /// When `read` or `last_page` last changed.
+    pub updated_at: Option<SystemTime>,
+}
 macro_rules! ImplObserver {
     () => {
         impl FnMut(DownloadChapterInfoMsg<'_>) + Send + 'static
@@ -29,6 +40,7 @@ pub type BoxObserver = Box<dyn FnMut(DownloadChapterInfoMsg<'_>) + Send>;
 pub enum DownloadChapterInfoMsg<'a> {
     StatusChanged(&'a DownloadStatus),
     DownloadImagesChanged(&'a Vec<Arc<DownloadChapterImageInfo>>),
+    ProgressChanged(&'a ReadProgress),
 }
 
 pub trait DownloadChapterInfoObserver: std::fmt::Debug + Send + 'static {
@@ -49,6 +61,7 @@ impl DownloadChapterInfo {
             chapter_id,
             path,
             status: Mutex::new(status),
+            progress: Default::default(),
             images: Default::default(),
             observers: Default::default(),
         }
@@ -76,6 +89,47 @@ impl DownloadChapterInfo {
             .emit(|it| it(DownloadChapterInfoMsg::StatusChanged(&lock)));
     }
 
+    pub fn progress(&self) -> ReadProgress {
+        *self.progress.lock()
+    }
+
+    /// Observers are only notified when `progress` differs from the current one.
+    pub fn set_progress(&self, progress: ReadProgress) {
+        let mut lock = self.progress.lock();
+        if *lock == progress {
+            return;
+        }
+
+        *lock = progress;
+        self.observers
+            .emit(|it| it(DownloadChapterInfoMsg::ProgressChanged(&lock)));
+    }
+
+    pub fn is_read(&self) -> bool {
+        self.progress().read
+    }
+
+    pub fn set_read(&self, read: bool) {
+        self.update_progress(|it| it.read = read);
+    }
+
+    pub fn set_last_page(&self, last_page: usize) {
+        self.update_progress(|it| it.last_page = last_page);
+    }
+
+    /// Change the progress with `fun`, [`ReadProgress::updated_at`] is only
+    /// set when something else changed.
+    fn update_progress(&self, fun: impl FnOnce(&mut ReadProgress)) {
+        let current = self.progress();
+        let mut progress = current;
+        fun(&mut progress);
+
+        if (progress.read, progress.last_page) != (current.read, current.last_page) {
+            progress.updated_at = Some(SystemTime::now());
+            self.set_progress(progress);
+        }
+    }
+
     pub fn images(&self) -> impl std::ops::Deref<Target = Vec<Arc<DownloadChapterImageInfo>>> + '_ {
         self.images.lock()
     }
@@ -103,6 +157,7 @@ impl DownloadChapterInfo {
         observer(DownloadChapterInfoMsg::DownloadImagesChanged(
             &self.images(),
         ));
+        observer(DownloadChapterInfoMsg::ProgressChanged(&self.progress()));
 
         self.connect_only(observer)
     }
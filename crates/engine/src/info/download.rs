@@ -1,12 +1,18 @@
 use crate::{
     core::{ChapterInfo, MangaInfo, Url, Uuid},
     path::Utf8PathBuf,
-    ArcMadoModule, DownloadChapterInfo, DownloadOption, DownloadProgressStatus,
-    DownloadResumedStatus, DownloadStatus, LateBindingModule, ModuleInfo, ObserverHandle,
-    Observers,
+    ArcMadoModule, DownloadChapterInfo, DownloadFailure, DownloadOption, DownloadProgressStatus,
+    DownloadResumedStatus, DownloadStatus, FailureLog, LateBindingModule, ModuleInfo,
+    ObserverHandle, Observers,
 };
 use parking_lot::Mutex;
-use std::sync::{atomic::AtomicUsize, Arc};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize},
+        Arc,
+    },
+    time::SystemTime,
+};
 use typed_builder::TypedBuilder;
 
 macro_rules! ImplObserver {
@@ -18,30 +24,108 @@ macro_rules! ImplObserver {
 
 pub type BoxObserver = Box<dyn FnMut(DownloadInfoMsg) + Send + 'static>;
 
+/// Identifies a download for the lifetime of the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DownloadId(usize);
+
+impl DownloadId {
+    pub fn next() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        Self(NEXT.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+}
+
+/// Scheduling class of a download, downloads with higher priority start
+/// first regardless of their order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DownloadPriority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl DownloadPriority {
+    pub const ALL: [Self; 3] = [Self::High, Self::Normal, Self::Low];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::High => "High",
+            Self::Normal => "Normal",
+            Self::Low => "Low",
+        }
+    }
+}
+
+impl std::str::FromStr for DownloadPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|it| it.as_str() == s)
+            .ok_or_else(|| format!("invalid priority {}", s))
+    }
+}
+
 #[derive(Debug, TypedBuilder)]
 pub struct DownloadInfo {
+    #[builder(default = DownloadId::next())]
+    id: DownloadId,
     #[builder(setter(into))]
     order: AtomicUsize,
     #[builder(setter(into))]
     module: ModuleInfo,
     #[builder(setter(into))]
     status: Mutex<DownloadStatus>,
+    #[builder(setter(into), default)]
+    priority: Mutex<DownloadPriority>,
+    #[builder(setter(into), default)]
+    start_at: Mutex<Option<SystemTime>>,
 
     #[builder(setter(into), default)]
     path: Utf8PathBuf,
     #[builder(setter(into), default)]
     manga_title: String,
+    #[builder(setter(into), default)]
+    cover_link: Option<String>,
     #[builder(default)]
     url: Option<Url>,
     #[builder(default)]
     chapters: Vec<Arc<DownloadChapterInfo>>,
     #[builder(default)]
     observers: Observers<BoxObserver>,
+    #[builder(default)]
+    failures: FailureLog,
+    #[builder(default)]
+    removed: AtomicBool,
+    /// Downloaders writing files of this download, see [`Self::start_running`].
+    #[builder(default)]
+    running: AtomicUsize,
+    #[builder(default)]
+    stopped: event_listener::Event,
+}
+
+/// Marks a downloader as writing files of a download until dropped.
+#[derive(Debug)]
+pub struct RunningGuard(Arc<DownloadInfo>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        if self.0.running.fetch_sub(1, atomic::Ordering::SeqCst) == 1 {
+            self.0.stopped.notify(usize::MAX);
+        }
+    }
 }
 
 pub enum DownloadInfoMsg<'a> {
     StatusChanged(&'a DownloadStatus),
     OrderChanged(usize),
+    PriorityChanged(DownloadPriority),
+    StartAtChanged(Option<SystemTime>),
+    /// The download was removed, see [`DownloadInfo::mark_removed`].
+    Removed,
 }
 
 impl DownloadInfo {
@@ -56,20 +140,40 @@ impl DownloadInfo {
         status: DownloadStatus,
     ) -> Self {
         Self {
+            id: DownloadId::next(),
             order: order.into(),
             module: module.into(),
             manga_title: title,
+            cover_link: None,
             chapters,
             path,
             url,
             status: Mutex::new(status),
+            priority: Default::default(),
+            start_at: Default::default(),
             observers: Default::default(),
+            failures: Default::default(),
+            removed: Default::default(),
+            running: Default::default(),
+            stopped: Default::default(),
         }
     }
 
     pub fn from_request(order: usize, request: DownloadRequest, option: DownloadOption) -> Self {
+        let module = LateBindingModule::Module(request.module().clone());
+        Self::from_request_with_module(order, request, module, option)
+    }
+
+    /// Same as [`Self::from_request`] but binds the download to `module`
+    /// instead of the request's module.
+    pub fn from_request_with_module(
+        order: usize,
+        request: DownloadRequest,
+        module: LateBindingModule,
+        option: DownloadOption,
+    ) -> Self {
         let DownloadRequest {
-            module,
+            module: request_module,
             manga,
             chapters,
             path,
@@ -81,9 +185,9 @@ impl DownloadInfo {
             .into_iter()
             .map(|it| {
                 let title = it.to_string();
-                let path = path.join(&option.sanitize_filename(&title));
+                let path = path.join(option.chapter_path(&request_module, &manga.title, &it));
                 DownloadChapterInfo::new(
-                    LateBindingModule::Module(module.clone()),
+                    module.clone(),
                     it.id.clone(),
                     title,
                     path,
@@ -95,19 +199,39 @@ impl DownloadInfo {
 
         Self::new(
             order,
-            LateBindingModule::Module(module),
+            module,
             manga.title.clone(),
             chapters,
             path,
             url,
             DownloadStatus::InProgress(status.into()),
         )
+        .with_cover_link(manga.cover_link.clone())
+    }
+
+    /// Set link of the manga cover saved as [`crate::COVER_FILENAME`].
+    pub fn with_cover_link(mut self, cover_link: Option<String>) -> Self {
+        self.cover_link = cover_link;
+        self
+    }
+
+    pub fn id(&self) -> DownloadId {
+        self.id
     }
 
     pub fn order(&self) -> usize {
         self.order.load(atomic::Ordering::Relaxed)
     }
 
+    pub fn priority(&self) -> DownloadPriority {
+        *self.priority.lock()
+    }
+
+    /// Time before which the scheduler won't start the download.
+    pub fn start_at(&self) -> Option<SystemTime> {
+        *self.start_at.lock()
+    }
+
     /// Get download info's status.
     pub fn status(&self) -> impl std::ops::Deref<Target = DownloadStatus> + '_ {
         self.status.lock()
@@ -152,6 +276,29 @@ impl DownloadInfo {
             .emit(|it| it(DownloadInfoMsg::OrderChanged(order)));
     }
 
+    pub fn set_priority(&self, priority: DownloadPriority) {
+        *self.priority.lock() = priority;
+        self.observers
+            .emit(|it| it(DownloadInfoMsg::PriorityChanged(priority)));
+    }
+
+    pub fn set_start_at(&self, start_at: Option<SystemTime>) {
+        *self.start_at.lock() = start_at;
+        self.observers
+            .emit(|it| it(DownloadInfoMsg::StartAtChanged(start_at)));
+    }
+
+    /// Resume the download so it's queued, but only started at `time`.
+    pub fn schedule_at(&self, time: SystemTime) {
+        self.set_start_at(Some(time));
+        self.resume(true);
+    }
+
+    /// Whether the download waits for [`Self::start_at`] at `now`.
+    pub fn is_scheduled(&self, now: SystemTime) -> bool {
+        self.start_at().map_or(false, |it| it > now)
+    }
+
     /// Change download's status, then emit [`DownloadInfoObserver::on_status_changed`]
     #[tracing::instrument]
     pub fn set_status(&self, status: DownloadStatus) {
@@ -177,11 +324,97 @@ impl DownloadInfo {
         self.set_status(DownloadStatus::InProgress(status));
     }
 
+    pub fn is_removed(&self) -> bool {
+        self.removed.load(atomic::Ordering::SeqCst)
+    }
+
+    /// Stop the download and tell observers to forget it.
+    ///
+    /// Work in progress stops at its next await point, so a file being
+    /// written may still be finished.
+    pub fn mark_removed(&self) {
+        if self.removed.swap(true, atomic::Ordering::SeqCst) {
+            return;
+        }
+
+        self.observers.emit(|it| it(DownloadInfoMsg::Removed));
+    }
+
+    /// Mark a downloader as writing files of this download until the guard
+    /// is dropped. Check [`Self::is_removed`] after this, files of removed
+    /// downloads may be deleted as soon as nothing runs.
+    pub fn start_running(self: &Arc<Self>) -> RunningGuard {
+        self.running.fetch_add(1, atomic::Ordering::SeqCst);
+        RunningGuard(self.clone())
+    }
+
+    /// Wait until no downloader is writing files of this download.
+    pub async fn wait_stopped(&self) {
+        loop {
+            let listener = self.stopped.listen();
+            if self.running.load(atomic::Ordering::SeqCst) == 0 {
+                return;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// Record a failure, see [`Self::failures`].
+    pub fn log_failure(&self, failure: DownloadFailure) {
+        tracing::debug!("logging failure {:?}", failure);
+        self.failures.push(failure);
+    }
+
+    /// Recent failures of this download, oldest first.
+    pub fn failures(&self) -> Vec<DownloadFailure> {
+        self.failures.entries()
+    }
+
+    /// Chapters that failed in the last run.
+    pub fn failed_chapters(&self) -> Vec<Arc<DownloadChapterInfo>> {
+        self.chapters
+            .iter()
+            .filter(|it| it.status().is_error())
+            .cloned()
+            .collect()
+    }
+
+    /// Queue failed chapters again, returning how many were retried.
+    ///
+    /// Finished chapters are kept, so only the failed ones are downloaded.
+    pub fn retry_failed(&self) -> usize {
+        let failed = self.failed_chapters();
+
+        for it in failed.iter() {
+            it.set_status(DownloadStatus::waiting());
+        }
+
+        let status = self.status().clone();
+        if !failed.is_empty() || status.is_error() || status.is_finished_with_errors() {
+            self.set_status(DownloadStatus::waiting());
+        }
+
+        failed.len()
+    }
+
+    /// Finished chapters that haven't been read.
+    pub fn unread_count(&self) -> usize {
+        self.chapters
+            .iter()
+            .filter(|it| it.status().is_finished() && !it.is_read())
+            .count()
+    }
+
     /// Get a reference to the download info's manga's title.
     pub fn manga(&self) -> &str {
         &self.manga_title
     }
 
+    pub fn cover_link(&self) -> Option<&str> {
+        self.cover_link.as_deref()
+    }
+
     /// Connect and send current state.
     pub fn connect(&self, mut observer: ImplObserver!()) -> ObserverHandle<BoxObserver> {
         observer(DownloadInfoMsg::StatusChanged(&self.status()));
@@ -267,12 +500,16 @@ mod tests {
     use mockall::predicate;
 
     use super::*;
+    use crate::{DownloadChapterInfoMsg, ReadProgress};
 
     mockall::mock! {
         pub Thing {
             fn on_status_changed(&self, status: &DownloadStatus);
             fn on_download(&self, info: &DownloadStatus);
             fn on_order_changed(&self, index: usize);
+            fn on_priority_changed(&self, priority: DownloadPriority);
+            fn on_start_at_changed(&self, start_at: Option<SystemTime>);
+            fn on_removed(&self);
         }
     }
 
@@ -281,6 +518,9 @@ mod tests {
             match msg {
                 DownloadInfoMsg::StatusChanged(status) => self.on_status_changed(status),
                 DownloadInfoMsg::OrderChanged(index) => self.on_order_changed(index),
+                DownloadInfoMsg::PriorityChanged(priority) => self.on_priority_changed(priority),
+                DownloadInfoMsg::StartAtChanged(start_at) => self.on_start_at_changed(start_at),
+                DownloadInfoMsg::Removed => self.on_removed(),
             }
         }
 
@@ -335,13 +575,20 @@ mod tests {
                 .with(predicate::eq(1))
                 .returning(|_| ());
 
+            mock.expect_on_priority_changed()
+                .once()
+                .with(predicate::eq(DownloadPriority::High))
+                .returning(|_| ());
+
             let handle = info.connect(mock.handler());
 
             info.set_status(DownloadStatus::waiting());
             info.set_order(1);
+            info.set_priority(DownloadPriority::High);
             let _ = handle.disconnect().unwrap();
             info.set_status(DownloadStatus::finished());
             info.set_order(2);
+            info.set_priority(DownloadPriority::Low);
         }
 
         {
@@ -352,6 +599,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn priority_test() {
+        for it in DownloadPriority::ALL {
+            assert_eq!(it.as_str().parse::<DownloadPriority>(), Ok(it));
+        }
+        "Urgent".parse::<DownloadPriority>().unwrap_err();
+
+        assert!(DownloadPriority::High < DownloadPriority::Normal);
+        assert!(DownloadPriority::Normal < DownloadPriority::Low);
+    }
+
     #[test]
     fn test_request() {
         let mut module = MockMadoModule::new();
@@ -402,4 +660,152 @@ mod tests {
         info.resume(false);
         assert!(info.status().is_finished());
     }
+
+    #[test]
+    fn test_schedule_at() {
+        let info = DownloadInfo::builder()
+            .order(0)
+            .module(LateBindingModule::WaitModule(
+                Arc::new(DefaultMadoModuleMap::new()),
+                Default::default(),
+            ))
+            .status(DownloadStatus::paused())
+            .build();
+
+        let now = SystemTime::now();
+        let later = now + std::time::Duration::from_secs(60);
+
+        let mut mock = MockThing::new();
+        mock.expect_on_start_at_changed()
+            .once()
+            .with(predicate::eq(Some(later)))
+            .returning(|_| ());
+        mock.expect_on_status_changed()
+            .once()
+            .with(predicate::eq(DownloadStatus::waiting()))
+            .returning(|_| ());
+        let _handle = info.connect_only(mock.handler());
+
+        assert!(!info.is_scheduled(now));
+        info.schedule_at(later);
+
+        assert!(info.status().is_resumed());
+        assert!(info.is_scheduled(now));
+        assert!(!info.is_scheduled(later));
+    }
+
+    #[test]
+    fn test_removed() {
+        let info = DownloadInfo::builder()
+            .order(0)
+            .module(LateBindingModule::WaitModule(
+                Arc::new(DefaultMadoModuleMap::new()),
+                Default::default(),
+            ))
+            .status(DownloadStatus::paused())
+            .build();
+
+        let mut mock = MockThing::new();
+        mock.expect_on_removed().once().returning(|| ());
+        let _handle = info.connect_only(mock.handler());
+
+        assert!(!info.is_removed());
+        info.mark_removed();
+        info.mark_removed();
+        assert!(info.is_removed());
+
+        let other = DownloadInfo::builder()
+            .order(0)
+            .module(LateBindingModule::WaitModule(
+                Arc::new(DefaultMadoModuleMap::new()),
+                Default::default(),
+            ))
+            .status(DownloadStatus::paused())
+            .build();
+        assert_ne!(info.id(), other.id());
+    }
+
+    #[test]
+    fn test_retry_failed() {
+        let map = Arc::new(DefaultMadoModuleMap::new());
+        let chapter = |status| {
+            Arc::new(DownloadChapterInfo::new(
+                LateBindingModule::WaitModule(map.clone(), Default::default()),
+                "id".to_string(),
+                "title".to_string(),
+                Default::default(),
+                status,
+            ))
+        };
+
+        let failed = chapter(DownloadStatus::error("failed"));
+        let finished = chapter(DownloadStatus::finished());
+
+        let info = DownloadInfo::builder()
+            .order(0)
+            .module(LateBindingModule::WaitModule(
+                map.clone(),
+                Default::default(),
+            ))
+            .chapters(vec![failed.clone(), finished.clone()])
+            .status(DownloadStatus::finished_with_errors(1))
+            .build();
+
+        assert_eq!(info.failed_chapters().len(), 1);
+        assert_eq!(info.retry_failed(), 1);
+
+        assert!(info.status().is_resumed());
+        assert!(failed.status().is_resumed());
+        assert!(finished.status().is_finished());
+        assert_eq!(info.retry_failed(), 0);
+    }
+
+    #[test]
+    fn unread_test() {
+        let map = Arc::new(DefaultMadoModuleMap::new());
+        let module = LateBindingModule::WaitModule(map, Default::default());
+        let chapters: Vec<_> = [
+            DownloadStatus::finished(),
+            DownloadStatus::finished(),
+            DownloadStatus::paused(),
+        ]
+        .into_iter()
+        .map(|status| {
+            Arc::new(DownloadChapterInfo::new(
+                module.clone(),
+                "id".to_string(),
+                "title".to_string(),
+                Default::default(),
+                status,
+            ))
+        })
+        .collect();
+
+        let info = DownloadInfo::builder()
+            .order(0)
+            .module(module)
+            .chapters(chapters.clone())
+            .status(DownloadStatus::paused())
+            .build();
+        assert_eq!(info.unread_count(), 2);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        chapters[0].connect_only(move |msg| {
+            if let DownloadChapterInfoMsg::ProgressChanged(progress) = msg {
+                tx.send(*progress).unwrap();
+            }
+        });
+
+        chapters[0].set_last_page(3);
+        chapters[0].set_read(true);
+        // unchanged progress isn't sent again.
+        chapters[0].set_read(true);
+
+        let sent: Vec<_> = rx
+            .try_iter()
+            .map(|it: ReadProgress| (it.read, it.last_page, it.updated_at.is_some()))
+            .collect();
+        assert_eq!(sent, [(false, 3, true), (true, 3, true)]);
+        assert_eq!(info.unread_count(), 1);
+    }
 }
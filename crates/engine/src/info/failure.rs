@@ -0,0 +1,87 @@
+use std::{collections::VecDeque, time::SystemTime};
+
+use parking_lot::Mutex;
+
+use crate::core::ClassifiedError;
+
+/// Maximum failures kept per download, older ones are dropped first.
+pub const FAILURE_LOG_LIMIT: usize = 100;
+
+/// A failure of a download, one of its chapters or one of its images.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadFailure {
+    pub time: SystemTime,
+    /// `None` if the failure isn't specific to a chapter.
+    pub chapter_id: Option<String>,
+    pub image_id: Option<String>,
+    pub error: ClassifiedError,
+}
+
+impl DownloadFailure {
+    pub fn new(error: impl Into<ClassifiedError>) -> Self {
+        Self {
+            time: SystemTime::now(),
+            chapter_id: None,
+            image_id: None,
+            error: error.into(),
+        }
+    }
+
+    pub fn chapter(mut self, chapter_id: impl Into<String>) -> Self {
+        self.chapter_id = Some(chapter_id.into());
+        self
+    }
+
+    pub fn image(mut self, image_id: impl Into<String>) -> Self {
+        self.image_id = Some(image_id.into());
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FailureLog {
+    entries: Mutex<VecDeque<DownloadFailure>>,
+}
+
+impl FailureLog {
+    pub fn push(&self, failure: DownloadFailure) {
+        let mut entries = self.entries.lock();
+
+        if entries.len() >= FAILURE_LOG_LIMIT {
+            entries.pop_front();
+        }
+        entries.push_back(failure);
+    }
+
+    /// Failures from oldest to newest.
+    pub fn entries(&self) -> Vec<DownloadFailure> {
+        self.entries.lock().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit() {
+        let log = FailureLog::default();
+
+        for i in 0..FAILURE_LOG_LIMIT + 1 {
+            log.push(DownloadFailure::new(i.to_string()).chapter("1"));
+        }
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), FAILURE_LOG_LIMIT);
+        assert_eq!(entries[0].error.message, "1");
+        assert_eq!(entries[0].chapter_id.as_deref(), Some("1"));
+        assert_eq!(entries[0].image_id, None);
+
+        log.clear();
+        assert!(log.entries().is_empty());
+    }
+}
@@ -1,13 +1,22 @@
 mod chapter;
 mod download;
+mod failure;
 mod image;
 mod module;
 mod option;
 mod status;
 
-pub use chapter::{DownloadChapterInfo, DownloadChapterInfoMsg};
-pub use download::{DownloadInfo, DownloadInfoMsg, DownloadRequest, DownloadRequestStatus};
+pub use chapter::{DownloadChapterInfo, DownloadChapterInfoMsg, ReadProgress};
+pub use download::{
+    DownloadId, DownloadInfo, DownloadInfoMsg, DownloadPriority, DownloadRequest,
+    DownloadRequestStatus, RunningGuard,
+};
+pub use failure::{DownloadFailure, FailureLog, FAILURE_LOG_LIMIT};
 pub use image::{DownloadChapterImageInfo, DownloadChapterImageInfoMsg};
-pub use module::{LateBindingModule, ModuleInfo, LATE_BINDING_MODULE_SLEEP_TIME};
-pub use option::DownloadOption;
+pub use module::{
+    LateBindingModule, ModuleFile, ModuleFileStatus, ModuleInfo, LATE_BINDING_MODULE_SLEEP_TIME,
+};
+pub use option::{
+    DownloadOption, DEFAULT_CHAPTER_TEMPLATE, DEFAULT_IMAGE_TEMPLATE, DEFAULT_MANGA_TEMPLATE,
+};
 pub use status::{DownloadProgressStatus, DownloadResumedStatus, DownloadStatus};
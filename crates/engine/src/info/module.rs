@@ -4,6 +4,8 @@ use futures::lock::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard};
 
 use mado_core::{ArcMadoModule, ArcMadoModuleMap, MadoModule, Uuid};
 
+use crate::path::{Utf8Path, Utf8PathBuf};
+
 #[derive(Clone)]
 pub enum LateBindingModule {
     Module(ArcMadoModule),
@@ -46,19 +48,15 @@ impl LateBindingModule {
     pub async fn wait(&mut self) -> ArcMadoModule {
         match self {
             LateBindingModule::Module(module) => module.clone(),
-            LateBindingModule::WaitModule(map, uuid) => {
-                let module = loop {
-                    let module = map.get_by_uuid(*uuid);
-                    if let Some(module) = module {
-                        break module;
-                    }
-
-                    crate::timer::sleep(LATE_BINDING_MODULE_SLEEP_TIME).await;
-                };
-
-                *self = Self::Module(module.clone());
-                module
-            }
+            // looked up on every call instead of cached, a module that is
+            // removed and pushed again is picked up by the next call.
+            LateBindingModule::WaitModule(map, uuid) => loop {
+                if let Some(module) = map.get_by_uuid(*uuid) {
+                    break module;
+                }
+
+                crate::timer::sleep(LATE_BINDING_MODULE_SLEEP_TIME).await;
+            },
         }
     }
 
@@ -110,6 +108,57 @@ impl<T: MadoModule> From<Arc<T>> for ModuleInfo {
     }
 }
 
+/// Load status of a [`ModuleFile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleFileStatus {
+    Loading,
+    Loaded,
+    /// The file couldn't be loaded, or some of its modules couldn't be
+    /// pushed.
+    Failed(String),
+}
+
+/// Module file loaded by [`crate::MadoEngine::module_loader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleFile {
+    path: Utf8PathBuf,
+    status: ModuleFileStatus,
+    modules: Vec<Uuid>,
+}
+
+impl ModuleFile {
+    pub fn new(path: Utf8PathBuf) -> Self {
+        Self {
+            path,
+            status: ModuleFileStatus::Loading,
+            modules: Vec::new(),
+        }
+    }
+
+    pub fn with_status(mut self, status: ModuleFileStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_modules(mut self, modules: Vec<Uuid>) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    pub fn status(&self) -> &ModuleFileStatus {
+        &self.status
+    }
+
+    /// Uuid of modules the file provides, including disabled ones.
+    pub fn modules(&self) -> &[Uuid] {
+        &self.modules
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
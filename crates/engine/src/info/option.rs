@@ -1,20 +1,60 @@
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize},
+        Arc,
+    },
+    time::Duration,
+};
 
 use parking_lot::Mutex;
 
-use crate::TaskSchedulerOption;
+use crate::{
+    core::{ArcMadoModule, ChapterInfo},
+    path::Utf8PathBuf,
+    PathTemplate, TaskSchedulerOption, TemplateError, TemplateKey, TemplateKind, TemplateValues,
+};
 
 #[derive(Debug)]
 struct Inner {
     sanitize_option: Mutex<SanitizeOptions>,
     scheduler: Arc<TaskSchedulerOption>,
+    // shared with running image downloads so changes apply immediately.
+    retry_limit: Arc<AtomicUsize>,
+    timeout: Arc<AtomicU64>,
+    templates: Mutex<Templates>,
 }
 
+#[derive(Debug)]
+struct Templates {
+    manga: PathTemplate,
+    chapter: PathTemplate,
+    image: PathTemplate,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        let parse = |kind, source| PathTemplate::parse(kind, source).unwrap();
+
+        Self {
+            manga: parse(TemplateKind::Manga, DEFAULT_MANGA_TEMPLATE),
+            chapter: parse(TemplateKind::Chapter, DEFAULT_CHAPTER_TEMPLATE),
+            image: parse(TemplateKind::Image, DEFAULT_IMAGE_TEMPLATE),
+        }
+    }
+}
+
+pub const DEFAULT_MANGA_TEMPLATE: &str = "{manga}";
+pub const DEFAULT_CHAPTER_TEMPLATE: &str = "{name}";
+pub const DEFAULT_IMAGE_TEMPLATE: &str = "{page:04}";
+
 impl Default for Inner {
     fn default() -> Self {
         Self {
             sanitize_option: Default::default(),
             scheduler: Default::default(),
+            retry_limit: Arc::new(AtomicUsize::new(10)),
+            timeout: Arc::new(AtomicU64::new(10)),
+            templates: Default::default(),
         }
     }
 }
@@ -94,6 +134,128 @@ impl DownloadOption {
         self.0.sanitize_option.lock().replacement = replacement;
     }
 
+    pub fn set_sanitize_windows(&self, windows: bool) {
+        self.0.sanitize_option.lock().windows = windows;
+    }
+
+    pub fn set_sanitize_truncate(&self, truncate: bool) {
+        self.0.sanitize_option.lock().truncate = truncate;
+    }
+
+    /// Replace reserved characters with similar looking unicode ones instead
+    /// of the replacement.
+    pub fn set_sanitize_convert_to_unicode(&self, convert_to_unicode: bool) {
+        self.0.sanitize_option.lock().convert_to_unicode = convert_to_unicode;
+    }
+
+    /// Times an image is retried before its chapter fails.
+    pub fn retry_limit(&self) -> usize {
+        self.0.retry_limit.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_retry_limit(&self, retry_limit: usize) {
+        self.0
+            .retry_limit
+            .store(retry_limit, atomic::Ordering::Relaxed);
+    }
+
+    /// Timeout of a single image request.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.0.timeout.load(atomic::Ordering::Relaxed))
+    }
+
+    pub fn set_timeout(&self, timeout: Duration) {
+        self.0
+            .timeout
+            .store(timeout.as_secs(), atomic::Ordering::Relaxed);
+    }
+
+    pub(crate) fn shared_retry_limit(&self) -> Arc<AtomicUsize> {
+        self.0.retry_limit.clone()
+    }
+
+    pub(crate) fn shared_timeout(&self) -> Arc<AtomicU64> {
+        self.0.timeout.clone()
+    }
+
+    pub fn template(&self, kind: TemplateKind) -> PathTemplate {
+        let templates = self.0.templates.lock();
+
+        match kind {
+            TemplateKind::Manga => templates.manga.clone(),
+            TemplateKind::Chapter => templates.chapter.clone(),
+            TemplateKind::Image => templates.image.clone(),
+        }
+    }
+
+    /// Parse `source` as the template of `kind`, keeping the current one if
+    /// it's invalid.
+    pub fn set_template(&self, kind: TemplateKind, source: &str) -> Result<(), TemplateError> {
+        let template = PathTemplate::parse(kind, source)?;
+        let mut templates = self.0.templates.lock();
+
+        match kind {
+            TemplateKind::Manga => templates.manga = template,
+            TemplateKind::Chapter => templates.chapter = template,
+            TemplateKind::Image => templates.image = template,
+        }
+
+        Ok(())
+    }
+
+    fn render(
+        &self,
+        kind: TemplateKind,
+        module: Option<&ArcMadoModule>,
+        values: TemplateValues<'_>,
+    ) -> Utf8PathBuf {
+        let template = self.template(kind);
+        // only asked for when used, not every module has a name to give.
+        let module = module
+            .filter(|_| template.uses(TemplateKey::Module))
+            .map(|it| it.name());
+        let values = TemplateValues { module, ..values };
+
+        template.render(&values, |it| self.sanitize_filename(it))
+    }
+
+    /// Directory of a manga, relative to the download directory.
+    pub fn manga_path(&self, module: &ArcMadoModule, manga: &str) -> Utf8PathBuf {
+        let values = TemplateValues {
+            manga: Some(manga),
+            ..Default::default()
+        };
+
+        self.render(TemplateKind::Manga, Some(module), values)
+    }
+
+    /// Directory of a chapter, relative to its manga directory.
+    pub fn chapter_path(
+        &self,
+        module: &ArcMadoModule,
+        manga: &str,
+        chapter: &ChapterInfo,
+    ) -> Utf8PathBuf {
+        let values = TemplateValues {
+            manga: Some(manga),
+            chapter: Some(chapter),
+            ..Default::default()
+        };
+
+        self.render(TemplateKind::Chapter, Some(module), values)
+    }
+
+    /// Filename of the `page`th image, starting from 1.
+    pub fn image_filename(&self, page: usize, extension: &str) -> String {
+        let values = TemplateValues {
+            page: Some(page),
+            ..Default::default()
+        };
+        let name = self.render(TemplateKind::Image, None, values);
+
+        self.sanitize_filename(&format!("{}.{}", name, extension))
+    }
+
     pub fn scheduler(&self) -> Arc<TaskSchedulerOption> {
         self.0.scheduler.clone()
     }
@@ -102,3 +264,54 @@ impl DownloadOption {
         self.0.scheduler.set_download_limit(value);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_test() {
+        let option = DownloadOption::default();
+        let mut module = mado_core::MockMadoModule::new();
+        module.expect_name().return_const("module".to_string());
+        let module: ArcMadoModule = Arc::new(module);
+        let chapter = ChapterInfo {
+            index: Some(1),
+            chapter: Some("2".to_string()),
+            volume: Some("1".to_string()),
+            language: "en".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(option.manga_path(&module, "a/b"), "a∕b");
+        assert_eq!(
+            option.chapter_path(&module, "manga", &chapter),
+            option.sanitize_filename(&chapter.to_string())
+        );
+        assert_eq!(option.image_filename(1, "png"), "0001.png");
+
+        assert!(option.set_template(TemplateKind::Image, "{manga}").is_err());
+        assert_eq!(option.image_filename(1, "png"), "0001.png");
+
+        option
+            .set_template(TemplateKind::Manga, "{module}/{manga}")
+            .unwrap();
+        option
+            .set_template(TemplateKind::Chapter, "Vol. {volume}/{chapter:03}")
+            .unwrap();
+        option
+            .set_template(TemplateKind::Image, "page {page}")
+            .unwrap();
+
+        assert_eq!(option.manga_path(&module, "manga"), "module/manga");
+        assert_eq!(
+            option.chapter_path(&module, "manga", &chapter),
+            "Vol. 1/002"
+        );
+        assert_eq!(option.image_filename(12, "jpg"), "page 12.jpg");
+        assert_eq!(
+            option.template(TemplateKind::Chapter).as_str(),
+            "Vol. {volume}/{chapter:03}"
+        );
+    }
+}
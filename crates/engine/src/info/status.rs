@@ -1,3 +1,5 @@
+use mado_core::{ClassifiedError, ErrorKind};
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
 pub enum DownloadResumedStatus {
     #[default]
@@ -32,12 +34,12 @@ impl DownloadResumedStatus {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DownloadProgressStatus {
     Resumed(DownloadResumedStatus),
     Paused,
-    // we don't need StdError here because this is only used to shows to user
-    Error(String),
+    // classified so it can be persisted and used to decide whether to retry.
+    Error(ClassifiedError),
 }
 
 impl DownloadProgressStatus {
@@ -50,10 +52,12 @@ impl DownloadProgressStatus {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DownloadStatus {
     InProgress(DownloadProgressStatus),
     Finished,
+    /// Finished, but this many chapters failed and can be retried.
+    FinishedWithErrors(usize),
 }
 
 impl DownloadStatus {
@@ -79,6 +83,10 @@ impl DownloadStatus {
         matches!(self, Self::Finished)
     }
 
+    pub fn is_finished_with_errors(&self) -> bool {
+        matches!(self, Self::FinishedWithErrors(..))
+    }
+
     pub fn is_error(&self) -> bool {
         matches!(self, Self::InProgress(DownloadProgressStatus::Error(..)))
     }
@@ -103,14 +111,18 @@ impl DownloadStatus {
         Self::InProgress(DownloadProgressStatus::Paused)
     }
 
-    pub fn error<S: std::fmt::Display>(error: S) -> Self {
-        Self::InProgress(DownloadProgressStatus::Error(error.to_string()))
+    pub fn error<E: Into<ClassifiedError>>(error: E) -> Self {
+        Self::InProgress(DownloadProgressStatus::Error(error.into()))
     }
 
     pub fn finished() -> Self {
         Self::Finished
     }
 
+    pub fn finished_with_errors(failed: usize) -> Self {
+        Self::FinishedWithErrors(failed)
+    }
+
     pub fn to_human_variant(&self) -> &'static str {
         match self {
             DownloadStatus::InProgress(status) => match status {
@@ -123,20 +135,32 @@ impl DownloadStatus {
                 DownloadProgressStatus::Error(_) => "Error",
             },
             DownloadStatus::Finished => "Finished",
+            DownloadStatus::FinishedWithErrors(_) => "Finished with errors",
         }
     }
 
     pub fn message(&self) -> Option<&str> {
+        self.as_error().map(|it| it.message.as_str())
+    }
+
+    pub fn as_error(&self) -> Option<&ClassifiedError> {
         match self {
-            DownloadStatus::InProgress(DownloadProgressStatus::Error(err)) => Some(err.as_str()),
+            DownloadStatus::InProgress(DownloadProgressStatus::Error(err)) => Some(err),
             _ => None,
         }
     }
 
+    pub fn error_kind(&self) -> Option<&ErrorKind> {
+        self.as_error().map(|it| &it.kind)
+    }
+
     pub fn to_human_string(&self) -> String {
-        match self.message() {
-            Some(str) => format!("{}: {}", self.to_human_variant(), str),
-            None => self.to_human_variant().to_string(),
+        match (self, self.message()) {
+            (_, Some(str)) => format!("{}: {}", self.to_human_variant(), str),
+            (DownloadStatus::FinishedWithErrors(failed), None) => {
+                format!("{}: {} failed", self.to_human_variant(), failed)
+            }
+            (_, None) => self.to_human_variant().to_string(),
         }
     }
 
@@ -173,6 +197,22 @@ mod tests {
         assert!(error("Err"), Some("Err"));
     }
 
+    #[test]
+    pub fn error_kind() {
+        let status = DownloadStatus::error(mado_core::Error::NotFound {
+            url: "https://localhost".to_string(),
+            message: "404".to_string(),
+        });
+
+        assert_eq!(status.error_kind(), Some(&ErrorKind::NotFound));
+        assert_eq!(status.message(), Some("https://localhost not found: 404"));
+        assert_eq!(
+            DownloadStatus::error("Err").error_kind(),
+            Some(&ErrorKind::Other)
+        );
+        assert_eq!(DownloadStatus::paused().error_kind(), None);
+    }
+
     #[test]
     pub fn to_human_variant() {
         macro_rules! assert {
@@ -187,6 +227,7 @@ mod tests {
         assert!(downloading, "Downloading");
         assert!(paused, "Paused");
         assert!(error("Err"), "Error");
+        assert!(finished_with_errors(2), "Finished with errors");
     }
 
     #[test]
@@ -203,5 +244,6 @@ mod tests {
         assert!(downloading, "Downloading");
         assert!(paused, "Paused");
         assert!(error("Err"), "Error: Err");
+        assert!(finished_with_errors(2), "Finished with errors: 2 failed");
     }
 }
@@ -1,7 +1,12 @@
+mod cancel;
+mod cover;
 mod image_downloader;
 mod info;
 mod observer;
+pub mod repository;
 mod scheduler;
+mod selector;
+mod template;
 mod watcher;
 pub use info::*;
 pub use observer::*;
@@ -10,12 +15,18 @@ mod task_downloader;
 pub mod timer;
 pub use engine::*;
 
-pub use image_downloader::{ImageDownloader, ImageDownloaderConfig};
+pub use cancel::CancellationToken;
+pub use cover::{fetch_cover, CoverCache, COVER_FILENAME};
+pub use image_downloader::{ImageBuffer, ImageDownloader, ImageDownloaderConfig};
 pub use task_downloader::TaskDownloader;
 
 mod state;
-pub use scheduler::{TaskRunner, TaskScheduler, TaskSchedulerOption};
+pub use scheduler::{
+    ActiveHours, LocalOffset, SourceKey, TaskRunner, TaskScheduler, TaskSchedulerOption,
+};
+pub use selector::{chapter_number, volume_number, ChapterRange, ChapterSelector};
 pub use state::{DownloadTaskList, MadoEngineState, MadoEngineStateMsg};
+pub use template::{PathTemplate, TemplateError, TemplateKey, TemplateKind, TemplateValues};
 
 /// Error happen when Loading Module.
 #[derive(Debug, thiserror::Error)]
@@ -0,0 +1,763 @@
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    core::{http::Client, Url},
+    path::{Utf8Path, Utf8PathBuf},
+};
+
+/// Index file name used when a repository source is a directory.
+pub const INDEX_FILE: &str = "index.json";
+
+/// File in the module directory recording modules installed from a
+/// repository.
+pub const INSTALLED_FILE: &str = "installed.json";
+
+/// Latest index format understood by [`Repository::index`].
+pub const INDEX_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RepositoryError {
+    #[error("{0}")]
+    IOError(#[from] std::io::Error),
+    #[error("{0}")]
+    RequestError(#[from] crate::core::Error),
+    #[error("invalid json: {0}")]
+    ParseError(#[from] serde_json::Error),
+    #[error("unsupported index version {0}")]
+    UnsupportedVersion(u32),
+    #[error("invalid url {0}")]
+    InvalidUrl(String),
+    #[error("invalid module file name {0}")]
+    InvalidFileName(String),
+    #[error("invalid version {0}")]
+    InvalidVersion(String),
+    #[error("checksum of {name} is {actual}, expected {expected}")]
+    ChecksumMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("{0} is not signed")]
+    MissingSignature(String),
+    #[error("invalid signature for {0}")]
+    InvalidSignature(String),
+    #[error("invalid public key")]
+    InvalidPublicKey,
+}
+
+/// Dotted version like `1.2.10`, compared number by number.
+///
+/// Missing numbers count as zero, so `1.2` equals `1.2.0`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ModuleVersion(Vec<u64>);
+
+impl ModuleVersion {
+    fn get(&self, index: usize) -> u64 {
+        self.0.get(index).copied().unwrap_or(0)
+    }
+}
+
+impl FromStr for ModuleVersion {
+    type Err = RepositoryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim()
+            .split('.')
+            .map(|it| it.parse())
+            .collect::<Result<_, _>>()
+            .map(Self)
+            .map_err(|_| RepositoryError::InvalidVersion(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for ModuleVersion {
+    type Error = RepositoryError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ModuleVersion> for String {
+    fn from(v: ModuleVersion) -> Self {
+        v.to_string()
+    }
+}
+
+impl Display for ModuleVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, it) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", it)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Ord for ModuleVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.0.len().max(other.0.len());
+
+        (0..len)
+            .map(|it| self.get(it).cmp(&other.get(it)))
+            .find(|it| it.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for ModuleVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ModuleVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for ModuleVersion {}
+
+/// Module listed in a [`RepositoryIndex`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepositoryModule {
+    /// Identifies the module across versions.
+    pub name: String,
+    pub version: ModuleVersion,
+    /// File name in the module directory, e.g. `mangadex.js`.
+    pub file: String,
+    /// Where to download the file, relative to the index unless absolute.
+    pub url: String,
+    /// Hex encoded SHA-256 of the file.
+    pub sha256: String,
+    /// Hex encoded ed25519 signature of [`Self::signed_message`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Permission manifest installed next to the file, a module without one
+    /// isn't allowed anything.
+    pub permissions: RepositoryFile,
+}
+
+impl RepositoryModule {
+    /// Message signed by the repository, binding the file and its manifest
+    /// to the name and version they're listed under.
+    ///
+    /// Each line is one of `mado-module-v1`, name, version, file, SHA-256
+    /// of the file and SHA-256 of the manifest.
+    pub fn signed_message(&self) -> Vec<u8> {
+        let lines = [
+            "mado-module-v1".to_string(),
+            self.name.clone(),
+            self.version.to_string(),
+            self.file.clone(),
+            self.sha256.trim().to_lowercase(),
+            self.permissions.sha256.trim().to_lowercase(),
+        ];
+
+        lines.join("\n").into_bytes()
+    }
+}
+
+/// File listed in a [`RepositoryIndex`] besides a module file.
+///
+/// It isn't signed by itself, its checksum is part of the module's
+/// [`RepositoryModule::signed_message`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepositoryFile {
+    /// Where to download the file, relative to the index unless absolute.
+    pub url: String,
+    /// Hex encoded SHA-256 of the file.
+    pub sha256: String,
+}
+
+/// Content of a repository's `index.json`, e.g.
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "modules": [{
+///     "name": "mangadex",
+///     "version": "1.2.0",
+///     "file": "mangadex.js",
+///     "url": "module/mangadex.js",
+///     "sha256": "9f86d0...",
+///     "signature": "1a2b3c...",
+///     "permissions": {
+///       "url": "module/mangadex.permissions.json",
+///       "sha256": "60303a..."
+///     }
+///   }]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepositoryIndex {
+    pub version: u32,
+    pub modules: Vec<RepositoryModule>,
+}
+
+impl RepositoryIndex {
+    pub fn get(&self, name: &str) -> Option<&RepositoryModule> {
+        self.modules.iter().find(|it| it.name == name)
+    }
+}
+
+/// Where a repository index is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepositorySource {
+    /// Index file, or a directory containing [`INDEX_FILE`].
+    Path(Utf8PathBuf),
+    Url(Url),
+}
+
+impl RepositorySource {
+    /// `http` and `https` urls are fetched, anything else is a local path.
+    pub fn parse(source: &str) -> Self {
+        match Url::parse(source) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Self::Url(url),
+            _ => Self::Path(source.into()),
+        }
+    }
+
+    fn index_path(path: &Utf8Path) -> Utf8PathBuf {
+        match path.is_dir() {
+            true => path.join(INDEX_FILE),
+            false => path.to_owned(),
+        }
+    }
+}
+
+impl Display for RepositorySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path(path) => path.fmt(f),
+            Self::Url(url) => url.fmt(f),
+        }
+    }
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|it| format!("{:02x}", it)).collect()
+}
+
+/// Hex encoded SHA-256 of `data`.
+pub fn sha256(data: &[u8]) -> String {
+    encode_hex(&Sha256::digest(data))
+}
+
+/// Name of the permission manifest of module `file`, e.g.
+/// `mangadex.permissions.json` for `mangadex.js`, as looked up by the
+/// module loader.
+pub fn manifest_name(file: &str) -> String {
+    let stem = Utf8Path::new(file).file_stem().unwrap_or(file);
+
+    format!("{}.permissions.json", stem)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim();
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|it| u8::from_str_radix(hex.get(it..it + 2)?, 16).ok())
+        .collect()
+}
+
+/// Module index with downloadable module files.
+///
+/// Files are checked against the checksum in the index, and each module entry
+/// must be signed by the repository's public key, see
+/// [`RepositoryModule::signed_message`].
+#[derive(Debug, Clone)]
+pub struct Repository {
+    source: RepositorySource,
+    client: Client,
+    public_key: PublicKey,
+}
+
+impl Repository {
+    /// Repository whose files are signed by the hex encoded ed25519
+    /// `public_key`.
+    pub fn new(
+        source: RepositorySource,
+        client: Client,
+        public_key: &str,
+    ) -> Result<Self, RepositoryError> {
+        let key = decode_hex(public_key).ok_or(RepositoryError::InvalidPublicKey)?;
+        let public_key =
+            PublicKey::from_bytes(&key).map_err(|_| RepositoryError::InvalidPublicKey)?;
+
+        Ok(Self {
+            source,
+            client,
+            public_key,
+        })
+    }
+
+    pub fn source(&self) -> &RepositorySource {
+        &self.source
+    }
+
+    pub async fn index(&self) -> Result<RepositoryIndex, RepositoryError> {
+        let data = match &self.source {
+            RepositorySource::Path(path) => {
+                tokio::fs::read(RepositorySource::index_path(path)).await?
+            }
+            RepositorySource::Url(url) => self.fetch(url.clone()).await?,
+        };
+
+        let index: RepositoryIndex = serde_json::from_slice(&data)?;
+        if index.version > INDEX_VERSION {
+            return Err(RepositoryError::UnsupportedVersion(index.version));
+        }
+
+        Ok(index)
+    }
+
+    async fn fetch(&self, url: Url) -> Result<Vec<u8>, RepositoryError> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(crate::core::Error::from)?
+            .error_for_status()?;
+
+        Ok(response.bytes().await.map_err(crate::core::Error::from)?)
+    }
+
+    /// Download the file of `module` and check it.
+    pub async fn download(&self, module: &RepositoryModule) -> Result<Vec<u8>, RepositoryError> {
+        let data = self.read(&module.url).await?;
+        self.verify(module, &data)?;
+
+        Ok(data)
+    }
+
+    /// Download the permission manifest of `module` and check it.
+    pub async fn download_permissions(
+        &self,
+        module: &RepositoryModule,
+    ) -> Result<Vec<u8>, RepositoryError> {
+        // the checksum is trusted once the module's signature is.
+        self.verify_signature(module)?;
+
+        let file = &module.permissions;
+        let data = self.read(&file.url).await?;
+        check_sha256(&manifest_name(&module.file), &file.sha256, &data)?;
+
+        Ok(data)
+    }
+
+    async fn read(&self, url: &str) -> Result<Vec<u8>, RepositoryError> {
+        let invalid_url = || RepositoryError::InvalidUrl(url.to_string());
+
+        let data = match Url::parse(url) {
+            Ok(url) if url.scheme() == "file" => {
+                tokio::fs::read(url.to_file_path().map_err(|_| invalid_url())?).await?
+            }
+            Ok(url) => self.fetch(url).await?,
+            Err(_) => match &self.source {
+                RepositorySource::Path(path) => {
+                    let index = RepositorySource::index_path(path);
+                    let dir = index.parent().unwrap_or_else(|| Utf8Path::new(""));
+                    tokio::fs::read(dir.join(url)).await?
+                }
+                RepositorySource::Url(base) => {
+                    let url = base.join(url).map_err(|_| invalid_url())?;
+                    self.fetch(url).await?
+                }
+            },
+        };
+
+        Ok(data)
+    }
+
+    /// Check `data` against the checksum and signature of `module`.
+    pub fn verify(&self, module: &RepositoryModule, data: &[u8]) -> Result<(), RepositoryError> {
+        self.verify_signature(module)?;
+        check_sha256(&module.name, &module.sha256, data)
+    }
+
+    fn verify_signature(&self, module: &RepositoryModule) -> Result<(), RepositoryError> {
+        let name = &module.name;
+        let invalid = || RepositoryError::InvalidSignature(name.to_string());
+        let signature = module
+            .signature
+            .as_deref()
+            .ok_or_else(|| RepositoryError::MissingSignature(name.to_string()))?;
+        let signature = decode_hex(signature).ok_or_else(invalid)?;
+        let signature = Signature::try_from(signature.as_slice()).map_err(|_| invalid())?;
+
+        self.public_key
+            .verify(&module.signed_message(), &signature)
+            .map_err(|_| invalid())
+    }
+}
+
+fn check_sha256(name: &str, sha256: &str, data: &[u8]) -> Result<(), RepositoryError> {
+    let actual = self::sha256(data);
+    if !actual.eq_ignore_ascii_case(sha256.trim()) {
+        return Err(RepositoryError::ChecksumMismatch {
+            name: name.to_string(),
+            expected: sha256.to_string(),
+            actual,
+        });
+    }
+
+    Ok(())
+}
+
+/// Module installed by [`ModuleInstaller`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledModule {
+    pub name: String,
+    pub version: ModuleVersion,
+    pub file: String,
+    pub sha256: String,
+}
+
+/// Installed module with a newer version in the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleUpdate {
+    pub installed: InstalledModule,
+    pub available: RepositoryModule,
+}
+
+/// Install modules from a [`Repository`] into a module directory.
+///
+/// Installed versions are recorded in [`INSTALLED_FILE`] inside the
+/// directory, files copied by hand aren't tracked.
+#[derive(Debug, Clone)]
+pub struct ModuleInstaller {
+    dir: Utf8PathBuf,
+}
+
+impl ModuleInstaller {
+    pub fn new(dir: impl Into<Utf8PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+
+    pub async fn installed(&self) -> Result<Vec<InstalledModule>, RepositoryError> {
+        match tokio::fs::read(self.dir.join(INSTALLED_FILE)).await {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn write(&self, name: &str, data: &[u8]) -> Result<Utf8PathBuf, RepositoryError> {
+        let path = self.dir.join(name);
+        // written to the side first so a module file is never half written.
+        let part = self.dir.join(format!("{}.part", name));
+        tokio::fs::write(&part, data).await?;
+        tokio::fs::rename(&part, &path).await?;
+
+        Ok(path)
+    }
+
+    /// Download `module` and its permission manifest into the directory,
+    /// replacing an installed version.
+    ///
+    /// Returns path of the module file, which still needs to be loaded.
+    pub async fn install(
+        &self,
+        repository: &Repository,
+        module: &RepositoryModule,
+    ) -> Result<Utf8PathBuf, RepositoryError> {
+        let file = &module.file;
+        let is_plain_name = Utf8Path::new(file).file_name() == Some(file.as_str());
+        if !is_plain_name || !file.ends_with(".js") {
+            return Err(RepositoryError::InvalidFileName(file.clone()));
+        }
+
+        let data = repository.download(module).await?;
+        let permissions = repository.download_permissions(module).await?;
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        // the manifest goes first so the module is never loaded without it.
+        self.write(&manifest_name(file), &permissions).await?;
+        let path = self.write(file, &data).await?;
+
+        let mut installed = self.installed().await?;
+        if let Some(previous) = installed.iter().find(|it| it.name == module.name) {
+            // the file was renamed in the new version.
+            if previous.file != module.file {
+                tokio::fs::remove_file(self.dir.join(&previous.file))
+                    .await
+                    .ok();
+                tokio::fs::remove_file(self.dir.join(manifest_name(&previous.file)))
+                    .await
+                    .ok();
+            }
+        }
+        installed.retain(|it| it.name != module.name);
+        installed.push(InstalledModule {
+            name: module.name.clone(),
+            version: module.version.clone(),
+            file: module.file.clone(),
+            sha256: sha256(&data),
+        });
+        self.write(INSTALLED_FILE, &serde_json::to_vec_pretty(&installed)?)
+            .await?;
+
+        Ok(path)
+    }
+
+    /// Installed modules with a newer version in `index`.
+    pub async fn updates(
+        &self,
+        index: &RepositoryIndex,
+    ) -> Result<Vec<ModuleUpdate>, RepositoryError> {
+        let updates = self
+            .installed()
+            .await?
+            .into_iter()
+            .filter_map(|installed| {
+                let available = index.get(&installed.name)?;
+                (available.version > installed.version).then(|| ModuleUpdate {
+                    available: available.clone(),
+                    installed,
+                })
+            })
+            .collect();
+
+        Ok(updates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Keypair, SecretKey, Signer};
+
+    use super::*;
+
+    fn keypair() -> Keypair {
+        let secret = SecretKey::from_bytes(&[7; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+
+        Keypair { secret, public }
+    }
+
+    fn public_key() -> String {
+        encode_hex(keypair().public.as_bytes())
+    }
+
+    fn sign(data: &[u8]) -> Option<String> {
+        Some(encode_hex(&keypair().sign(data).to_bytes()))
+    }
+
+    const PERMISSIONS: &[u8] = br#"{"hosts": ["localhost"]}"#;
+
+    fn signed(mut module: RepositoryModule) -> RepositoryModule {
+        module.signature = sign(&module.signed_message());
+        module
+    }
+
+    fn module(name: &str, version: &str, data: &[u8]) -> RepositoryModule {
+        signed(RepositoryModule {
+            name: name.to_string(),
+            version: version.parse().unwrap(),
+            file: format!("{}.js", name),
+            url: format!("files/{}.js", name),
+            sha256: sha256(data),
+            signature: None,
+            permissions: RepositoryFile {
+                url: format!("files/{}", manifest_name(name)),
+                sha256: sha256(PERMISSIONS),
+            },
+        })
+    }
+
+    /// Write a repository with one file per module into `dir`.
+    fn repository(dir: &Utf8Path, modules: &[(&RepositoryModule, &[u8])]) -> Repository {
+        std::fs::create_dir_all(dir.join("files")).unwrap();
+        for (module, data) in modules {
+            std::fs::write(dir.join(&module.url), data).unwrap();
+            std::fs::write(dir.join(&module.permissions.url), PERMISSIONS).unwrap();
+        }
+
+        let index = RepositoryIndex {
+            version: INDEX_VERSION,
+            modules: modules.iter().map(|(it, _)| (*it).clone()).collect(),
+        };
+        std::fs::write(dir.join(INDEX_FILE), serde_json::to_string(&index).unwrap()).unwrap();
+
+        Repository::new(
+            RepositorySource::Path(dir.to_owned()),
+            Client::default(),
+            &public_key(),
+        )
+        .unwrap()
+    }
+
+    fn tempdir() -> (tempfile::TempDir, Utf8PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::from_path_buf(dir.path().to_owned()).unwrap();
+
+        (dir, path)
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn version_test() {
+        let version = |it: &str| it.parse::<ModuleVersion>().unwrap();
+
+        assert!(version("1.2.10") > version("1.2.9"));
+        assert!(version("2") > version("1.9"));
+        assert_eq!(version("1.2"), version("1.2.0"));
+        assert_eq!(version("1.2.0").to_string(), "1.2.0");
+        assert!("1.x".parse::<ModuleVersion>().is_err());
+        assert!("".parse::<ModuleVersion>().is_err());
+    }
+
+    #[test]
+    fn source_test() {
+        assert_eq!(
+            RepositorySource::parse("https://localhost/index.json"),
+            RepositorySource::Url(Url::parse("https://localhost/index.json").unwrap())
+        );
+        assert_eq!(
+            RepositorySource::parse("/modules/index.json"),
+            RepositorySource::Path("/modules/index.json".into())
+        );
+        assert_eq!(manifest_name("a.js"), "a.permissions.json");
+    }
+
+    #[test]
+    fn install_test() {
+        let (_temp, dir) = tempdir();
+        let old = module("a", "1.0", b"old");
+        let new = module("a", "1.1", b"new");
+
+        let repository = repository(&dir.join("repo"), &[(&old, b"old")]);
+        let installer = ModuleInstaller::new(dir.join("modules"));
+
+        block_on(async {
+            let index = repository.index().await.unwrap();
+            assert_eq!(index.modules, [old.clone()]);
+
+            let path = installer.install(&repository, &old).await.unwrap();
+            assert_eq!(std::fs::read(&path).unwrap(), b"old");
+            assert_eq!(
+                std::fs::read(dir.join("modules").join("a.permissions.json")).unwrap(),
+                PERMISSIONS
+            );
+            assert_eq!(installer.installed().await.unwrap()[0].version, old.version);
+            assert!(installer.updates(&index).await.unwrap().is_empty());
+
+            // the index file can be used directly.
+            let repository = self::repository(&dir.join("repo"), &[(&new, b"new")]);
+            let index = Repository::new(
+                RepositorySource::Path(dir.join("repo").join(INDEX_FILE)),
+                Client::default(),
+                &public_key(),
+            )
+            .unwrap()
+            .index()
+            .await
+            .unwrap();
+
+            let updates = installer.updates(&index).await.unwrap();
+            assert_eq!(updates.len(), 1);
+            assert_eq!(updates[0].available, new);
+
+            installer.install(&repository, &new).await.unwrap();
+            assert_eq!(std::fs::read(&path).unwrap(), b"new");
+            assert_eq!(installer.installed().await.unwrap().len(), 1);
+            assert!(installer.updates(&index).await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn verify_test() {
+        let (_temp, dir) = tempdir();
+        let repository = repository(&dir, &[]);
+        let installer = ModuleInstaller::new(dir.join("modules"));
+
+        let module = self::module("a", "1.0", b"data");
+        assert!(repository.verify(&module, b"data").is_ok());
+        assert!(matches!(
+            repository.verify(&module, b"other"),
+            Err(RepositoryError::ChecksumMismatch { .. })
+        ));
+
+        let mut unsigned = module.clone();
+        unsigned.signature = None;
+        assert!(matches!(
+            repository.verify(&unsigned, b"data"),
+            Err(RepositoryError::MissingSignature(_))
+        ));
+
+        let mut forged = self::module("a", "1.0", b"other");
+        forged.sha256 = sha256(b"data");
+        assert!(matches!(
+            repository.verify(&forged, b"data"),
+            Err(RepositoryError::InvalidSignature(_))
+        ));
+
+        // a signed file can't be listed under another version.
+        let mut downgraded = module.clone();
+        downgraded.version = "2.0".parse().unwrap();
+        assert!(matches!(
+            repository.verify(&downgraded, b"data"),
+            Err(RepositoryError::InvalidSignature(_))
+        ));
+
+        // nor can the manifest of another module be swapped in.
+        let mut other = self::module("b", "1.0", b"other");
+        other.permissions.sha256 = sha256(br#"{"hosts": ["*"]}"#);
+        let other = signed(other);
+        let mut swapped = module.clone();
+        swapped.permissions = other.permissions.clone();
+        assert!(repository.verify(&other, b"other").is_ok());
+        assert!(matches!(
+            repository.verify(&swapped, b"data"),
+            Err(RepositoryError::InvalidSignature(_))
+        ));
+
+        let mut outside = module.clone();
+        outside.file = "../a.js".to_string();
+        let result = block_on(installer.install(&repository, &outside));
+        assert!(matches!(result, Err(RepositoryError::InvalidFileName(_))));
+
+        // a manifest that doesn't match its signed checksum fails the
+        // whole install.
+        let repository = self::repository(&dir, &[(&module, b"data")]);
+        let mut changed = module;
+        changed.permissions.sha256 = sha256(br#"{"hosts": ["*"]}"#);
+        let changed = signed(changed);
+        let result = block_on(installer.install(&repository, &changed));
+        assert!(matches!(
+            result,
+            Err(RepositoryError::ChecksumMismatch { .. })
+        ));
+        assert!(!dir.join("modules").join("a.js").exists());
+
+        assert!(matches!(
+            Repository::new(RepositorySource::Path(dir), Client::default(), "00"),
+            Err(RepositoryError::InvalidPublicKey)
+        ));
+    }
+}
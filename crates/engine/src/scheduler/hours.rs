@@ -0,0 +1,94 @@
+use std::time::{Duration, SystemTime};
+
+const MINUTES_IN_DAY: u16 = 24 * 60;
+
+/// Daily window in which downloads may run, in minutes since midnight.
+///
+/// `end` before `start` wraps past midnight, `start == end` is the whole day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveHours {
+    start: u16,
+    end: u16,
+}
+
+impl ActiveHours {
+    /// Create window from `start` to `end` as `(hour, minute)`.
+    ///
+    /// Returns `None` if either is not a valid time of day.
+    pub fn new(start: (u16, u16), end: (u16, u16)) -> Option<Self> {
+        let minute =
+            |(hour, minute): (u16, u16)| (hour < 24 && minute < 60).then_some(hour * 60 + minute);
+
+        Some(Self {
+            start: minute(start)?,
+            end: minute(end)?,
+        })
+    }
+
+    pub fn start(&self) -> (u16, u16) {
+        (self.start / 60, self.start % 60)
+    }
+
+    pub fn end(&self) -> (u16, u16) {
+        (self.end / 60, self.end % 60)
+    }
+
+    /// Whether `minute` since midnight is inside the window.
+    pub fn contains(&self, minute: u16) -> bool {
+        match self.start.cmp(&self.end) {
+            std::cmp::Ordering::Equal => true,
+            std::cmp::Ordering::Less => self.start <= minute && minute < self.end,
+            std::cmp::Ordering::Greater => self.start <= minute || minute < self.end,
+        }
+    }
+}
+
+/// Minutes since local midnight of `time`, `utc_offset` is in seconds.
+pub(crate) fn minute_of_day(time: SystemTime, utc_offset: i32) -> u16 {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs() as i64;
+
+    let local = (secs + utc_offset as i64).rem_euclid(MINUTES_IN_DAY as i64 * 60);
+
+    (local / 60) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contains_test() {
+        let night = ActiveHours::new((22, 0), (6, 30)).unwrap();
+        assert!(night.contains(23 * 60));
+        assert!(night.contains(0));
+        assert!(night.contains(6 * 60 + 29));
+        assert!(!night.contains(6 * 60 + 30));
+        assert!(!night.contains(12 * 60));
+
+        let day = ActiveHours::new((9, 0), (17, 0)).unwrap();
+        assert!(day.contains(9 * 60));
+        assert!(!day.contains(17 * 60));
+        assert!(!day.contains(0));
+
+        let always = ActiveHours::new((0, 0), (0, 0)).unwrap();
+        assert!(always.contains(0));
+        assert!(always.contains(MINUTES_IN_DAY - 1));
+
+        assert_eq!(ActiveHours::new((24, 0), (1, 0)), None);
+        assert_eq!(ActiveHours::new((1, 60), (1, 0)), None);
+        assert_eq!(night.start(), (22, 0));
+        assert_eq!(night.end(), (6, 30));
+    }
+
+    #[test]
+    fn minute_of_day_test() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(3 * 3600 + 15 * 60);
+
+        assert_eq!(minute_of_day(time, 0), 3 * 60 + 15);
+        assert_eq!(minute_of_day(time, 7 * 3600), 10 * 60 + 15);
+        assert_eq!(minute_of_day(time, -5 * 3600), 22 * 60 + 15);
+    }
+}
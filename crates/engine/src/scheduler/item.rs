@@ -13,13 +13,13 @@ impl std::ops::Deref for QueueItem {
 
 impl PartialOrd for QueueItem {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.0.order().partial_cmp(&other.0.order())
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for QueueItem {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.order().cmp(&other.0.order())
+        (self.0.priority(), self.0.order()).cmp(&(other.0.priority(), other.0.order()))
     }
 }
 
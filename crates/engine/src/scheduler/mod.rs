@@ -1,17 +1,23 @@
 use std::{
-    sync::{atomic::AtomicUsize, Arc},
-    time::Duration,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicI32, AtomicUsize},
+        Arc,
+    },
+    time::{Duration, SystemTime},
 };
 
 use by_address::ByAddress;
 use futures::StreamExt;
 use parking_lot::Mutex;
 
-use crate::{DownloadInfo, DownloadStatus, MadoEngineState};
+use crate::{DownloadInfo, DownloadPriority, DownloadStatus, MadoEngineState};
 
+pub use self::hours::ActiveHours;
 use self::item::QueueItem;
 pub use self::runner::TaskRunner;
 
+mod hours;
 mod item;
 mod runner;
 
@@ -19,6 +25,8 @@ pub enum SchedulerMsg {
     NewQueue(Arc<DownloadInfo>),
     RemoveQueue(Arc<DownloadInfo>),
     OrderChanged(Arc<DownloadInfo>),
+    /// Something affecting when the download starts changed.
+    Reschedule(Arc<DownloadInfo>),
 }
 
 pub struct TaskScheduler {
@@ -29,6 +37,58 @@ pub struct TaskScheduler {
 #[derive(Debug)]
 pub struct TaskSchedulerOption {
     download_limit: AtomicUsize,
+    source_limit: AtomicUsize,
+    source_key: Mutex<SourceKey>,
+    round_robin: AtomicBool,
+    active_hours: Mutex<Vec<ActiveHours>>,
+    utc_offset: AtomicI32,
+    local_offset: Mutex<Option<LocalOffset>>,
+}
+
+/// Offset of local time from UTC in seconds at a given time, `None` if it
+/// can't be known.
+#[derive(Clone)]
+pub struct LocalOffset(Arc<dyn Fn(SystemTime) -> Option<i32> + Send + Sync>);
+
+impl LocalOffset {
+    pub fn new(fun: impl Fn(SystemTime) -> Option<i32> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(fun))
+    }
+
+    pub fn at(&self, time: SystemTime) -> Option<i32> {
+        (self.0)(time)
+    }
+}
+
+impl std::fmt::Debug for LocalOffset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("LocalOffset").finish()
+    }
+}
+
+/// What downloads are grouped by for [`TaskSchedulerOption::source_limit`]
+/// and round-robin.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SourceKey {
+    #[default]
+    Module,
+    /// Host of the manga's url, downloads without url fall back to their
+    /// module.
+    Host,
+}
+
+impl SourceKey {
+    fn key(&self, info: &DownloadInfo) -> String {
+        let host = match self {
+            Self::Module => None,
+            Self::Host => info.url().and_then(|it| it.host_str()),
+        };
+
+        match host {
+            Some(host) => host.to_string(),
+            None => info.module_uuid().to_string(),
+        }
+    }
 }
 
 const _: () = {
@@ -43,6 +103,12 @@ impl Default for TaskSchedulerOption {
     fn default() -> Self {
         Self {
             download_limit: AtomicUsize::new(usize::MAX),
+            source_limit: AtomicUsize::new(usize::MAX),
+            source_key: Default::default(),
+            round_robin: AtomicBool::new(true),
+            active_hours: Default::default(),
+            utc_offset: AtomicI32::new(0),
+            local_offset: Default::default(),
         }
     }
 }
@@ -56,9 +122,170 @@ impl TaskSchedulerOption {
         self.download_limit
             .store(download_limit, atomic::Ordering::Relaxed);
     }
+
+    /// Maximum downloads running at once from the same source.
+    pub fn source_limit(&self) -> usize {
+        self.source_limit.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_source_limit(&self, source_limit: usize) {
+        self.source_limit
+            .store(source_limit, atomic::Ordering::Relaxed);
+    }
+
+    pub fn source_key(&self) -> SourceKey {
+        *self.source_key.lock()
+    }
+
+    pub fn set_source_key(&self, source_key: SourceKey) {
+        *self.source_key.lock() = source_key;
+    }
+
+    /// Whether downloads of the same priority take turns between sources
+    /// instead of starting strictly by order.
+    pub fn round_robin(&self) -> bool {
+        self.round_robin.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_round_robin(&self, round_robin: bool) {
+        self.round_robin
+            .store(round_robin, atomic::Ordering::Relaxed);
+    }
+
+    /// Windows in which downloads may run, empty means any time.
+    pub fn active_hours(&self) -> Vec<ActiveHours> {
+        self.active_hours.lock().clone()
+    }
+
+    pub fn set_active_hours(&self, active_hours: Vec<ActiveHours>) {
+        *self.active_hours.lock() = active_hours;
+    }
+
+    /// Offset of local time from UTC in seconds, used for
+    /// [`Self::active_hours`].
+    pub fn utc_offset(&self) -> i32 {
+        self.utc_offset.load(atomic::Ordering::Relaxed)
+    }
+
+    pub fn set_utc_offset(&self, utc_offset: i32) {
+        self.utc_offset.store(utc_offset, atomic::Ordering::Relaxed);
+    }
+
+    /// Recompute [`Self::utc_offset`] with `local_offset` every time the
+    /// queue is scheduled, so it follows daylight saving changes.
+    pub fn set_local_offset(&self, local_offset: Option<LocalOffset>) {
+        *self.local_offset.lock() = local_offset;
+    }
+
+    /// Update [`Self::utc_offset`] to the local offset at `now`, if it's
+    /// known.
+    pub fn update_utc_offset(&self, now: SystemTime) {
+        let local_offset = self.local_offset.lock().clone();
+
+        if let Some(offset) = local_offset.and_then(|it| it.at(now)) {
+            self.set_utc_offset(offset);
+        }
+    }
+
+    pub fn is_active_at(&self, now: SystemTime) -> bool {
+        let active_hours = self.active_hours.lock();
+        if active_hours.is_empty() {
+            return true;
+        }
+
+        let minute = hours::minute_of_day(now, self.utc_offset());
+        active_hours.iter().any(|it| it.contains(minute))
+    }
+}
+
+/// Queued items that should start downloading at `now`, in the order they
+/// should be started.
+///
+/// Higher priority classes are served first, inside a class items are taken
+/// by order, alternating between sources if round-robin is enabled. Sources
+/// with fewer running downloads get their turn first. Nothing is started
+/// outside active hours, nor items scheduled later than `now`.
+fn next_downloads<'a>(
+    items: &'a [QueueItem],
+    option: &TaskSchedulerOption,
+    now: SystemTime,
+) -> Vec<&'a QueueItem> {
+    if !option.is_active_at(now) {
+        return Vec::new();
+    }
+
+    let key = option.source_key();
+    let download_limit = option.download_limit();
+    let source_limit = option.source_limit();
+
+    let mut running = HashMap::<String, usize>::new();
+    let mut queued = Vec::new();
+    for it in items {
+        match it.status().as_resumed() {
+            Some(status) if status.is_downloading() => {
+                *running.entry(key.key(it)).or_default() += 1;
+            }
+            Some(status) if status.is_queue() && !it.is_scheduled(now) => queued.push(it),
+            _ => {}
+        }
+    }
+
+    let mut downloading: usize = running.values().sum();
+    let mut result = Vec::new();
+
+    for priority in DownloadPriority::ALL {
+        let class = queued
+            .iter()
+            .copied()
+            .filter(|it| it.priority() == priority);
+
+        let class: Vec<_> = if option.round_robin() {
+            // group by source keeping the order inside each group.
+            let mut groups = Vec::<(String, Vec<&QueueItem>)>::new();
+            for it in class {
+                let source = key.key(it);
+                match groups.iter_mut().find(|(key, _)| *key == source) {
+                    Some((_, group)) => group.push(it),
+                    None => groups.push((source, vec![it])),
+                }
+            }
+            groups.sort_by_key(|(key, _)| running.get(key).copied().unwrap_or_default());
+
+            let mut groups: Vec<_> = groups.into_iter().map(|(_, it)| it.into_iter()).collect();
+            let mut round = Vec::new();
+            loop {
+                let len = round.len();
+                round.extend(groups.iter_mut().filter_map(|it| it.next()));
+                if round.len() == len {
+                    break round;
+                }
+            }
+        } else {
+            class.collect()
+        };
+
+        for it in class {
+            if downloading >= download_limit {
+                return result;
+            }
+
+            let running = running.entry(key.key(it)).or_default();
+            if *running >= source_limit {
+                continue;
+            }
+
+            *running += 1;
+            downloading += 1;
+            result.push(it);
+        }
+    }
+
+    result
 }
 
 pub const TASK_SCHEDULER_DEBOUNCE_DURATION: Duration = Duration::from_millis(100);
+/// How often the queue is checked for scheduled downloads and active hours.
+pub const TASK_SCHEDULER_TICK_DURATION: Duration = Duration::from_secs(30);
 
 impl TaskScheduler {
     pub fn connect(
@@ -88,38 +315,23 @@ impl TaskScheduler {
                 vec.sort();
                 vec.dedup();
 
-                // count currently downloading task
-                let mut downloading = vec
-                    .iter()
-                    .filter(|it| {
-                        it.status()
-                            .as_resumed()
-                            .map(|it| it.is_downloading())
-                            .unwrap_or(false)
-                    })
-                    .count();
-
-                let limit = self.option.download_limit();
-
-                // set queued item to downloading until downloading is not less than limit
-                for it in vec.iter() {
-                    if !(downloading < limit) {
-                        break;
-                    }
+                let now = SystemTime::now();
+                self.option.update_utc_offset(now);
 
-                    if it
-                        .status()
-                        .as_resumed()
-                        .map(|it| it.is_queue())
-                        .unwrap_or(false)
-                    {
-                        tracing::debug!("resuming {:?}", it);
-                        it.set_status(DownloadStatus::resumed(
-                            crate::DownloadResumedStatus::Downloading,
-                        ));
-                        downloading += 1;
+                if !self.option.is_active_at(now) {
+                    // the runner stops them, they start again in the next window.
+                    for it in vec.iter().filter(|it| it.status().is_downloading()) {
+                        tracing::debug!("outside active hours, queueing {:?}", it);
+                        it.set_status(DownloadStatus::queued());
                     }
                 }
+
+                for it in next_downloads(&vec, &self.option, now) {
+                    tracing::debug!("resuming {:?}", it);
+                    it.set_status(DownloadStatus::resumed(
+                        crate::DownloadResumedStatus::Downloading,
+                    ));
+                }
             }
         };
 
@@ -133,13 +345,20 @@ impl TaskScheduler {
                         let address = ByAddress(info);
                         vec.lock().retain(|it| it.0 != address);
                     }
-                    SchedulerMsg::OrderChanged(_) => {}
+                    SchedulerMsg::OrderChanged(_) | SchedulerMsg::Reschedule(_) => {}
                 }
                 let _ = tx.unbounded_send(());
             }
         };
 
-        let _ = futures::join!(schedule_rx, connect_rx);
+        let tick = async {
+            loop {
+                crate::timer::sleep(TASK_SCHEDULER_TICK_DURATION).await;
+                let _ = tx.unbounded_send(());
+            }
+        };
+
+        let _ = futures::join!(schedule_rx, connect_rx, tick);
 
         handle.disconnect();
     }
@@ -176,9 +395,20 @@ impl TaskScheduler {
                         crate::DownloadInfoMsg::OrderChanged(_) => {
                             let _ = tx.unbounded_send(SchedulerMsg::OrderChanged(info.clone()));
                         }
+                        crate::DownloadInfoMsg::PriorityChanged(_)
+                        | crate::DownloadInfoMsg::StartAtChanged(_) => {
+                            let msg = SchedulerMsg::Reschedule(info.clone());
+                            let _ = tx.unbounded_send(msg);
+                        }
+                        crate::DownloadInfoMsg::Removed => {
+                            let _ = tx.unbounded_send(SchedulerMsg::RemoveQueue(info.clone()));
+                        }
                     });
                 }
-                crate::MadoEngineStateMsg::PushModule(_) => {}
+                crate::MadoEngineStateMsg::RemoveDownload(_)
+                | crate::MadoEngineStateMsg::PushModule(_)
+                | crate::MadoEngineStateMsg::RemoveModule(_)
+                | crate::MadoEngineStateMsg::ModuleFile(_) => {}
             }
         });
 
@@ -192,10 +422,163 @@ mod tests {
 
     use mado_core::{MockMadoModule, Uuid};
 
-    use crate::{
-        scheduler::TASK_SCHEDULER_DEBOUNCE_DURATION, DownloadRequest, DownloadStatus,
-        MadoEngineState,
-    };
+    use super::*;
+    use crate::{scheduler::TASK_SCHEDULER_DEBOUNCE_DURATION, DownloadRequest};
+
+    fn item(
+        module: u128,
+        order: usize,
+        priority: DownloadPriority,
+        status: DownloadStatus,
+    ) -> QueueItem {
+        let mut mock = MockMadoModule::new();
+        mock.expect_uuid().return_const(Uuid::from_u128(module));
+
+        QueueItem::new(Arc::new(
+            DownloadInfo::builder()
+                .order(order)
+                .module(Arc::new(mock))
+                .priority(priority)
+                .url(Some("https://example.com/manga".parse().unwrap()))
+                .status(status)
+                .build(),
+        ))
+    }
+
+    fn queued(module: u128, order: usize) -> QueueItem {
+        item(module, order, Default::default(), DownloadStatus::queued())
+    }
+
+    fn orders(items: Vec<&QueueItem>) -> Vec<usize> {
+        items.into_iter().map(|it| it.order()).collect()
+    }
+
+    #[test]
+    fn next_downloads_priority_test() {
+        let option = TaskSchedulerOption::default();
+        option.set_download_limit(1);
+
+        let mut items = vec![
+            queued(1, 0),
+            item(1, 1, DownloadPriority::High, DownloadStatus::queued()),
+            item(1, 2, DownloadPriority::Low, DownloadStatus::queued()),
+        ];
+        items.sort();
+
+        assert_eq!(
+            orders(next_downloads(&items, &option, SystemTime::now())),
+            vec![1]
+        );
+
+        option.set_download_limit(usize::MAX);
+        assert_eq!(
+            orders(next_downloads(&items, &option, SystemTime::now())),
+            vec![1, 0, 2]
+        );
+    }
+
+    #[test]
+    fn next_downloads_source_test() {
+        let option = TaskSchedulerOption::default();
+        let items = vec![queued(1, 0), queued(1, 1), queued(1, 2), queued(2, 3)];
+
+        option.set_download_limit(2);
+        assert_eq!(
+            orders(next_downloads(&items, &option, SystemTime::now())),
+            vec![0, 3]
+        );
+
+        option.set_round_robin(false);
+        assert_eq!(
+            orders(next_downloads(&items, &option, SystemTime::now())),
+            vec![0, 1]
+        );
+
+        option.set_download_limit(usize::MAX);
+        option.set_source_limit(1);
+        assert_eq!(
+            orders(next_downloads(&items, &option, SystemTime::now())),
+            vec![0, 3]
+        );
+
+        // both modules download from the same host.
+        option.set_source_key(SourceKey::Host);
+        assert_eq!(
+            orders(next_downloads(&items, &option, SystemTime::now())),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn next_downloads_time_test() {
+        let option = TaskSchedulerOption::default();
+        let items = vec![queued(1, 0), queued(1, 1)];
+
+        let noon = SystemTime::UNIX_EPOCH + Duration::from_secs(12 * 3600);
+        items[0].set_start_at(Some(noon + Duration::from_secs(60)));
+        assert_eq!(orders(next_downloads(&items, &option, noon)), vec![1]);
+
+        let later = noon + Duration::from_secs(120);
+        assert_eq!(orders(next_downloads(&items, &option, later)), vec![0, 1]);
+
+        option.set_active_hours(vec![ActiveHours::new((22, 0), (6, 0)).unwrap()]);
+        assert!(!option.is_active_at(later));
+        assert!(next_downloads(&items, &option, later).is_empty());
+
+        // noon in UTC is 23:00 at UTC+11.
+        option.set_utc_offset(11 * 3600);
+        assert!(option.is_active_at(later));
+        assert_eq!(orders(next_downloads(&items, &option, later)), vec![0, 1]);
+    }
+
+    #[test]
+    fn update_utc_offset_test() {
+        let option = TaskSchedulerOption::default();
+        let noon = SystemTime::UNIX_EPOCH + Duration::from_secs(12 * 3600);
+
+        // nothing changes without a local offset.
+        option.set_utc_offset(3600);
+        option.update_utc_offset(noon);
+        assert_eq!(option.utc_offset(), 3600);
+
+        // an hour more after noon, like daylight saving starting.
+        option.set_local_offset(Some(LocalOffset::new(move |time| {
+            Some(if time < noon { 3600 } else { 7200 })
+        })));
+        option.update_utc_offset(noon - Duration::from_secs(1));
+        assert_eq!(option.utc_offset(), 3600);
+        option.update_utc_offset(noon);
+        assert_eq!(option.utc_offset(), 7200);
+
+        option.set_local_offset(Some(LocalOffset::new(|_| None)));
+        option.update_utc_offset(noon);
+        assert_eq!(option.utc_offset(), 7200);
+    }
+
+    #[test]
+    fn next_downloads_running_test() {
+        let option = TaskSchedulerOption::default();
+        let items = vec![
+            item(1, 0, Default::default(), DownloadStatus::downloading()),
+            queued(1, 1),
+            queued(2, 2),
+        ];
+
+        // module 2 has nothing running so it goes first.
+        assert_eq!(
+            orders(next_downloads(&items, &option, SystemTime::now())),
+            vec![2, 1]
+        );
+
+        option.set_source_limit(1);
+        assert_eq!(
+            orders(next_downloads(&items, &option, SystemTime::now())),
+            vec![2]
+        );
+
+        option.set_download_limit(1);
+        assert!(next_downloads(&items, &option, SystemTime::now()).is_empty());
+    }
 
     #[test]
     pub fn test_limit() {
@@ -1,14 +1,23 @@
 use std::sync::Arc;
 
-use futures::FutureExt;
+use futures::future::Either;
 
-use crate::{watcher::DownloadInfoWatcher, DownloadStatus, TaskDownloader};
+use crate::{watcher::DownloadInfoWatcher, CancellationToken, DownloadStatus, TaskDownloader};
 
-pub struct TaskRunner {}
+pub struct TaskRunner {
+    cancel: CancellationToken,
+}
 
 impl TaskRunner {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Token cancelling every download started by this runner.
+    pub fn cancel_token(&self) -> &CancellationToken {
+        &self.cancel
     }
 
     #[tracing::instrument(
@@ -18,6 +27,8 @@ impl TaskRunner {
             url = ?download.url().map(|it| it.as_str()),
         )
     )]
+    /// Run `download` whenever it's resumed, until the runner's token is
+    /// cancelled.
     pub async fn run<F>(&self, download: Arc<crate::DownloadInfo>, mut create_download: F)
     where
         F: FnMut(&Arc<crate::DownloadInfo>) -> TaskDownloader,
@@ -25,35 +36,63 @@ impl TaskRunner {
         let status = DownloadInfoWatcher::connect(download.clone());
 
         loop {
-            tracing::trace!("waiting for resumed");
-            status.wait_status(DownloadStatus::is_resumed).await;
-            download.set_status(DownloadStatus::resumed(
-                crate::DownloadResumedStatus::Waiting,
-            ));
-
-            tracing::trace!("waiting for module");
-            let _ = download.wait_module().await;
+            let ready = async {
+                tracing::trace!("waiting for resumed");
+                status.wait_status(DownloadStatus::is_resumed).await;
+                download.set_status(DownloadStatus::resumed(
+                    crate::DownloadResumedStatus::Waiting,
+                ));
 
-            download.set_status(DownloadStatus::resumed(crate::DownloadResumedStatus::Queue));
+                tracing::trace!("waiting for module");
+                let _ = download.wait_module().await;
 
-            tracing::trace!("waiting for downloading");
-            status.wait_status(DownloadStatus::is_downloading).await;
+                download.set_status(DownloadStatus::resumed(crate::DownloadResumedStatus::Queue));
 
-            tracing::trace!("download resumed");
-            let paused = status
-                .wait_status(DownloadStatus::is_paused)
-                .inspect(|_| tracing::trace!("download paused"))
-                .map(|_| Ok::<(), mado_core::Error>(()));
+                tracing::trace!("waiting for downloading");
+                status.wait_status(DownloadStatus::is_downloading).await;
+                Ok(())
+            };
 
-            let dl = create_download(&download).run();
+            if self.cancel.run(ready).await.is_err() {
+                tracing::trace!("runner stopped");
+                return;
+            }
 
-            futures::pin_mut!(dl, paused);
+            tracing::trace!("download resumed");
+            // paused, removed, or queued again by the scheduler.
+            let stopped = status.wait_status(|it| !it.is_downloading());
+            let removed = status.wait_removed();
+            futures::pin_mut!(stopped, removed);
+            let stop = futures::future::select(stopped, removed);
+
+            let cancel = self.cancel.child();
+            let dl = create_download(&download).with_cancel(cancel.clone()).run();
+
+            futures::pin_mut!(dl);
+
+            let result = match futures::future::select(dl, stop).await {
+                Either::Left((result, _)) => result,
+                Either::Right((_, dl)) => {
+                    tracing::trace!("download stopped");
+                    cancel.cancel();
+                    dl.await
+                }
+            };
 
-            let (result, _) = futures::future::select(dl, paused).await.factor_first();
+            match result {
+                Err(err) if err.is_cancelled() => tracing::trace!("download cancelled"),
+                Err(err) => {
+                    tracing::error!("{}", err);
+                    download.log_failure(crate::DownloadFailure::new(&err));
+                    download.set_status(DownloadStatus::error(err));
+                }
+                Ok(()) => {}
+            }
 
-            if let Err(err) = result {
-                tracing::error!("{}", err);
-                download.set_status(DownloadStatus::error(err));
+            // keep the status as is so the download resumes on next start.
+            if self.cancel.is_cancelled() {
+                tracing::trace!("runner stopped");
+                return;
             }
         }
     }
@@ -175,11 +214,124 @@ mod tests {
                     crate::DownloadResumedStatus::Downloading,
                 ));
                 sleep().await;
-                assert!(info.status().is_error());
+                assert!(info.status().is_finished_with_errors());
+                assert!(info.chapters()[0].status().is_error());
+                assert_eq!(info.failures()[0].chapter_id.as_deref(), Some("1"));
             };
 
             futures::pin_mut!(test, runner);
             let _ = futures::future::select(test, runner).await;
         });
     }
+
+    #[test]
+    pub fn test_pause_cancels() {
+        let server = httpmock::MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.path("/slow");
+            then.delay(std::time::Duration::from_secs(5)).body("test");
+        });
+        let url = crate::tests::server_url(mock.server_address())
+            .join("/slow")
+            .unwrap();
+
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+        module.expect_get_chapter_images().returning(|_, mut task| {
+            task.add(mado_core::ChapterImageInfo {
+                id: "1".to_string(),
+                extension: "png".to_string(),
+                name: None,
+            });
+            Ok(())
+        });
+        let client = mado_core::http::Client::default();
+        module
+            .expect_download_image()
+            .returning(move |_| Ok(client.get(url.clone()).into()));
+
+        let module = Arc::new(module);
+        let temp = tempfile::tempdir().unwrap();
+        let path = camino::Utf8PathBuf::from_path_buf(temp.path().to_path_buf()).unwrap();
+
+        let chapter = Arc::new(DownloadChapterInfo::new(
+            module.clone().into(),
+            "1".to_string(),
+            "1".to_string(),
+            path.join("1"),
+            DownloadStatus::waiting(),
+        ));
+        let info = Arc::new(
+            DownloadInfo::builder()
+                .order(0)
+                .module(module)
+                .chapters(vec![chapter.clone()])
+                .path(path)
+                .status(DownloadStatus::resumed(
+                    crate::DownloadResumedStatus::Downloading,
+                ))
+                .build(),
+        );
+
+        let runner = TaskRunner::new();
+        let option = DownloadOption::default();
+        let runner = runner.run(info.clone(), move |info| {
+            TaskDownloader::new(info.clone(), option.clone())
+        });
+
+        futures::executor::block_on(async {
+            let test = async {
+                let sleep = || crate::timer::sleep(std::time::Duration::from_millis(50));
+                sleep().await;
+                info.set_status(DownloadStatus::resumed(
+                    crate::DownloadResumedStatus::Downloading,
+                ));
+                sleep().await;
+                info.resume(false);
+                sleep().await;
+
+                // the transfer is dropped instead of finishing in the background.
+                assert!(info.status().is_paused());
+                assert!(!chapter.status().is_error());
+                assert!(!chapter.images()[0].path().exists());
+                assert!(info.failures().is_empty());
+            };
+
+            futures::pin_mut!(test, runner);
+            let _ = futures::future::select(test, runner).await;
+        });
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    pub fn test_cancel_runner() {
+        let runner = TaskRunner::new();
+
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+
+        let info = Arc::new(
+            DownloadInfo::builder()
+                .order(0)
+                .module(Arc::new(module))
+                .status(DownloadStatus::paused())
+                .build(),
+        );
+
+        let option = DownloadOption::default();
+        futures::executor::block_on(async {
+            let cancel = async {
+                crate::timer::sleep(std::time::Duration::from_millis(10)).await;
+                runner.cancel_token().cancel();
+            };
+            let run = runner.run(info.clone(), move |info| {
+                TaskDownloader::new(info.clone(), option.clone())
+            });
+
+            futures::join!(run, cancel);
+        });
+
+        assert!(info.status().is_paused());
+    }
 }
@@ -0,0 +1,337 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    core::{ChapterInfo, Url, Uuid},
+    MadoEngineState,
+};
+
+/// Inclusive range of chapter or volume numbers, `None` bounds are open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChapterRange {
+    Chapter {
+        start: Option<f64>,
+        end: Option<f64>,
+    },
+    Volume {
+        start: Option<f64>,
+        end: Option<f64>,
+    },
+}
+
+impl ChapterRange {
+    pub fn chapter(start: impl Into<Option<f64>>, end: impl Into<Option<f64>>) -> Self {
+        Self::Chapter {
+            start: start.into(),
+            end: end.into(),
+        }
+    }
+
+    pub fn volume(start: impl Into<Option<f64>>, end: impl Into<Option<f64>>) -> Self {
+        Self::Volume {
+            start: start.into(),
+            end: end.into(),
+        }
+    }
+
+    /// Chapters without a number never match.
+    pub fn contains(&self, chapter: &ChapterInfo) -> bool {
+        let (number, start, end) = match *self {
+            Self::Chapter { start, end } => (chapter_number(chapter), start, end),
+            Self::Volume { start, end } => (volume_number(chapter), start, end),
+        };
+
+        match number {
+            Some(number) => {
+                start.map_or(true, |it| it <= number) && end.map_or(true, |it| number <= it)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Choose which chapters of a manga to download.
+///
+/// Every filter is optional, a default selector selects every chapter.
+/// Selected chapters keep the order they were given in.
+#[derive(Debug, Clone, Default)]
+pub struct ChapterSelector {
+    ranges: Vec<ChapterRange>,
+    languages: Vec<String>,
+    scanlators: Vec<String>,
+    dedup: bool,
+    exclude: HashSet<String>,
+}
+
+impl ChapterSelector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Select chapters inside `range`, chapters matching any of the ranges
+    /// are selected.
+    pub fn range(mut self, range: ChapterRange) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    /// Select chapters in `language`, compared case-insensitively.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.languages.push(language.into());
+        self
+    }
+
+    /// Prefer chapters by `scanlator` when deduplicating, earlier calls are
+    /// preferred over later ones. Chapters by other scanlators are kept if
+    /// no preferred one has the same chapter.
+    pub fn prefer_scanlator(mut self, scanlator: impl Into<String>) -> Self {
+        self.scanlators.push(scanlator.into());
+        self
+    }
+
+    /// Keep only one chapter per chapter number and language.
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Skip chapters with these ids.
+    pub fn exclude<I, S>(mut self, ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude.extend(ids.into_iter().map(Into::into));
+        self
+    }
+
+    /// Skip chapters already in a download of the manga at `url`.
+    pub fn not_downloaded(self, state: &MadoEngineState, module: &Uuid, url: &Url) -> Self {
+        let ids = state
+            .tasks()
+            .iter()
+            .filter(|it| it.module_uuid() == module && it.url() == Some(url))
+            .flat_map(|it| it.chapters().iter())
+            .map(|it| it.chapter_id().to_string())
+            .collect::<Vec<_>>();
+
+        self.exclude(ids)
+    }
+
+    fn matches(&self, chapter: &ChapterInfo) -> bool {
+        if self.exclude.contains(&chapter.id) {
+            return false;
+        }
+
+        if !self.languages.is_empty()
+            && !self
+                .languages
+                .iter()
+                .any(|it| it.eq_ignore_ascii_case(&chapter.language))
+        {
+            return false;
+        }
+
+        self.ranges.is_empty() || self.ranges.iter().any(|it| it.contains(chapter))
+    }
+
+    /// Rank of the most preferred scanlator of `chapter`, lower is better.
+    fn scanlator_rank(&self, chapter: &ChapterInfo) -> usize {
+        chapter
+            .scanlator
+            .iter()
+            .filter_map(|name| {
+                self.scanlators
+                    .iter()
+                    .position(|it| it.eq_ignore_ascii_case(name))
+            })
+            .min()
+            .unwrap_or(self.scanlators.len())
+    }
+
+    pub fn select(&self, chapters: &[Arc<ChapterInfo>]) -> Vec<Arc<ChapterInfo>> {
+        let matched = chapters.iter().filter(|it| self.matches(it));
+
+        if !self.dedup {
+            return matched.cloned().collect();
+        }
+
+        // index of the chapter kept for each chapter number and language.
+        let mut kept = HashMap::<(String, u64), usize>::new();
+        let mut selected: Vec<Option<&Arc<ChapterInfo>>> = Vec::new();
+
+        for it in matched {
+            let number = match chapter_number(it) {
+                Some(number) => number,
+                None => {
+                    selected.push(Some(it));
+                    continue;
+                }
+            };
+
+            let key = (it.language.to_lowercase(), number.to_bits());
+            match kept.get(&key) {
+                Some(&index) => {
+                    let current = selected[index].unwrap();
+                    if self.scanlator_rank(it) < self.scanlator_rank(current) {
+                        selected[index] = None;
+                        kept.insert(key, selected.len());
+                        selected.push(Some(it));
+                    }
+                }
+                None => {
+                    kept.insert(key, selected.len());
+                    selected.push(Some(it));
+                }
+            }
+        }
+
+        selected.into_iter().flatten().cloned().collect()
+    }
+}
+
+/// Leading number of `text`, e.g. `10.5` for `"10.5 extra"`.
+fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let end = text
+        .find(|it: char| !(it.is_ascii_digit() || it == '.'))
+        .unwrap_or(text.len());
+
+    text[..end].trim_end_matches('.').parse().ok()
+}
+
+pub fn chapter_number(chapter: &ChapterInfo) -> Option<f64> {
+    chapter.chapter.as_deref().and_then(parse_number)
+}
+
+pub fn volume_number(chapter: &ChapterInfo) -> Option<f64> {
+    chapter.volume.as_deref().and_then(parse_number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(id: &str, chapter: &str, language: &str, scanlator: &str) -> Arc<ChapterInfo> {
+        Arc::new(ChapterInfo {
+            id: id.to_string(),
+            chapter: Some(chapter.to_string()),
+            volume: Some("1".to_string()),
+            language: language.to_string(),
+            scanlator: vec![scanlator.to_string()],
+            ..Default::default()
+        })
+    }
+
+    fn ids(chapters: Vec<Arc<ChapterInfo>>) -> Vec<String> {
+        chapters.iter().map(|it| it.id.clone()).collect()
+    }
+
+    #[test]
+    fn parse_number_test() {
+        assert_eq!(parse_number("10"), Some(10.0));
+        assert_eq!(parse_number(" 10.5 extra"), Some(10.5));
+        assert_eq!(parse_number("3."), Some(3.0));
+        assert_eq!(parse_number("extra"), None);
+        assert_eq!(parse_number(""), None);
+    }
+
+    #[test]
+    fn range_test() {
+        let chapters = vec![
+            chapter("1", "1", "en", "a"),
+            chapter("2", "2", "en", "a"),
+            chapter("2.5", "2.5", "en", "a"),
+            chapter("3", "3", "en", "a"),
+            Arc::new(ChapterInfo {
+                id: "oneshot".to_string(),
+                ..Default::default()
+            }),
+        ];
+
+        let it = ChapterSelector::new().range(ChapterRange::chapter(2.0, 2.5));
+        assert_eq!(ids(it.select(&chapters)), ["2", "2.5"]);
+
+        let it = ChapterSelector::new()
+            .range(ChapterRange::chapter(None, 1.0))
+            .range(ChapterRange::chapter(3.0, None));
+        assert_eq!(ids(it.select(&chapters)), ["1", "3"]);
+
+        let it = ChapterSelector::new().range(ChapterRange::volume(1.0, 1.0));
+        assert_eq!(ids(it.select(&chapters)), ["1", "2", "2.5", "3"]);
+
+        assert_eq!(ChapterSelector::new().select(&chapters).len(), 5);
+    }
+
+    #[test]
+    fn filter_test() {
+        let chapters = vec![
+            chapter("1", "1", "en", "a"),
+            chapter("2", "1", "id", "a"),
+            chapter("3", "2", "EN", "a"),
+        ];
+
+        let it = ChapterSelector::new().language("en");
+        assert_eq!(ids(it.select(&chapters)), ["1", "3"]);
+
+        let it = ChapterSelector::new().exclude(["1", "2"]);
+        assert_eq!(ids(it.select(&chapters)), ["3"]);
+    }
+
+    #[test]
+    fn dedup_test() {
+        let chapters = vec![
+            chapter("1a", "1", "en", "a"),
+            chapter("1b", "1", "en", "b"),
+            chapter("2a", "2", "en", "a"),
+            chapter("2c", "2.0", "en", "c"),
+            chapter("3c", "3", "en", "c"),
+            chapter("1id", "1", "id", "a"),
+        ];
+
+        let it = ChapterSelector::new().dedup(true);
+        assert_eq!(ids(it.select(&chapters)), ["1a", "2a", "3c", "1id"]);
+
+        let it = ChapterSelector::new()
+            .dedup(true)
+            .prefer_scanlator("b")
+            .prefer_scanlator("c");
+        assert_eq!(ids(it.select(&chapters)), ["1b", "2c", "3c", "1id"]);
+
+        // preference doesn't drop chapters when not deduplicating.
+        let it = ChapterSelector::new().prefer_scanlator("b");
+        assert_eq!(it.select(&chapters).len(), chapters.len());
+    }
+
+    #[test]
+    fn not_downloaded_test() {
+        let mut module = mado_core::MockMadoModule::new();
+        let uuid = Uuid::from_u128(1);
+        module.expect_uuid().return_const(uuid);
+        module
+            .expect_domain()
+            .return_const(Url::parse("http://localhost").unwrap());
+
+        let url = Url::parse("http://localhost/manga").unwrap();
+        let chapters = vec![chapter("1", "1", "en", "a"), chapter("2", "2", "en", "a")];
+
+        let state = MadoEngineState::default();
+        state.download_request(crate::DownloadRequest::new(
+            Arc::new(module),
+            Default::default(),
+            vec![chapters[0].clone()],
+            Default::default(),
+            Some(url.clone()),
+            crate::DownloadRequestStatus::Pause,
+        ));
+
+        let it = ChapterSelector::new().not_downloaded(&state, &uuid, &url);
+        assert_eq!(ids(it.select(&chapters)), ["2"]);
+
+        let other = Url::parse("http://localhost/other").unwrap();
+        let it = ChapterSelector::new().not_downloaded(&state, &uuid, &other);
+        assert_eq!(it.select(&chapters).len(), 2);
+    }
+}
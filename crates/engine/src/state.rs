@@ -1,11 +1,19 @@
-use std::{ops::Index, sync::Arc};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    ops::Index,
+    sync::Arc,
+};
 
 use mado_core::{
-    ArcMadoModule, ArcMadoModuleMap, DefaultMadoModuleMap, MutMadoModuleMap, MutexMadoModuleMap,
+    ArcMadoModule, ArcMadoModuleMap, DefaultMadoModuleMap, MadoModuleMap, MutMadoModuleMap,
+    MutexMadoModuleMap, Uuid,
 };
 use parking_lot::{RwLock, RwLockReadGuard};
 
-use crate::{DownloadInfo, DownloadOption, DownloadRequest, Observers};
+use crate::{
+    path::Utf8Path, DownloadId, DownloadInfo, DownloadOption, DownloadRequest, LateBindingModule,
+    ModuleFile, Observers,
+};
 
 #[derive(Default, Debug)]
 pub struct DownloadTaskList {
@@ -19,6 +27,16 @@ impl DownloadTaskList {
         self.tasks.push(value);
     }
 
+    pub fn get(&self, id: DownloadId) -> Option<&Arc<DownloadInfo>> {
+        self.tasks.iter().find(|it| it.id() == id)
+    }
+
+    pub fn remove(&mut self, id: DownloadId) -> Option<Arc<DownloadInfo>> {
+        let index = self.tasks.iter().position(|it| it.id() == id)?;
+
+        Some(self.tasks.remove(index))
+    }
+
     pub fn iter(&self) -> std::slice::Iter<Arc<DownloadInfo>> {
         self.tasks.iter()
     }
@@ -44,6 +62,9 @@ impl Index<usize> for DownloadTaskList {
 pub struct MadoEngineState {
     modules: Arc<MutexMadoModuleMap<DefaultMadoModuleMap>>,
     tasks: RwLock<DownloadTaskList>,
+    /// Disabled uuids, with the module once it's pushed.
+    disabled: RwLock<HashMap<Uuid, Option<ArcMadoModule>>>,
+    module_files: RwLock<Vec<ModuleFile>>,
     observers: Observers<BoxObserver>,
     option: DownloadOption,
 }
@@ -58,7 +79,11 @@ pub type BoxObserver = Box<dyn FnMut(MadoEngineStateMsg<'_>) + Send>;
 
 pub enum MadoEngineStateMsg<'a> {
     Download(&'a Arc<DownloadInfo>),
+    RemoveDownload(&'a Arc<DownloadInfo>),
     PushModule(&'a ArcMadoModule),
+    RemoveModule(&'a ArcMadoModule),
+    /// A module file started loading or its status changed.
+    ModuleFile(&'a ModuleFile),
 }
 
 impl MadoEngineState {
@@ -72,6 +97,8 @@ impl MadoEngineState {
         Self {
             modules,
             tasks,
+            disabled: Default::default(),
+            module_files: Default::default(),
             observers: Default::default(),
             option,
         }
@@ -79,22 +106,131 @@ impl MadoEngineState {
     pub fn modules(&self) -> ArcMadoModuleMap {
         self.modules.clone()
     }
+    /// Modules currently in the map.
+    pub fn module_list(&self) -> Vec<ArcMadoModule> {
+        self.modules.lock().unwrap().vec().to_vec()
+    }
+
+    /// Push module to the map, disabled modules are kept aside until
+    /// [`Self::enable_module`] is called.
     pub fn push_module(&self, module: ArcMadoModule) -> Result<(), mado_core::MadoModuleMapError> {
+        if let Some(slot) = self.disabled.write().get_mut(&module.uuid()) {
+            *slot = Some(module);
+            return Ok(());
+        }
+
         self.modules.push_mut(module.clone())?;
         self.observers
             .emit(move |it| it(MadoEngineStateMsg::PushModule(&module)));
         Ok(())
     }
 
+    /// Remove module from the map, downloads waiting on it through
+    /// [`LateBindingModule`] wait until it's pushed again.
+    pub fn remove_module(&self, uuid: Uuid) -> Option<ArcMadoModule> {
+        let module = self.modules.remove_mut(uuid)?;
+        self.observers
+            .emit(|it| it(MadoEngineStateMsg::RemoveModule(&module)));
+
+        Some(module)
+    }
+
+    /// Remove module from the map and keep it out until enabled, including
+    /// when it's pushed again. Returns `false` if it's already disabled.
+    pub fn disable_module(&self, uuid: Uuid) -> bool {
+        match self.disabled.write().entry(uuid) {
+            Entry::Occupied(_) => return false,
+            Entry::Vacant(it) => {
+                it.insert(None);
+            }
+        }
+
+        // removed outside the lock, observers may call back into the state.
+        if let Some(module) = self.remove_module(uuid) {
+            if let Some(slot) = self.disabled.write().get_mut(&uuid) {
+                *slot = Some(module);
+            }
+        }
+
+        true
+    }
+
+    /// Push the module back if it was loaded while disabled. Returns `false`
+    /// if it wasn't disabled.
+    pub fn enable_module(&self, uuid: Uuid) -> Result<bool, mado_core::MadoModuleMapError> {
+        let module = match self.disabled.write().remove(&uuid) {
+            Some(module) => module,
+            None => return Ok(false),
+        };
+
+        if let Some(module) = module {
+            self.push_module(module)?;
+        }
+
+        Ok(true)
+    }
+
+    pub fn is_module_disabled(&self, uuid: Uuid) -> bool {
+        self.disabled.read().contains_key(&uuid)
+    }
+
+    /// Disabled module with `uuid`, if it was loaded.
+    pub fn disabled_module(&self, uuid: Uuid) -> Option<ArcMadoModule> {
+        self.disabled.read().get(&uuid).cloned().flatten()
+    }
+
+    /// Remove module from the map or from the disabled modules, keeping it
+    /// disabled.
+    pub(crate) fn take_module(&self, uuid: Uuid) -> Option<ArcMadoModule> {
+        let disabled = self.disabled.write().get_mut(&uuid).and_then(Option::take);
+
+        disabled.or_else(|| self.remove_module(uuid))
+    }
+
+    pub fn module_files(&self) -> Vec<ModuleFile> {
+        self.module_files.read().clone()
+    }
+
+    pub fn module_file(&self, path: &Utf8Path) -> Option<ModuleFile> {
+        self.module_files
+            .read()
+            .iter()
+            .find(|it| it.path() == path)
+            .cloned()
+    }
+
+    /// Add or replace the file with the same path.
+    pub(crate) fn set_module_file(&self, file: ModuleFile) {
+        {
+            let mut files = self.module_files.write();
+            match files.iter_mut().find(|it| it.path() == file.path()) {
+                Some(it) => *it = file.clone(),
+                None => files.push(file.clone()),
+            }
+        }
+
+        self.observers
+            .emit(move |it| it(MadoEngineStateMsg::ModuleFile(&file)));
+    }
+
     pub fn option(&self) -> DownloadOption {
         self.option.clone()
     }
 
     pub fn download_request(&self, request: DownloadRequest) {
+        // bind through the map so the download follows the module if it is
+        // replaced, e.g. after its runtime restarts.
+        let uuid = request.module().uuid();
+        let module = match self.modules.get_by_uuid(uuid) {
+            Some(_) => LateBindingModule::WaitModule(self.modules(), uuid),
+            None => LateBindingModule::Module(request.module().clone()),
+        };
+
         let mut tasks = self.tasks.write();
-        let info = Arc::new(DownloadInfo::from_request(
+        let info = Arc::new(DownloadInfo::from_request_with_module(
             tasks.max_order + 1,
             request,
+            module,
             self.option(),
         ));
         tasks.push(info.clone());
@@ -103,6 +239,56 @@ impl MadoEngineState {
             .emit(move |it| it(MadoEngineStateMsg::Download(&info)));
     }
 
+    /// Remove download with `id`, stopping it if it's running.
+    ///
+    /// With `delete_files`, directories of its chapters are deleted too, and
+    /// the manga directory if nothing else is left in it. Files are deleted
+    /// only after the running download has stopped writing them.
+    pub async fn remove_download(
+        &self,
+        id: DownloadId,
+        delete_files: bool,
+    ) -> std::io::Result<Option<Arc<DownloadInfo>>> {
+        let info = match self.tasks.write().remove(id) {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+
+        info.mark_removed();
+        self.observers
+            .emit(|it| it(MadoEngineStateMsg::RemoveDownload(&info)));
+
+        if delete_files {
+            info.wait_stopped().await;
+
+            for chapter in info.chapters() {
+                // never take the manga directory, and other downloads in it,
+                // along with a chapter.
+                if chapter.path() == info.path() {
+                    continue;
+                }
+
+                match tokio::fs::remove_dir_all(chapter.path()).await {
+                    Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+
+            // other downloads of the same manga may share the directory.
+            let shared = self.tasks().iter().any(|it| it.path() == info.path());
+            if !shared {
+                let cover = info.path().join(crate::COVER_FILENAME);
+                match tokio::fs::remove_file(cover).await {
+                    Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+                tokio::fs::remove_dir(info.path()).await.ok();
+            }
+        }
+
+        Ok(Some(info))
+    }
+
     /// Connect observer to state.
     ///
     /// This will also call on_* of previously pushed item.
@@ -115,6 +301,10 @@ impl MadoEngineState {
             observer(MadoEngineStateMsg::PushModule(it));
         }
 
+        for it in self.module_files() {
+            observer(MadoEngineStateMsg::ModuleFile(&it));
+        }
+
         self.connect_only(observer)
     }
 
@@ -177,6 +367,9 @@ mod tests {
             .connect(move |msg| {
                 match msg {
                     MadoEngineStateMsg::Download(_) => unreachable!(),
+                    MadoEngineStateMsg::RemoveDownload(_) => unreachable!(),
+                    MadoEngineStateMsg::RemoveModule(_) => unreachable!(),
+                    MadoEngineStateMsg::ModuleFile(_) => unreachable!(),
                     MadoEngineStateMsg::PushModule(_) => it.handle_msg(msg),
                 };
             })
@@ -213,5 +406,97 @@ mod tests {
                 it.handle_msg(msg);
             })
             .disconnect();
+
+        let mut it = MockCall::new();
+        it.expect_handle_msg()
+            .times(1)
+            .withf(|it| matches!(it, MadoEngineStateMsg::RemoveModule(_)))
+            .return_const(());
+
+        state.connect_only(move |msg| it.handle_msg(msg));
+        assert_eq!(state.remove_module(uuid).unwrap().uuid(), uuid);
+        assert!(state.remove_module(uuid).is_none());
+        assert!(state.modules().get_by_uuid(uuid).is_none());
+    }
+
+    #[test]
+    fn remove_download_test() {
+        let state = MadoEngineState::default();
+
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = crate::path::Utf8PathBuf::from_path_buf(temp.path().join("manga")).unwrap();
+
+        state.download_request(DownloadRequest::new(
+            Arc::new(module),
+            Default::default(),
+            vec![Default::default()],
+            path.clone(),
+            None,
+            DownloadRequestStatus::Pause,
+        ));
+
+        let info = state.tasks()[0].clone();
+        let chapter = info.chapters()[0].path().to_owned();
+        std::fs::create_dir_all(&chapter).unwrap();
+        std::fs::write(chapter.join("0001.png"), "image").unwrap();
+        std::fs::write(path.join(crate::COVER_FILENAME), "cover").unwrap();
+
+        let mut it = MockCall::new();
+        it.expect_handle_msg()
+            .times(1)
+            .withf(|it| matches!(it, MadoEngineStateMsg::RemoveDownload(_)))
+            .return_const(());
+        let handle = state.connect_only(move |msg| it.handle_msg(msg));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let removed = state.remove_download(info.id(), true).await;
+            assert!(removed.unwrap().unwrap().is_removed());
+            assert!(state.tasks().is_empty());
+            assert!(!path.exists());
+
+            let removed = state.remove_download(info.id(), true).await;
+            assert!(removed.unwrap().is_none());
+        });
+        handle.disconnect();
+    }
+
+    #[test]
+    fn disable_module_test() {
+        let state = MadoEngineState::default();
+        let uuid = Uuid::from_u128(1);
+        let module = || {
+            let mut module = MockMadoModule::new();
+            module.expect_uuid().return_const(uuid);
+            module
+                .expect_domain()
+                .return_const(mado_core::Url::parse("http://localhost").unwrap());
+            Arc::new(module)
+        };
+
+        assert!(state.disable_module(uuid));
+        assert!(!state.disable_module(uuid));
+
+        // modules pushed while disabled are kept aside.
+        state.push_module(module()).unwrap();
+        assert!(state.modules().get_by_uuid(uuid).is_none());
+        assert!(state.disabled_module(uuid).is_some());
+
+        assert!(state.enable_module(uuid).unwrap());
+        assert!(!state.enable_module(uuid).unwrap());
+        assert!(!state.is_module_disabled(uuid));
+        assert!(state.modules().get_by_uuid(uuid).is_some());
+
+        assert!(state.disable_module(uuid));
+        assert!(state.modules().get_by_uuid(uuid).is_none());
+        assert!(state.take_module(uuid).is_some());
+        assert!(state.disabled_module(uuid).is_none());
+        assert!(state.is_module_disabled(uuid));
     }
 }
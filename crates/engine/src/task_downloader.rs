@@ -1,14 +1,14 @@
-use std::{
-    io::Write,
-    sync::{
-        atomic::{AtomicU64, AtomicUsize},
-        Arc,
-    },
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize},
+    Arc,
 };
 
 use futures::{channel::mpsc, FutureExt, SinkExt, StreamExt};
 
-use crate::{DownloadChapterImageInfo, DownloadChapterInfo, DownloadStatus};
+use crate::{
+    core::ClassifiedError, fetch_cover, path::Utf8PathBuf, CancellationToken,
+    DownloadChapterImageInfo, DownloadChapterInfo, DownloadFailure, DownloadStatus,
+};
 
 pub use super::*;
 
@@ -16,11 +16,23 @@ pub use super::*;
 pub struct TaskDownloader {
     info: Arc<crate::DownloadInfo>,
     option: DownloadOption,
+    cancel: CancellationToken,
 }
 
 impl TaskDownloader {
     pub fn new(info: Arc<crate::DownloadInfo>, option: DownloadOption) -> Self {
-        Self { info, option }
+        Self {
+            info,
+            option,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Stop downloading with [`core::Error::Cancelled`] once `cancel` is
+    /// cancelled, already downloaded images are kept.
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
     }
 
     pub async fn run(self) -> Result<(), core::Error> {
@@ -28,22 +40,80 @@ impl TaskDownloader {
     }
 
     pub async fn download(&self) -> Result<(), mado_core::Error> {
+        // removal waits for this before deleting files.
+        let _running = self.info.start_running();
+        if self.info.is_removed() {
+            return Ok(());
+        }
+
         let _ = self.info.wait_module().await;
         self.info.set_status(DownloadStatus::downloading());
+        self.save_cover().await;
+
+        // a broken chapter shouldn't stop the rest from downloading.
+        let mut failed = 0;
         for it in self.info.chapters() {
-            self.download_chapter(it.clone()).await?;
+            if self.info.is_removed() {
+                return Ok(());
+            }
+            self.cancel.check()?;
+
+            if let Err(err) = self.download_chapter(it.clone()).await {
+                // interrupted chapters are resumed later, they didn't fail.
+                self.cancel.check()?;
+
+                tracing::error!("error downloading chapter {}: {}", it.chapter_id(), err);
+                self.info
+                    .log_failure(DownloadFailure::new(err.clone()).chapter(it.chapter_id()));
+                it.set_status(DownloadStatus::error(err));
+                failed += 1;
+            }
+        }
+
+        if failed == 0 {
+            self.info.set_status(DownloadStatus::Finished);
+        } else {
+            self.info
+                .set_status(DownloadStatus::finished_with_errors(failed));
         }
-        self.info.set_status(DownloadStatus::Finished);
         Ok(())
     }
 
+    /// Save the manga's cover in the download directory if it's not
+    /// there yet. Failing to get it doesn't fail the download.
+    async fn save_cover(&self) {
+        let url = match self.info.cover_link() {
+            Some(url) => url,
+            None => return,
+        };
+
+        let path = self.info.path().join(crate::COVER_FILENAME);
+        if self.info.is_removed() || crate::cover::exists(&path).await {
+            return;
+        }
+
+        let module = self.info.wait_module().await;
+        let fetch = crate::timer::timeout(self.option.timeout(), fetch_cover(&module, url));
+        let result = match fetch.await {
+            Ok(Ok(data)) => crate::cover::write_file(&path, &data)
+                .await
+                .map_err(Into::into),
+            Ok(Err(err)) => Err(err),
+            Err(elapsed) => Err(mado_core::Error::ExternalError(elapsed.into())),
+        };
+
+        if let Err(err) = result {
+            tracing::error!("error saving cover of {}: {}", self.info.manga(), err);
+        }
+    }
+
     #[tracing::instrument(
         skip_all,
         fields(
             chapter = %it.chapter_id()
         )
     )]
-    async fn download_chapter(&self, it: Arc<DownloadChapterInfo>) -> Result<(), mado_core::Error> {
+    async fn download_chapter(&self, it: Arc<DownloadChapterInfo>) -> Result<(), ClassifiedError> {
         if it.status().is_finished() {
             return Ok(());
         }
@@ -66,15 +136,43 @@ impl TaskDownloader {
             })
             .forward(image_tx.sink_map_err(|err| mado_core::Error::ExternalError(err.into())));
 
+        let chapter_id = it.chapter_id().to_string();
         let fut = async move {
+            let mut total = 0;
+            let mut failed = Vec::new();
+
             while let Some(image) = image_rx.next().await {
-                let image = image;
-                self.download_image(image.clone()).await?;
+                total += 1;
+
+                if let Err(err) = self.download_image(image.clone()).await {
+                    if err.is_cancelled() {
+                        return Err(err);
+                    }
+
+                    tracing::error!("error downloading image {}: {}", image.image().id, err);
+                    self.info.log_failure(
+                        DownloadFailure::new(&err)
+                            .chapter(&chapter_id)
+                            .image(&image.image().id),
+                    );
+                    image.set_status(DownloadStatus::error(&err));
+                    failed.push(err);
+                }
             }
-            Ok::<_, mado_core::Error>(())
+            Ok::<_, mado_core::Error>((total, failed))
         };
 
-        let _ = futures::future::try_join(get_images, fut).await?;
+        let (_, (total, failed)) = self
+            .cancel
+            .run(futures::future::try_join(get_images, fut))
+            .await?;
+
+        if let Some(first) = failed.first() {
+            return Err(ClassifiedError::new(
+                first.kind(),
+                format!("{} of {} images failed: {}", failed.len(), total, first),
+            ));
+        }
 
         it.set_status(DownloadStatus::Finished);
 
@@ -103,8 +201,7 @@ impl TaskDownloader {
 
         let mut stream = image_rx.enumerate().map(move |(i, image)| {
             let i = i + 1;
-            let filename = format!("{:0>4}.{}", i, image.extension);
-            let path = it.path().join(option.sanitize_filename(&filename));
+            let path = it.path().join(option.image_filename(i, &image.extension));
 
             let image = DownloadChapterImageInfo::new(image, path, it.status().clone());
             Ok(Arc::new(image))
@@ -130,6 +227,8 @@ impl TaskDownloader {
         &self,
         download: Arc<DownloadChapterImageInfo>,
     ) -> Result<(), mado_core::Error> {
+        self.cancel.check()?;
+
         let module = self.info.wait_module().await;
         self.info
             .set_status(DownloadStatus::resumed(DownloadResumedStatus::Downloading));
@@ -138,27 +237,36 @@ impl TaskDownloader {
         let image = download.image();
         let exists = path.exists();
 
-        const RETRY_LIMIT: usize = 10;
-        const TIMEOUT: u64 = 10;
-        let retry = Arc::new(RETRY_LIMIT.into());
-        let timeout = Arc::new(TIMEOUT.into());
+        let retry = self.option.shared_retry_limit();
+        let timeout = self.option.shared_timeout();
 
         if !exists {
-            let task = ImageDownloader::new(module.clone(), image.clone(), Config(retry, timeout));
+            // only complete images get the final name, an interrupted one
+            // is continued from its part file instead of being skipped.
+            let part = Utf8PathBuf::from(format!("{}.part", path));
+            let config = Config {
+                retry,
+                timeout,
+                part: part.clone(),
+            };
+            let task = ImageDownloader::new(module.clone(), image.clone(), config)
+                .with_cancel(self.cancel.clone());
 
             tracing::trace!("Start downloading {}", path);
 
-            let buffer = task.download().await?;
+            // the file is closed before it's renamed.
+            drop(task.download().await?);
 
             tracing::trace!("Finished downloading {}", path);
 
-            if let Some(parent) = path.parent() {
-                std::fs::create_dir_all(parent)?;
+            // don't leave files of a download that was just deleted.
+            if self.info.is_removed() {
+                std::fs::remove_file(&part).ok();
+                return Ok(());
             }
+            self.cancel.check()?;
 
-            let mut file = std::fs::File::create(path)?;
-
-            file.write_all(&buffer)?;
+            std::fs::rename(&part, path)?;
             tracing::trace!("Finished writing to {}", path);
         } else {
             tracing::trace!("File {} already exists, skipping...", path);
@@ -168,20 +276,41 @@ impl TaskDownloader {
     }
 }
 
-struct Config(Arc<AtomicUsize>, Arc<AtomicU64>);
+struct Config {
+    retry: Arc<AtomicUsize>,
+    timeout: Arc<AtomicU64>,
+    /// File the image is written to until it's complete.
+    part: Utf8PathBuf,
+}
+
 impl crate::ImageDownloaderConfig for Config {
-    type Buffer = Vec<u8>;
+    type Buffer = futures::io::AllowStdIo<std::fs::File>;
 
     fn should_retry(&self, retry_count: usize) -> bool {
-        retry_count < self.0.load(atomic::Ordering::Relaxed)
+        retry_count < self.retry.load(atomic::Ordering::Relaxed)
+    }
+
+    fn retry_delay(&self, retry_count: usize) -> std::time::Duration {
+        // 1s, 2s, 4s, ... capped at a minute.
+        let exponent = retry_count.saturating_sub(1).min(6) as u32;
+        std::time::Duration::from_secs(2u64.pow(exponent)).min(std::time::Duration::from_secs(60))
     }
 
     fn timeout(&self) -> std::time::Duration {
-        std::time::Duration::from_secs(self.1.load(atomic::Ordering::Relaxed))
+        std::time::Duration::from_secs(self.timeout.load(atomic::Ordering::Relaxed))
     }
 
-    fn buffer(&self) -> Self::Buffer {
-        Vec::new()
+    fn buffer(&self) -> std::io::Result<Self::Buffer> {
+        if let Some(parent) = self.part.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.part)?;
+
+        Ok(futures::io::AllowStdIo::new(file))
     }
 }
 
@@ -216,7 +345,8 @@ mod tests {
     use mockall::predicate::{always, eq};
 
     use crate::{
-        tests::server_url, DownloadChapterInfo, DownloadInfo, DownloadStatus, TaskDownloader,
+        tests::server_url, CancellationToken, DownloadChapterInfo, DownloadInfo, DownloadStatus,
+        TaskDownloader,
     };
 
     // TODO: improve this test to not use fs
@@ -304,6 +434,243 @@ mod tests {
         temp.close().unwrap();
     }
 
+    #[test]
+    fn resume_part_test() {
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+
+        let image = ChapterImageInfo {
+            id: "1".to_string(),
+            extension: "png".to_string(),
+            name: None,
+        };
+        module
+            .expect_get_chapter_images()
+            .returning(move |_, mut a| {
+                a.add(image.clone());
+                Ok(())
+            });
+
+        let mock = httpmock::MockServer::start();
+        let h = mock.mock(|when, then| {
+            when.path("/test").header("Range", "bytes=4-");
+            then.status(206).body("rest");
+        });
+
+        let client = mado_core::http::Client::default();
+        let url = server_url(h.server_address()).join("/test").unwrap();
+        module
+            .expect_download_image()
+            .returning(move |_| Ok(client.get(url.clone()).into()));
+
+        let module = Arc::new(module);
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::from_path_buf(temp.path().to_path_buf()).unwrap();
+        std::fs::create_dir_all(path.join("1")).unwrap();
+        // left by a paused download.
+        std::fs::write(path.join("1").join("0001.png.part"), "test").unwrap();
+
+        let chapter = Arc::new(DownloadChapterInfo::new(
+            module.clone().into(),
+            "1".to_string(),
+            "title".to_string(),
+            path.join("1"),
+            DownloadStatus::waiting(),
+        ));
+        let info = Arc::new(
+            DownloadInfo::builder()
+                .order(0)
+                .module(module)
+                .chapters(vec![chapter])
+                .path(path.clone())
+                .status(DownloadStatus::waiting())
+                .build(),
+        );
+
+        futures::executor::block_on(TaskDownloader::new(info, Default::default()).download())
+            .unwrap();
+
+        h.assert_hits(1);
+        assert_eq!(
+            std::fs::read_to_string(path.join("1").join("0001.png")).unwrap(),
+            "testrest"
+        );
+        assert!(!path.join("1").join("0001.png.part").exists());
+    }
+
+    #[test]
+    fn save_cover_test() {
+        let server = httpmock::MockServer::start();
+        let cover = server.mock(|when, then| {
+            when.path("/cover.jpg").method(GET);
+            then.body("cover");
+        });
+
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+        module
+            .expect_domain()
+            .return_const(server_url(server.address()));
+        module
+            .expect_client()
+            .return_const(mado_core::Client::from(mado_core::http::Client::default()));
+        let module = Arc::new(module);
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::from_path_buf(temp.path().join("manga")).unwrap();
+        let info = Arc::new(
+            DownloadInfo::builder()
+                .order(0)
+                .module(module)
+                .cover_link(Some("/cover.jpg".to_string()))
+                .path(path.clone())
+                .status(DownloadStatus::waiting())
+                .build(),
+        );
+
+        // covers are written with tokio::fs.
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let downloader = TaskDownloader::new(info.clone(), Default::default());
+            downloader.download().await.unwrap();
+            downloader.download().await.unwrap();
+        });
+
+        let saved = std::fs::read_to_string(path.join(crate::COVER_FILENAME)).unwrap();
+        assert_eq!(saved, "cover");
+        cover.assert_hits(1);
+        assert_eq!(*info.status(), DownloadStatus::finished());
+    }
+
+    #[test]
+    fn failed_chapter_test() {
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+
+        let image = ChapterImageInfo {
+            id: "1".to_string(),
+            extension: "png".to_string(),
+            name: None,
+        };
+
+        module
+            .expect_get_chapter_images()
+            .with(eq("1"), always())
+            .returning(|_, _| {
+                Err(mado_core::Error::NotFound {
+                    url: "1".to_string(),
+                    message: "removed".to_string(),
+                })
+            });
+
+        let i2 = image.clone();
+        module
+            .expect_get_chapter_images()
+            .with(eq("2"), always())
+            .returning(move |_, mut a| {
+                a.add(image.clone());
+                Ok(())
+            });
+
+        let mock = httpmock::MockServer::start();
+        let h = mock.mock(|when, then| {
+            when.path("/test").method(GET);
+            then.body("test");
+        });
+
+        let client = mado_core::http::Client::default();
+        let url = server_url(h.server_address()).join("/test").unwrap();
+
+        module
+            .expect_download_image()
+            .with(eq(i2))
+            .returning(move |_| Ok(client.get(url.clone()).into()));
+
+        let module = Arc::new(module);
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::from_path_buf(temp.path().to_path_buf()).unwrap();
+
+        let chapter = |id: &str| {
+            Arc::new(DownloadChapterInfo::new(
+                module.clone().into(),
+                id.to_string(),
+                id.to_string(),
+                path.join(id),
+                DownloadStatus::waiting(),
+            ))
+        };
+        let chapters = vec![chapter("1"), chapter("2")];
+
+        let info = Arc::new(
+            DownloadInfo::builder()
+                .order(0)
+                .module(module.clone())
+                .chapters(chapters.clone())
+                .path(path.clone())
+                .status(DownloadStatus::waiting())
+                .build(),
+        );
+
+        futures::executor::block_on(async {
+            TaskDownloader::new(info.clone(), Default::default())
+                .download()
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(*info.status(), DownloadStatus::finished_with_errors(1));
+        assert_eq!(
+            chapters[0].status().error_kind(),
+            Some(&mado_core::ErrorKind::NotFound)
+        );
+        assert_eq!(*chapters[1].status(), DownloadStatus::finished());
+        assert_eq!(info.failed_chapters().len(), 1);
+
+        temp.close().unwrap();
+    }
+
+    #[test]
+    fn cancelled_test() {
+        let mut module = MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+        module.expect_get_chapter_images().never();
+
+        let module = Arc::new(module);
+        let chapter = Arc::new(DownloadChapterInfo::new(
+            module.clone().into(),
+            "1".to_string(),
+            "title".to_string(),
+            Default::default(),
+            DownloadStatus::waiting(),
+        ));
+        let info = Arc::new(
+            DownloadInfo::builder()
+                .order(0)
+                .module(module)
+                .chapters(vec![chapter.clone()])
+                .status(DownloadStatus::waiting())
+                .build(),
+        );
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let error = futures::executor::block_on(
+            TaskDownloader::new(info.clone(), Default::default())
+                .with_cancel(cancel)
+                .download(),
+        )
+        .unwrap_err();
+
+        assert!(error.is_cancelled());
+        assert_eq!(*chapter.status(), DownloadStatus::waiting());
+        assert!(info.failures().is_empty());
+    }
+
     #[test]
     fn get_chapter_test() {
         let mut module = MockMadoModule::new();
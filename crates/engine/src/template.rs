@@ -0,0 +1,512 @@
+use std::fmt::Display;
+
+use crate::{core::ChapterInfo, path::Utf8PathBuf};
+
+/// Placeholder of a [`PathTemplate`], written as `{name}` or `{name:04}` to
+/// zero-pad its leading number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateKey {
+    Module,
+    Manga,
+    Volume,
+    Chapter,
+    Title,
+    Scanlator,
+    Lang,
+    /// Full chapter name, e.g. `0001. Vol. 1 Chapter 1 [en]`.
+    Name,
+    Index,
+    Page,
+}
+
+impl TemplateKey {
+    pub const ALL: [Self; 10] = [
+        Self::Module,
+        Self::Manga,
+        Self::Volume,
+        Self::Chapter,
+        Self::Title,
+        Self::Scanlator,
+        Self::Lang,
+        Self::Name,
+        Self::Index,
+        Self::Page,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Module => "module",
+            Self::Manga => "manga",
+            Self::Volume => "volume",
+            Self::Chapter => "chapter",
+            Self::Title => "title",
+            Self::Scanlator => "scanlator",
+            Self::Lang => "lang",
+            Self::Name => "name",
+            Self::Index => "index",
+            Self::Page => "page",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|it| it.as_str() == name)
+    }
+}
+
+/// What a [`PathTemplate`] is used for, which decides its placeholders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateKind {
+    /// Directory of a manga, relative to the download directory.
+    Manga,
+    /// Directory of a chapter, relative to the manga directory.
+    Chapter,
+    /// Filename of an image, without extension.
+    Image,
+}
+
+impl TemplateKind {
+    pub const ALL: [Self; 3] = [Self::Manga, Self::Chapter, Self::Image];
+
+    pub fn keys(&self) -> &'static [TemplateKey] {
+        use TemplateKey::*;
+
+        match self {
+            Self::Manga => &[Module, Manga],
+            Self::Chapter => &[
+                Module, Manga, Volume, Chapter, Title, Scanlator, Lang, Name, Index,
+            ],
+            Self::Image => &[Page],
+        }
+    }
+
+    /// Keys that tell apart items of this kind, a template must use at
+    /// least one so two of them can't render to the same path.
+    pub fn required(&self) -> &'static [TemplateKey] {
+        use TemplateKey::*;
+
+        match self {
+            Self::Manga => &[Manga],
+            Self::Chapter => &[Chapter, Title, Name, Index],
+            Self::Image => &[Page],
+        }
+    }
+
+    /// Key rendered instead when none of [`Self::required`] has a value.
+    fn fallback(&self) -> TemplateKey {
+        match self {
+            Self::Manga => TemplateKey::Manga,
+            Self::Chapter => TemplateKey::Name,
+            Self::Image => TemplateKey::Page,
+        }
+    }
+}
+
+impl Display for TemplateKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Manga => write!(f, "manga"),
+            Self::Chapter => write!(f, "chapter"),
+            Self::Image => write!(f, "image"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TemplateError {
+    #[error("template is empty")]
+    Empty,
+    #[error("unknown placeholder {{{0}}}")]
+    UnknownKey(String),
+    #[error("{{{}}} can't be used in {kind} templates", .key.as_str())]
+    UnavailableKey {
+        key: TemplateKey,
+        kind: TemplateKind,
+    },
+    #[error("invalid width in {{{0}}}, must be at most {MAX_WIDTH}")]
+    InvalidWidth(String),
+    #[error("{0} templates must use one of {}", key_list(.0.required()))]
+    MissingKey(TemplateKind),
+    #[error("unclosed {{")]
+    Unclosed,
+    #[error("unmatched }}, use }}}} for a literal one")]
+    Unmatched,
+    #[error("{0} templates can't contain /")]
+    Separator(TemplateKind),
+    #[error("template must stay inside its directory")]
+    OutsideDirectory,
+}
+
+/// Largest width of a `{name:N}` placeholder.
+pub const MAX_WIDTH: usize = 16;
+
+fn key_list(keys: &[TemplateKey]) -> String {
+    keys.iter()
+        .map(|it| format!("{{{}}}", it.as_str()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Key { key: TemplateKey, width: usize },
+}
+
+/// Values placeholders are replaced with, missing ones render as empty.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TemplateValues<'a> {
+    pub module: Option<&'a str>,
+    pub manga: Option<&'a str>,
+    pub chapter: Option<&'a ChapterInfo>,
+    pub page: Option<usize>,
+}
+
+impl TemplateValues<'_> {
+    fn get(&self, key: TemplateKey) -> Option<String> {
+        let chapter = self.chapter;
+
+        match key {
+            TemplateKey::Module => self.module.map(ToString::to_string),
+            TemplateKey::Manga => self.manga.map(ToString::to_string),
+            TemplateKey::Volume => chapter?.volume.clone(),
+            TemplateKey::Chapter => chapter?.chapter.clone(),
+            TemplateKey::Title => chapter?.title.clone(),
+            TemplateKey::Scanlator => Some(chapter?.scanlator.join(", ")),
+            TemplateKey::Lang => Some(chapter?.language.clone()),
+            TemplateKey::Name => Some(chapter?.to_string()),
+            TemplateKey::Index => chapter?.index.map(|it| it.to_string()),
+            TemplateKey::Page => self.page.map(|it| it.to_string()),
+        }
+    }
+}
+
+/// Pad the leading number of `value` with zeros to `width` digits.
+fn pad(value: &str, width: usize) -> String {
+    let digits = value
+        .find(|it: char| !it.is_ascii_digit())
+        .unwrap_or(value.len());
+
+    if digits == 0 || digits >= width {
+        return value.to_string();
+    }
+
+    format!("{}{}", "0".repeat(width - digits), value)
+}
+
+/// Path built from text and placeholders, e.g. `{manga}/Vol. {volume}`.
+///
+/// `/` separates directories, except in image templates. Components left
+/// empty by missing values are dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathTemplate {
+    source: String,
+    kind: TemplateKind,
+    segments: Vec<Segment>,
+}
+
+impl PathTemplate {
+    pub fn parse(kind: TemplateKind, source: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(it) = chars.next() {
+            match it {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(TemplateError::Unmatched),
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(it) => inner.push(it),
+                            None => return Err(TemplateError::Unclosed),
+                        }
+                    }
+
+                    let (name, width) = match inner.split_once(':') {
+                        Some((name, width)) => {
+                            let width = width
+                                .parse()
+                                .ok()
+                                .filter(|it| *it <= MAX_WIDTH)
+                                .ok_or_else(|| TemplateError::InvalidWidth(inner.clone()))?;
+                            (name, width)
+                        }
+                        None => (inner.as_str(), 0),
+                    };
+
+                    let key = TemplateKey::parse(name)
+                        .ok_or_else(|| TemplateError::UnknownKey(name.to_string()))?;
+                    if !kind.keys().contains(&key) {
+                        return Err(TemplateError::UnavailableKey { key, kind });
+                    }
+
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Key { key, width });
+                }
+                it => literal.push(it),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Self::validate(kind, &segments)?;
+
+        Ok(Self {
+            source: source.to_string(),
+            kind,
+            segments,
+        })
+    }
+
+    fn validate(kind: TemplateKind, segments: &[Segment]) -> Result<(), TemplateError> {
+        if segments.is_empty() {
+            return Err(TemplateError::Empty);
+        }
+
+        if matches!(&segments[0], Segment::Literal(it) if it.starts_with('/')) {
+            return Err(TemplateError::OutsideDirectory);
+        }
+
+        for it in segments {
+            if let Segment::Literal(literal) = it {
+                if kind == TemplateKind::Image && literal.contains('/') {
+                    return Err(TemplateError::Separator(kind));
+                }
+
+                if literal.split('/').any(|it| it.trim() == "..") {
+                    return Err(TemplateError::OutsideDirectory);
+                }
+            }
+        }
+
+        let identifies =
+            |it: &Segment| matches!(it, Segment::Key { key, .. } if kind.required().contains(key));
+        if !segments.iter().any(identifies) {
+            return Err(TemplateError::MissingKey(kind));
+        }
+
+        Ok(())
+    }
+
+    pub fn uses(&self, key: TemplateKey) -> bool {
+        self.segments
+            .iter()
+            .any(|it| matches!(it, Segment::Key { key: it, .. } if *it == key))
+    }
+
+    pub fn kind(&self) -> TemplateKind {
+        self.kind
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Render the template, every path component is passed through
+    /// `sanitize` so values can't add directories.
+    ///
+    /// When none of the [`TemplateKind::required`] keys has a value, the
+    /// path would be shared with other items, so the fallback key of the
+    /// kind (e.g. `{name}` for chapters) is rendered instead.
+    pub fn render(
+        &self,
+        values: &TemplateValues<'_>,
+        sanitize: impl Fn(&str) -> String,
+    ) -> Utf8PathBuf {
+        let mut rendered = String::new();
+        let mut identified = false;
+
+        for it in &self.segments {
+            match it {
+                Segment::Literal(literal) => rendered.push_str(literal),
+                Segment::Key { key, width } => {
+                    if let Some(value) = values.get(*key).filter(|it| !it.trim().is_empty()) {
+                        identified |= self.kind.required().contains(key);
+                        rendered.push_str(&sanitize(&pad(&value, *width)));
+                    }
+                }
+            }
+        }
+
+        if !identified {
+            rendered = values
+                .get(self.kind.fallback())
+                .map(|it| sanitize(&it))
+                .unwrap_or_default();
+        }
+
+        rendered
+            .split('/')
+            .map(str::trim)
+            .filter(|it| !it.is_empty())
+            .map(sanitize)
+            .collect()
+    }
+}
+
+impl Display for PathTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(kind: TemplateKind, source: &str, values: &TemplateValues) -> String {
+        PathTemplate::parse(kind, source)
+            .unwrap()
+            .render(values, |it| it.replace('/', "_"))
+            .to_string()
+    }
+
+    #[test]
+    fn parse_test() {
+        use TemplateKind::*;
+
+        let it = PathTemplate::parse(Chapter, "{manga}/{chapter:04} {title}").unwrap();
+        assert!(it.uses(TemplateKey::Chapter));
+        assert!(!it.uses(TemplateKey::Module));
+        assert_eq!(
+            PathTemplate::parse(Manga, "").unwrap_err(),
+            TemplateError::Empty
+        );
+        assert_eq!(
+            PathTemplate::parse(Manga, "{foo}").unwrap_err(),
+            TemplateError::UnknownKey("foo".to_string())
+        );
+        assert_eq!(
+            PathTemplate::parse(Manga, "{page}").unwrap_err(),
+            TemplateError::UnavailableKey {
+                key: TemplateKey::Page,
+                kind: Manga
+            }
+        );
+        assert_eq!(
+            PathTemplate::parse(Image, "{page:x}").unwrap_err(),
+            TemplateError::InvalidWidth("page:x".to_string())
+        );
+        assert_eq!(
+            PathTemplate::parse(Manga, "{manga").unwrap_err(),
+            TemplateError::Unclosed
+        );
+        assert_eq!(
+            PathTemplate::parse(Manga, "manga}").unwrap_err(),
+            TemplateError::Unmatched
+        );
+        assert_eq!(
+            PathTemplate::parse(Image, "a/{page}").unwrap_err(),
+            TemplateError::Separator(Image)
+        );
+        assert_eq!(
+            PathTemplate::parse(Manga, "../{manga}").unwrap_err(),
+            TemplateError::OutsideDirectory
+        );
+        assert_eq!(
+            PathTemplate::parse(Manga, "/{manga}").unwrap_err(),
+            TemplateError::OutsideDirectory
+        );
+        assert_eq!(
+            PathTemplate::parse(Image, "{page:100000000000}").unwrap_err(),
+            TemplateError::InvalidWidth("page:100000000000".to_string())
+        );
+        assert!(PathTemplate::parse(Image, "{page:16}").is_ok());
+        assert_eq!(
+            PathTemplate::parse(Image, "page").unwrap_err(),
+            TemplateError::MissingKey(Image)
+        );
+        assert_eq!(
+            PathTemplate::parse(Chapter, "{manga}/Vol. {volume}").unwrap_err(),
+            TemplateError::MissingKey(Chapter)
+        );
+        assert_eq!(
+            PathTemplate::parse(Manga, "{module}").unwrap_err(),
+            TemplateError::MissingKey(Manga)
+        );
+    }
+
+    #[test]
+    fn render_test() {
+        let chapter = ChapterInfo {
+            index: Some(3),
+            chapter: Some("5.5".to_string()),
+            volume: None,
+            title: Some("a/b".to_string()),
+            scanlator: vec!["x".to_string(), "y".to_string()],
+            language: "en".to_string(),
+            ..Default::default()
+        };
+        let values = TemplateValues {
+            module: Some("module"),
+            manga: Some("manga"),
+            chapter: Some(&chapter),
+            page: Some(7),
+        };
+
+        use TemplateKind::*;
+        assert_eq!(render(Manga, "{module}/{manga}", &values), "module/manga");
+        assert_eq!(
+            render(
+                Chapter,
+                "Vol. {volume}/{chapter:03} {title} [{scanlator}] {{{lang}}}",
+                &values
+            ),
+            "Vol./005.5 a_b [x, y] {en}"
+        );
+        assert_eq!(render(Chapter, "{volume}/{index:2}", &values), "03");
+        assert_eq!(
+            render(Chapter, "{name}", &values),
+            chapter.to_string().replace('/', "_")
+        );
+        assert_eq!(render(Image, "{page:04}", &values), "0007");
+        assert_eq!(render(Image, "{page:1}", &values), "7");
+    }
+
+    #[test]
+    fn render_fallback_test() {
+        let chapter = ChapterInfo {
+            index: None,
+            chapter: None,
+            volume: Some("1".to_string()),
+            title: Some(" ".to_string()),
+            language: "en".to_string(),
+            ..Default::default()
+        };
+        let values = TemplateValues {
+            manga: Some("manga"),
+            chapter: Some(&chapter),
+            ..Default::default()
+        };
+
+        use TemplateKind::*;
+        assert_eq!(
+            render(Chapter, "Vol. {volume}/{chapter} {title}", &values),
+            chapter.to_string().trim()
+        );
+        assert_eq!(render(Manga, "{module} {manga}", &values), "manga");
+        assert_eq!(render(Manga, "{manga}", &TemplateValues::default()), "");
+    }
+
+    #[test]
+    fn pad_test() {
+        assert_eq!(pad("5", 3), "005");
+        assert_eq!(pad("12.5", 4), "0012.5");
+        assert_eq!(pad("1234", 2), "1234");
+        assert_eq!(pad("extra", 4), "extra");
+        assert_eq!(pad("5", 0), "5");
+    }
+}
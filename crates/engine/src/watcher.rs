@@ -48,6 +48,16 @@ impl DownloadInfoWatcher {
         }
     }
 
+    pub async fn wait_removed(&self) {
+        loop {
+            if self.info.is_removed() {
+                return;
+            }
+
+            self.event.listen().await;
+        }
+    }
+
     pub async fn wait_order(&self, fun: impl Fn(usize) -> bool) {
         loop {
             if fun(self.info.order()) {
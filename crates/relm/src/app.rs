@@ -1,9 +1,17 @@
 use crate::{
+    config::ConfigFile,
     download::{DownloadModel, DownloadMsg, DownloadOutputMsg},
+    library::{LibraryModel, LibraryOutput},
     manga_info::{MangaInfoInit, MangaInfoModel, MangaInfoMsg, MangaInfoOutput},
+    modules::{ModulesInit, ModulesModel, ModulesOutput},
+    reader::{ReaderModel, ReaderMsg, ReaderOutput},
+    settings::{SettingsInit, SettingsModel, SettingsOutput},
 };
 use gtk::prelude::*;
-use mado::engine::{DownloadRequest, MadoEngineState, MadoEngineStateMsg};
+use mado::engine::{
+    CoverCache, DownloadId, DownloadInfo, DownloadRequest, MadoEngineState, MadoEngineStateMsg,
+    ModuleLoaderHandle,
+};
 use mado::{core::ArcMadoModule, engine::path::Utf8PathBuf};
 use relm4::{
     Component, ComponentController, ComponentParts, ComponentSender, Controller, SimpleComponent,
@@ -14,10 +22,22 @@ use std::sync::Arc;
 pub enum AppMsg {
     PushModule(ArcMadoModule),
     DownloadRequest(DownloadRequest),
+    RemoveDownloads {
+        ids: Vec<DownloadId>,
+        delete_files: bool,
+    },
     OpenManga {
         url: mado_core::Url,
         path: Utf8PathBuf,
     },
+    Read(Arc<DownloadInfo>),
+    /// Mark downloaded chapters with these ids as read or unread.
+    MarkRead {
+        module: mado_core::Uuid,
+        chapters: Vec<String>,
+        read: bool,
+    },
+    DownloadDirChanged(Utf8PathBuf),
     ChangeVisibleChild(String),
     Error(mado::core::Error),
 }
@@ -27,12 +47,24 @@ pub struct AppModel {
     pub(super) state: Arc<MadoEngineState>,
 
     downloads: Controller<DownloadModel>,
+    library: Controller<LibraryModel>,
     manga_info: Controller<MangaInfoModel>,
+    settings: Controller<SettingsModel>,
+    modules: Controller<ModulesModel>,
+    reader: Controller<ReaderModel>,
 
     visible_child: String,
     root: gtk::ApplicationWindow,
 }
 
+pub struct AppInit {
+    pub state: Arc<MadoEngineState>,
+    pub config: Arc<ConfigFile>,
+    pub loader: ModuleLoaderHandle,
+    /// Client given to modules, changed when the proxy setting changes.
+    pub http_client: mado::core::http::Client,
+}
+
 pub struct RelmMadoEngineStateObserver {
     sender: relm4::Sender<AppMsg>,
     download_sender: relm4::Sender<DownloadMsg>,
@@ -57,6 +89,17 @@ impl RelmMadoEngineStateObserver {
                 MadoEngineStateMsg::PushModule(module) => {
                     self.sender.send(AppMsg::PushModule(module.clone())).ok();
                 }
+                MadoEngineStateMsg::RemoveModule(module) => {
+                    tracing::trace!("Removing module uuid:{}", module.uuid());
+                }
+                // the view removes itself through the download's observer.
+                MadoEngineStateMsg::RemoveDownload(info) => {
+                    tracing::trace!("Removing download {:?}", info.id());
+                }
+                // shown by the modules page.
+                MadoEngineStateMsg::ModuleFile(file) => {
+                    tracing::trace!("Module file {} {:?}", file.path(), file.status());
+                }
             };
         });
     }
@@ -67,6 +110,15 @@ pub fn convert_manga_list(msg: MangaInfoOutput) -> AppMsg {
         crate::manga_info::MangaInfoOutput::DownloadRequest(request) => {
             AppMsg::DownloadRequest(request)
         }
+        crate::manga_info::MangaInfoOutput::MarkRead {
+            module,
+            chapters,
+            read,
+        } => AppMsg::MarkRead {
+            module,
+            chapters,
+            read,
+        },
         crate::manga_info::MangaInfoOutput::Error(err) => AppMsg::Error(err),
     }
 }
@@ -74,6 +126,36 @@ pub fn convert_manga_list(msg: MangaInfoOutput) -> AppMsg {
 pub fn convert_downloads(msg: DownloadOutputMsg) -> AppMsg {
     match msg {
         DownloadOutputMsg::OpenManga { url, path } => AppMsg::OpenManga { url, path },
+        DownloadOutputMsg::RemoveDownloads { ids, delete_files } => {
+            AppMsg::RemoveDownloads { ids, delete_files }
+        }
+        DownloadOutputMsg::Read(info) => AppMsg::Read(info),
+    }
+}
+
+pub fn convert_library(msg: LibraryOutput) -> AppMsg {
+    match msg {
+        LibraryOutput::OpenManga { url, path } => AppMsg::OpenManga { url, path },
+        LibraryOutput::Read(info) => AppMsg::Read(info),
+    }
+}
+
+pub fn convert_settings(msg: SettingsOutput) -> AppMsg {
+    match msg {
+        SettingsOutput::DownloadDirChanged(path) => AppMsg::DownloadDirChanged(path),
+        SettingsOutput::Error(err) => AppMsg::Error(err),
+    }
+}
+
+pub fn convert_modules(msg: ModulesOutput) -> AppMsg {
+    match msg {
+        ModulesOutput::Error(err) => AppMsg::Error(err),
+    }
+}
+
+pub fn convert_reader(msg: ReaderOutput) -> AppMsg {
+    match msg {
+        ReaderOutput::Error(err) => AppMsg::Error(err),
     }
 }
 
@@ -83,28 +165,68 @@ pub fn convert_downloads(msg: DownloadOutputMsg) -> AppMsg {
 impl SimpleComponent for AppModel {
     type Widgets = AppWidgets;
 
-    type Init = Arc<MadoEngineState>;
+    type Init = AppInit;
 
     type Input = AppMsg;
     type Output = ();
 
     fn init(
-        state: Self::Init,
+        init: Self::Init,
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> relm4::ComponentParts<Self> {
+        let AppInit {
+            state,
+            config,
+            loader,
+            http_client,
+        } = init;
+        let utf8_path = |path: std::path::PathBuf, fallback: &str| {
+            Utf8PathBuf::from_path_buf(path).unwrap_or_else(|it| {
+                tracing::error!("{:?} is not a valid utf8 path", it);
+                fallback.into()
+            })
+        };
+        let current = config.config();
+        let download_dir = utf8_path(current.download_dir, "downloads");
+        let cache_dir = utf8_path(current.cache_dir, "cache");
+
         let downloads = DownloadModel::builder()
             .launch(())
             .forward(sender.input_sender(), convert_downloads);
 
+        let library = LibraryModel::builder()
+            .launch(state.clone())
+            .forward(sender.input_sender(), convert_library);
+
         let manga_info = MangaInfoModel::builder()
             .launch(MangaInfoInit {
-                modules: state.modules(),
-                option: state.option(),
-                default_download_path: "downloads".into(),
+                state: state.clone(),
+                default_download_path: download_dir,
+                covers: CoverCache::new(cache_dir.join("covers")),
             })
             .forward(sender.input_sender(), convert_manga_list);
 
+        let modules = ModulesModel::builder()
+            .launch(ModulesInit {
+                state: state.clone(),
+                loader: loader.clone(),
+                config: config.clone(),
+            })
+            .forward(sender.input_sender(), convert_modules);
+
+        let reader = ReaderModel::builder()
+            .launch(config.clone())
+            .forward(sender.input_sender(), convert_reader);
+
+        let settings = SettingsModel::builder()
+            .launch(SettingsInit {
+                config,
+                loader,
+                http_client,
+            })
+            .forward(sender.input_sender(), convert_settings);
+
         let observer = RelmMadoEngineStateObserver::new(
             sender.input_sender().clone(),
             downloads.sender().clone(),
@@ -114,7 +236,11 @@ impl SimpleComponent for AppModel {
         let model = Self {
             state,
             downloads,
+            library,
             manga_info,
+            settings,
+            modules,
+            reader,
 
             root: root.clone(),
             visible_child: "Download".to_string(),
@@ -125,7 +251,7 @@ impl SimpleComponent for AppModel {
     }
 
     #[tracing::instrument(skip(self))]
-    fn update(&mut self, msg: Self::Input, _: ComponentSender<Self>) {
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
         match msg {
             AppMsg::PushModule(module) => {
                 tracing::trace!(
@@ -137,6 +263,18 @@ impl SimpleComponent for AppModel {
             AppMsg::DownloadRequest(info) => {
                 self.state.download_request(info);
             }
+            AppMsg::RemoveDownloads { ids, delete_files } => {
+                // removal waits for running downloads to stop before deleting
+                // files, errors come back as messages.
+                let state = self.state.clone();
+                tokio::spawn(async move {
+                    for id in ids {
+                        if let Err(err) = state.remove_download(id, delete_files).await {
+                            sender.input(AppMsg::Error(err.into()));
+                        }
+                    }
+                });
+            }
             AppMsg::OpenManga { url, path } => {
                 self.manga_info.emit(MangaInfoMsg::GetInfo {
                     url: url.to_string(),
@@ -144,13 +282,39 @@ impl SimpleComponent for AppModel {
                 });
                 self.visible_child = "Manga Info".to_string();
             }
+            AppMsg::Read(info) => {
+                self.reader.emit(ReaderMsg::Resume(info));
+                self.visible_child = "Reader".to_string();
+            }
+            AppMsg::MarkRead {
+                module,
+                chapters,
+                read,
+            } => {
+                let tasks = self.state.tasks();
+                let downloaded = tasks
+                    .iter()
+                    .filter(|it| *it.module_uuid() == module)
+                    .flat_map(|it| it.chapters().iter())
+                    .filter(|it| chapters.iter().any(|id| id == it.chapter_id()));
+
+                for chapter in downloaded {
+                    chapter.set_read(read);
+                }
+            }
+            AppMsg::DownloadDirChanged(path) => {
+                self.manga_info
+                    .emit(MangaInfoMsg::DefaultDownloadPathChanged(path));
+            }
             AppMsg::ChangeVisibleChild(string) => {
                 self.visible_child = string;
             }
             AppMsg::Error(error) => {
+                let hint = error.kind().hint();
                 let error = format!("{:#}", error);
                 gtk::MessageDialog::builder()
                     .text(&error)
+                    .secondary_text(hint)
                     .transient_for(&self.root)
                     .build()
                     .show();
@@ -178,12 +342,36 @@ impl SimpleComponent for AppModel {
                         set_orientation: gtk::Orientation::Vertical,
                         append: model.downloads.widget()
                     },
+                    // Library tab
+                    #[name = "library"]
+                    add_titled[Some("Library"), "Library"] = &gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        append: model.library.widget()
+                    },
                     // Manga Info tab
                     #[name = "manga_info"]
                     add_titled[Some("Manga Info"), "Manga Info"] = &gtk::Box {
                         set_orientation: gtk::Orientation::Vertical,
                         append: model.manga_info.widget()
                     },
+                    // Reader tab
+                    #[name = "reader"]
+                    add_titled[Some("Reader"), "Reader"] = &gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        append: model.reader.widget()
+                    },
+                    // Modules tab
+                    #[name = "modules"]
+                    add_titled[Some("Modules"), "Modules"] = &gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        append: model.modules.widget()
+                    },
+                    // Settings tab
+                    #[name = "settings"]
+                    add_titled[Some("Settings"), "Settings"] = &gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        append: model.settings.widget()
+                    },
 
                     #[track(Some(model.visible_child.as_str()) != stack.visible_child_name().as_ref().map(|it| it.as_str()))]
                     set_visible_child_name: &model.visible_child,
@@ -202,12 +390,25 @@ impl SimpleComponent for AppModel {
 
 #[cfg(test)]
 mod tests {
-    use mado::engine::MadoEngine;
+    use mado::engine::{MadoEngine, MadoModuleLoader, ModuleLoadError};
     use mado_core::{DefaultMadoModuleMap, MangaInfo, MutexMadoModuleMap, Url, Uuid};
 
     use super::*;
     use crate::tests::*;
 
+    struct Loader;
+
+    #[async_trait::async_trait]
+    impl MadoModuleLoader for Loader {
+        async fn get_paths(&self) -> Vec<Utf8PathBuf> {
+            vec![]
+        }
+
+        async fn load(&self, _: Utf8PathBuf) -> Result<Vec<ArcMadoModule>, ModuleLoadError> {
+            Ok(vec![])
+        }
+    }
+
     fn state() -> MadoEngine {
         let map = DefaultMadoModuleMap::new();
         let map = MutexMadoModuleMap::new(map);
@@ -217,10 +418,23 @@ mod tests {
         MadoEngine::new(state)
     }
 
+    fn init(mado: &MadoEngine, dir: &tempfile::TempDir) -> AppInit {
+        let path = dir.path().join("config.toml");
+        let config = ConfigFile::open(path, mado.state().option()).unwrap();
+
+        AppInit {
+            state: mado.state(),
+            config: Arc::new(config),
+            loader: mado.module_loader(Loader).0,
+            http_client: Default::default(),
+        }
+    }
+
     #[gtk::test]
     fn test_app() {
         let mado = state();
-        let app = AppModel::builder().launch(mado.state()).detach();
+        let dir = tempfile::tempdir().unwrap();
+        let app = AppModel::builder().launch(init(&mado, &dir)).detach();
 
         let mut module = mado_core::MockMadoModule::new();
         module.expect_uuid().return_const(Uuid::from_u128(1));
@@ -245,10 +459,55 @@ mod tests {
         assert_eq!(app.model().downloads.model().task_len(), 1);
     }
 
+    #[gtk::test]
+    fn test_remove_downloads() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+
+        let mado = state();
+        let dir = tempfile::tempdir().unwrap();
+        let app = AppModel::builder().launch(init(&mado, &dir)).detach();
+
+        let mut module = mado_core::MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+        module
+            .expect_domain()
+            .return_const(Url::parse("https://localhost").unwrap());
+        let path = Utf8PathBuf::from_path_buf(dir.path().join("manga")).unwrap();
+        mado.state().download_request(DownloadRequest::new(
+            Arc::new(module),
+            Arc::new(MangaInfo::default()),
+            vec![Default::default()],
+            path.clone(),
+            None,
+            mado::engine::DownloadRequestStatus::Pause,
+        ));
+
+        let info = mado.state().tasks()[0].clone();
+        std::fs::create_dir_all(info.chapters()[0].path()).unwrap();
+
+        app.emit(AppMsg::RemoveDownloads {
+            ids: vec![info.id()],
+            delete_files: true,
+        });
+
+        for _ in 0..100 {
+            run_loop();
+            if !path.exists() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        assert!(mado.state().tasks().is_empty());
+        assert!(!path.exists());
+    }
+
     #[gtk::test]
     fn test_open_manga() {
         let mado = state();
-        let app = AppModel::builder().launch(mado.state()).detach();
+        let dir = tempfile::tempdir().unwrap();
+        let app = AppModel::builder().launch(init(&mado, &dir)).detach();
 
         let url = Url::parse("https://localhost").unwrap();
         let path = Utf8PathBuf::from("path");
@@ -257,4 +516,100 @@ mod tests {
 
         assert_eq!(app.model().visible_child, "Manga Info");
     }
+
+    #[gtk::test]
+    fn test_read() {
+        let mado = state();
+        let dir = tempfile::tempdir().unwrap();
+        let app = AppModel::builder().launch(init(&mado, &dir)).detach();
+
+        let map = Arc::new(DefaultMadoModuleMap::new());
+        let module = mado::engine::LateBindingModule::WaitModule(map, Uuid::from_u128(1));
+        let status = mado::engine::DownloadStatus::finished();
+        let chapter = mado::engine::DownloadChapterInfo::new(
+            module.clone(),
+            "1".to_string(),
+            "chapter".to_string(),
+            "chapter".into(),
+            status.clone(),
+        );
+        let info = DownloadInfo::builder()
+            .order(0)
+            .module(module)
+            .chapters(vec![Arc::new(chapter)])
+            .status(status)
+            .build();
+
+        app.emit(AppMsg::Read(Arc::new(info)));
+        run_loop();
+
+        assert_eq!(app.model().visible_child, "Reader");
+        assert_eq!(
+            app.model().reader.model().chapter().unwrap().title(),
+            "chapter"
+        );
+    }
+
+    #[gtk::test]
+    fn test_mark_read() {
+        let mado = state();
+        let dir = tempfile::tempdir().unwrap();
+        let app = AppModel::builder().launch(init(&mado, &dir)).detach();
+
+        let mut module = mado_core::MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+        module
+            .expect_domain()
+            .return_const(Url::parse("https://localhost").unwrap());
+        module.expect_name().return_const("module".to_string());
+        let module = Arc::new(module);
+
+        let chapters = ["1", "2"].map(|id| {
+            Arc::new(mado_core::ChapterInfo {
+                id: id.to_string(),
+                ..Default::default()
+            })
+        });
+        mado.state().download_request(DownloadRequest::new(
+            module,
+            Arc::new(MangaInfo::default()),
+            chapters.to_vec(),
+            "path".into(),
+            None,
+            mado::engine::DownloadRequestStatus::Pause,
+        ));
+
+        app.emit(AppMsg::MarkRead {
+            module: Uuid::from_u128(1),
+            chapters: vec!["2".to_string()],
+            read: true,
+        });
+        run_loop();
+
+        let tasks = mado.state().tasks();
+        let read: Vec<_> = tasks
+            .iter()
+            .next()
+            .unwrap()
+            .chapters()
+            .iter()
+            .map(|it| it.is_read())
+            .collect();
+        assert_eq!(read, [false, true]);
+    }
+
+    #[gtk::test]
+    fn test_download_dir_changed() {
+        let mado = state();
+        let dir = tempfile::tempdir().unwrap();
+        let app = AppModel::builder().launch(init(&mado, &dir)).detach();
+
+        app.emit(AppMsg::DownloadDirChanged("new".into()));
+        run_loop();
+
+        assert_eq!(
+            app.model().manga_info.model().path(),
+            crate::manga_info::DownloadPath::FromUser("new".to_string())
+        );
+    }
 }
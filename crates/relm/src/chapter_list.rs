@@ -1,15 +1,80 @@
-use std::{cell::Cell, rc::Rc, sync::Arc};
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    collections::{BTreeSet, HashSet},
+    rc::Rc,
+    sync::Arc,
+};
 
 use gtk::prelude::*;
+use mado::engine::{chapter_number, ChapterSelector, DownloadStatus};
 use mado_core::ChapterInfo;
 use relm4::{ComponentParts, ComponentSender, SimpleComponent};
 
-use crate::list_model::{ListModel, ListModelBase};
+use crate::list_model::{ListModel, ListModelBase, ListModelBaseExt};
+
+/// Download state of a chapter shown next to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChapterBadge {
+    Downloaded,
+    Queued,
+    Failed,
+}
+
+impl ChapterBadge {
+    const CSS_CLASSES: [&'static str; 3] = ["success", "dim-label", "error"];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChapterBadge::Downloaded => "Downloaded",
+            ChapterBadge::Queued => "Queued",
+            ChapterBadge::Failed => "Failed",
+        }
+    }
+
+    /// Badge of a chapter in more than one download, a finished download
+    /// over one in progress over one that failed.
+    pub fn furthest(self, other: Self) -> Self {
+        fn rank(badge: ChapterBadge) -> u8 {
+            match badge {
+                ChapterBadge::Downloaded => 2,
+                ChapterBadge::Queued => 1,
+                ChapterBadge::Failed => 0,
+            }
+        }
+
+        if rank(other) > rank(self) {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn css_class(&self) -> &'static str {
+        match self {
+            ChapterBadge::Downloaded => Self::CSS_CLASSES[0],
+            ChapterBadge::Queued => Self::CSS_CLASSES[1],
+            ChapterBadge::Failed => Self::CSS_CLASSES[2],
+        }
+    }
+}
+
+impl From<&DownloadStatus> for ChapterBadge {
+    fn from(status: &DownloadStatus) -> Self {
+        match status {
+            DownloadStatus::Finished => ChapterBadge::Downloaded,
+            DownloadStatus::FinishedWithErrors(_) => ChapterBadge::Failed,
+            _ if status.is_error() => ChapterBadge::Failed,
+            _ => ChapterBadge::Queued,
+        }
+    }
+}
 
 #[derive(Default, Debug, Clone)]
 pub struct CheckChapterInfo {
     info: Arc<ChapterInfo>,
     active: Rc<Cell<bool>>,
+    badge: Rc<Cell<Option<ChapterBadge>>>,
 }
 impl From<Arc<ChapterInfo>> for CheckChapterInfo {
     fn from(info: Arc<ChapterInfo>) -> Self {
@@ -22,6 +87,7 @@ impl CheckChapterInfo {
         Self {
             info,
             active: Rc::new(Cell::new(active)),
+            badge: Default::default(),
         }
     }
 
@@ -36,13 +102,133 @@ impl CheckChapterInfo {
     pub fn set_active(&self, val: bool) {
         self.active.set(val)
     }
+
+    pub fn badge(&self) -> Option<ChapterBadge> {
+        self.badge.get()
+    }
+
+    pub fn set_badge(&self, badge: Option<ChapterBadge>) {
+        self.badge.set(badge)
+    }
+}
+
+/// Which chapters are shown, criteria that aren't set match every chapter.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChapterFilter {
+    pub language: Option<String>,
+    pub scanlator: Option<String>,
+    pub volume: Option<String>,
+    /// Searched case-insensitively in the displayed name of the chapter.
+    pub search: String,
+}
+
+impl ChapterFilter {
+    pub fn matches(&self, chapter: &ChapterInfo) -> bool {
+        if let Some(language) = &self.language {
+            if chapter.language != *language {
+                return false;
+            }
+        }
+
+        if let Some(scanlator) = &self.scanlator {
+            if !chapter.scanlator.contains(scanlator) {
+                return false;
+            }
+        }
+
+        if let Some(volume) = &self.volume {
+            if chapter.volume.as_ref() != Some(volume) {
+                return false;
+            }
+        }
+
+        let search = self.search.trim().to_lowercase();
+        search.is_empty()
+            || chapter
+                .display_without_index()
+                .to_string()
+                .to_lowercase()
+                .contains(&search)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ChapterSort {
+    /// Order given by the module.
+    #[default]
+    Source,
+    Number,
+    Uploaded,
+}
+
+impl ChapterSort {
+    pub const ALL: [Self; 3] = [Self::Source, Self::Number, Self::Uploaded];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Source => "Source order",
+            Self::Number => "Chapter number",
+            Self::Uploaded => "Upload date",
+        }
+    }
+
+    /// Compare in ascending order, chapters without the value come after
+    /// the others and ties keep the source order.
+    pub fn compare(self, first: &ChapterInfo, second: &ChapterInfo) -> Ordering {
+        fn some_first<T>(
+            first: Option<T>,
+            second: Option<T>,
+            compare: impl FnOnce(T, T) -> Ordering,
+        ) -> Ordering {
+            match (first, second) {
+                (Some(first), Some(second)) => compare(first, second),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+
+        let ordering = match self {
+            Self::Source => Ordering::Equal,
+            Self::Number => some_first(chapter_number(first), chapter_number(second), |a, b| {
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+            }),
+            Self::Uploaded => some_first(
+                first.uploaded_at.as_ref(),
+                second.uploaded_at.as_ref(),
+                Ord::cmp,
+            ),
+        };
+
+        ordering.then_with(|| first.index.cmp(&second.index))
+    }
+}
+
+/// Value chosen in a filter dropdown, the first item matches everything.
+fn dropdown_value(dropdown: &gtk::DropDown) -> Option<String> {
+    if dropdown.selected() == 0 {
+        return None;
+    }
+
+    dropdown
+        .selected_item()?
+        .downcast::<gtk::StringObject>()
+        .ok()
+        .map(|it| it.string().to_string())
 }
 
 // #[derive(Debug)]
 pub struct ChapterListModel {
-    #[allow(dead_code)]
     chapters: ListModel<CheckChapterInfo>,
     selection_model: gtk::MultiSelection,
+    filter: Rc<RefCell<ChapterFilter>>,
+    bool_filter: gtk::BoolFilter,
+    sort: Rc<Cell<ChapterSort>>,
+    reverse: Rc<Cell<bool>>,
+    sorter: gtk::CustomSorter,
+    languages: gtk::DropDown,
+    scanlators: gtk::DropDown,
+    volumes: gtk::DropDown,
 }
 
 #[derive(Debug)]
@@ -50,47 +236,226 @@ pub enum ChapterListMsg {
     Setup(gtk::ListItem),
     Change(gtk::ListItem),
     Activate,
+    /// Check only chapters chosen by the selector.
+    Select(ChapterSelector),
+    SelectAll,
+    SelectNone,
+    Invert,
+    /// Check rows from the first to the last highlighted row.
+    SelectRange,
+    /// Rebuild the choices of the filters after chapters were added or
+    /// removed.
+    UpdateFilters,
+    Language(Option<String>),
+    Scanlator(Option<String>),
+    Volume(Option<String>),
+    Search(String),
+    Sort(ChapterSort),
+    Reverse(bool),
 }
 
 const CHECK_BUTTON_ROW: i32 = 0;
 const CHECK_BUTTON_COLUMN: i32 = 0;
+const LABEL_COLUMN: i32 = 2;
+const BADGE_COLUMN: i32 = 3;
 
 impl ChapterListModel {
-    /// Create gtk::Grid from ChapterInfo
-    pub fn create_chapter_info(chapter: CheckChapterInfo) -> gtk::Grid {
+    /// Create an empty row, `toggled` is called with the state of its
+    /// checkbox. Rows are reused for other chapters, the shown chapter is
+    /// set with [`Self::bind_chapter_info`].
+    pub fn create_chapter_info(toggled: impl Fn(bool) + 'static) -> gtk::Grid {
         let check = gtk::CheckButton::default();
-        let label = gtk::Label::builder()
-            .label(&format!("{}", chapter.info()))
-            .build();
+        let label = gtk::Label::default();
 
         let grid = gtk::Grid::builder()
             .orientation(gtk::Orientation::Horizontal)
             .build();
 
         grid.attach(&check, CHECK_BUTTON_COLUMN, CHECK_BUTTON_ROW, 1, 1);
-        grid.attach(&label, 2, 0, 1, 1);
+        grid.attach(&label, LABEL_COLUMN, CHECK_BUTTON_ROW, 1, 1);
         grid.set_column_spacing(5);
 
-        check.connect_toggled(move |it| {
-            chapter.set_active(it.is_active());
-        });
+        let badge = gtk::Label::default();
+        Self::set_badge(&badge, None);
+        grid.attach(&badge, BADGE_COLUMN, CHECK_BUTTON_ROW, 1, 1);
+
+        check.connect_toggled(move |it| toggled(it.is_active()));
 
         grid
     }
 
+    /// Show `chapter` in a row made by [`Self::create_chapter_info`].
+    pub fn bind_chapter_info(grid: &gtk::Grid, chapter: &CheckChapterInfo) {
+        if let Some(label) = Self::get_label(grid) {
+            label.set_label(&format!("{}", chapter.info()));
+        }
+
+        if let Some(badge) = Self::get_badge(grid) {
+            Self::set_badge(&badge, chapter.badge());
+        }
+
+        if let Some(check) = Self::get_check(grid) {
+            check.set_active(chapter.active());
+        }
+    }
+
     fn get_check(grid: &gtk::Grid) -> Option<gtk::CheckButton> {
         grid.child_at(CHECK_BUTTON_COLUMN, CHECK_BUTTON_ROW)?
             .downcast::<gtk::CheckButton>()
             .ok()
     }
 
-    fn for_each(&self, call: impl Fn(u32, gtk::glib::Object)) {
+    fn get_label(grid: &gtk::Grid) -> Option<gtk::Label> {
+        grid.child_at(LABEL_COLUMN, CHECK_BUTTON_ROW)?
+            .downcast::<gtk::Label>()
+            .ok()
+    }
+
+    fn get_badge(grid: &gtk::Grid) -> Option<gtk::Label> {
+        grid.child_at(BADGE_COLUMN, CHECK_BUTTON_ROW)?
+            .downcast::<gtk::Label>()
+            .ok()
+    }
+
+    fn set_badge(label: &gtk::Label, badge: Option<ChapterBadge>) {
+        for class in ChapterBadge::CSS_CLASSES {
+            label.remove_css_class(class);
+        }
+
+        label.set_visible(badge.is_some());
+        if let Some(badge) = badge {
+            label.set_label(badge.label());
+            label.add_css_class(badge.css_class());
+        }
+    }
+
+    pub fn filter(&self) -> ChapterFilter {
+        self.filter.borrow().clone()
+    }
+
+    /// Chapters in the order they are shown.
+    pub fn visible_chapters(&self) -> Vec<Arc<ChapterInfo>> {
+        let mut chapters = Vec::new();
+        self.for_each(|_, it| {
+            if let Some(it) = self.chapters.get_by_object(&it) {
+                chapters.push(it.info().clone());
+            }
+        });
+
+        chapters
+    }
+
+    /// Checked chapters that are shown, in source order. Hidden chapters
+    /// keep their check for when the filter changes again, but they aren't
+    /// downloaded or marked along with the shown ones.
+    pub fn checked_chapters(&self) -> Vec<Arc<ChapterInfo>> {
+        let filter = self.filter.borrow();
+        let mut chapters = Vec::new();
+        self.chapters.for_each(|it| {
+            if it.active() && filter.matches(it.info()) {
+                chapters.push(it.info().clone());
+            }
+        });
+
+        chapters
+    }
+
+    fn for_each(&self, mut call: impl FnMut(u32, gtk::glib::Object)) {
         let mut i = 0;
         while let Some(it) = self.selection_model.item(i) {
             call(i, it);
             i += 1;
         }
     }
+
+    /// Check the shown rows `active` returns true for, and uncheck the rest.
+    fn set_active_each(&self, active: impl Fn(u32, &CheckChapterInfo) -> bool) {
+        self.for_each(|i, it| {
+            if let Some(it) = self.chapters.get_by_object(&it) {
+                it.set_active(active(i, &*it));
+
+                // Notify model that value has changed
+                self.selection_model.selection_changed(i, 1);
+            }
+        });
+    }
+
+    fn filter_dropdown(
+        sender: &ComponentSender<Self>,
+        msg: fn(Option<String>) -> ChapterListMsg,
+    ) -> gtk::DropDown {
+        let dropdown = gtk::DropDown::default();
+        dropdown.connect_selected_notify({
+            let sender = sender.clone();
+            move |it| sender.input(msg(dropdown_value(it)))
+        });
+
+        dropdown
+    }
+
+    /// Show `values` in `dropdown` after `all`, keeping `current` chosen if
+    /// it's still there.
+    fn set_filter_values(
+        dropdown: &gtk::DropDown,
+        all: &str,
+        values: BTreeSet<String>,
+        current: Option<&String>,
+    ) {
+        let list = gtk::StringList::new(&[all]);
+        for it in &values {
+            list.append(it);
+        }
+
+        let position = current
+            .and_then(|current| values.iter().position(|it| it == current))
+            .map(|it| it as u32 + 1)
+            .unwrap_or(0);
+
+        dropdown.set_model(Some(&list));
+        dropdown.set_selected(position);
+        dropdown.set_sensitive(values.len() > 1);
+    }
+
+    fn update_filters(&self) {
+        let mut languages = BTreeSet::new();
+        let mut scanlators = BTreeSet::new();
+        let mut volumes = BTreeSet::new();
+        self.chapters.for_each(|it| {
+            let info = it.info();
+            languages.insert(info.language.clone());
+            scanlators.extend(info.scanlator.iter().cloned());
+            volumes.extend(info.volume.clone());
+        });
+
+        let filter = self.filter();
+        Self::set_filter_values(
+            &self.languages,
+            "All languages",
+            languages,
+            filter.language.as_ref(),
+        );
+        Self::set_filter_values(
+            &self.scanlators,
+            "All scanlators",
+            scanlators,
+            filter.scanlator.as_ref(),
+        );
+        Self::set_filter_values(
+            &self.volumes,
+            "All volumes",
+            volumes,
+            filter.volume.as_ref(),
+        );
+    }
+
+    fn update_filter(&self, update: impl FnOnce(&mut ChapterFilter)) {
+        let old = self.filter();
+        update(&mut self.filter.borrow_mut());
+
+        if *self.filter.borrow() != old {
+            self.bool_filter.changed(gtk::FilterChange::Different);
+        }
+    }
 }
 
 /// Widget that show Chapter with checkbox
@@ -107,11 +472,55 @@ impl SimpleComponent for ChapterListModel {
         root: &Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let selection_model = gtk::MultiSelection::new(Some(&chapters.list_model()));
+        let filter = Rc::new(RefCell::new(ChapterFilter::default()));
+        let bool_filter = chapters.bool_filter({
+            let filter = filter.clone();
+            move |it| filter.borrow().matches(it.info())
+        });
+
+        let sort = Rc::new(Cell::new(ChapterSort::default()));
+        let reverse = Rc::new(Cell::new(false));
+        let sorter = chapters.custom_sorter({
+            let sort = sort.clone();
+            let reverse = reverse.clone();
+            move |first, second| {
+                let ordering = sort.get().compare(first.info(), second.info());
+                if reverse.get() {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+        });
+
+        let list_model = chapters.list_model();
+        list_model.connect_items_changed({
+            let sender = sender.clone();
+            move |_, _, removed, added| {
+                // rebinding a row replaces it with itself.
+                if removed != added {
+                    sender.input(ChapterListMsg::UpdateFilters);
+                }
+            }
+        });
+        let list_model = gtk::FilterListModel::new(Some(&list_model), Some(&bool_filter));
+        let list_model = gtk::SortListModel::new(Some(&list_model), Some(&sorter));
+        let selection_model = gtk::MultiSelection::new(Some(&list_model));
+
         let model = ChapterListModel {
             chapters,
             selection_model,
+            filter,
+            bool_filter,
+            sort,
+            reverse,
+            sorter,
+            languages: Self::filter_dropdown(&sender, ChapterListMsg::Language),
+            scanlators: Self::filter_dropdown(&sender, ChapterListMsg::Scanlator),
+            volumes: Self::filter_dropdown(&sender, ChapterListMsg::Volume),
         };
+        model.update_filters();
+
         let widgets = view_output!();
         ComponentParts { model, widgets }
     }
@@ -120,21 +529,31 @@ impl SimpleComponent for ChapterListModel {
         match msg {
             // Initialize Children
             ChapterListMsg::Setup(item) => {
-                let data = item.item().and_then(|it| self.chapters.get_by_object(&it));
+                let chapters = self.chapters.clone();
+                let row = item.downgrade();
+                let grid = Self::create_chapter_info(move |active| {
+                    // the row is reused, so toggle the chapter it shows now.
+                    let data = row
+                        .upgrade()
+                        .and_then(|it| it.item())
+                        .and_then(|it| chapters.get_by_object(&it).map(|it| it.clone()));
 
-                if let Some(data) = data {
-                    let grid = Self::create_chapter_info(data.clone());
-                    item.set_child(Some(&grid));
-                }
+                    if let Some(data) = data {
+                        data.set_active(active);
+                    }
+                });
+                item.set_child(Some(&grid));
             }
 
             // Sync children with data
             ChapterListMsg::Change(item) => {
-                let child = item.child().unwrap().downcast::<gtk::Grid>().unwrap();
-                let child = Self::get_check(&child).unwrap();
+                let grid = item.child().and_then(|it| it.downcast::<gtk::Grid>().ok());
+                let data = item
+                    .item()
+                    .and_then(|it| self.chapters.get_by_object(&it).map(|it| it.clone()));
 
-                if let Some(data) = item.item().and_then(|it| self.chapters.get_by_object(&it)) {
-                    child.set_active(data.active());
+                if let (Some(grid), Some(data)) = (grid, data) {
+                    Self::bind_chapter_info(&grid, &data);
                 }
             }
             ChapterListMsg::Activate => {
@@ -150,31 +569,141 @@ impl SimpleComponent for ChapterListModel {
                     }
                 });
             }
+            ChapterListMsg::Select(selector) => {
+                let selected = selector
+                    .select(&self.visible_chapters())
+                    .into_iter()
+                    .map(|it| it.id.clone())
+                    .collect::<HashSet<_>>();
+
+                self.set_active_each(|_, it| selected.contains(&it.info().id));
+            }
+            ChapterListMsg::SelectAll => self.set_active_each(|_, _| true),
+            ChapterListMsg::SelectNone => self.set_active_each(|_, _| false),
+            ChapterListMsg::Invert => self.set_active_each(|_, it| !it.active()),
+            ChapterListMsg::SelectRange => {
+                let selection = self.selection_model.selection();
+                if selection.is_empty() {
+                    return;
+                }
+
+                let range = selection.minimum()..=selection.maximum();
+                self.set_active_each(|i, it| it.active() || range.contains(&i));
+            }
+            ChapterListMsg::UpdateFilters => self.update_filters(),
+            ChapterListMsg::Language(language) => {
+                self.update_filter(|it| it.language = language);
+            }
+            ChapterListMsg::Scanlator(scanlator) => {
+                self.update_filter(|it| it.scanlator = scanlator);
+            }
+            ChapterListMsg::Volume(volume) => {
+                self.update_filter(|it| it.volume = volume);
+            }
+            ChapterListMsg::Search(search) => {
+                self.update_filter(|it| it.search = search);
+            }
+            ChapterListMsg::Sort(sort) => {
+                self.sort.set(sort);
+                self.sorter.changed(gtk::SorterChange::Different);
+            }
+            ChapterListMsg::Reverse(reverse) => {
+                self.reverse.set(reverse);
+                self.sorter.changed(gtk::SorterChange::Inverted);
+            }
         }
     }
 
     view! {
-        gtk::ScrolledWindow {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 5,
             set_vexpand : true,
             set_hexpand: true,
-            #[wrap(Some)]
-            set_child: list = &gtk::ListView {
-                #[wrap(Some)]
-                set_factory = &gtk::SignalListItemFactory {
-                    connect_setup[sender] => move |_, item| {
-                        sender.input(ChapterListMsg::Setup(item.clone()))
-                    },
 
-                    connect_bind[sender] => move |_, item| {
-                        sender.input(ChapterListMsg::Change(item.clone()))
+            append = &gtk::Box {
+                set_spacing: 5,
+                append: search_entry = &gtk::SearchEntry {
+                    set_hexpand: true,
+                    set_placeholder_text: Some("Search chapters"),
+                    connect_search_changed[sender] => move |it| {
+                        sender.input(ChapterListMsg::Search(it.text().to_string()));
+                    }
+                },
+                append: &model.languages,
+                append: &model.scanlators,
+                append: &model.volumes,
+            },
+
+            append = &gtk::Box {
+                set_spacing: 5,
+                append = &gtk::DropDown::from_strings(&ChapterSort::ALL.map(ChapterSort::label)) {
+                    connect_selected_notify[sender] => move |it| {
+                        if let Some(sort) = ChapterSort::ALL.get(it.selected() as usize) {
+                            sender.input(ChapterListMsg::Sort(*sort));
+                        }
+                    }
+                },
+                append = &gtk::ToggleButton {
+                    set_label: "Reverse",
+                    connect_toggled[sender] => move |it| {
+                        sender.input(ChapterListMsg::Reverse(it.is_active()));
+                    }
+                },
+
+                append = &gtk::Separator {
+                    set_orientation: gtk::Orientation::Vertical,
+                },
+
+                append: select_all_button = &gtk::Button {
+                    set_label: "All",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(ChapterListMsg::SelectAll);
+                    }
+                },
+                append: select_none_button = &gtk::Button {
+                    set_label: "None",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(ChapterListMsg::SelectNone);
+                    }
+                },
+                append: invert_button = &gtk::Button {
+                    set_label: "Invert",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(ChapterListMsg::Invert);
                     }
                 },
-                set_single_click_activate: false,
-                connect_activate[sender] => move |_, _| {
-                    sender.input(ChapterListMsg::Activate)
+                append: select_range_button = &gtk::Button {
+                    set_label: "Range",
+                    set_tooltip_text: Some("Check rows between the first and last highlighted rows"),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(ChapterListMsg::SelectRange);
+                    }
                 },
+            },
+
+            append = &gtk::ScrolledWindow {
+                set_vexpand : true,
+                set_hexpand: true,
+                #[wrap(Some)]
+                set_child: list = &gtk::ListView {
+                    #[wrap(Some)]
+                    set_factory = &gtk::SignalListItemFactory {
+                        connect_setup[sender] => move |_, item| {
+                            sender.input(ChapterListMsg::Setup(item.clone()))
+                        },
+
+                        connect_bind[sender] => move |_, item| {
+                            sender.input(ChapterListMsg::Change(item.clone()))
+                        }
+                    },
+                    set_single_click_activate: false,
+                    connect_activate[sender] => move |_, _| {
+                        sender.input(ChapterListMsg::Activate)
+                    },
 
-                set_model: Some(&model.selection_model)
+                    set_model: Some(&model.selection_model)
+                },
             },
         }
     }
@@ -192,10 +721,20 @@ mod tests {
 
     #[gtk::test]
     fn test_chapter_info() {
-        let chapter = Arc::new(ChapterInfo::default());
-        let chapter = CheckChapterInfo::from(chapter);
+        let chapter = CheckChapterInfo::from(Arc::new(ChapterInfo {
+            title: Some("title".to_string()),
+            ..Default::default()
+        }));
+
+        let it = ChapterListModel::create_chapter_info({
+            let chapter = chapter.clone();
+            move |active| chapter.set_active(active)
+        });
 
-        let it = ChapterListModel::create_chapter_info(chapter.clone());
+        let label = ChapterListModel::get_label(&it).expect("should exist");
+        assert_eq!(label.label(), "");
+        let badge = ChapterListModel::get_badge(&it).expect("should exist");
+        assert!(!badge.is_visible());
 
         let check = ChapterListModel::get_check(&it).expect("should exist");
         for i in [true, false] {
@@ -203,6 +742,33 @@ mod tests {
             run_loop();
             assert_eq!(chapter.active(), i);
         }
+
+        chapter.set_badge(Some(ChapterBadge::Failed));
+        ChapterListModel::bind_chapter_info(&it, &chapter);
+        assert_eq!(label.label(), format!("{}", chapter.info()));
+        assert_eq!(badge.label(), "Failed");
+        assert!(badge.has_css_class("error"));
+    }
+
+    #[test]
+    fn test_badge() {
+        assert_eq!(
+            ChapterBadge::from(&DownloadStatus::finished()),
+            ChapterBadge::Downloaded
+        );
+        assert_eq!(
+            ChapterBadge::from(&DownloadStatus::paused()),
+            ChapterBadge::Queued
+        );
+        assert_eq!(
+            ChapterBadge::from(&DownloadStatus::error("error")),
+            ChapterBadge::Failed
+        );
+
+        let it = ChapterBadge::Failed.furthest(ChapterBadge::Queued);
+        assert_eq!(it, ChapterBadge::Queued);
+        let it = ChapterBadge::Downloaded.furthest(ChapterBadge::Queued);
+        assert_eq!(it, ChapterBadge::Downloaded);
     }
 
     #[gtk::test]
@@ -239,4 +805,237 @@ mod tests {
             assert_eq!(vec.get(&first).unwrap().active(), i);
         }
     }
+
+    #[gtk::test]
+    fn test_select() {
+        let vec = ListStore::default();
+
+        let window = gtk::ApplicationWindow::default();
+        let model = ChapterListModel::builder().launch(vec.base()).detach();
+        window.set_child(Some(model.widget()));
+
+        let chapters = ["1", "2", "3"].map(|it| {
+            vec.push(CheckChapterInfo::new(
+                Arc::new(ChapterInfo {
+                    id: it.to_string(),
+                    chapter: Some(it.to_string()),
+                    ..Default::default()
+                }),
+                it == "1",
+            ))
+        });
+
+        run_loop();
+
+        let selector = ChapterSelector::new().range(mado::engine::ChapterRange::chapter(2.0, None));
+        model.emit(ChapterListMsg::Select(selector));
+        run_loop();
+
+        let active = chapters.map(|it| vec.get(&it).unwrap().active());
+        assert_eq!(active, [false, true, true]);
+    }
+    fn chapter(
+        index: usize,
+        number: &str,
+        language: &str,
+        uploaded_at: Option<&str>,
+    ) -> Arc<ChapterInfo> {
+        Arc::new(ChapterInfo {
+            index: Some(index),
+            id: index.to_string(),
+            title: Some(format!("title {}", index)),
+            chapter: Some(number.to_string()),
+            volume: Some(if index < 3 { "1" } else { "2" }.to_string()),
+            scanlator: vec![format!("group {}", index % 2)],
+            language: language.to_string(),
+            uploaded_at: uploaded_at.map(ToString::to_string),
+        })
+    }
+
+    #[test]
+    fn test_filter() {
+        let chapter = chapter(1, "1", "en", None);
+        let filter = |filter: ChapterFilter| filter.matches(&chapter);
+
+        assert!(filter(ChapterFilter::default()));
+        assert!(filter(ChapterFilter {
+            language: Some("en".to_string()),
+            scanlator: Some("group 1".to_string()),
+            volume: Some("1".to_string()),
+            search: " TITLE 1 ".to_string(),
+        }));
+        assert!(!filter(ChapterFilter {
+            language: Some("id".to_string()),
+            ..Default::default()
+        }));
+        assert!(!filter(ChapterFilter {
+            scanlator: Some("group 0".to_string()),
+            ..Default::default()
+        }));
+        assert!(!filter(ChapterFilter {
+            volume: Some("2".to_string()),
+            ..Default::default()
+        }));
+        // the index isn't part of the displayed name.
+        assert!(!filter(ChapterFilter {
+            search: "0001".to_string(),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_sort() {
+        let first = chapter(1, "10", "en", Some("2022-01-02T00:00:00+00:00"));
+        let second = chapter(2, "9.5", "en", None);
+        let third = chapter(3, "extra", "en", Some("2022-01-01T00:00:00+00:00"));
+
+        let sort = |sort: ChapterSort| {
+            let mut chapters = [&first, &second, &third];
+            chapters.sort_by(|a, b| sort.compare(a, b));
+            chapters.map(|it| it.index.unwrap())
+        };
+
+        assert_eq!(sort(ChapterSort::Source), [1, 2, 3]);
+        assert_eq!(sort(ChapterSort::Number), [2, 1, 3]);
+        assert_eq!(sort(ChapterSort::Uploaded), [3, 1, 2]);
+    }
+
+    /// Rows of the chapter list, in no particular order.
+    fn rows(widget: &gtk::Widget) -> Vec<gtk::Grid> {
+        let mut rows = Vec::new();
+        let mut child = widget.first_child();
+        while let Some(it) = child {
+            match it.clone().downcast::<gtk::Grid>() {
+                Ok(grid) if ChapterListModel::get_check(&grid).is_some() => rows.push(grid),
+                _ => rows.extend(self::rows(&it)),
+            }
+            child = it.next_sibling();
+        }
+
+        rows
+    }
+
+    #[gtk::test]
+    fn test_sort_and_toggle() {
+        let vec = ListStore::default();
+
+        let window = gtk::ApplicationWindow::default();
+        let model = ChapterListModel::builder().launch(vec.base()).detach();
+        window.set_child(Some(model.widget()));
+        window.present();
+
+        let chapters = [
+            chapter(1, "3", "en", None),
+            chapter(2, "1", "en", None),
+            chapter(3, "2", "en", None),
+        ]
+        .map(|it| vec.push(CheckChapterInfo::new(it, false)));
+        run_loop();
+
+        // sorting shows other chapters in the rows that already exist.
+        model.emit(ChapterListMsg::Sort(ChapterSort::Number));
+        run_loop();
+
+        let rows = rows(model.widgets().list.upcast_ref());
+        assert_eq!(rows.len(), 3);
+
+        let second = format!("{}", vec.get(&chapters[1]).unwrap().info());
+        let row = rows
+            .iter()
+            .find(|it| ChapterListModel::get_label(it).unwrap().label() == second)
+            .expect("second chapter should be shown");
+
+        ChapterListModel::get_check(row).unwrap().set_active(true);
+        run_loop();
+
+        let active = chapters.map(|it| vec.get(&it).unwrap().active());
+        assert_eq!(active, [false, true, false]);
+    }
+
+    #[gtk::test]
+    fn test_filter_and_select() {
+        let vec = ListStore::default();
+
+        let window = gtk::ApplicationWindow::default();
+        let model = ChapterListModel::builder().launch(vec.base()).detach();
+        window.set_child(Some(model.widget()));
+
+        let chapters = [
+            chapter(1, "3", "en", None),
+            chapter(2, "1", "id", None),
+            chapter(3, "2", "en", None),
+            chapter(4, "4", "en", None),
+        ]
+        .map(|it| vec.push(CheckChapterInfo::new(it, false)));
+        run_loop();
+
+        let active = || chapters.map(|it| vec.get(&it).unwrap().active());
+        let visible = || {
+            model
+                .model()
+                .visible_chapters()
+                .iter()
+                .map(|it| it.index.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // "All languages", "en" and "id"
+        let languages = model.model().languages.clone();
+        assert_eq!(languages.model().unwrap().n_items(), 3);
+        languages.set_selected(1);
+        run_loop();
+        assert_eq!(model.model().filter().language.as_deref(), Some("en"));
+        assert_eq!(visible(), [1, 3, 4]);
+
+        model.emit(ChapterListMsg::SelectAll);
+        run_loop();
+        assert_eq!(active(), [true, false, true, true]);
+
+        model.emit(ChapterListMsg::Sort(ChapterSort::Number));
+        model.emit(ChapterListMsg::Reverse(true));
+        run_loop();
+        assert_eq!(visible(), [4, 1, 3]);
+
+        model.emit(ChapterListMsg::Invert);
+        model.emit(ChapterListMsg::Volume(Some("1".to_string())));
+        run_loop();
+        assert_eq!(active(), [false, false, false, false]);
+        assert_eq!(visible(), [1]);
+
+        model.emit(ChapterListMsg::Volume(None));
+        model.emit(ChapterListMsg::Language(None));
+        model.emit(ChapterListMsg::Reverse(false));
+        run_loop();
+        assert_eq!(visible(), [2, 3, 1, 4]);
+
+        let selection = model.model().selection_model.clone();
+        selection.select_item(1, true);
+        selection.select_item(2, false);
+        model.emit(ChapterListMsg::SelectRange);
+        run_loop();
+        assert_eq!(active(), [true, false, true, false]);
+
+        model.emit(ChapterListMsg::Search("TITLE 4".to_string()));
+        run_loop();
+        assert_eq!(visible(), [4]);
+
+        model.emit(ChapterListMsg::SelectNone);
+        run_loop();
+        // hidden chapters stay checked but aren't taken.
+        assert_eq!(active(), [true, false, true, false]);
+        assert!(model.model().checked_chapters().is_empty());
+
+        model.emit(ChapterListMsg::Search(String::new()));
+        run_loop();
+        assert_eq!(active(), [true, false, true, false]);
+        let checked = model.model().checked_chapters();
+        let checked = checked.iter().map(|it| it.index.unwrap());
+        assert_eq!(checked.collect::<Vec<_>>(), [1, 3]);
+
+        // choices follow the chapters.
+        vec.clear();
+        run_loop();
+        assert_eq!(languages.model().unwrap().n_items(), 1);
+        assert_eq!(model.model().filter(), ChapterFilter::default());
+    }
 }
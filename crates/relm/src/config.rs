@@ -0,0 +1,590 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use mado::core::http::HttpCache;
+use mado::engine::{DownloadOption, PathTemplate, TemplateError, TemplateKind};
+use serde::{Deserialize, Serialize};
+
+use crate::reader::{ReaderFit, ReaderMode};
+
+/// Directory name used inside the XDG directories.
+const APP_DIR: &str = "mado";
+
+/// How often [`ConfigFile::watch`] checks the file for changes.
+pub const CONFIG_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("{0}")]
+    IOError(#[from] std::io::Error),
+    #[error("invalid config: {0}")]
+    ParseError(#[from] toml::de::Error),
+    #[error("{0}")]
+    SerializeError(#[from] toml::ser::Error),
+    #[error("invalid template: {0}")]
+    TemplateError(#[from] TemplateError),
+}
+
+/// Application settings, stored as TOML.
+///
+/// Missing keys take their default value, so an empty file is valid.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub database: PathBuf,
+    /// Directories to load modules from.
+    pub module_dirs: Vec<PathBuf>,
+    /// Uuid of modules kept out of the module list.
+    pub disabled_modules: Vec<String>,
+    /// Where new downloads are saved unless another path is chosen.
+    pub download_dir: PathBuf,
+    /// Cached files that can be fetched again, like covers.
+    pub cache_dir: PathBuf,
+    pub sanitize: SanitizeConfig,
+    pub download: DownloadConfig,
+    pub http: HttpConfig,
+    pub template: TemplateConfig,
+    pub reader: ReaderConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SanitizeConfig {
+    pub windows: bool,
+    pub truncate: bool,
+    pub convert_to_unicode: bool,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadConfig {
+    /// Downloads running at once, unlimited if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// Downloads running at once from the same module.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_limit: Option<usize>,
+    pub retry_limit: usize,
+    /// Seconds before an image request times out.
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Applied right away from the settings page, edits to the file only on
+    /// startup.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Keep responses on disk and revalidate them instead of fetching them
+    /// again. Changes apply on startup.
+    pub cache: bool,
+    /// Where responses are kept, `http` in [`Config::cache_dir`] if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<PathBuf>,
+    /// Seconds cached responses stay fresh regardless of what the server
+    /// says.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<u64>,
+}
+
+/// How the reader shows pages, kept from the last time it was used.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReaderConfig {
+    pub mode: ReaderMode,
+    pub fit: ReaderFit,
+    /// Pages are turned right to left, like most manga.
+    pub rtl: bool,
+    /// Read progress is saved under this name, see [`Self::name`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl ReaderConfig {
+    /// Name read progress is saved under, the login name when not set, so
+    /// people sharing a library keep their own progress.
+    pub fn name(&self) -> String {
+        let login = || {
+            ["USER", "USERNAME"]
+                .into_iter()
+                .find_map(|it| std::env::var(it).ok())
+                .filter(|it| !it.is_empty())
+        };
+
+        self.name
+            .clone()
+            .filter(|it| !it.is_empty())
+            .or_else(login)
+            .unwrap_or_else(|| mado_sqlite::DEFAULT_READER.to_string())
+    }
+}
+
+/// Sources of the path templates, see [`PathTemplate`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemplateConfig {
+    pub manga: String,
+    pub chapter: String,
+    pub image: String,
+}
+
+impl TemplateConfig {
+    pub fn get(&self, kind: TemplateKind) -> &str {
+        match kind {
+            TemplateKind::Manga => &self.manga,
+            TemplateKind::Chapter => &self.chapter,
+            TemplateKind::Image => &self.image,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        for kind in TemplateKind::ALL {
+            PathTemplate::parse(kind, self.get(kind))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for TemplateConfig {
+    fn default() -> Self {
+        Self {
+            manga: mado::engine::DEFAULT_MANGA_TEMPLATE.to_string(),
+            chapter: mado::engine::DEFAULT_CHAPTER_TEMPLATE.to_string(),
+            image: mado::engine::DEFAULT_IMAGE_TEMPLATE.to_string(),
+        }
+    }
+}
+
+fn data_dir() -> PathBuf {
+    // falls back to the working directory.
+    dirs_next::data_dir()
+        .map(|it| it.join(APP_DIR))
+        .unwrap_or_default()
+}
+
+/// Database in the working directory, used before the data directory was,
+/// kept when there's no database in the data directory yet.
+fn database_path() -> PathBuf {
+    let path = data_dir().join("data.db");
+    let legacy = std::env::current_dir().map(|it| it.join("data.db"));
+
+    match legacy {
+        Ok(legacy) if legacy.is_file() && !path.exists() => legacy,
+        _ => path,
+    }
+}
+
+/// Modules bundled with the source, only present when running from it.
+fn bundled_module_dir() -> Option<PathBuf> {
+    let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../deno/dist/module"));
+
+    dir.is_dir().then(|| dir.to_path_buf())
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let download_dir = dirs_next::download_dir()
+            .map(|it| it.join("Mado"))
+            .unwrap_or_else(|| "downloads".into());
+
+        Self {
+            database: database_path(),
+            // modules are installed to the first one.
+            module_dirs: std::iter::once(data_dir().join("modules"))
+                .chain(bundled_module_dir())
+                .collect(),
+            disabled_modules: Vec::new(),
+            download_dir,
+            cache_dir: dirs_next::cache_dir()
+                .map(|it| it.join(APP_DIR))
+                .unwrap_or_else(|| "cache".into()),
+            sanitize: Default::default(),
+            download: Default::default(),
+            http: Default::default(),
+            template: Default::default(),
+            reader: Default::default(),
+        }
+    }
+}
+
+impl Default for SanitizeConfig {
+    fn default() -> Self {
+        Self {
+            windows: true,
+            truncate: false,
+            convert_to_unicode: true,
+            replacement: "_".to_string(),
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            cache: true,
+            cache_dir: None,
+            cache_ttl: None,
+        }
+    }
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        let option = DownloadOption::default();
+
+        Self {
+            limit: None,
+            source_limit: None,
+            retry_limit: option.retry_limit(),
+            timeout: option.timeout().as_secs(),
+        }
+    }
+}
+
+impl Config {
+    /// Load config from `path`, a missing file gives the default config.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        match read_file(path)? {
+            Some(text) => Self::parse(&text),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(text)?;
+        config.template.validate()?;
+
+        Ok(config)
+    }
+
+    /// Directories modules are actually loaded from, `MADO_MODULE` replaces
+    /// [`Self::module_dirs`] when set.
+    pub fn module_roots(&self) -> Vec<PathBuf> {
+        match std::env::var_os("MADO_MODULE") {
+            Some(dir) => vec![dir.into()],
+            None => self.module_dirs.clone(),
+        }
+    }
+
+    /// Cache for the http client, `MADO_HTTP_CACHE` and
+    /// `MADO_HTTP_CACHE_TTL` override the directory and ttl, the first
+    /// also turns the cache on.
+    pub fn http_cache(&self) -> Option<HttpCache> {
+        let http = &self.http;
+        let dir = match std::env::var_os("MADO_HTTP_CACHE") {
+            Some(dir) => PathBuf::from(dir),
+            None if http.cache => http
+                .cache_dir
+                .clone()
+                .unwrap_or_else(|| self.cache_dir.join("http")),
+            None => return None,
+        };
+
+        let ttl = match std::env::var("MADO_HTTP_CACHE_TTL") {
+            Ok(ttl) => ttl.parse().ok(),
+            Err(_) => http.cache_ttl,
+        };
+
+        Some(HttpCache::new(dir).with_force_ttl(ttl.map(Duration::from_secs)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let text = toml::to_string_pretty(self)?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // written to the side first so a crash never leaves half a config.
+        let part = path.with_extension("toml.part");
+        std::fs::write(&part, text)?;
+        std::fs::rename(&part, path)?;
+
+        Ok(())
+    }
+
+    /// Update `option` and its scheduler option to match this config.
+    pub fn apply(&self, option: &DownloadOption) {
+        let sanitize = &self.sanitize;
+        option.set_sanitize_windows(sanitize.windows);
+        option.set_sanitize_truncate(sanitize.truncate);
+        option.set_sanitize_convert_to_unicode(sanitize.convert_to_unicode);
+        option.set_sanitize_replacement(sanitize.replacement.clone());
+
+        let download = &self.download;
+        option.set_download_limit(download.limit.unwrap_or(usize::MAX));
+        option
+            .scheduler()
+            .set_source_limit(download.source_limit.unwrap_or(usize::MAX));
+        option.set_retry_limit(download.retry_limit);
+        option.set_timeout(Duration::from_secs(download.timeout));
+
+        for kind in TemplateKind::ALL {
+            if let Err(err) = option.set_template(kind, self.template.get(kind)) {
+                tracing::error!("invalid {} template: {}", kind, err);
+            }
+        }
+    }
+}
+
+/// Contents of the file at `path`, `None` when it doesn't exist.
+fn read_file(path: &Path) -> Result<Option<String>, std::io::Error> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Config backed by a file, kept in sync with a [`DownloadOption`].
+#[derive(Debug)]
+pub struct ConfigFile {
+    path: PathBuf,
+    option: DownloadOption,
+    config: Mutex<Config>,
+    /// Contents of the file when last loaded or saved. Changes are found
+    /// by contents, timestamps can be too coarse to tell writes apart.
+    loaded: Mutex<Option<String>>,
+}
+
+impl ConfigFile {
+    /// `MADO_CONFIG`, or `mado/config.toml` in the XDG config directory.
+    pub fn default_path() -> PathBuf {
+        match std::env::var_os("MADO_CONFIG") {
+            Some(path) => path.into(),
+            None => dirs_next::config_dir()
+                .map(|it| it.join(APP_DIR))
+                .unwrap_or_default()
+                .join("config.toml"),
+        }
+    }
+
+    /// Load config at `path` and apply it to `option`.
+    pub fn open(path: impl Into<PathBuf>, option: DownloadOption) -> Result<Self, ConfigError> {
+        let path = path.into();
+        let loaded = read_file(&path)?;
+        let config = match &loaded {
+            Some(text) => Config::parse(text)?,
+            None => Config::default(),
+        };
+
+        Ok(Self::with_loaded(path, config, option, loaded))
+    }
+
+    /// Use `config` for the file at `path` and apply it to `option`, the
+    /// file is only read again once it changes.
+    ///
+    /// Used when the file can't be loaded, it's left as is until fixed or
+    /// saved from the app.
+    pub fn with_config(path: impl Into<PathBuf>, config: Config, option: DownloadOption) -> Self {
+        let path = path.into();
+        let loaded = read_file(&path).unwrap_or_default();
+
+        Self::with_loaded(path, config, option, loaded)
+    }
+
+    fn with_loaded(
+        path: PathBuf,
+        config: Config,
+        option: DownloadOption,
+        loaded: Option<String>,
+    ) -> Self {
+        config.apply(&option);
+
+        Self {
+            path,
+            option,
+            config: Mutex::new(config),
+            loaded: Mutex::new(loaded),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn config(&self) -> Config {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Change config with `fun`, then save and apply it.
+    pub fn update(&self, fun: impl FnOnce(&mut Config)) -> Result<(), ConfigError> {
+        let mut config = self.config.lock().unwrap();
+        let mut new = config.clone();
+        fun(&mut new);
+
+        new.template.validate()?;
+        new.save(&self.path)?;
+        new.apply(&self.option);
+        *self.loaded.lock().unwrap() = read_file(&self.path)?;
+        *config = new;
+
+        Ok(())
+    }
+
+    /// Load and apply the file again if it changed since last loaded.
+    ///
+    /// Returns whether the config was reloaded.
+    pub fn reload(&self) -> Result<bool, ConfigError> {
+        let current = read_file(&self.path)?;
+        let mut loaded = self.loaded.lock().unwrap();
+        if *loaded == current {
+            return Ok(false);
+        }
+
+        let config = match &current {
+            Some(text) => Config::parse(text)?,
+            None => Config::default(),
+        };
+        // only marked as loaded when valid, so a broken file is reported
+        // until it's fixed.
+        *loaded = current;
+        config.apply(&self.option);
+        *self.config.lock().unwrap() = config;
+
+        Ok(true)
+    }
+
+    /// Reload the file whenever it changes, until dropped.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        loop {
+            mado::engine::timer::sleep(interval).await;
+
+            match self.reload() {
+                Ok(true) => tracing::info!("reloaded config {}", self.path.display()),
+                Ok(false) => {}
+                Err(err) => tracing::error!("error reloading {}: {}", self.path.display(), err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+
+        std::fs::write(&path, "download_dir = \"dl\"\n[download]\nlimit = 2\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.download_dir, PathBuf::from("dl"));
+        assert_eq!(config.download.limit, Some(2));
+        assert_eq!(config.sanitize, SanitizeConfig::default());
+
+        let mut config = Config::default();
+        config.http.proxy = Some("http://localhost:8080".to_string());
+        config.reader.name = Some("reader".to_string());
+        config.save(&path).unwrap();
+        assert_eq!(Config::load(&path).unwrap(), config);
+        assert_eq!(config.reader.name(), "reader");
+
+        std::fs::write(&path, "[template]\nimage = \"{manga}\"").unwrap();
+        assert!(matches!(
+            Config::load(&path),
+            Err(ConfigError::TemplateError(_))
+        ));
+
+        std::fs::write(&path, "download = 1").unwrap();
+        assert!(matches!(
+            Config::load(&path),
+            Err(ConfigError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn apply_test() {
+        let option = DownloadOption::default();
+        let mut config = Config::default();
+        config.sanitize.convert_to_unicode = false;
+        config.sanitize.replacement = "-".to_string();
+        config.download.limit = Some(3);
+        config.download.retry_limit = 1;
+        config.download.timeout = 30;
+        config.template.image = "page {page}".to_string();
+
+        config.apply(&option);
+        assert_eq!(option.sanitize_filename("a?b"), "a-b");
+        assert_eq!(option.scheduler().download_limit(), 3);
+        assert_eq!(option.scheduler().source_limit(), usize::MAX);
+        assert_eq!(option.retry_limit(), 1);
+        assert_eq!(option.timeout(), Duration::from_secs(30));
+        assert_eq!(option.image_filename(1, "png"), "page 1.png");
+    }
+
+    #[test]
+    fn http_cache_test() {
+        let mut config = Config::default();
+        config.cache_dir = "cache".into();
+
+        let cache = config.http_cache().unwrap();
+        assert_eq!(cache.directory(), Path::new("cache/http"));
+        assert_eq!(cache.force_ttl(), None);
+
+        config.http.cache_dir = Some("responses".into());
+        config.http.cache_ttl = Some(60);
+        let cache = config.http_cache().unwrap();
+        assert_eq!(cache.directory(), Path::new("responses"));
+        assert_eq!(cache.force_ttl(), Some(Duration::from_secs(60)));
+
+        config.http.cache = false;
+        assert!(config.http_cache().is_none());
+    }
+
+    #[test]
+    fn file_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mado").join("config.toml");
+        let option = DownloadOption::default();
+
+        let file = ConfigFile::open(&path, option.clone()).unwrap();
+        assert!(!file.reload().unwrap());
+
+        file.update(|it| it.download.limit = Some(1)).unwrap();
+        assert_eq!(option.scheduler().download_limit(), 1);
+        assert!(!file.reload().unwrap());
+
+        let mut config = file.config();
+        config.download.limit = Some(4);
+        config.save(&path).unwrap();
+
+        assert!(file.reload().unwrap());
+        assert_eq!(file.config().download.limit, Some(4));
+        assert_eq!(option.scheduler().download_limit(), 4);
+
+        // the same contents aren't loaded again.
+        config.save(&path).unwrap();
+        assert!(!file.reload().unwrap());
+    }
+
+    #[test]
+    fn broken_file_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let option = DownloadOption::default();
+
+        std::fs::write(&path, "download = 1").unwrap();
+        assert!(ConfigFile::open(&path, option.clone()).is_err());
+
+        let file = ConfigFile::with_config(&path, Config::default(), option.clone());
+        assert_eq!(file.config(), Config::default());
+        assert!(!file.reload().unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "download = 1");
+
+        // picked up once fixed.
+        std::fs::write(&path, "[download]\nlimit = 2").unwrap();
+        assert!(file.reload().unwrap());
+        assert_eq!(option.scheduler().download_limit(), 2);
+    }
+}
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use gtk::prelude::*;
 use mado::engine::path::Utf8PathBuf;
-use mado::engine::DownloadInfo;
+use mado::engine::{DownloadId, DownloadInfo};
 use relm4::{
     Component, ComponentController, ComponentParts, ComponentSender, Controller, SimpleComponent,
 };
@@ -15,12 +15,16 @@ use crate::task_list::TaskListModel;
 #[derive(Debug)]
 pub enum DownloadMsg {
     CreateDownloadView(Arc<DownloadInfo>),
+    RemoveDownloadView(ListStoreIndex),
     OrderChanged(ListStoreIndex),
     PauseSelected,
     ResumeSelected,
+    RetryFailedSelected,
+    RemoveSelected { delete_files: bool },
     MoveUp,
     MoveDown,
     OpenMangaSelected,
+    ReadSelected,
 }
 
 #[derive(Debug)]
@@ -29,6 +33,11 @@ pub enum DownloadOutputMsg {
         url: mado_core::Url,
         path: Utf8PathBuf,
     },
+    RemoveDownloads {
+        ids: Vec<DownloadId>,
+        delete_files: bool,
+    },
+    Read(Arc<DownloadInfo>),
 }
 
 #[derive(Copy, Clone)]
@@ -43,7 +52,7 @@ pub struct DownloadModel {
 }
 
 impl DownloadModel {
-    pub fn resume(&mut self, resume: bool) {
+    fn for_each_selected(&self, mut f: impl FnMut(&DownloadItem)) {
         let selection = &self.task_list.model().selection;
 
         let model = match selection.model() {
@@ -57,12 +66,29 @@ impl DownloadModel {
 
             if selection.contains(index as u32) {
                 if let Some(it) = self.list.get_by_object(&it) {
-                    it.info().resume(resume);
+                    f(&it);
                 }
             }
         }
     }
 
+    pub fn resume(&mut self, resume: bool) {
+        self.for_each_selected(|it| it.info().resume(resume));
+    }
+
+    pub fn selected_ids(&self) -> Vec<DownloadId> {
+        let mut ids = Vec::new();
+        self.for_each_selected(|it| ids.push(it.info().id()));
+
+        ids
+    }
+
+    pub fn retry_failed(&mut self) {
+        self.for_each_selected(|it| {
+            it.info().retry_failed();
+        });
+    }
+
     pub fn move_selected(&mut self, direction: DownloadMoveDirection) {
         let selection = &self.task_list.model().selection;
 
@@ -175,6 +201,12 @@ impl DownloadModel {
         })
     }
 
+    pub fn read_selected(&self) -> Option<DownloadOutputMsg> {
+        Self::open_manga_selected_static(&self.task_list.model().selection, &self.list, |it| {
+            Some(DownloadOutputMsg::Read(it.info().clone()))
+        })
+    }
+
     pub fn is_selected_only(&self, size: u64) -> bool {
         self.task_list.model().selection.selection().size() == size
     }
@@ -209,6 +241,7 @@ impl SimpleComponent for DownloadModel {
         let widgets = view_output!();
 
         let open_manga = widgets.open_manga.clone();
+        let read = widgets.read.clone();
 
         let list = model.list.clone();
         model
@@ -223,6 +256,7 @@ impl SimpleComponent for DownloadModel {
                     .unwrap_or(false);
 
                 open_manga.set_sensitive(is_sensitive);
+                read.set_sensitive(selection.selection().size() == 1);
             });
 
         ComponentParts { model, widgets }
@@ -234,12 +268,20 @@ impl SimpleComponent for DownloadModel {
                 let index = self.list.push(DownloadItem::new(info.clone()));
 
                 info.connect_only(move |msg| match msg {
-                    mado::engine::DownloadInfoMsg::StatusChanged(_) => {}
+                    mado::engine::DownloadInfoMsg::StatusChanged(_)
+                    | mado::engine::DownloadInfoMsg::PriorityChanged(_)
+                    | mado::engine::DownloadInfoMsg::StartAtChanged(_) => {}
                     mado::engine::DownloadInfoMsg::OrderChanged(_) => {
                         sender.input(DownloadMsg::OrderChanged(index.clone()));
                     }
+                    mado::engine::DownloadInfoMsg::Removed => {
+                        sender.input(DownloadMsg::RemoveDownloadView(index.clone()));
+                    }
                 });
             }
+            DownloadMsg::RemoveDownloadView(index) => {
+                self.list.remove(index);
+            }
             DownloadMsg::OrderChanged(index) => {
                 self.list.notify_changed(&index);
             }
@@ -249,6 +291,17 @@ impl SimpleComponent for DownloadModel {
             DownloadMsg::ResumeSelected => {
                 self.resume(true);
             }
+            DownloadMsg::RetryFailedSelected => {
+                self.retry_failed();
+            }
+            DownloadMsg::RemoveSelected { delete_files } => {
+                let ids = self.selected_ids();
+                if !ids.is_empty() {
+                    sender
+                        .output(DownloadOutputMsg::RemoveDownloads { ids, delete_files })
+                        .ok();
+                }
+            }
             DownloadMsg::MoveUp => {
                 self.move_selected(DownloadMoveDirection::Up);
             }
@@ -260,6 +313,11 @@ impl SimpleComponent for DownloadModel {
                     sender.output(msg).ok();
                 }
             }
+            DownloadMsg::ReadSelected => {
+                if let Some(msg) = self.read_selected() {
+                    sender.output(msg).ok();
+                }
+            }
         }
     }
 
@@ -285,6 +343,30 @@ impl SimpleComponent for DownloadModel {
                     }
                 },
 
+                #[name = "retry_failed_button"]
+                append = &gtk::Button {
+                    set_label: "Retry Failed",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(DownloadMsg::RetryFailedSelected);
+                    }
+                },
+
+                #[name = "remove_button"]
+                append = &gtk::Button {
+                    set_label: "Remove",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(DownloadMsg::RemoveSelected { delete_files: false });
+                    }
+                },
+
+                #[name = "remove_with_files_button"]
+                append = &gtk::Button {
+                    set_label: "Remove with Files",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(DownloadMsg::RemoveSelected { delete_files: true });
+                    }
+                },
+
                 #[name = "move_up_button"]
                 append = &gtk::Button {
                     set_label: "Move Up",
@@ -309,6 +391,16 @@ impl SimpleComponent for DownloadModel {
                     connect_clicked[sender] => move |_| {
                         sender.input(DownloadMsg::OpenMangaSelected);
                     }
+                },
+
+                #[name = "read"]
+                append = &gtk::Button {
+                    set_label: "Read",
+                    #[track(model.is_selected_only(1) == read.is_sensitive())]
+                    set_sensitive: model.is_selected_only(1),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(DownloadMsg::ReadSelected);
+                    }
                 }
             },
 
@@ -402,6 +494,26 @@ mod tests {
         assert!(second.status().is_paused());
     }
 
+    #[gtk::test]
+    pub fn retry_failed_test() {
+        let model = DownloadModel::builder().launch(()).detach();
+
+        let state = State::new(model);
+
+        let first = state.create_info(1);
+        first.set_status(mado::engine::DownloadStatus::finished_with_errors(1));
+
+        state.emit_create(first.clone());
+        run_loop();
+
+        state.selection().select_item(0, false);
+
+        state.model.widgets().retry_failed_button.emit_clicked();
+        run_loop();
+
+        assert!(first.status().is_resumed());
+    }
+
     #[gtk::test]
     pub fn sort_test() {
         let model = DownloadModel::builder().launch(()).detach();
@@ -560,11 +672,19 @@ mod tests {
         let (url, path) = rt.block_on(async {
             match try_recv(&rx).await.unwrap() {
                 DownloadOutputMsg::OpenManga { url, path } => (url, path),
-                // _ => unreachable!(),
+                _ => unreachable!(),
             }
         });
 
         assert_eq!(url.to_string(), "https://127.0.0.1/");
         assert_eq!(path.to_string(), "path-2");
+
+        state.model.emit(DownloadMsg::ReadSelected);
+        run_loop();
+
+        match rt.block_on(try_recv(&rx)).unwrap() {
+            DownloadOutputMsg::Read(info) => assert_eq!(info.id(), dl2.id()),
+            _ => unreachable!(),
+        }
     }
 }
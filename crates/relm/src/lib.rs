@@ -1,7 +1,12 @@
 pub mod app;
 pub mod chapter_list;
+pub mod config;
 pub mod download;
+pub mod library;
 pub mod manga_info;
+pub mod modules;
+pub mod reader;
+pub mod settings;
 pub mod task_list;
 pub mod task;
 pub mod list_store;
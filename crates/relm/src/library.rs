@@ -0,0 +1,411 @@
+use gtk::prelude::*;
+use mado::core::{url::Url, Uuid};
+use mado::engine::{
+    path::Utf8PathBuf, AnyObserverHandleSend, DownloadChapterInfoMsg, DownloadId, DownloadInfo,
+    MadoEngineState, MadoEngineStateMsg,
+};
+use relm4::{Component, ComponentParts, ComponentSender};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Downloads of the same manga are shown as one entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MangaKey {
+    Url(Uuid, Url),
+    /// Downloads without a link are grouped by their directory.
+    Path(Utf8PathBuf),
+}
+
+impl MangaKey {
+    pub fn new(info: &DownloadInfo) -> Self {
+        match info.url() {
+            Some(url) => Self::Url(*info.module_uuid(), url.clone()),
+            None => Self::Path(info.path().clone()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LibraryMsg {
+    Add(Arc<DownloadInfo>),
+    Remove(DownloadId),
+    /// Read state or status of a chapter of the download changed.
+    Changed(DownloadId),
+    Read(MangaKey),
+    Open(MangaKey),
+}
+
+#[derive(Debug)]
+pub enum LibraryOutput {
+    Read(Arc<DownloadInfo>),
+    OpenManga { url: Url, path: Utf8PathBuf },
+}
+
+struct LibraryEntry {
+    key: MangaKey,
+    downloads: Vec<Arc<DownloadInfo>>,
+    row: gtk::Box,
+    title: gtk::Label,
+    chapters: gtk::Label,
+    unread: gtk::Label,
+}
+
+impl LibraryEntry {
+    fn new(key: MangaKey, sender: &ComponentSender<LibraryModel>) -> Self {
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+
+        let title = gtk::Label::new(None);
+        title.set_hexpand(true);
+        title.set_halign(gtk::Align::Start);
+        row.append(&title);
+
+        let chapters = gtk::Label::new(None);
+        row.append(&chapters);
+
+        let unread = gtk::Label::new(None);
+        row.append(&unread);
+
+        let read = gtk::Button::with_label("Read");
+        read.connect_clicked({
+            let sender = sender.clone();
+            let key = key.clone();
+            move |_| sender.input(LibraryMsg::Read(key.clone()))
+        });
+        row.append(&read);
+
+        // manga info needs a link to fetch.
+        let open = gtk::Button::with_label("Open");
+        open.set_sensitive(matches!(key, MangaKey::Url(..)));
+        open.connect_clicked({
+            let sender = sender.clone();
+            let key = key.clone();
+            move |_| sender.input(LibraryMsg::Open(key.clone()))
+        });
+        row.append(&open);
+
+        Self {
+            key,
+            downloads: Vec::new(),
+            row,
+            title,
+            chapters,
+            unread,
+        }
+    }
+
+    fn unread_count(&self) -> usize {
+        self.downloads.iter().map(|it| it.unread_count()).sum()
+    }
+
+    fn update(&self) {
+        let title = self
+            .downloads
+            .first()
+            .map(|it| it.manga_title())
+            .unwrap_or_default();
+        self.title.set_text(title);
+
+        let downloaded = self
+            .downloads
+            .iter()
+            .flat_map(|it| it.chapters().iter())
+            .filter(|it| it.status().is_finished())
+            .count();
+        self.chapters.set_text(&format!("{downloaded} chapters"));
+
+        let unread = self.unread_count();
+        if unread > 0 {
+            self.unread.set_text(&format!("{unread} unread"));
+        } else {
+            self.unread.set_text("");
+        }
+    }
+
+    /// Download to resume reading from, the first one with unread chapters.
+    fn next_download(&self) -> Option<&Arc<DownloadInfo>> {
+        self.downloads
+            .iter()
+            .find(|it| it.unread_count() > 0)
+            .or_else(|| self.downloads.first())
+    }
+}
+
+type ChapterHandles = Arc<Mutex<HashMap<DownloadId, Vec<AnyObserverHandleSend>>>>;
+
+pub struct LibraryModel {
+    entries: Vec<LibraryEntry>,
+    list: gtk::ListBox,
+
+    state_handle: AnyObserverHandleSend,
+    /// Observers of the chapters of each download.
+    chapter_handles: ChapterHandles,
+}
+
+impl LibraryModel {
+    /// Observe read state and status of chapters in `info`, so the unread
+    /// count follows them.
+    fn connect_download(
+        info: &DownloadInfo,
+        handles: &ChapterHandles,
+        input: &relm4::Sender<LibraryMsg>,
+    ) {
+        let id = info.id();
+        let chapter_handles = info
+            .chapters()
+            .iter()
+            .map(|chapter| {
+                let input = input.clone();
+                let handle = chapter.connect_only(move |msg| match msg {
+                    DownloadChapterInfoMsg::StatusChanged(_)
+                    | DownloadChapterInfoMsg::ProgressChanged(_) => {
+                        input.send(LibraryMsg::Changed(id)).ok();
+                    }
+                    DownloadChapterInfoMsg::DownloadImagesChanged(_) => {}
+                });
+                handle.send_handle_any()
+            })
+            .collect();
+
+        handles.lock().unwrap().insert(id, chapter_handles);
+    }
+
+    fn entry(&self, key: &MangaKey) -> Option<&LibraryEntry> {
+        self.entries.iter().find(|it| it.key == *key)
+    }
+
+    fn entry_of(&self, id: DownloadId) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|it| it.downloads.iter().any(|it| it.id() == id))
+    }
+
+    fn add(&mut self, info: Arc<DownloadInfo>, sender: &ComponentSender<Self>) {
+        if self.entry_of(info.id()).is_some() {
+            return;
+        }
+
+        let key = MangaKey::new(&info);
+        let index = match self.entries.iter().position(|it| it.key == key) {
+            Some(index) => index,
+            None => {
+                let entry = LibraryEntry::new(key, sender);
+                self.list.append(&entry.row);
+                self.entries.push(entry);
+                self.entries.len() - 1
+            }
+        };
+
+        let entry = &mut self.entries[index];
+        entry.downloads.push(info);
+        entry.update();
+    }
+
+    fn remove(&mut self, id: DownloadId) {
+        let index = match self.entry_of(id) {
+            Some(it) => it,
+            None => return,
+        };
+
+        let entry = &mut self.entries[index];
+        entry.downloads.retain(|it| it.id() != id);
+
+        if entry.downloads.is_empty() {
+            let entry = self.entries.remove(index);
+            if let Some(row) = entry.row.parent() {
+                self.list.remove(&row);
+            }
+        } else {
+            entry.update();
+        }
+    }
+
+    /// Number of unread chapters of each manga, in the order they're shown.
+    pub fn unread_counts(&self) -> Vec<(MangaKey, usize)> {
+        self.entries
+            .iter()
+            .map(|it| (it.key.clone(), it.unread_count()))
+            .collect()
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for LibraryModel {
+    type Widgets = LibraryWidgets;
+    type Init = Arc<MadoEngineState>;
+
+    type Input = LibraryMsg;
+    type Output = LibraryOutput;
+
+    type CommandOutput = ();
+
+    fn init(
+        state: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let input = sender.input_sender().clone();
+        let chapter_handles = ChapterHandles::default();
+        let handles = chapter_handles.clone();
+        let state_handle = state.connect(move |msg| match msg {
+            MadoEngineStateMsg::Download(info) => {
+                Self::connect_download(info, &handles, &input);
+                input.send(LibraryMsg::Add(info.clone())).ok();
+            }
+            MadoEngineStateMsg::RemoveDownload(info) => {
+                let removed = handles.lock().unwrap().remove(&info.id());
+                for it in removed.into_iter().flatten() {
+                    it.disconnect();
+                }
+
+                input.send(LibraryMsg::Remove(info.id())).ok();
+            }
+            MadoEngineStateMsg::PushModule(_)
+            | MadoEngineStateMsg::RemoveModule(_)
+            | MadoEngineStateMsg::ModuleFile(_) => {}
+        });
+
+        let model = Self {
+            entries: Vec::new(),
+            list: gtk::ListBox::new(),
+            state_handle: state_handle.send_handle_any(),
+            chapter_handles,
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>, _: &Self::Root) {
+        match msg {
+            LibraryMsg::Add(info) => self.add(info, &sender),
+            LibraryMsg::Remove(id) => self.remove(id),
+            LibraryMsg::Changed(id) => {
+                if let Some(index) = self.entry_of(id) {
+                    self.entries[index].update();
+                }
+            }
+            LibraryMsg::Read(key) => {
+                if let Some(info) = self.entry(&key).and_then(|it| it.next_download()) {
+                    sender.output(LibraryOutput::Read(info.clone())).ok();
+                }
+            }
+            LibraryMsg::Open(key) => {
+                let path = match self.entry(&key).and_then(|it| it.downloads.first()) {
+                    Some(it) => it.path().clone(),
+                    None => return,
+                };
+
+                if let MangaKey::Url(_, url) = key {
+                    sender.output(LibraryOutput::OpenManga { url, path }).ok();
+                }
+            }
+        }
+    }
+
+    fn shutdown(&mut self, _: &mut Self::Widgets, _: relm4::Sender<Self::Output>) {
+        self.state_handle.clone().disconnect();
+
+        for (_, handles) in self.chapter_handles.lock().unwrap().drain() {
+            for it in handles {
+                it.disconnect();
+            }
+        }
+    }
+
+    view! {
+        gtk::ScrolledWindow {
+            set_vexpand: true,
+            set_child: Some(&model.list),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use mado::engine::{DownloadRequest, DownloadRequestStatus, DownloadStatus};
+    use mado_core::{ChapterInfo, MangaInfo};
+
+    fn request(url: Option<&str>, chapters: &[&str]) -> DownloadRequest {
+        let mut module = mado_core::MockMadoModule::new();
+        module.expect_uuid().return_const(Uuid::from_u128(1));
+        module
+            .expect_domain()
+            .return_const(Url::parse("https://localhost").unwrap());
+        module.expect_name().return_const("module".to_string());
+
+        let chapters = chapters
+            .iter()
+            .map(|id| {
+                Arc::new(ChapterInfo {
+                    id: id.to_string(),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        DownloadRequest::new(
+            Arc::new(module),
+            Arc::new(MangaInfo {
+                title: "manga".to_string(),
+                ..Default::default()
+            }),
+            chapters,
+            "path".into(),
+            url.map(|it| Url::parse(it).unwrap()),
+            DownloadRequestStatus::Pause,
+        )
+    }
+
+    #[gtk::test]
+    fn test_unread() {
+        let state = Arc::new(MadoEngineState::default());
+        let first = Url::parse("https://localhost/1").unwrap();
+        let second = Url::parse("https://localhost/2").unwrap();
+
+        state.download_request(request(Some(first.as_str()), &["1", "2"]));
+        let library = LibraryModel::builder().launch(state.clone()).detach();
+        state.download_request(request(Some(first.as_str()), &["3"]));
+        state.download_request(request(Some(second.as_str()), &["1"]));
+        run_loop();
+
+        let key = |url: &Url| MangaKey::Url(Uuid::from_u128(1), url.clone());
+        assert_eq!(
+            library.model().unread_counts(),
+            [(key(&first), 0), (key(&second), 0)]
+        );
+
+        let tasks: Vec<_> = state.tasks().iter().cloned().collect();
+        for chapter in tasks[0].chapters().iter().chain(tasks[1].chapters()) {
+            chapter.set_status(DownloadStatus::finished());
+        }
+        run_loop();
+
+        let entry = library.model().entry(&key(&first)).unwrap();
+        assert_eq!(entry.unread.text(), "3 unread");
+        assert_eq!(entry.chapters.text(), "3 chapters");
+
+        tasks[0].chapters()[0].set_read(true);
+        tasks[1].chapters()[0].set_read(true);
+        run_loop();
+
+        let entry = library.model().entry(&key(&first)).unwrap();
+        assert_eq!(entry.unread.text(), "1 unread");
+        assert!(Arc::ptr_eq(entry.next_download().unwrap(), &tasks[0]));
+
+        futures::executor::block_on(state.remove_download(tasks[0].id(), false)).unwrap();
+        run_loop();
+
+        let entry = library.model().entry(&key(&first)).unwrap();
+        assert_eq!(entry.unread.text(), "");
+        assert_eq!(library.model().chapter_handles.lock().unwrap().len(), 2);
+
+        futures::executor::block_on(state.remove_download(tasks[2].id(), false)).unwrap();
+        run_loop();
+
+        assert_eq!(library.model().unread_counts(), [(key(&first), 0)]);
+    }
+}
@@ -1,13 +1,18 @@
 use futures::{SinkExt, StreamExt};
 use mado::core::{ArcMadoModule, DefaultMadoModuleMap, MutexMadoModuleMap};
 use mado::engine::{
-    path::Utf8PathBuf, MadoEngine, MadoEngineState, MadoModuleLoader, ModuleLoadError,
+    path::Utf8PathBuf, DownloadOption, MadoEngine, MadoEngineState, MadoModuleLoader,
+    ModuleLoadError,
 };
-use mado_relm::AppModel;
+use mado_relm::config::{Config, ConfigFile, CONFIG_WATCH_INTERVAL};
+use mado_relm::{AppInit, AppModel};
 use relm4::RelmApp;
 use tracing_subscriber::{util::SubscriberInitExt, EnvFilter};
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 pub enum LoaderMsg {
     Load(
@@ -16,35 +21,134 @@ pub enum LoaderMsg {
     ),
 }
 pub struct Loader {
-    root: Utf8PathBuf,
+    /// Module folders are read from the config on every scan.
+    config: Arc<ConfigFile>,
     sender: futures::channel::mpsc::Sender<LoaderMsg>,
+    isolation: Option<Isolation>,
 }
-#[async_trait::async_trait]
-impl MadoModuleLoader for Loader {
-    async fn get_paths(&self) -> Vec<Utf8PathBuf> {
-        let mut dir = tokio::fs::read_dir(self.root.as_path()).await.unwrap();
 
-        let mut paths = Vec::new();
-        loop {
-            let it = dir.next_entry().await;
-            match it {
-                Ok(Some(it)) => {
-                    if it.path().is_file() {
-                        let it = Utf8PathBuf::from_path_buf(it.path());
-                        match it {
-                            Ok(it) => paths.push(it),
-                            Err(it) => tracing::error!("{:?} is not a valid utf8 path", it),
+/// Run every module file in its own runtime thread.
+pub struct Isolation {
+    state: Arc<MadoEngineState>,
+    option: mado_deno::IsolationOption,
+    handles: Mutex<HashMap<Utf8PathBuf, mado_deno::IsolatedModuleHandle>>,
+}
+
+impl Isolation {
+    async fn load(&self, path: Utf8PathBuf) -> Result<Vec<ArcMadoModule>, ModuleLoadError> {
+        let (tx, rx) = futures::channel::oneshot::channel();
+        let mut first = Some(tx);
+        let mut loaded = Vec::new();
+        let state = self.state.clone();
+        let key = path.clone();
+
+        let handle = mado_deno::spawn_isolated(
+            path.clone().into_std_path_buf(),
+            self.option.clone(),
+            move |event| match event {
+                mado_deno::IsolatedModuleEvent::Loaded(modules) => {
+                    let modules: Vec<ArcMadoModule> = modules
+                        .into_iter()
+                        .map(|it| Arc::new(it) as ArcMadoModule)
+                        .collect();
+                    loaded = modules.iter().map(|it| it.uuid()).collect();
+
+                    match first.take() {
+                        Some(tx) => {
+                            tx.send(Ok(modules)).ok();
                         }
+                        None => {
+                            for module in modules {
+                                if let Err(err) = state.push_module(module) {
+                                    tracing::error!("error re-registering {}: {}", path, err);
+                                }
+                            }
+                        }
+                    }
+                }
+                mado_deno::IsolatedModuleEvent::LoadError(err) => match first.take() {
+                    Some(tx) => {
+                        tx.send(Err(err.into())).ok();
+                    }
+                    None => tracing::error!("error reloading {}: {}", path, err),
+                },
+                mado_deno::IsolatedModuleEvent::Crashed { reason, restart } => {
+                    if restart {
+                        tracing::error!("{} crashed, restarting: {}", path, reason);
                     } else {
-                        continue;
+                        tracing::error!("{} crashed, giving up: {}", path, reason);
+                    }
+
+                    for uuid in loaded.drain(..) {
+                        state.remove_module(uuid);
                     }
                 }
-                Ok(None) => break,
+            },
+        )?;
+
+        // the engine closes modules of the previous load, so its runtime
+        // shouldn't come back.
+        if let Some(previous) = self.handles.lock().unwrap().insert(key, handle) {
+            previous.stop();
+        }
+
+        rx.await.map_err(anyhow::Error::from)?
+    }
+}
+impl Loader {
+    fn roots(&self) -> Vec<Utf8PathBuf> {
+        self.config
+            .config()
+            .module_roots()
+            .into_iter()
+            .filter_map(|it| match Utf8PathBuf::from_path_buf(it) {
+                Ok(it) => Some(it),
+                Err(it) => {
+                    tracing::error!("{:?} is not a valid utf8 path", it);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl MadoModuleLoader for Loader {
+    async fn get_paths(&self) -> Vec<Utf8PathBuf> {
+        let mut paths = Vec::new();
+
+        for root in self.roots() {
+            let mut dir = match tokio::fs::read_dir(root.as_path()).await {
+                Ok(dir) => dir,
                 Err(err) => {
-                    tracing::error!("error loading: {}", err);
+                    tracing::error!("error reading module directory {}: {}", root, err);
                     continue;
                 }
             };
+
+            loop {
+                let it = dir.next_entry().await;
+                match it {
+                    Ok(Some(it)) => {
+                        let is_script = it.path().extension() == Some(std::ffi::OsStr::new("js"));
+
+                        if it.path().is_file() && is_script {
+                            let it = Utf8PathBuf::from_path_buf(it.path());
+                            match it {
+                                Ok(it) => paths.push(it),
+                                Err(it) => tracing::error!("{:?} is not a valid utf8 path", it),
+                            }
+                        } else {
+                            continue;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(err) => {
+                        tracing::error!("error loading: {}", err);
+                        continue;
+                    }
+                };
+            }
         }
 
         paths
@@ -54,6 +158,10 @@ impl MadoModuleLoader for Loader {
         &self,
         path: Utf8PathBuf,
     ) -> Result<Vec<mado::core::ArcMadoModule>, ModuleLoadError> {
+        if let Some(isolation) = &self.isolation {
+            return isolation.load(path).await;
+        }
+
         let (tx, rx) = futures::channel::oneshot::channel();
 
         self.sender
@@ -92,6 +200,9 @@ async fn handle_loader_msg(loader: &mut mado_deno::ModuleLoader, msg: LoaderMsg)
     }
 }
 
+/// How long to wait for downloads and modules to stop before exiting anyway.
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
 pub struct DisplayInstant(std::time::Instant);
 impl std::fmt::Debug for DisplayInstant {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -99,6 +210,19 @@ impl std::fmt::Debug for DisplayInstant {
     }
 }
 
+fn http_client(config: &Config) -> mado::core::http::Client {
+    let mut client = mado::core::http::Client::from_env();
+
+    if let Some(proxy) = &config.http.proxy {
+        match client.clone().with_proxy(proxy) {
+            Ok(it) => client = it,
+            Err(err) => tracing::error!("invalid proxy {}: {}", proxy, err),
+        }
+    }
+
+    client.with_cache(config.http_cache())
+}
+
 pub fn main() {
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -118,7 +242,27 @@ pub fn main() {
     let _guard = runtime.enter();
     tracing::trace!("tokio runtime {time:?}");
 
-    let db = mado_sqlite::Database::open("data.db").unwrap();
+    let option = DownloadOption::default();
+    let config_path = ConfigFile::default_path();
+    let config = Config::load(&config_path).unwrap_or_else(|err| {
+        // a hand-edited file mustn't keep the app from starting.
+        tracing::error!("error loading {}: {}", config_path.display(), err);
+        Config::default()
+    });
+    let config = Arc::new(ConfigFile::with_config(config_path, config, option.clone()));
+    let Config {
+        database,
+        disabled_modules,
+        reader,
+        ..
+    } = config.config();
+    tracing::trace!("config {time:?}");
+
+    if let Some(parent) = database.parent() {
+        std::fs::create_dir_all(parent).unwrap();
+    }
+    let db = mado_sqlite::Database::open(database).unwrap();
+    let db = db.with_reader(reader.name());
     let channel = mado_sqlite::channel(db);
     tracing::trace!("sqlite {time:?}");
 
@@ -126,30 +270,68 @@ pub fn main() {
     let downloads = channel.load_connect(map.clone()).unwrap();
     tracing::trace!("downloads {time:?}");
 
-    let state = MadoEngineState::new(map, downloads, Default::default());
+    let state = MadoEngineState::new(map, downloads, option);
     channel.connect_only(&state);
 
+    // active hours are in local time, which changes with daylight saving.
+    let local_offset = mado::engine::LocalOffset::new(|time| {
+        let secs = time.duration_since(std::time::UNIX_EPOCH).ok()?.as_secs();
+        let local = gtk::glib::DateTime::from_unix_local(secs as i64).ok()?;
+        Some(local.utc_offset().as_seconds() as i32)
+    });
+    state
+        .option()
+        .scheduler()
+        .set_local_offset(Some(local_offset));
+
+    for it in disabled_modules {
+        match it.parse() {
+            Ok(uuid) => {
+                state.disable_module(uuid);
+            }
+            Err(err) => tracing::error!("invalid module uuid {}: {}", it, err),
+        }
+    }
+
     let mado = MadoEngine::new(state);
     let state = mado.state();
     tracing::trace!("state {time:?}");
 
     let (loader_tx, mut loader_rx) = futures::channel::mpsc::channel(5);
 
-    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../deno/dist/module");
-    let dir = std::env::var("MADO_MODULE").unwrap_or_else(|_| dir.to_string());
+    // shared by every module, so proxy changes apply to all of them.
+    let client = http_client(&config.config());
+    let limits = mado_deno::ModuleLimits {
+        max_heap_size: Some(512 * 1024 * 1024),
+        ..Default::default()
+    };
+
+    let isolation = std::env::var_os("MADO_MODULE_ISOLATION").map(|_| Isolation {
+        state: state.clone(),
+        option: mado_deno::IsolationOption {
+            limits: limits.clone(),
+            http_client: Some(client.clone()),
+            ..Default::default()
+        },
+        handles: Default::default(),
+    });
+
     let deno_loader = Loader {
-        root: Utf8PathBuf::from(dir),
+        config: config.clone(),
         sender: loader_tx,
+        isolation,
     };
 
     let handle = runtime.handle().clone();
+    let runtime_client = client.clone();
 
     std::thread::Builder::new()
         .name("deno-runtime".to_string())
         .spawn(move || {
             let handle = handle;
             let task = tokio::task::LocalSet::new();
-            let deno_runtime = mado_deno::Runtime::default();
+            let mut deno_runtime = mado_deno::Runtime::with_limits(limits);
+            deno_runtime.set_http_client(runtime_client);
             let mut deno_loader = mado_deno::ModuleLoader::from_runtime(deno_runtime);
 
             task.spawn_local(async move {
@@ -162,18 +344,40 @@ pub fn main() {
         })
         .unwrap();
 
-    tokio::spawn(mado.load_module(deno_loader));
+    let shutdown = mado.shutdown_handle();
+    let (loader, load) = mado.module_loader(deno_loader);
+    tokio::spawn(load);
     tokio::spawn(mado.run());
+    tokio::spawn(config.clone().watch(CONFIG_WATCH_INTERVAL));
     tracing::trace!("engine run {time:?}");
 
-    let _guard = scopeguard::guard(channel.sender(), |sender| {
-        sender.send(mado_sqlite::DbMsg::Close).unwrap();
-    });
-    runtime.spawn_blocking(|| {
+    let sender = channel.sender();
+    let db = runtime.spawn_blocking(|| {
         let mut channel = channel;
-        channel.run().unwrap();
+        channel.run()
+    });
+
+    let _shutdown = scopeguard::guard((shutdown, sender, db), |(shutdown, sender, db)| {
+        tracing::trace!("shutting down");
+        let stop = mado::engine::timer::timeout(SHUTDOWN_TIMEOUT, shutdown.shutdown());
+        if runtime.block_on(stop).is_err() {
+            tracing::error!("engine didn't stop in {:?}", SHUTDOWN_TIMEOUT);
+        }
+
+        // status changes sent by the engine are written before closing.
+        sender.send(mado_sqlite::DbMsg::Close).unwrap();
+        match runtime.block_on(db) {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => tracing::error!("error writing to database: {}", err),
+            Err(err) => tracing::error!("database thread stopped: {}", err),
+        }
     });
 
     tracing::trace!("running relm {time:?}");
-    RelmApp::new("").run::<AppModel>(state);
+    RelmApp::new("").run::<AppModel>(AppInit {
+        state,
+        config,
+        loader,
+        http_client: client,
+    });
 }
@@ -1,22 +1,39 @@
-use mado::core::ArcMadoModuleMap;
-
-use gtk::prelude::*;
-use mado::engine::DownloadOption;
-use std::sync::Arc;
+use gtk::{gdk_pixbuf::Pixbuf, prelude::*};
+use mado::engine::{
+    AnyObserverHandleSend, ChapterSelector, CoverCache, DownloadChapterInfoMsg, DownloadId,
+    DownloadInfo, DownloadOption, MadoEngineState, MadoEngineStateMsg,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{Arc, Mutex},
+};
 
 use crate::list_model::ListModelBaseExt;
-use crate::list_store::ListStore;
+use crate::list_store::{ListStore, ListStoreIndex};
 use crate::AbortOnDropHandle;
-use mado::core::{url::Url, ArcMadoModule, Error, MangaAndChaptersInfo};
+use mado::core::{url::Url, ArcMadoModule, Error, MangaAndChaptersInfo, Uuid};
 use mado::engine::{path::Utf8PathBuf, DownloadRequest, DownloadRequestStatus};
 
-use crate::chapter_list::{ChapterListModel, CheckChapterInfo};
+use crate::chapter_list::{ChapterBadge, ChapterListModel, ChapterListMsg, CheckChapterInfo};
 use relm4::{Component, ComponentController, ComponentParts, ComponentSender, SimpleComponent};
 
 #[derive(Debug)]
 pub enum MangaInfoMsg {
     Download,
+    /// Mark checked chapters as read or unread.
+    MarkRead(bool),
+    /// Check only shown chapters that were never downloaded.
+    SelectNew,
+    /// Download status of chapters `ids` of the manga at `url` may have
+    /// changed.
+    ChaptersChanged {
+        module: Uuid,
+        url: Url,
+        ids: HashSet<String>,
+    },
     DownloadPathChanged(String),
+    DefaultDownloadPathChanged(Utf8PathBuf),
     Error(mado::core::Error),
     /// Get info from string
     /// string should be convertible to URL
@@ -30,40 +47,80 @@ pub enum MangaInfoMsg {
         path: Option<String>,
         manga: MangaAndChaptersInfo,
     },
+    /// Thumbnail of the cover at `url` is ready at `path`.
+    CoverLoaded {
+        url: String,
+        path: Utf8PathBuf,
+    },
     Clear,
 }
 
 #[derive(Debug)]
 pub enum MangaInfoOutput {
     DownloadRequest(DownloadRequest),
+    /// Only downloaded chapters keep their read state.
+    MarkRead {
+        module: Uuid,
+        chapters: Vec<String>,
+        read: bool,
+    },
     Error(mado::core::Error),
 }
 
 pub struct MangaInfoModel {
-    modules: ArcMadoModuleMap,
+    state: Arc<MadoEngineState>,
     option: DownloadOption,
     chapters: ListStore<CheckChapterInfo>,
     download_path: relm4::Controller<DownloadPathModel>,
     chapter_list: relm4::Controller<ChapterListModel>,
     manga_info: Option<(ArcMadoModule, Url, Arc<MangaAndChaptersInfo>)>,
+    /// Row of each chapter of the current manga, by chapter id.
+    chapter_rows: HashMap<String, ListStoreIndex>,
     url: String,
     default_download_path: Utf8PathBuf,
+    covers: CoverCache,
+    cover: Option<Pixbuf>,
 
     current_handle: Option<AbortOnDropHandle<()>>,
+    cover_handle: Option<AbortOnDropHandle<()>>,
+    state_handle: AnyObserverHandleSend,
+    /// Observers of the chapters of each download.
+    chapter_handles: ChapterHandles,
+}
+
+type ChapterHandles = Arc<Mutex<HashMap<DownloadId, Vec<AnyObserverHandleSend>>>>;
+
+/// Height of cover thumbnails in pixels.
+pub const COVER_HEIGHT: u32 = 300;
+
+/// Scale the image at `cover` down to [`COVER_HEIGHT`] and save it as png.
+pub fn make_thumbnail(cover: &Path, thumbnail: &Path) -> Result<(), gtk::glib::Error> {
+    // covers are portrait, so the width rarely limits the scale.
+    let height = COVER_HEIGHT as i32;
+    let pixbuf = Pixbuf::from_file_at_scale(cover, height, height, true)?;
+
+    pixbuf.savev(thumbnail, "png", &[])
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum DownloadPath {
     FromGetInfo(String),
     FromUser(String),
 }
 
 impl DownloadPath {
-    pub fn join(&self, v: &str, option: &DownloadOption) -> Utf8PathBuf {
+    /// Directory of `manga`, paths chosen by the user get the manga
+    /// template appended.
+    pub fn join(
+        &self,
+        module: &ArcMadoModule,
+        manga: &str,
+        option: &DownloadOption,
+    ) -> Utf8PathBuf {
         match self {
             DownloadPath::FromGetInfo(path) => Utf8PathBuf::from(path),
             DownloadPath::FromUser(path) => {
-                Utf8PathBuf::from(path).join(option.sanitize_filename(v))
+                Utf8PathBuf::from(path).join(option.manga_path(module, manga))
             }
         }
     }
@@ -84,7 +141,7 @@ impl MangaInfoModel {
     fn get_module(&self, link: &str) -> Result<(Url, ArcMadoModule), Error> {
         let url = mado::core::url::fill_host(link)?;
 
-        let module = self.modules.get_by_url(url.clone());
+        let module = self.state.modules().get_by_url(url.clone());
 
         match module {
             Some(module) => Ok((url, module)),
@@ -137,22 +194,17 @@ impl MangaInfoModel {
     pub fn create_download_request(&self) -> Option<DownloadRequest> {
         let (module, url, manga_info) = self.manga_info.as_ref()?;
 
-        let mut selected = Vec::new();
-        self.chapters.for_each(|it| {
-            if it.active() {
-                selected.push(it.info().clone());
-            }
-        });
+        let selected = self.chapter_list.model().checked_chapters();
 
         if selected.is_empty() {
             return None;
         }
 
-        let path = self
-            .download_path
-            .model()
-            .path
-            .join(&manga_info.manga.title, &self.option);
+        let path =
+            self.download_path
+                .model()
+                .path
+                .join(module, &manga_info.manga.title, &self.option);
 
         let request = DownloadRequest::new(
             module.clone(),
@@ -166,6 +218,135 @@ impl MangaInfoModel {
         Some(request)
     }
 
+    /// Badges of chapters of the current manga in downloads, only chapters
+    /// in `ids` are looked up when given.
+    fn badges(&self, ids: Option<&HashSet<String>>) -> HashMap<String, ChapterBadge> {
+        let mut badges = HashMap::new();
+        let (module, url, _) = match &self.manga_info {
+            Some(it) => it,
+            None => return badges,
+        };
+
+        let uuid = module.uuid();
+        let tasks = self.state.tasks();
+        let infos = tasks
+            .iter()
+            .filter(|it| *it.module_uuid() == uuid && it.url() == Some(url));
+
+        for info in infos {
+            for chapter in info.chapters() {
+                let id = chapter.chapter_id();
+                if matches!(ids, Some(ids) if !ids.contains(id)) {
+                    continue;
+                }
+
+                let badge = ChapterBadge::from(&*chapter.status());
+                badges
+                    .entry(id.to_string())
+                    .and_modify(|it: &mut ChapterBadge| *it = it.furthest(badge))
+                    .or_insert(badge);
+            }
+        }
+
+        badges
+    }
+
+    /// Update badges of chapters `ids` of the current manga, only rows whose
+    /// badge changed are redrawn.
+    fn update_badges(&self, ids: &HashSet<String>) {
+        let badges = self.badges(Some(ids));
+
+        for id in ids {
+            let index = match self.chapter_rows.get(id) {
+                Some(it) => it,
+                None => continue,
+            };
+
+            let badge = badges.get(id).copied();
+            let changed = match self.chapters.get(index) {
+                Some(it) if it.badge() != badge => {
+                    it.set_badge(badge);
+                    true
+                }
+                _ => false,
+            };
+
+            if changed {
+                self.chapters.notify_changed(index);
+            }
+        }
+    }
+
+    /// Observe status of chapters in `info`, so their badges follow the
+    /// download.
+    fn connect_download(
+        info: &DownloadInfo,
+        handles: &ChapterHandles,
+        input: &relm4::Sender<MangaInfoMsg>,
+    ) {
+        let url = match info.url() {
+            Some(it) => it.clone(),
+            None => return,
+        };
+        let module = *info.module_uuid();
+
+        let mut chapter_handles = Vec::new();
+        for chapter in info.chapters() {
+            let input = input.clone();
+            let url = url.clone();
+            let id = chapter.chapter_id().to_string();
+
+            let handle = chapter.connect_only(move |msg| {
+                if let DownloadChapterInfoMsg::StatusChanged(_) = msg {
+                    let msg = MangaInfoMsg::ChaptersChanged {
+                        module,
+                        url: url.clone(),
+                        ids: HashSet::from([id.clone()]),
+                    };
+                    input.send(msg).ok();
+                }
+            });
+            chapter_handles.push(handle.send_handle_any());
+        }
+        handles.lock().unwrap().insert(info.id(), chapter_handles);
+
+        input.send(Self::chapters_changed(info, url)).ok();
+    }
+
+    fn chapters_changed(info: &DownloadInfo, url: Url) -> MangaInfoMsg {
+        MangaInfoMsg::ChaptersChanged {
+            module: *info.module_uuid(),
+            url,
+            ids: info
+                .chapters()
+                .iter()
+                .map(|it| it.chapter_id().to_string())
+                .collect(),
+        }
+    }
+
+    pub fn create_mark_read(&self, read: bool) -> Option<MangaInfoOutput> {
+        let (module, _, _) = self.manga_info.as_ref()?;
+
+        let chapters = self
+            .chapter_list
+            .model()
+            .checked_chapters()
+            .iter()
+            .map(|it| it.id.clone())
+            .collect::<Vec<_>>();
+
+        if chapters.is_empty() {
+            return None;
+        }
+
+        Some(MangaInfoOutput::MarkRead {
+            module: module.uuid(),
+            chapters,
+            read,
+        })
+    }
+
     pub async fn get_info(
         module: ArcMadoModule,
         url: Url,
@@ -189,6 +370,47 @@ impl MangaInfoModel {
         }
     }
 
+    fn spawn_cover(&mut self, sender: ComponentSender<Self>, module: ArcMadoModule, url: String) {
+        let covers = self.covers.clone();
+        let task = async move {
+            match Self::load_cover(&covers, &module, &url).await {
+                Ok(path) => sender.input(MangaInfoMsg::CoverLoaded { url, path }),
+                // the page is still usable without a cover.
+                Err(err) => tracing::error!("error loading cover {}: {}", url, err),
+            }
+        };
+
+        self.cover_handle = None;
+        self.cover_handle = Some(tokio::spawn(task).into());
+    }
+
+    async fn load_cover(
+        covers: &CoverCache,
+        module: &ArcMadoModule,
+        url: &str,
+    ) -> Result<Utf8PathBuf, Error> {
+        let thumbnail = covers.thumbnail_path(url, COVER_HEIGHT);
+
+        if tokio::fs::metadata(&thumbnail).await.is_err() {
+            let cover = covers.get(module, url).await?;
+            let path = thumbnail.clone();
+
+            // decoding and scaling the image blocks.
+            tokio::task::spawn_blocking(move || {
+                make_thumbnail(cover.as_std_path(), path.as_std_path())
+            })
+            .await
+            .map_err(|err| Error::ExternalError(err.into()))?
+            .map_err(|err| Error::ExternalError(err.into()))?;
+        }
+
+        Ok(thumbnail)
+    }
+
+    pub fn cover(&self) -> Option<&Pixbuf> {
+        self.cover.as_ref()
+    }
+
     pub fn set_download_path(&self, path: DownloadPath) {
         self.download_path
             .widgets()
@@ -206,9 +428,9 @@ impl MangaInfoModel {
 }
 
 pub struct MangaInfoInit {
-    pub modules: ArcMadoModuleMap,
+    pub state: Arc<MadoEngineState>,
     pub default_download_path: Utf8PathBuf,
-    pub option: DownloadOption,
+    pub covers: CoverCache,
 }
 
 #[relm4::component(pub)]
@@ -228,11 +450,34 @@ impl Component for MangaInfoModel {
     ) -> ComponentParts<Self> {
         let chapters = ListStore::default();
         let Self::Init {
-            modules,
+            state,
             default_download_path,
-            option,
+            covers,
         } = init;
 
+        // chapters change status while their download runs.
+        let input = sender.input_sender().clone();
+        let chapter_handles = ChapterHandles::default();
+        let handles = chapter_handles.clone();
+        let state_handle = state.connect(move |msg| match msg {
+            MadoEngineStateMsg::Download(info) => {
+                Self::connect_download(info, &handles, &input);
+            }
+            MadoEngineStateMsg::RemoveDownload(info) => {
+                let removed = handles.lock().unwrap().remove(&info.id());
+                for it in removed.into_iter().flatten() {
+                    it.disconnect();
+                }
+
+                if let Some(url) = info.url() {
+                    input.send(Self::chapters_changed(info, url.clone())).ok();
+                }
+            }
+            MadoEngineStateMsg::PushModule(_)
+            | MadoEngineStateMsg::RemoveModule(_)
+            | MadoEngineStateMsg::ModuleFile(_) => {}
+        });
+
         let chapter_list = ChapterListModel::builder().launch(chapters.base()).detach();
         let download_path = DownloadPathModel::builder()
             .launch(default_download_path.to_string())
@@ -241,15 +486,21 @@ impl Component for MangaInfoModel {
             });
 
         let model = Self {
-            modules,
-            option,
+            option: state.option(),
+            state,
             chapters,
             chapter_list,
             current_handle: None,
+            cover_handle: None,
             manga_info: None,
+            chapter_rows: HashMap::new(),
             url: "".to_string(),
             default_download_path,
+            covers,
+            cover: None,
             download_path,
+            state_handle: state_handle.send_handle_any(),
+            chapter_handles,
         };
 
         let widgets = view_output!();
@@ -269,6 +520,24 @@ impl Component for MangaInfoModel {
                     .output(MangaInfoOutput::DownloadRequest(request))
                     .ok();
             }
+            MangaInfoMsg::MarkRead(read) => {
+                if let Some(msg) = self.create_mark_read(read) {
+                    sender.output(msg).ok();
+                }
+            }
+            MangaInfoMsg::SelectNew => {
+                if let Some((module, url, _)) = &self.manga_info {
+                    let selector =
+                        ChapterSelector::new().not_downloaded(&self.state, &module.uuid(), url);
+                    self.chapter_list.emit(ChapterListMsg::Select(selector));
+                }
+            }
+            MangaInfoMsg::ChaptersChanged { module, url, ids } => {
+                let current = self.manga_info.as_ref().map(|it| (it.0.uuid(), &it.1));
+                if current == Some((module, &url)) {
+                    self.update_badges(&ids);
+                }
+            }
             MangaInfoMsg::GetInfo { url, path } => {
                 self.spawn_get_info(sender, url, path);
             }
@@ -279,6 +548,9 @@ impl Component for MangaInfoModel {
                 manga,
             } => {
                 let manga = Arc::new(manga);
+                if let Some(cover) = manga.manga.cover_link.clone() {
+                    self.spawn_cover(sender, module.clone(), cover);
+                }
                 self.manga_info.replace((module, url, manga.clone()));
 
                 if let Some(path) = path {
@@ -290,16 +562,44 @@ impl Component for MangaInfoModel {
                         self.set_download_path(path);
                     }
                 }
+                let badges = self.badges(None);
                 for it in manga.chapters.iter() {
-                    self.chapters.push(CheckChapterInfo::new(it.clone(), false));
+                    let chapter = CheckChapterInfo::new(it.clone(), false);
+                    chapter.set_badge(badges.get(&it.id).copied());
+
+                    let index = self.chapters.push(chapter);
+                    self.chapter_rows.insert(it.id.clone(), index);
                 }
             }
             MangaInfoMsg::DownloadPathChanged(path) => {
                 self.set_download_path(DownloadPath::FromUser(path));
             }
+            MangaInfoMsg::DefaultDownloadPathChanged(path) => {
+                // keep paths chosen by the user or the download.
+                let old = DownloadPath::FromUser(self.default_download_path.to_string());
+                if self.path() == old {
+                    self.set_download_path(DownloadPath::FromUser(path.to_string()));
+                }
+                self.default_download_path = path;
+            }
+            MangaInfoMsg::CoverLoaded { url, path } => {
+                // a cover of a previous manga may finish late.
+                let current = self.manga_and_chapters().map(|it| &it.manga.cover_link);
+                if current != Some(&Some(url)) {
+                    return;
+                }
+
+                match Pixbuf::from_file(&path) {
+                    Ok(pixbuf) => self.cover = Some(pixbuf),
+                    Err(err) => tracing::error!("error loading thumbnail {}: {}", path, err),
+                }
+            }
             MangaInfoMsg::Clear => {
                 self.chapters.clear();
+                self.chapter_rows.clear();
                 self.manga_info = None;
+                self.cover = None;
+                self.cover_handle = None;
             }
 
             MangaInfoMsg::Error(error) => {
@@ -308,6 +608,16 @@ impl Component for MangaInfoModel {
         }
     }
 
+    fn shutdown(&mut self, _: &mut Self::Widgets, _: relm4::Sender<Self::Output>) {
+        self.state_handle.clone().disconnect();
+
+        for (_, handles) in self.chapter_handles.lock().unwrap().drain() {
+            for it in handles {
+                it.disconnect();
+            }
+        }
+    }
+
     view! {
         gtk::Box {
             set_orientation: gtk::Orientation::Vertical,
@@ -338,9 +648,39 @@ impl Component for MangaInfoModel {
             append = &gtk::Box {
                 set_vexpand: true,
                 set_hexpand: true,
+                set_spacing: 5,
+                append: cover = &gtk::Picture {
+                    set_valign: gtk::Align::Start,
+                    #[watch]
+                    set_visible: model.cover.is_some(),
+                    #[watch]
+                    set_pixbuf: model.cover.as_ref(),
+                },
                 append: model.chapter_list.widget(),
             },
 
+            append = &gtk::Box {
+                set_spacing: 5,
+                append: select_new_button = &gtk::Button {
+                    set_label: "Select New",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(MangaInfoMsg::SelectNew);
+                    }
+                },
+                append: mark_read_button = &gtk::Button {
+                    set_label: "Mark Read",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(MangaInfoMsg::MarkRead(true));
+                    }
+                },
+                append: mark_unread_button = &gtk::Button {
+                    set_label: "Mark Unread",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(MangaInfoMsg::MarkRead(false));
+                    }
+                },
+            },
+
             append: model.download_path.widget(),
         }
     }
@@ -433,15 +773,20 @@ mod tests {
         let map = DefaultMadoModuleMap::new();
         let map = MutexMadoModuleMap::new(map);
         let map = Arc::new(map);
+        let state = Arc::new(MadoEngineState::new(
+            map.clone(),
+            Default::default(),
+            Default::default(),
+        ));
 
         let default_download_path = Utf8PathBuf::from("downloads");
 
         let (tx, rx) = relm4::channel();
         let model = MangaInfoModel::builder()
             .launch(MangaInfoInit {
-                modules: map.clone(),
+                state: state.clone(),
                 default_download_path: default_download_path.clone(),
-                option: Default::default(),
+                covers: CoverCache::new("covers"),
             })
             .forward(&tx, |msg| msg);
 
@@ -608,7 +953,33 @@ mod tests {
             assert_eq!(request.chapters().len(), 1);
             assert_eq!(request.module().domain(), module.domain());
 
+            state.download_request(request);
             run_loop();
+
+            model.model().chapters.for_each(|info| {
+                assert_eq!(info.badge(), Some(ChapterBadge::Queued));
+            });
+
+            model.widgets().select_new_button.emit_clicked();
+            run_loop();
+            model.model().chapters.for_each(|info| {
+                assert!(!info.active());
+            });
+
+            state.tasks()[0].chapters()[0].set_status(mado::engine::DownloadStatus::finished());
+            run_loop();
+            model.model().chapters.for_each(|info| {
+                assert_eq!(info.badge(), Some(ChapterBadge::Downloaded));
+            });
+
+            assert_eq!(model.model().chapter_handles.lock().unwrap().len(), 1);
+            let id = state.tasks()[0].id();
+            futures::executor::block_on(state.remove_download(id, false)).unwrap();
+            run_loop();
+            assert!(model.model().chapter_handles.lock().unwrap().is_empty());
+            model.model().chapters.for_each(|info| {
+                assert_eq!(info.badge(), None);
+            });
         }
         // end of test Download
 
@@ -646,6 +1017,22 @@ mod tests {
             assert_eq!(request.url(), Some(&get_info_link));
             assert_eq!(request.chapters().len(), 1);
             assert_eq!(request.module().domain(), module.domain());
+
+            model.widgets().mark_read_button.emit_clicked();
+            run_loop();
+
+            match rt.block_on(try_recv(&rx)).unwrap() {
+                MangaInfoOutput::MarkRead {
+                    module,
+                    chapters,
+                    read,
+                } => {
+                    assert_eq!(module, mado_core::Uuid::from_u128(1));
+                    assert_eq!(chapters, ["1"]);
+                    assert!(read);
+                }
+                _ => unreachable!(),
+            }
         }
         // end of test DownloadPath::FromUser join with title
 
@@ -690,4 +1077,35 @@ mod tests {
             };
         }
     }
+
+    #[gtk::test]
+    fn test_cover() {
+        let temp = tempfile::tempdir().unwrap();
+        let cover = temp.path().join("cover.png");
+        let thumbnail = temp.path().join("thumbnail.png");
+
+        let pixbuf = Pixbuf::new(gtk::gdk_pixbuf::Colorspace::Rgb, false, 8, 400, 600).unwrap();
+        pixbuf.savev(&cover, "png", &[]).unwrap();
+
+        make_thumbnail(&cover, &thumbnail).unwrap();
+        let it = Pixbuf::from_file(&thumbnail).unwrap();
+        assert_eq!(it.height(), COVER_HEIGHT as i32);
+        assert_eq!(it.width(), 200);
+
+        let model = MangaInfoModel::builder()
+            .launch(MangaInfoInit {
+                state: Default::default(),
+                default_download_path: "downloads".into(),
+                covers: CoverCache::new("covers"),
+            })
+            .detach();
+
+        // no manga is shown, so the cover is outdated.
+        model.emit(MangaInfoMsg::CoverLoaded {
+            url: "cover".to_string(),
+            path: Utf8PathBuf::from_path_buf(thumbnail).unwrap(),
+        });
+        run_loop();
+        assert!(model.model().cover().is_none());
+    }
 }
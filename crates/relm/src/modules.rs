@@ -0,0 +1,461 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use gtk::prelude::*;
+use mado::core::{ArcMadoModule, MadoModuleMap, Uuid};
+use mado::engine::{
+    path::Utf8PathBuf, MadoEngineState, MadoEngineStateMsg, ModuleFile, ModuleFileStatus,
+    ModuleLoaderHandle,
+};
+use mado_deno::permission::ModulePermissions;
+use relm4::{ComponentParts, ComponentSender, SimpleComponent};
+
+use crate::config::ConfigFile;
+
+#[derive(Debug)]
+pub enum ModulesMsg {
+    /// Rebuild the list from the engine state.
+    Refresh,
+    Enable(Uuid, bool),
+    Reload(Utf8PathBuf),
+    /// Choose a file to install.
+    OpenInstall,
+    /// Copy the file and its permission manifest into the module folder
+    /// and load it, asking first if that would replace another module.
+    Install(PathBuf),
+    /// Install, replacing a module with the same filename.
+    Replace(PathBuf),
+}
+
+#[derive(Debug)]
+pub enum ModulesOutput {
+    Error(mado::core::Error),
+}
+
+pub struct ModulesInit {
+    pub state: Arc<MadoEngineState>,
+    pub loader: ModuleLoaderHandle,
+    pub config: Arc<ConfigFile>,
+}
+
+/// Page listing module files, their modules and load errors.
+pub struct ModulesModel {
+    state: Arc<MadoEngineState>,
+    loader: ModuleLoaderHandle,
+    config: Arc<ConfigFile>,
+    files: Vec<ModuleFile>,
+    list: gtk::ListBox,
+    dialog: gtk::FileChooserNative,
+}
+
+fn external_error(err: impl Into<anyhow::Error>) -> ModulesOutput {
+    ModulesOutput::Error(mado::core::Error::ExternalError(err.into()))
+}
+
+impl ModulesModel {
+    pub fn files(&self) -> &[ModuleFile] {
+        &self.files
+    }
+
+    fn module(&self, uuid: Uuid) -> Option<ArcMadoModule> {
+        self.state
+            .modules()
+            .get_by_uuid(uuid)
+            .or_else(|| self.state.disabled_module(uuid))
+    }
+
+    fn refresh(&mut self, sender: &ComponentSender<Self>) {
+        while let Some(row) = self.list.row_at_index(0) {
+            self.list.remove(&row);
+        }
+
+        self.files = self.state.module_files();
+        for file in &self.files {
+            self.list.append(&self.file_row(file, sender));
+        }
+    }
+
+    fn file_row(&self, file: &ModuleFile, sender: &ComponentSender<Self>) -> gtk::Box {
+        let row = gtk::Box::new(gtk::Orientation::Vertical, 5);
+
+        let header = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+        let path = gtk::Label::new(Some(file.path().as_str()));
+        path.set_hexpand(true);
+        path.set_halign(gtk::Align::Start);
+        header.append(&path);
+
+        let status = match file.status() {
+            ModuleFileStatus::Loading => "Loading",
+            ModuleFileStatus::Loaded => "Loaded",
+            ModuleFileStatus::Failed(_) => "Failed",
+        };
+        header.append(&gtk::Label::new(Some(status)));
+
+        let reload = gtk::Button::with_label("Reload");
+        reload.set_sensitive(*file.status() != ModuleFileStatus::Loading);
+        reload.connect_clicked({
+            let sender = sender.clone();
+            let path = file.path().to_owned();
+            move |_| sender.input(ModulesMsg::Reload(path.clone()))
+        });
+        header.append(&reload);
+        row.append(&header);
+
+        if let ModuleFileStatus::Failed(error) = file.status() {
+            let error = gtk::Label::new(Some(error));
+            error.add_css_class("error");
+            error.set_halign(gtk::Align::Start);
+            error.set_wrap(true);
+            error.set_selectable(true);
+            row.append(&error);
+        }
+
+        for uuid in file.modules() {
+            row.append(&self.module_row(*uuid, sender));
+        }
+
+        row
+    }
+
+    fn module_row(&self, uuid: Uuid, sender: &ComponentSender<Self>) -> gtk::Box {
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+        row.set_margin_start(20);
+
+        if let Some(module) = self.module(uuid) {
+            row.append(&gtk::Label::new(Some(module.name())));
+            row.append(&gtk::Label::new(Some(module.domain().as_str())));
+        }
+
+        let label = gtk::Label::new(Some(&uuid.to_string()));
+        label.set_hexpand(true);
+        label.set_halign(gtk::Align::Start);
+        label.set_selectable(true);
+        row.append(&label);
+
+        let enabled = gtk::Switch::new();
+        enabled.set_active(!self.state.is_module_disabled(uuid));
+        enabled.connect_active_notify({
+            let sender = sender.clone();
+            move |it| sender.input(ModulesMsg::Enable(uuid, it.is_active()))
+        });
+        row.append(&enabled);
+
+        row
+    }
+
+    fn enable(&self, uuid: Uuid, enable: bool, sender: &ComponentSender<Self>) {
+        if enable {
+            if let Err(err) = self.state.enable_module(uuid) {
+                sender.output(external_error(err)).ok();
+            }
+        } else {
+            self.state.disable_module(uuid);
+        }
+
+        let uuid = uuid.to_string();
+        let result = self.config.update(|it| {
+            it.disabled_modules.retain(|it| *it != uuid);
+            if !enable {
+                it.disabled_modules.push(uuid);
+            }
+        });
+
+        if let Err(err) = result {
+            sender.output(external_error(err)).ok();
+        }
+    }
+
+    fn load(&self, path: Utf8PathBuf, sender: &ComponentSender<Self>) {
+        if !self.loader.load(path) {
+            let err = anyhow::anyhow!("module loader has stopped");
+            sender.output(external_error(err)).ok();
+        }
+    }
+
+    fn install(&self, path: PathBuf, replace: bool, sender: &ComponentSender<Self>) {
+        match self.copy_module(&path, replace) {
+            Ok(Some(target)) => self.load(target, sender),
+            Ok(None) => self.confirm_replace(path, sender),
+            Err(err) => {
+                sender.output(external_error(err)).ok();
+            }
+        }
+    }
+
+    /// Copy the module at `path` into the folder modules are loaded from,
+    /// `None` if it would replace an existing one without `replace`.
+    fn copy_module(&self, path: &Path, replace: bool) -> anyhow::Result<Option<Utf8PathBuf>> {
+        let dir = self
+            .config
+            .config()
+            .module_roots()
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no module folder is set"))?;
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("{} is not a file", path.display()))?;
+
+        // modules without a manifest aren't allowed anything.
+        let manifest = ModulePermissions::manifest_path(path);
+        if !manifest.is_file() {
+            anyhow::bail!(
+                "{} has no permission manifest, expected {}",
+                path.display(),
+                manifest.display()
+            );
+        }
+
+        let target = dir.join(name);
+        let target_manifest = ModulePermissions::manifest_path(&target);
+        if !replace && (target.exists() || target_manifest.exists()) {
+            return Ok(None);
+        }
+
+        std::fs::create_dir_all(&dir)?;
+        // the manifest goes first so the module is never loaded without it.
+        std::fs::copy(&manifest, &target_manifest)?;
+        std::fs::copy(path, &target)?;
+
+        Utf8PathBuf::from_path_buf(target)
+            .map(Some)
+            .map_err(|it| anyhow::anyhow!("{:?} is not a valid utf8 path", it))
+    }
+
+    fn confirm_replace(&self, path: PathBuf, sender: &ComponentSender<Self>) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let dialog = gtk::MessageDialog::builder()
+            .text(&format!("Replace {}?", name))
+            .secondary_text("A module with the same filename is already installed.")
+            .buttons(gtk::ButtonsType::OkCancel)
+            .modal(true)
+            .build();
+        dialog.set_transient_for(self.window().as_ref());
+
+        dialog.connect_response({
+            let sender = sender.clone();
+            move |dialog, response| {
+                if response == gtk::ResponseType::Ok {
+                    sender.input(ModulesMsg::Replace(path.clone()));
+                }
+                dialog.close();
+            }
+        });
+        dialog.show();
+    }
+
+    fn window(&self) -> Option<gtk::Window> {
+        self.list
+            .root()
+            .and_then(|it| it.downcast::<gtk::Window>().ok())
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for ModulesModel {
+    type Widgets = ModulesWidgets;
+    type Init = ModulesInit;
+
+    type Input = ModulesMsg;
+    type Output = ModulesOutput;
+
+    fn init(
+        init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let ModulesInit {
+            state,
+            loader,
+            config,
+        } = init;
+
+        let dialog = gtk::FileChooserNative::new(
+            Some("Install module"),
+            None::<&gtk::Window>,
+            gtk::FileChooserAction::Open,
+            Some("Install"),
+            None,
+        );
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Modules"));
+        filter.add_pattern("*.js");
+        dialog.add_filter(&filter);
+        dialog.connect_response({
+            let sender = sender.clone();
+            move |dialog, response| {
+                if response == gtk::ResponseType::Accept {
+                    if let Some(path) = dialog.file().and_then(|it| it.path()) {
+                        sender.input(ModulesMsg::Install(path));
+                    }
+                }
+            }
+        });
+
+        let input = sender.input_sender().clone();
+        state.connect(move |msg| match msg {
+            MadoEngineStateMsg::PushModule(_)
+            | MadoEngineStateMsg::RemoveModule(_)
+            | MadoEngineStateMsg::ModuleFile(_) => {
+                input.send(ModulesMsg::Refresh).ok();
+            }
+            MadoEngineStateMsg::Download(_) | MadoEngineStateMsg::RemoveDownload(_) => {}
+        });
+
+        let model = Self {
+            state,
+            loader,
+            config,
+            files: Vec::new(),
+            list: gtk::ListBox::new(),
+            dialog,
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            ModulesMsg::Refresh => self.refresh(&sender),
+            ModulesMsg::Enable(uuid, enable) => {
+                if self.state.is_module_disabled(uuid) == enable {
+                    self.enable(uuid, enable, &sender);
+                }
+            }
+            ModulesMsg::Reload(path) => self.load(path, &sender),
+            ModulesMsg::OpenInstall => {
+                self.dialog.set_transient_for(self.window().as_ref());
+                self.dialog.show();
+            }
+            ModulesMsg::Install(path) => self.install(path, false, &sender),
+            ModulesMsg::Replace(path) => self.install(path, true, &sender),
+        }
+    }
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 5,
+
+            append = &gtk::Box {
+                set_spacing: 5,
+                set_margin_start: 10,
+                set_margin_end: 10,
+                append = &gtk::Button {
+                    set_label: "Install from file",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(ModulesMsg::OpenInstall);
+                    }
+                },
+            },
+
+            append = &gtk::ScrolledWindow {
+                set_vexpand: true,
+                set_child: Some(&model.list),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mado::engine::{MadoEngine, MadoModuleLoader, ModuleLoadError};
+    use mado_core::{MockMadoModule, Url};
+    use relm4::{Component, ComponentController};
+
+    use super::*;
+    use crate::tests::*;
+
+    struct Loader;
+
+    #[async_trait::async_trait]
+    impl MadoModuleLoader for Loader {
+        async fn get_paths(&self) -> Vec<Utf8PathBuf> {
+            vec!["a.js".into(), "b.js".into()]
+        }
+
+        async fn load(&self, path: Utf8PathBuf) -> Result<Vec<ArcMadoModule>, ModuleLoadError> {
+            if path == "b.js" {
+                return Err(anyhow::anyhow!("syntax error").into());
+            }
+
+            let mut module = MockMadoModule::new();
+            module.expect_uuid().return_const(Uuid::from_u128(1));
+            module.expect_name().return_const("module".to_string());
+            module
+                .expect_domain()
+                .return_const(Url::parse("https://localhost").unwrap());
+
+            Ok(vec![Arc::new(module)])
+        }
+    }
+
+    #[gtk::test]
+    fn test_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let mado = MadoEngine::new(MadoEngineState::default());
+        futures::executor::block_on(mado.load_module(Loader));
+
+        let config = ConfigFile::open(dir.path().join("config.toml"), mado.state().option());
+        let config = Arc::new(config.unwrap());
+        config
+            .update(|it| it.module_dirs = vec![dir.path().join("modules")])
+            .unwrap();
+
+        // nothing loads files sent to the handle.
+        let (loader, _) = mado.module_loader(Loader);
+        let modules = ModulesModel::builder()
+            .launch(ModulesInit {
+                state: mado.state(),
+                loader,
+                config: config.clone(),
+            })
+            .detach();
+        run_loop();
+
+        let files = modules.model().files().to_vec();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].status(), &ModuleFileStatus::Loaded);
+        assert_eq!(
+            files[1].status(),
+            &ModuleFileStatus::Failed("syntax error".to_string())
+        );
+
+        let uuid = Uuid::from_u128(1);
+        modules.emit(ModulesMsg::Enable(uuid, false));
+        run_loop();
+        assert!(mado.state().is_module_disabled(uuid));
+        assert_eq!(config.config().disabled_modules, [uuid.to_string()]);
+
+        modules.emit(ModulesMsg::Enable(uuid, true));
+        run_loop();
+        assert!(mado.state().modules().get_by_uuid(uuid).is_some());
+        assert!(config.config().disabled_modules.is_empty());
+
+        let file = dir.path().join("c.js");
+        let installed = dir.path().join("modules").join("c.js");
+        std::fs::write(&file, "module").unwrap();
+        modules.emit(ModulesMsg::Install(file.clone()));
+        run_loop();
+        assert!(!installed.exists(), "installed without a manifest");
+
+        std::fs::write(dir.path().join("c.permissions.json"), "{}").unwrap();
+        modules.emit(ModulesMsg::Install(file.clone()));
+        run_loop();
+        assert!(installed.exists());
+        assert!(dir.path().join("modules/c.permissions.json").exists());
+
+        // waits for confirmation instead of replacing.
+        std::fs::write(&file, "new module").unwrap();
+        modules.emit(ModulesMsg::Install(file.clone()));
+        run_loop();
+        assert_eq!(std::fs::read_to_string(&installed).unwrap(), "module");
+
+        modules.emit(ModulesMsg::Replace(file));
+        run_loop();
+        assert_eq!(std::fs::read_to_string(&installed).unwrap(), "new module");
+    }
+}
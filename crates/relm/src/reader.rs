@@ -0,0 +1,872 @@
+use std::sync::Arc;
+
+use gtk::prelude::*;
+use mado::engine::{path::Utf8PathBuf, DownloadChapterInfo, DownloadInfo};
+use relm4::{ComponentParts, ComponentSender, SimpleComponent};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigFile, ReaderConfig};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReaderMode {
+    #[default]
+    Single,
+    Double,
+    /// Every page of the chapter in one vertical strip.
+    Webtoon,
+}
+
+impl ReaderMode {
+    pub const ALL: [Self; 3] = [Self::Single, Self::Double, Self::Webtoon];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Single => "Single page",
+            Self::Double => "Double page",
+            Self::Webtoon => "Webtoon",
+        }
+    }
+
+    /// Pages turned at once, `None` when the whole chapter is shown.
+    fn step(self) -> Option<usize> {
+        match self {
+            Self::Single => Some(1),
+            Self::Double => Some(2),
+            Self::Webtoon => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReaderFit {
+    #[default]
+    Page,
+    Width,
+    Height,
+    /// Pages are shown at their own size.
+    Original,
+}
+
+impl ReaderFit {
+    pub const ALL: [Self; 4] = [Self::Page, Self::Width, Self::Height, Self::Original];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Page => "Fit page",
+            Self::Width => "Fit width",
+            Self::Height => "Fit height",
+            Self::Original => "Original size",
+        }
+    }
+}
+
+fn position<T: PartialEq>(all: &[T], it: &T) -> u32 {
+    all.iter().position(|item| item == it).unwrap_or_default() as u32
+}
+
+/// Page a chapter is opened at.
+#[derive(Debug, Clone, Copy)]
+enum OpenAt {
+    Start,
+    End,
+    /// Where it was left, or the start once it's read.
+    LastRead,
+}
+
+#[derive(Debug)]
+pub enum ReaderMsg {
+    /// Open the first finished chapter of `info` starting from `chapter`.
+    Open {
+        info: Arc<DownloadInfo>,
+        chapter: usize,
+    },
+    /// Open the first unread chapter of `info`.
+    Resume(Arc<DownloadInfo>),
+    NextPage,
+    PrevPage,
+    /// Turn the page to the left, which is the next page when reading
+    /// right to left.
+    Left,
+    Right,
+    NextChapter,
+    PrevChapter,
+    Mode(ReaderMode),
+    Fit(ReaderFit),
+    Rtl(bool),
+    MarkRead(bool),
+    /// Less than a screen is left below in webtoon mode.
+    ScrolledNearEnd,
+    /// The end of the chapter is shown in webtoon mode.
+    ScrolledToEnd,
+}
+
+/// Pages added at once in webtoon mode, the next ones are added when
+/// scrolling near the end.
+const WEBTOON_BATCH: usize = 5;
+
+#[derive(Debug)]
+pub enum ReaderOutput {
+    Error(mado::core::Error),
+}
+
+/// Only finished chapters can be read.
+fn is_readable(chapter: &DownloadChapterInfo) -> bool {
+    chapter.status().is_finished()
+}
+
+/// Image files of a downloaded chapter in reading order.
+pub fn chapter_pages(chapter: &DownloadChapterInfo) -> Vec<Utf8PathBuf> {
+    let images = chapter.images();
+    if !images.is_empty() {
+        return images.iter().map(|it| it.path().to_owned()).collect();
+    }
+
+    // images of finished chapters aren't kept across restarts.
+    let mut pages: Vec<_> = chapter
+        .path()
+        .read_dir_utf8()
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|it| it.path().to_owned())
+        .filter(|it| it.is_file() && it.extension() != Some("part"))
+        .collect();
+    pages.sort_by(|a, b| {
+        let a_key = name_key(a.file_name().unwrap_or_default());
+        let b_key = name_key(b.file_name().unwrap_or_default());
+        a_key.cmp(&b_key).then_with(|| a.cmp(b))
+    });
+
+    pages
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum NamePart<'a> {
+    /// Digits without leading zeros, so longer ones are bigger.
+    Number(usize, &'a str),
+    Text(&'a str),
+}
+
+/// Sort key comparing numbers in `name` by their value, e.g. `page 2.jpg`
+/// comes before `page 10.jpg`.
+fn name_key(name: &str) -> Vec<NamePart<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = name;
+
+    while let Some(first) = rest.chars().next() {
+        let digit = first.is_ascii_digit();
+        let end = rest
+            .find(|it: char| it.is_ascii_digit() != digit)
+            .unwrap_or(rest.len());
+        let (part, next) = rest.split_at(end);
+
+        parts.push(if digit {
+            let number = part.trim_start_matches('0');
+            NamePart::Number(number.len(), number)
+        } else {
+            NamePart::Text(part)
+        });
+        rest = next;
+    }
+
+    parts
+}
+
+/// Page showing the images of a downloaded chapter.
+pub struct ReaderModel {
+    config: Arc<ConfigFile>,
+    info: Option<Arc<DownloadInfo>>,
+    chapter: usize,
+    pages: Vec<Utf8PathBuf>,
+    /// Pages added to the view, only the first ones in webtoon mode.
+    loaded: usize,
+    page: usize,
+    mode: ReaderMode,
+    fit: ReaderFit,
+    rtl: bool,
+    view: gtk::Box,
+    scroll: gtk::ScrolledWindow,
+}
+
+impl ReaderModel {
+    pub fn chapter(&self) -> Option<&Arc<DownloadChapterInfo>> {
+        self.info.as_ref()?.chapters().get(self.chapter)
+    }
+
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    pub fn is_read(&self) -> bool {
+        self.chapter().map(|it| it.is_read()).unwrap_or(false)
+    }
+
+    /// Pages currently shown, in reading order.
+    pub fn visible_pages(&self) -> &[Utf8PathBuf] {
+        match self.mode.step() {
+            Some(step) => {
+                let start = self.page.min(self.pages.len());
+                let end = (self.page + step).min(self.pages.len());
+                &self.pages[start..end]
+            }
+            None => &self.pages[..self.loaded.min(self.pages.len())],
+        }
+    }
+
+    fn title(&self) -> String {
+        let (info, chapter) = match (&self.info, self.chapter()) {
+            (Some(info), Some(chapter)) => (info, chapter),
+            _ => return String::new(),
+        };
+
+        let title = format!("{} - {}", info.manga_title(), chapter.title());
+        match self.mode.step() {
+            Some(step) if !self.pages.is_empty() => {
+                let last = (self.page + step).min(self.pages.len());
+                let pages = if last > self.page + 1 {
+                    format!("{}-{}", self.page + 1, last)
+                } else {
+                    last.to_string()
+                };
+                format!("{} ({}/{})", title, pages, self.pages.len())
+            }
+            _ => title,
+        }
+    }
+
+    /// Index of the nearest readable chapter from `from`, `from` included.
+    fn find_chapter(&self, from: usize, forward: bool) -> Option<usize> {
+        let chapters = self.info.as_ref()?.chapters();
+        if forward {
+            (from..chapters.len()).find(|it| is_readable(&chapters[*it]))
+        } else {
+            (0..=from.min(chapters.len().checked_sub(1)?))
+                .rev()
+                .find(|it| is_readable(&chapters[*it]))
+        }
+    }
+
+    /// First page of the last spread.
+    fn last_page(&self) -> usize {
+        let step = self.mode.step().unwrap_or(1);
+        self.pages.len().saturating_sub(1) / step * step
+    }
+
+    fn open_chapter(&mut self, index: usize, at: OpenAt) {
+        let chapter = match self.info.as_ref().and_then(|it| it.chapters().get(index)) {
+            Some(chapter) => chapter.clone(),
+            None => return,
+        };
+
+        self.chapter = index;
+        self.pages = chapter_pages(&chapter);
+        self.page = match at {
+            OpenAt::Start => 0,
+            OpenAt::End => self.last_page(),
+            OpenAt::LastRead => match chapter.progress() {
+                progress if progress.read => 0,
+                progress => {
+                    let step = self.mode.step().unwrap_or(1);
+                    progress.last_page.min(self.last_page()) / step * step
+                }
+            },
+        };
+        self.render();
+    }
+
+    fn open(&mut self, info: Arc<DownloadInfo>, chapter: usize, sender: &ComponentSender<Self>) {
+        self.info = Some(info.clone());
+        match self.find_chapter(chapter, true) {
+            Some(index) => self.open_chapter(index, OpenAt::LastRead),
+            None => {
+                self.info = None;
+                self.pages.clear();
+                self.render();
+
+                let err = anyhow::anyhow!("{} has no finished chapter", info.manga_title());
+                let err = mado::core::Error::ExternalError(err);
+                sender.output(ReaderOutput::Error(err)).ok();
+            }
+        }
+    }
+
+    /// Open the nearest readable chapter after or before the current one.
+    fn change_chapter(&mut self, forward: bool, at: OpenAt) {
+        let from = if forward {
+            self.chapter + 1
+        } else {
+            match self.chapter.checked_sub(1) {
+                Some(it) => it,
+                None => return,
+            }
+        };
+
+        if let Some(index) = self.find_chapter(from, forward) {
+            self.open_chapter(index, at);
+        }
+    }
+
+    fn next_page(&mut self) {
+        match self.mode.step() {
+            Some(step) if self.page + step < self.pages.len() => {
+                self.page += step;
+                self.render();
+            }
+            _ => self.change_chapter(true, OpenAt::Start),
+        }
+    }
+
+    fn prev_page(&mut self) {
+        match self.mode.step() {
+            Some(step) if self.page > 0 => {
+                self.page = self.page.saturating_sub(step);
+                self.render();
+            }
+            Some(_) => self.change_chapter(false, OpenAt::End),
+            None => self.change_chapter(false, OpenAt::Start),
+        }
+    }
+
+    fn render(&mut self) {
+        while let Some(child) = self.view.first_child() {
+            self.view.remove(&child);
+        }
+
+        use gtk::PolicyType::*;
+        let (hpolicy, vpolicy) = match (self.mode, self.fit) {
+            (_, ReaderFit::Original) => (Automatic, Automatic),
+            (ReaderMode::Webtoon, _) | (_, ReaderFit::Width) => (Never, Automatic),
+            (_, ReaderFit::Height) => (Automatic, Never),
+            (_, ReaderFit::Page) => (Never, Never),
+        };
+        self.scroll.set_policy(hpolicy, vpolicy);
+
+        let webtoon = self.mode == ReaderMode::Webtoon;
+        self.view.set_orientation(if webtoon {
+            gtk::Orientation::Vertical
+        } else {
+            gtk::Orientation::Horizontal
+        });
+
+        if webtoon {
+            self.loaded = 0;
+            self.load_more();
+        } else {
+            let mut pages: Vec<_> = self.visible_pages().iter().collect();
+            if self.rtl {
+                pages.reverse();
+            }
+
+            for path in pages {
+                self.view.append(&self.picture(path));
+            }
+        }
+
+        self.scroll.vadjustment().set_value(0.0);
+        self.scroll.hadjustment().set_value(0.0);
+
+        self.save_progress();
+    }
+
+    /// Add the next pages in webtoon mode, so a long chapter doesn't load
+    /// every image at once.
+    fn load_more(&mut self) {
+        let end = (self.loaded + WEBTOON_BATCH).min(self.pages.len());
+        for path in &self.pages[self.loaded.min(end)..end] {
+            self.view.append(&self.picture(path));
+        }
+        self.loaded = end;
+    }
+
+    fn picture(&self, path: &Utf8PathBuf) -> gtk::Picture {
+        let picture = gtk::Picture::for_filename(path);
+        picture.set_can_shrink(self.fit != ReaderFit::Original);
+        picture.set_hexpand(true);
+        picture.set_vexpand(self.mode != ReaderMode::Webtoon);
+
+        picture
+    }
+
+    /// Remember the shown page, paged chapters are read once their last
+    /// page is shown.
+    fn save_progress(&self) {
+        let chapter = match self.chapter() {
+            Some(it) if !self.pages.is_empty() => it,
+            _ => return,
+        };
+
+        chapter.set_last_page(self.page);
+        if let Some(step) = self.mode.step() {
+            if self.page + step >= self.pages.len() {
+                chapter.set_read(true);
+            }
+        }
+    }
+
+    fn save(&self, sender: &ComponentSender<Self>) {
+        let result = self.config.update(|it| {
+            it.reader.mode = self.mode;
+            it.reader.fit = self.fit;
+            it.reader.rtl = self.rtl;
+        });
+
+        if let Err(err) = result {
+            let err = mado::core::Error::ExternalError(err.into());
+            sender.output(ReaderOutput::Error(err)).ok();
+        }
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for ReaderModel {
+    type Widgets = ReaderWidgets;
+    type Init = Arc<ConfigFile>;
+
+    type Input = ReaderMsg;
+    type Output = ReaderOutput;
+
+    fn init(
+        config: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let ReaderConfig { mode, fit, rtl, .. } = config.config().reader;
+
+        let keys = gtk::EventControllerKey::new();
+        keys.connect_key_pressed({
+            let sender = sender.clone();
+            move |_, key, _, _| {
+                let msg = match key {
+                    gtk::gdk::Key::Left => ReaderMsg::Left,
+                    gtk::gdk::Key::Right => ReaderMsg::Right,
+                    _ => return gtk::Inhibit(false),
+                };
+                sender.input(msg);
+
+                gtk::Inhibit(true)
+            }
+        });
+        root.add_controller(&keys);
+
+        let at_end = {
+            let sender = sender.clone();
+            move |it: &gtk::Adjustment| {
+                if it.upper() <= 0.0 {
+                    return;
+                }
+
+                let shown = it.value() + it.page_size();
+                if shown >= it.upper() - 1.0 {
+                    sender.input(ReaderMsg::ScrolledToEnd);
+                } else if shown + it.page_size() >= it.upper() {
+                    sender.input(ReaderMsg::ScrolledNearEnd);
+                }
+            }
+        };
+
+        let model = Self {
+            config,
+            info: None,
+            chapter: 0,
+            pages: Vec::new(),
+            loaded: 0,
+            page: 0,
+            mode,
+            fit,
+            rtl,
+            view: gtk::Box::new(gtk::Orientation::Horizontal, 0),
+            scroll: gtk::ScrolledWindow::new(),
+        };
+        model.scroll.set_vexpand(true);
+        model.scroll.set_child(Some(&model.view));
+        model
+            .scroll
+            .vadjustment()
+            .connect_value_changed(at_end.clone());
+        model.scroll.vadjustment().connect_changed(at_end);
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            ReaderMsg::Open { info, chapter } => self.open(info, chapter, &sender),
+            ReaderMsg::Resume(info) => {
+                let chapter = info
+                    .chapters()
+                    .iter()
+                    .position(|it| is_readable(it) && !it.is_read())
+                    .unwrap_or(0);
+                self.open(info, chapter, &sender);
+            }
+            ReaderMsg::NextPage => self.next_page(),
+            ReaderMsg::PrevPage => self.prev_page(),
+            ReaderMsg::Left if self.rtl => self.next_page(),
+            ReaderMsg::Left => self.prev_page(),
+            ReaderMsg::Right if self.rtl => self.prev_page(),
+            ReaderMsg::Right => self.next_page(),
+            ReaderMsg::NextChapter => self.change_chapter(true, OpenAt::Start),
+            ReaderMsg::PrevChapter => self.change_chapter(false, OpenAt::Start),
+            ReaderMsg::Mode(mode) => {
+                if mode != self.mode {
+                    self.mode = mode;
+                    // keeps spreads starting on the same pages.
+                    let step = mode.step().unwrap_or(1);
+                    self.page = self.page / step * step;
+                    self.render();
+                    self.save(&sender);
+                }
+            }
+            ReaderMsg::Fit(fit) => {
+                if fit != self.fit {
+                    self.fit = fit;
+                    self.render();
+                    self.save(&sender);
+                }
+            }
+            ReaderMsg::Rtl(rtl) => {
+                if rtl != self.rtl {
+                    self.rtl = rtl;
+                    self.render();
+                    self.save(&sender);
+                }
+            }
+            ReaderMsg::MarkRead(read) => {
+                if let Some(chapter) = self.chapter() {
+                    chapter.set_read(read);
+                }
+            }
+            ReaderMsg::ScrolledNearEnd => {
+                if self.mode == ReaderMode::Webtoon {
+                    self.load_more();
+                }
+            }
+            ReaderMsg::ScrolledToEnd => {
+                if self.mode != ReaderMode::Webtoon {
+                    return;
+                }
+
+                // the end of the chapter is only shown once every page is.
+                if self.loaded < self.pages.len() {
+                    self.load_more();
+                } else if let Some(chapter) = self.chapter() {
+                    chapter.set_read(true);
+                }
+            }
+        }
+    }
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 5,
+            set_focusable: true,
+
+            append = &gtk::Box {
+                set_spacing: 5,
+                set_margin_start: 10,
+                set_margin_end: 10,
+
+                append = &gtk::Button {
+                    set_label: "Previous Chapter",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(ReaderMsg::PrevChapter);
+                    }
+                },
+                append = &gtk::Button {
+                    set_icon_name: "go-previous-symbolic",
+                    set_tooltip_text: Some("Page to the left"),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(ReaderMsg::Left);
+                    }
+                },
+
+                #[name = "title"]
+                append = &gtk::Label {
+                    set_hexpand: true,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    #[watch]
+                    set_label: &model.title(),
+                },
+
+                append = &gtk::Button {
+                    set_icon_name: "go-next-symbolic",
+                    set_tooltip_text: Some("Page to the right"),
+                    connect_clicked[sender] => move |_| {
+                        sender.input(ReaderMsg::Right);
+                    }
+                },
+                append = &gtk::Button {
+                    set_label: "Next Chapter",
+                    connect_clicked[sender] => move |_| {
+                        sender.input(ReaderMsg::NextChapter);
+                    }
+                },
+
+                #[name = "mode"]
+                append = &gtk::DropDown::from_strings(&ReaderMode::ALL.map(ReaderMode::label)) {
+                    set_selected: position(&ReaderMode::ALL, &model.mode),
+                    connect_selected_notify[sender] => move |it| {
+                        if let Some(mode) = ReaderMode::ALL.get(it.selected() as usize) {
+                            sender.input(ReaderMsg::Mode(*mode));
+                        }
+                    }
+                },
+                #[name = "fit"]
+                append = &gtk::DropDown::from_strings(&ReaderFit::ALL.map(ReaderFit::label)) {
+                    set_selected: position(&ReaderFit::ALL, &model.fit),
+                    connect_selected_notify[sender] => move |it| {
+                        if let Some(fit) = ReaderFit::ALL.get(it.selected() as usize) {
+                            sender.input(ReaderMsg::Fit(*fit));
+                        }
+                    }
+                },
+                #[name = "read"]
+                append = &gtk::ToggleButton {
+                    set_label: "Read",
+                    set_tooltip_text: Some("Mark the chapter as read"),
+                    #[watch]
+                    set_active: model.is_read(),
+                    connect_toggled[sender] => move |it| {
+                        sender.input(ReaderMsg::MarkRead(it.is_active()));
+                    }
+                },
+                #[name = "rtl"]
+                append = &gtk::ToggleButton {
+                    set_label: "Right to left",
+                    set_active: model.rtl,
+                    connect_toggled[sender] => move |it| {
+                        sender.input(ReaderMsg::Rtl(it.is_active()));
+                    }
+                },
+            },
+
+            append: &model.scroll,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mado::engine::{DownloadChapterImageInfo, DownloadStatus, LateBindingModule};
+    use mado_core::{DefaultMadoModuleMap, Uuid};
+    use relm4::{Component, ComponentController};
+
+    use super::*;
+    use crate::tests::*;
+
+    fn chapter(
+        module: &LateBindingModule,
+        title: &str,
+        status: DownloadStatus,
+        pages: usize,
+    ) -> Arc<DownloadChapterInfo> {
+        let chapter = DownloadChapterInfo::new(
+            module.clone(),
+            title.to_string(),
+            title.to_string(),
+            title.into(),
+            status,
+        );
+        chapter.set_images(
+            (0..pages)
+                .map(|it| {
+                    Arc::new(DownloadChapterImageInfo::new(
+                        Default::default(),
+                        format!("{}/{}.jpg", title, it).into(),
+                        DownloadStatus::finished(),
+                    ))
+                })
+                .collect(),
+        );
+
+        Arc::new(chapter)
+    }
+
+    #[test]
+    fn test_chapter_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Utf8PathBuf::from_path_buf(dir.path().to_owned()).unwrap();
+        for name in ["page 10.jpg", "page 2.jpg", "page 1.jpg", "page 3.jpg.part"] {
+            std::fs::write(path.join(name), "image").unwrap();
+        }
+
+        let map = Arc::new(DefaultMadoModuleMap::new());
+        let module = LateBindingModule::WaitModule(map, Uuid::from_u128(1));
+        let chapter = DownloadChapterInfo::new(
+            module,
+            "a".to_string(),
+            "a".to_string(),
+            path.clone(),
+            DownloadStatus::finished(),
+        );
+
+        let pages = chapter_pages(&chapter);
+        let names: Vec<_> = pages.iter().map(|it| it.file_name().unwrap()).collect();
+        assert_eq!(names, ["page 1.jpg", "page 2.jpg", "page 10.jpg"]);
+
+        let mut names = vec!["10", "9", "009b", "a", "09a"];
+        names.sort_by(|a, b| name_key(a).cmp(&name_key(b)));
+        assert_eq!(names, ["9", "09a", "009b", "10", "a"]);
+    }
+
+    #[gtk::test]
+    fn test_webtoon() {
+        let map = Arc::new(DefaultMadoModuleMap::new());
+        let module = LateBindingModule::WaitModule(map, Uuid::from_u128(1));
+        let pages = WEBTOON_BATCH + 2;
+        let info = Arc::new(
+            DownloadInfo::builder()
+                .order(0)
+                .module(module.clone())
+                .manga_title("manga".to_string())
+                .chapters(vec![chapter(
+                    &module,
+                    "a",
+                    DownloadStatus::finished(),
+                    pages,
+                )])
+                .status(DownloadStatus::finished())
+                .build(),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let option = mado::engine::DownloadOption::default();
+        let config = ConfigFile::open(dir.path().join("config.toml"), option).unwrap();
+        config
+            .update(|it| it.reader.mode = ReaderMode::Webtoon)
+            .unwrap();
+
+        let reader = ReaderModel::builder().launch(Arc::new(config)).detach();
+        reader.emit(ReaderMsg::Open {
+            info: info.clone(),
+            chapter: 0,
+        });
+        run_loop();
+        assert_eq!(reader.model().visible_pages().len(), WEBTOON_BATCH);
+
+        // the end of the first pages isn't the end of the chapter.
+        reader.emit(ReaderMsg::ScrolledToEnd);
+        run_loop();
+        assert_eq!(reader.model().visible_pages().len(), pages);
+        assert!(!reader.model().is_read());
+
+        reader.emit(ReaderMsg::ScrolledNearEnd);
+        reader.emit(ReaderMsg::ScrolledToEnd);
+        run_loop();
+        assert_eq!(reader.model().visible_pages().len(), pages);
+        assert!(reader.model().is_read());
+    }
+
+    #[gtk::test]
+    fn test_reader() {
+        let map = Arc::new(DefaultMadoModuleMap::new());
+        let module = LateBindingModule::WaitModule(map, Uuid::from_u128(1));
+        let info = Arc::new(
+            DownloadInfo::builder()
+                .order(0)
+                .module(module.clone())
+                .manga_title("manga".to_string())
+                .chapters(vec![
+                    chapter(&module, "a", DownloadStatus::finished(), 3),
+                    chapter(&module, "b", DownloadStatus::paused(), 2),
+                    chapter(&module, "c", DownloadStatus::finished(), 2),
+                ])
+                .status(DownloadStatus::finished())
+                .build(),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let option = mado::engine::DownloadOption::default();
+        let config = ConfigFile::open(dir.path().join("config.toml"), option).unwrap();
+        let config = Arc::new(config);
+
+        let reader = ReaderModel::builder().launch(config.clone()).detach();
+        let pages = |reader: &relm4::Controller<ReaderModel>| {
+            let model = reader.model();
+            let title = model.chapter().unwrap().title().to_string();
+            let pages = model.visible_pages().iter().map(|it| it.to_string());
+
+            (title, pages.collect::<Vec<_>>())
+        };
+
+        reader.emit(ReaderMsg::Open {
+            info: info.clone(),
+            chapter: 0,
+        });
+        run_loop();
+        assert_eq!(
+            pages(&reader),
+            ("a".to_string(), vec!["a/0.jpg".to_string()])
+        );
+        assert_eq!(reader.widgets().title.text(), "manga - a (1/3)");
+        assert!(!reader.model().is_read());
+
+        reader.emit(ReaderMsg::Right);
+        reader.emit(ReaderMsg::Rtl(true));
+        reader.emit(ReaderMsg::Left);
+        run_loop();
+        assert_eq!(reader.model().page(), 2);
+        // the last page was shown.
+        assert!(reader.model().is_read());
+
+        // unfinished chapters are skipped.
+        reader.emit(ReaderMsg::NextPage);
+        run_loop();
+        assert_eq!(
+            pages(&reader),
+            ("c".to_string(), vec!["c/0.jpg".to_string()])
+        );
+
+        reader.emit(ReaderMsg::PrevPage);
+        reader.emit(ReaderMsg::Mode(ReaderMode::Double));
+        run_loop();
+        assert_eq!(
+            pages(&reader),
+            ("a".to_string(), vec!["a/2.jpg".to_string()])
+        );
+
+        reader.emit(ReaderMsg::PrevPage);
+        run_loop();
+        assert_eq!(
+            pages(&reader),
+            (
+                "a".to_string(),
+                vec!["a/0.jpg".to_string(), "a/1.jpg".to_string()]
+            )
+        );
+
+        reader.emit(ReaderMsg::Mode(ReaderMode::Webtoon));
+        reader.emit(ReaderMsg::NextChapter);
+        reader.emit(ReaderMsg::NextChapter);
+        run_loop();
+        assert_eq!(pages(&reader).0, "c");
+        assert_eq!(reader.model().visible_pages().len(), 2);
+
+        let saved = config.config().reader;
+        assert_eq!(saved.mode, ReaderMode::Webtoon);
+        assert!(saved.rtl);
+
+        reader.emit(ReaderMsg::Mode(ReaderMode::Single));
+        run_loop();
+        info.chapters()[2].set_last_page(1);
+
+        // opens the first unread chapter where it was left.
+        reader.emit(ReaderMsg::Resume(info.clone()));
+        run_loop();
+        assert_eq!(
+            pages(&reader),
+            ("c".to_string(), vec!["c/1.jpg".to_string()])
+        );
+        assert!(info.chapters()[2].is_read());
+
+        reader.emit(ReaderMsg::MarkRead(false));
+        run_loop();
+        assert!(!info.chapters()[2].is_read());
+        assert_eq!(info.unread_count(), 1);
+    }
+}
@@ -0,0 +1,393 @@
+use std::{sync::Arc, time::Duration};
+
+use gtk::prelude::*;
+use mado::engine::{path::Utf8PathBuf, ModuleLoaderHandle};
+use relm4::{ComponentParts, ComponentSender, SimpleComponent};
+
+use crate::config::{Config, ConfigFile};
+
+#[derive(Debug)]
+pub enum SettingsMsg {
+    /// `0` for no limit.
+    DownloadLimit(usize),
+    SanitizeReplacement(String),
+    DownloadDir(String),
+    ModuleDir(String),
+    RetryLimit(usize),
+    Timeout(u64),
+    /// Empty to use the proxy from the environment.
+    Proxy(String),
+    /// Save spin button changes if none came after the one numbered.
+    SavePending(usize),
+}
+
+/// How long spin buttons have to stay unchanged before they're saved, so
+/// holding an arrow doesn't write the file on every step.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+type ConfigChange = Box<dyn FnOnce(&mut Config)>;
+
+pub struct SettingsInit {
+    pub config: Arc<ConfigFile>,
+    pub loader: ModuleLoaderHandle,
+    pub http_client: mado::core::http::Client,
+}
+
+#[derive(Debug)]
+pub enum SettingsOutput {
+    DownloadDirChanged(Utf8PathBuf),
+    Error(mado::core::Error),
+}
+
+/// Page editing [`Config`], every change is saved and applied right away.
+pub struct SettingsModel {
+    config: Arc<ConfigFile>,
+    loader: ModuleLoaderHandle,
+    http_client: mado::core::http::Client,
+    /// Spin button changes waiting for [`SAVE_DELAY`].
+    pending: Vec<ConfigChange>,
+    /// Number of the last pending change.
+    generation: usize,
+}
+
+impl SettingsModel {
+    fn save_later(&mut self, sender: &ComponentSender<Self>, change: ConfigChange) {
+        self.pending.push(change);
+        self.generation += 1;
+
+        let generation = self.generation;
+        let sender = sender.clone();
+        gtk::glib::timeout_add_local_once(SAVE_DELAY, move || {
+            sender.input(SettingsMsg::SavePending(generation));
+        });
+    }
+
+    /// Returns whether the change was saved.
+    fn update_config(&self, sender: &ComponentSender<Self>, fun: impl FnOnce(&mut Config)) -> bool {
+        match self.config.update(fun) {
+            Ok(()) => true,
+            Err(err) => {
+                let err = mado::core::Error::ExternalError(err.into());
+                sender.output(SettingsOutput::Error(err)).ok();
+                false
+            }
+        }
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for SettingsModel {
+    type Widgets = SettingsWidgets;
+    type Init = SettingsInit;
+
+    type Input = SettingsMsg;
+    type Output = SettingsOutput;
+
+    fn init(
+        init: Self::Init,
+        root: &Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let SettingsInit {
+            config,
+            loader,
+            http_client,
+        } = init;
+        let current = config.config();
+        let module_dir = current
+            .module_dirs
+            .first()
+            .map(|it| it.display().to_string())
+            .unwrap_or_default();
+
+        let model = Self {
+            config,
+            loader,
+            http_client,
+            pending: Vec::new(),
+            generation: 0,
+        };
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        match msg {
+            SettingsMsg::DownloadLimit(limit) => {
+                self.save_later(
+                    &sender,
+                    Box::new(move |it: &mut Config| {
+                        it.download.limit = (limit > 0).then_some(limit)
+                    }),
+                );
+            }
+            SettingsMsg::SanitizeReplacement(replacement) => {
+                self.update_config(&sender, |it| it.sanitize.replacement = replacement);
+            }
+            SettingsMsg::DownloadDir(dir) => {
+                if self.update_config(&sender, |it| it.download_dir = dir.clone().into()) {
+                    sender
+                        .output(SettingsOutput::DownloadDirChanged(dir.into()))
+                        .ok();
+                }
+            }
+            SettingsMsg::ModuleDir(dir) => {
+                // other folders, like the bundled one, are kept.
+                let saved = self.update_config(&sender, |it| match it.module_dirs.first_mut() {
+                    Some(first) => *first = dir.into(),
+                    None => it.module_dirs.push(dir.into()),
+                });
+
+                if saved && !self.loader.scan() {
+                    let err = anyhow::anyhow!("module loader has stopped");
+                    let err = mado::core::Error::ExternalError(err);
+                    sender.output(SettingsOutput::Error(err)).ok();
+                }
+            }
+            SettingsMsg::RetryLimit(retry_limit) => {
+                self.save_later(
+                    &sender,
+                    Box::new(move |it: &mut Config| it.download.retry_limit = retry_limit),
+                );
+            }
+            SettingsMsg::Timeout(timeout) => {
+                self.save_later(
+                    &sender,
+                    Box::new(move |it: &mut Config| it.download.timeout = timeout),
+                );
+            }
+            SettingsMsg::Proxy(proxy) => {
+                let proxy = Some(proxy).filter(|it| !it.is_empty());
+
+                // an invalid proxy isn't saved.
+                match self.http_client.set_proxy(proxy.as_deref()) {
+                    Ok(()) => {
+                        self.update_config(&sender, |it| it.http.proxy = proxy);
+                    }
+                    Err(err) => {
+                        sender.output(SettingsOutput::Error(err.into())).ok();
+                    }
+                }
+            }
+            SettingsMsg::SavePending(generation) => {
+                if generation == self.generation {
+                    let pending = std::mem::take(&mut self.pending);
+                    self.update_config(&sender, |it| {
+                        for change in pending {
+                            change(it);
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    view! {
+        gtk::ScrolledWindow {
+            set_vexpand: true,
+            #[wrap(Some)]
+            set_child = &gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 5,
+                set_margin_start: 10,
+                set_margin_end: 10,
+
+                append = &gtk::Label {
+                    set_markup: "<b>Downloads</b>",
+                    set_halign: gtk::Align::Start,
+                },
+                append = &gtk::Box {
+                    set_spacing: 5,
+                    append = &gtk::Label {
+                        set_label: "Download folder",
+                    },
+                    #[name = "download_dir"]
+                    append = &gtk::Entry {
+                        set_hexpand: true,
+                        set_text: &current.download_dir.display().to_string(),
+                        set_tooltip_text: Some("Press Enter to save"),
+                        connect_activate[sender] => move |it| {
+                            sender.input(SettingsMsg::DownloadDir(it.text().to_string()));
+                        }
+                    },
+                },
+                append = &gtk::Box {
+                    set_spacing: 5,
+                    append = &gtk::Label {
+                        set_label: "Simultaneous downloads",
+                    },
+                    #[name = "download_limit"]
+                    append = &gtk::SpinButton::with_range(0.0, 100.0, 1.0) {
+                        set_value: current.download.limit.unwrap_or(0) as f64,
+                        set_tooltip_text: Some("0 for no limit"),
+                        connect_value_changed[sender] => move |it| {
+                            sender.input(SettingsMsg::DownloadLimit(it.value_as_int() as usize));
+                        }
+                    },
+                },
+                append = &gtk::Box {
+                    set_spacing: 5,
+                    append = &gtk::Label {
+                        set_label: "Filename replacement",
+                    },
+                    #[name = "replacement"]
+                    append = &gtk::Entry {
+                        set_hexpand: true,
+                        set_text: &current.sanitize.replacement,
+                        set_tooltip_text: Some("Replaces characters not allowed in filenames, press Enter to save"),
+                        connect_activate[sender] => move |it| {
+                            sender.input(SettingsMsg::SanitizeReplacement(it.text().to_string()));
+                        }
+                    },
+                },
+
+                append = &gtk::Label {
+                    set_markup: "<b>Modules</b>",
+                    set_halign: gtk::Align::Start,
+                },
+                append = &gtk::Box {
+                    set_spacing: 5,
+                    append = &gtk::Label {
+                        set_label: "Module folder",
+                    },
+                    #[name = "module_dir"]
+                    append = &gtk::Entry {
+                        set_hexpand: true,
+                        set_text: &module_dir,
+                        set_tooltip_text: Some("Modules are installed here, press Enter to save"),
+                        connect_activate[sender] => move |it| {
+                            sender.input(SettingsMsg::ModuleDir(it.text().to_string()));
+                        }
+                    },
+                },
+
+                append = &gtk::Label {
+                    set_markup: "<b>Network</b>",
+                    set_halign: gtk::Align::Start,
+                },
+                append = &gtk::Box {
+                    set_spacing: 5,
+                    append = &gtk::Label {
+                        set_label: "Retries",
+                    },
+                    #[name = "retry_limit"]
+                    append = &gtk::SpinButton::with_range(0.0, 100.0, 1.0) {
+                        set_value: current.download.retry_limit as f64,
+                        connect_value_changed[sender] => move |it| {
+                            sender.input(SettingsMsg::RetryLimit(it.value_as_int() as usize));
+                        }
+                    },
+                    append = &gtk::Label {
+                        set_label: "Timeout (seconds)",
+                    },
+                    #[name = "timeout"]
+                    append = &gtk::SpinButton::with_range(1.0, 3600.0, 1.0) {
+                        set_value: current.download.timeout as f64,
+                        connect_value_changed[sender] => move |it| {
+                            sender.input(SettingsMsg::Timeout(it.value_as_int() as u64));
+                        }
+                    },
+                },
+                append = &gtk::Box {
+                    set_spacing: 5,
+                    append = &gtk::Label {
+                        set_label: "Proxy",
+                    },
+                    #[name = "proxy"]
+                    append = &gtk::Entry {
+                        set_hexpand: true,
+                        set_text: current.http.proxy.as_deref().unwrap_or_default(),
+                        set_placeholder_text: Some("http://localhost:8080"),
+                        set_tooltip_text: Some("Press Enter to save"),
+                        connect_activate[sender] => move |it| {
+                            sender.input(SettingsMsg::Proxy(it.text().to_string()));
+                        }
+                    },
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use mado::engine::{DownloadOption, MadoEngine, MadoModuleLoader, ModuleLoadError};
+    use mado_core::ArcMadoModule;
+    use relm4::{Component, ComponentController};
+
+    use super::*;
+    use crate::tests::*;
+
+    struct Loader;
+
+    #[async_trait::async_trait]
+    impl MadoModuleLoader for Loader {
+        async fn get_paths(&self) -> Vec<Utf8PathBuf> {
+            vec![]
+        }
+
+        async fn load(&self, _: Utf8PathBuf) -> Result<Vec<ArcMadoModule>, ModuleLoadError> {
+            Ok(vec![])
+        }
+    }
+
+    #[gtk::test]
+    fn test_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let option = DownloadOption::default();
+        let config = Arc::new(ConfigFile::open(&path, option.clone()).unwrap());
+        config
+            .update(|it| it.module_dirs = vec!["modules".into(), "bundled".into()])
+            .unwrap();
+
+        // nothing loads the folders, the handle only has to stay open.
+        let mado = MadoEngine::new(Default::default());
+        let (loader, _run) = mado.module_loader(Loader);
+        let http_client = mado::core::http::Client::default();
+
+        let settings = SettingsModel::builder()
+            .launch(SettingsInit {
+                config: config.clone(),
+                loader,
+                http_client,
+            })
+            .detach();
+
+        // holding an arrow saves once, after it's released.
+        settings.widgets().download_limit.set_value(1.0);
+        settings.widgets().download_limit.set_value(2.0);
+        run_loop();
+        assert_eq!(option.scheduler().download_limit(), usize::MAX);
+
+        let start = Instant::now();
+        while option.scheduler().download_limit() != 2 {
+            assert!(start.elapsed() < SAVE_DELAY * 10);
+            std::thread::sleep(SAVE_DELAY / 10);
+            run_loop();
+        }
+
+        settings.emit(SettingsMsg::SanitizeReplacement("-".to_string()));
+        settings.emit(SettingsMsg::ModuleDir("other".to_string()));
+        settings.emit(SettingsMsg::Proxy("http://localhost:8080".to_string()));
+        run_loop();
+
+        assert_eq!(option.sanitize_filename("a\u{1}b"), "a-b");
+
+        // an invalid proxy isn't saved.
+        settings.emit(SettingsMsg::Proxy("not a proxy".to_string()));
+        run_loop();
+
+        let saved = Config::load(&path).unwrap();
+        assert_eq!(saved.download.limit, Some(2));
+        assert_eq!(saved.sanitize.replacement, "-");
+        assert_eq!(
+            saved.module_dirs,
+            vec![std::path::PathBuf::from("other"), "bundled".into()]
+        );
+        assert_eq!(saved.http.proxy.as_deref(), Some("http://localhost:8080"));
+    }
+}
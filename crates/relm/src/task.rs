@@ -28,6 +28,7 @@ pub struct DownloadView {
     chapter_progress: gtk::Label,
     chapter_title: gtk::Label,
     manga_progress: gtk::Label,
+    unread: gtk::Label,
 }
 
 const DOWNLOAD_RESUMED_CSS: &str = "download-resumed";
@@ -50,6 +51,11 @@ impl From<&DownloadInfo> for DownloadView {
                     },
 
                     #[name = "manga_progress"]
+                    append = &gtk::Label {
+                        set_text: "",
+                    },
+
+                    #[name = "unread"]
                     append = &gtk::Label {
                         set_text: "",
                     }
@@ -107,6 +113,7 @@ impl From<&DownloadInfo> for DownloadView {
             chapter_progress,
             chapter_title,
             manga_progress,
+            unread,
         }
     }
 }
@@ -114,6 +121,7 @@ impl From<&DownloadInfo> for DownloadView {
 pub fn status_to_class(status: &DownloadStatus) -> &'static str {
     match status {
         DownloadStatus::Finished => DOWNLOAD_RESUMED_CSS,
+        DownloadStatus::FinishedWithErrors(_) => DOWNLOAD_ERROR_CSS,
         DownloadStatus::InProgress(progress) => match progress {
             DownloadProgressStatus::Resumed(_) => DOWNLOAD_RESUMED_CSS,
             DownloadProgressStatus::Paused => DOWNLOAD_PAUSED_CSS,
@@ -158,6 +166,9 @@ impl DownloadView {
 
         add_css(status_to_class(status));
         set_text(&status.to_human_string());
+
+        let hint = status.error_kind().map(|it| it.hint()).unwrap_or_default();
+        self.widget.set_tooltip_text(Some(hint).filter(|it| !it.is_empty()));
     }
 
     pub fn update_info(&self, info: &DownloadInfo) {
@@ -170,6 +181,13 @@ impl DownloadView {
 
         self.manga_progress
             .set_text(&format!("[{countfinished}/{total}]"));
+
+        let unread = info.unread_count();
+        if unread > 0 {
+            self.unread.set_text(&format!("{unread} unread"));
+        } else {
+            self.unread.set_text("");
+        }
     }
 
     pub fn update_chapter_info(&self, info: &DownloadInfo) {
@@ -215,14 +233,18 @@ impl DownloadViewController {
         let sender = this.sender.clone();
         let handle = info.connect(move |msg| match msg {
             DownloadInfoMsg::StatusChanged(_) => sender.send(DownloadMsg::StatusChanged).unwrap(),
-            DownloadInfoMsg::OrderChanged(_) => {}
+            DownloadInfoMsg::OrderChanged(_)
+            | DownloadInfoMsg::PriorityChanged(_)
+            | DownloadInfoMsg::StartAtChanged(_)
+            | DownloadInfoMsg::Removed => {}
         });
         handles.push(handle.send_handle_any());
 
         for it in info.chapters().iter() {
             let sender = this.sender.clone();
             let handle = it.connect(move |msg| match msg {
-                mado::engine::DownloadChapterInfoMsg::StatusChanged(_) => {
+                mado::engine::DownloadChapterInfoMsg::StatusChanged(_)
+                | mado::engine::DownloadChapterInfoMsg::ProgressChanged(_) => {
                     sender.send(DownloadMsg::ChapterChanged).unwrap()
                 }
                 mado::engine::DownloadChapterInfoMsg::DownloadImagesChanged(images) => {
@@ -246,6 +268,7 @@ impl DownloadViewController {
         let chapter_progress = view.chapter_progress.downgrade();
         let manga_progress = view.manga_progress.downgrade();
         let chapter_title = view.chapter_title.downgrade();
+        let unread = view.unread.downgrade();
 
         let upgrade_view = move || {
             widget.upgrade().and_then(|widget| {
@@ -253,13 +276,16 @@ impl DownloadViewController {
                     chapter_title.upgrade().and_then(|chapter_title| {
                         chapter_progress.upgrade().and_then(|chapter_progress| {
                             manga_progress.upgrade().and_then(|manga_progress| {
-                                status.upgrade().map(|status| DownloadView {
-                                    widget,
-                                    title,
-                                    status,
-                                    chapter_progress,
-                                    manga_progress,
-                                    chapter_title,
+                                unread.upgrade().and_then(|unread| {
+                                    status.upgrade().map(|status| DownloadView {
+                                        widget,
+                                        title,
+                                        status,
+                                        chapter_progress,
+                                        manga_progress,
+                                        chapter_title,
+                                        unread,
+                                    })
                                 })
                             })
                         })
@@ -317,6 +343,11 @@ mod tests {
         }
 
         assert_status!(DownloadStatus::Finished, DOWNLOAD_RESUMED_CSS, "Finished");
+        assert_status!(
+            DownloadStatus::finished_with_errors(1),
+            DOWNLOAD_ERROR_CSS,
+            "Finished with errors: 1 failed"
+        );
         assert_status!(DownloadStatus::waiting(), DOWNLOAD_RESUMED_CSS, "Waiting");
         assert_status!(
             DownloadStatus::downloading(),
@@ -404,6 +435,14 @@ mod tests {
         run_loop();
         assert_eq!(view.chapter_progress.text(), "[1/2]");
 
+        chapter.set_status(DownloadStatus::finished());
+        run_loop();
+        assert_eq!(view.unread.text(), "1 unread");
+
+        chapter.set_read(true);
+        run_loop();
+        assert_eq!(view.unread.text(), "");
+
         // check that dropping view should stop controller receiver
         drop(view);
 
@@ -1,11 +1,15 @@
 use crossbeam_channel as mpsc;
 use parking_lot::Mutex;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::SystemTime,
+};
 
 use mado_engine::{
     core::{ArcMadoModule, ArcMadoModuleMap, Uuid},
     DownloadChapterImageInfo, DownloadChapterInfo, DownloadChapterInfoMsg, DownloadInfo,
-    DownloadTaskList, MadoEngineState, MadoEngineStateMsg,
+    DownloadPriority, DownloadTaskList, MadoEngineState, MadoEngineStateMsg, ReadProgress,
 };
 
 use crate::{
@@ -24,7 +28,11 @@ pub enum DbMsg {
     PushModule(ArcMadoModule),
     DownloadStatusChanged(DownloadPK, DownloadStatus),
     DownloadOrderChanged(DownloadPK, usize),
+    DownloadPriorityChanged(DownloadPK, DownloadPriority),
+    DownloadStartAtChanged(DownloadPK, Option<SystemTime>),
+    DeleteDownload(DownloadPK, Vec<DownloadChapterPK>),
     DownloadChapterStatusChanged(DownloadChapterPK, DownloadStatus),
+    DownloadChapterProgressChanged(DownloadChapterPK, ReadProgress),
     DownloadChapterImagesChanged(DownloadChapterPK, Vec<Arc<DownloadChapterImageInfo>>),
     DownloadChapterImageStatusChanged(DownloadChapterImagePK, DownloadStatus),
     Close,
@@ -63,6 +71,8 @@ pub struct Channel {
     module: HashMap<Uuid, Module>,
     download_chapter_images:
         Mutex<HashMap<DownloadChapterPK, Vec<mado_engine::AnyObserverHandleSend>>>,
+    /// Chapters of deleted downloads, their late messages are ignored.
+    deleted_chapters: HashSet<DownloadChapterPK>,
 }
 
 pub fn channel(db: Database) -> Channel {
@@ -74,6 +84,7 @@ pub fn channel(db: Database) -> Channel {
         tx,
         module: HashMap::new(),
         download_chapter_images: Default::default(),
+        deleted_chapters: Default::default(),
     }
 }
 
@@ -123,9 +134,32 @@ impl Channel {
             DbMsg::DownloadOrderChanged(id, order) => {
                 self.db.update_download_order(id, order)?;
             }
+            DbMsg::DownloadPriorityChanged(id, priority) => {
+                self.db.update_download_priority(id, priority)?;
+            }
+            DbMsg::DownloadStartAtChanged(id, start_at) => {
+                self.db.update_download_start_at(id, start_at)?;
+            }
+            DbMsg::DeleteDownload(pk, chapters) => {
+                self.db.delete_download(pk)?;
+
+                for it in chapters {
+                    if let Some(handles) = self.download_chapter_images.lock().remove(&it) {
+                        for handle in handles {
+                            handle.disconnect();
+                        }
+                    }
+                    self.deleted_chapters.insert(it);
+                }
+            }
+            DbMsg::DownloadChapterImagesChanged(ch_pk, _)
+                if self.deleted_chapters.contains(&ch_pk) => {}
             DbMsg::DownloadChapterStatusChanged(pk, status) => {
                 self.db.update_download_chapter_status(pk, status)?;
             }
+            DbMsg::DownloadChapterProgressChanged(pk, progress) => {
+                self.db.update_download_chapter_progress(pk, progress)?;
+            }
             DbMsg::DownloadChapterImagesChanged(ch_pk, images) => {
                 let image = self.db.update_download_chapter_images(ch_pk, images)?;
 
@@ -183,6 +217,8 @@ impl Channel {
     }
 
     fn connect_info(&self, join: DownloadInfoJoin) {
+        let chapters: Vec<_> = join.chapters.iter().map(|it| it.pk).collect();
+
         for info in join.chapters {
             self.connect_download_chapter(info.pk, info.chapter.clone());
 
@@ -200,6 +236,15 @@ impl Channel {
                 mado_engine::DownloadInfoMsg::OrderChanged(order) => {
                     tx.send(DbMsg::DownloadOrderChanged(dl_pk, order)).ok()
                 }
+                mado_engine::DownloadInfoMsg::PriorityChanged(priority) => tx
+                    .send(DbMsg::DownloadPriorityChanged(dl_pk, priority))
+                    .ok(),
+                mado_engine::DownloadInfoMsg::StartAtChanged(start_at) => {
+                    tx.send(DbMsg::DownloadStartAtChanged(dl_pk, start_at)).ok()
+                }
+                mado_engine::DownloadInfoMsg::Removed => {
+                    tx.send(DbMsg::DeleteDownload(dl_pk, chapters.clone())).ok()
+                }
             };
         });
     }
@@ -214,6 +259,11 @@ impl Channel {
                 MadoEngineStateMsg::PushModule(module) => {
                     tx.send(DbMsg::PushModule(module.clone())).ok();
                 }
+                // keep the module row, downloads still reference it.
+                MadoEngineStateMsg::RemoveModule(_) => {}
+                // rows are deleted through the download's own observer.
+                MadoEngineStateMsg::RemoveDownload(_) => {}
+                MadoEngineStateMsg::ModuleFile(_) => {}
             }
         });
     }
@@ -236,6 +286,9 @@ impl Channel {
                 DownloadChapterInfoMsg::DownloadImagesChanged(images) => {
                     tx.send(DbMsg::DownloadChapterImagesChanged(pk, images.clone()))
                 }
+                DownloadChapterInfoMsg::ProgressChanged(progress) => {
+                    tx.send(DbMsg::DownloadChapterProgressChanged(pk, *progress))
+                }
             }
             .ok();
         });
@@ -391,6 +444,18 @@ mod tests {
             assert_eq!(ch.chapter.status, DownloadStatus::Finished);
         }
 
+        info.chapters()[0].set_last_page(4);
+        info.chapters()[0].set_read(true);
+        rx.try_all().unwrap();
+
+        {
+            let it = rx.load_connect(state.map.clone()).unwrap();
+            let progress = it[0].chapters()[0].progress();
+            assert!(progress.read);
+            assert_eq!(progress.last_page, 4);
+            assert_eq!(it[0].unread_count(), u8::MAX as usize - 1);
+        }
+
         {
             // test that it is connected
             let it = rx.load_connect(state.map.clone()).unwrap();
@@ -428,6 +493,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn delete_test() {
+        let db = connection();
+
+        let state = State::default();
+        let info = setup_info_with_state(4, &state);
+
+        let mut rx = channel(Database::new(db).unwrap());
+        rx.connect_only(&state.engine);
+
+        state
+            .engine
+            .push_module(Arc::new(mock_module(Uuid::default())))
+            .unwrap();
+
+        rx.send(DbMsg::NewDownload(info.clone())).unwrap();
+        rx.try_all().unwrap();
+        assert_eq!(rx.db.load_download().unwrap().len(), 1);
+
+        info.mark_removed();
+        // late messages of the removed download must not fail the channel.
+        state.populate_chapter_image(info.chapters()[0].clone(), 2);
+        rx.try_all().unwrap();
+
+        assert!(rx.db.load_download().unwrap().is_empty());
+    }
+
     #[test]
     #[ntest::timeout(1000)]
     pub fn close_test() {
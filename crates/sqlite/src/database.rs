@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
-use mado_engine::{core::ArcMadoModuleMap, DownloadChapterImageInfo, DownloadInfo};
+use mado_engine::{
+    core::ArcMadoModuleMap, DownloadChapterImageInfo, DownloadInfo, DownloadPriority, ReadProgress,
+};
 use rusqlite::{Connection, Error};
 
 use crate::{
@@ -12,15 +14,34 @@ use crate::{
     status::DownloadStatus,
 };
 
+/// Reader read progress is saved for unless [`Database::with_reader`] is
+/// used.
+pub const DEFAULT_READER: &str = "default";
+
 pub struct Database {
     conn: Connection,
+    reader: String,
 }
 
 impl Database {
     pub fn new(conn: Connection) -> Result<Self, Error> {
         crate::setup_schema(&conn)?;
 
-        Ok(Self { conn })
+        Ok(Self {
+            conn,
+            reader: DEFAULT_READER.to_string(),
+        })
+    }
+
+    /// Load and save read progress of `reader`, so people sharing the
+    /// database don't mark chapters read for each other.
+    pub fn with_reader(mut self, reader: impl Into<String>) -> Self {
+        self.reader = reader.into();
+        self
+    }
+
+    pub fn reader(&self) -> &str {
+        &self.reader
     }
 
     pub fn open(path: &str) -> Result<Self, Error> {
@@ -34,7 +55,16 @@ impl Database {
         module: ModulePK,
         download: &Arc<DownloadInfo>,
     ) -> Result<DownloadInfoJoin, Error> {
-        crate::downloads::insert_info(&mut self.conn, module, download)
+        let join = crate::downloads::insert_info(&mut self.conn, module, download)?;
+
+        for it in &join.chapters {
+            let progress = it.chapter.progress();
+            if progress != ReadProgress::default() {
+                self.update_download_chapter_progress(it.pk, progress)?;
+            }
+        }
+
+        Ok(join)
     }
 
     pub fn insert_module(&mut self, module: InsertModule<'_>) -> Result<Module, Error> {
@@ -63,6 +93,26 @@ impl Database {
         crate::downloads::update_order(&self.conn, pk, order)
     }
 
+    pub fn update_download_priority(
+        &self,
+        pk: DownloadPK,
+        priority: DownloadPriority,
+    ) -> Result<usize, Error> {
+        crate::downloads::update_priority(&self.conn, pk, priority)
+    }
+
+    pub fn update_download_start_at(
+        &self,
+        pk: DownloadPK,
+        start_at: Option<std::time::SystemTime>,
+    ) -> Result<usize, Error> {
+        crate::downloads::update_start_at(&self.conn, pk, start_at)
+    }
+
+    pub fn delete_download(&self, pk: DownloadPK) -> Result<usize, Error> {
+        crate::downloads::delete(&self.conn, pk)
+    }
+
     pub fn update_download_chapter_status(
         &self,
         pk: DownloadChapterPK,
@@ -71,6 +121,14 @@ impl Database {
         crate::download_chapters::update_status(&self.conn, pk, status)
     }
 
+    pub fn update_download_chapter_progress(
+        &self,
+        pk: DownloadChapterPK,
+        progress: ReadProgress,
+    ) -> Result<usize, Error> {
+        crate::download_chapters::update_progress(&self.conn, pk, &self.reader, progress)
+    }
+
     pub fn update_download_chapter_images(
         &mut self,
         pk: DownloadChapterPK,
@@ -88,14 +146,14 @@ impl Database {
     }
 
     pub fn load_download(&self) -> Result<Vec<DownloadJoin>, Error> {
-        crate::query::load_download_join(&self.conn)
+        crate::query::load_download_join(&self.conn, &self.reader)
     }
 
     pub fn load_download_info(
         &self,
         module_map: ArcMadoModuleMap,
     ) -> Result<Vec<DownloadInfoJoin>, Error> {
-        crate::query::load_download_info_join(&self.conn, module_map)
+        crate::query::load_download_info_join(&self.conn, module_map, &self.reader)
         // let joins = self.load_download()?;
         // let mut downloads = Vec::new();
         //
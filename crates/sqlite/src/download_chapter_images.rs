@@ -174,10 +174,13 @@ mod tests {
             &db,
             InsertDownload {
                 order: 0,
+                priority: Default::default(),
+                start_at: None,
                 title: "title",
                 module_id: &module_id,
                 path: "path",
                 url: None,
+                cover_link: None,
                 status: "Finished".into(),
             },
         )
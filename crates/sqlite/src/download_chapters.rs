@@ -1,9 +1,12 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::SystemTime};
 
-use mado_engine::{path::Utf8PathBuf, DownloadChapterInfo};
+use mado_engine::{path::Utf8PathBuf, DownloadChapterInfo, ReadProgress};
 use rusqlite::{params, Connection, Error};
 
-use crate::{downloads::DownloadPK, status::DownloadStatus};
+use crate::{
+    downloads::{from_unix, to_unix, DownloadPK},
+    status::DownloadStatus,
+};
 
 #[derive(Debug)]
 pub struct DownloadChapter {
@@ -13,6 +16,20 @@ pub struct DownloadChapter {
     pub chapter_id: String,
     pub path: Utf8PathBuf,
     pub status: DownloadStatus,
+    /// Progress of the reader the chapters were loaded for.
+    pub read: bool,
+    pub last_page: usize,
+    pub updated_at: Option<SystemTime>,
+}
+
+impl DownloadChapter {
+    pub fn progress(&self) -> ReadProgress {
+        ReadProgress {
+            read: self.read,
+            last_page: self.last_page,
+            updated_at: self.updated_at,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
@@ -43,7 +60,7 @@ pub fn insert(conn: &Connection, model: InsertDownloadChapter<'_>) -> Result<usi
             ":title": model.title,
             ":chapter_id": model.chapter_id,
             ":path": model.path,
-            ":status": model.status
+            ":status": model.status,
         },
     )
 }
@@ -67,13 +84,22 @@ pub fn insert_info(
     Ok(DownloadChapterPK { id })
 }
 
-pub fn load(conn: &Connection) -> Result<HashMap<DownloadPK, Vec<DownloadChapter>>, Error> {
+/// Load every chapter with the progress of `reader`.
+pub fn load(
+    conn: &Connection,
+    reader: &str,
+) -> Result<HashMap<DownloadPK, Vec<DownloadChapter>>, Error> {
     let mut map: HashMap<DownloadPK, Vec<DownloadChapter>> = HashMap::new();
 
     let mut stmt = conn.prepare(
-        "SELECT id, download_id, title, path, status, chapter_id FROM download_chapters",
+        "SELECT c.id AS id, c.download_id AS download_id, c.title AS title, c.path AS path,
+                c.status AS status, c.chapter_id AS chapter_id,
+                p.read AS read, p.last_page AS last_page, p.updated_at AS updated_at
+            FROM download_chapters c
+            LEFT JOIN download_chapter_progress p
+                ON p.download_chapter_id = c.id AND p.reader = ?",
     )?;
-    let mut rows = stmt.query([])?;
+    let mut rows = stmt.query([reader])?;
 
     while let Some(row) = rows.next()? {
         let dl_pk = DownloadPK::new(row.get("download_id")?);
@@ -87,6 +113,9 @@ pub fn load(conn: &Connection) -> Result<HashMap<DownloadPK, Vec<DownloadChapter
             chapter_id: row.get("chapter_id")?,
             path: row.get::<_, String>("path")?.into(),
             status: row.get("status")?,
+            read: row.get::<_, Option<bool>>("read")?.unwrap_or_default(),
+            last_page: row.get::<_, Option<usize>>("last_page")?.unwrap_or_default(),
+            updated_at: row.get::<_, Option<i64>>("updated_at")?.map(from_unix),
         };
 
         let chapters = map.entry(dl_pk).or_default();
@@ -107,6 +136,31 @@ pub fn update_status(
     )
 }
 
+/// Save `progress` of `reader`, progress of other readers is kept.
+pub fn update_progress(
+    conn: &Connection,
+    pk: DownloadChapterPK,
+    reader: &str,
+    progress: ReadProgress,
+) -> Result<usize, Error> {
+    conn.execute(
+        "INSERT INTO download_chapter_progress
+            (download_chapter_id, reader, read, last_page, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (download_chapter_id, reader) DO UPDATE SET
+                read = excluded.read,
+                last_page = excluded.last_page,
+                updated_at = excluded.updated_at",
+        params![
+            pk.id,
+            reader,
+            progress.read,
+            progress.last_page,
+            progress.updated_at.map(to_unix),
+        ],
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,10 +182,13 @@ mod tests {
             &db,
             InsertDownload {
                 order: 1,
+                priority: Default::default(),
+                start_at: None,
                 title: "title",
                 module_id: &module_id,
                 path: "path",
                 url: None,
+                cover_link: None,
                 status: "Finished".into(),
             },
         )
@@ -149,7 +206,19 @@ mod tests {
         )
         .unwrap();
 
-        let chapters = load(&db).unwrap();
+        let progress = ReadProgress {
+            read: true,
+            last_page: 2,
+            updated_at: Some(SystemTime::UNIX_EPOCH),
+        };
+        update_progress(&db, DownloadChapterPK::new(1), "a", progress).unwrap();
+        update_progress(&db, DownloadChapterPK::new(1), "a", progress).unwrap();
+
+        // other readers didn't read it.
+        let chapters = load(&db, "b").unwrap();
+        assert_eq!(chapters[&DownloadPK::new(1)][0].progress(), Default::default());
+
+        let chapters = load(&db, "a").unwrap();
 
         let pk = DownloadPK::new(1);
         assert_eq!(chapters.len(), 1);
@@ -163,5 +232,6 @@ mod tests {
         assert_eq!(it.chapter_id, "chapter-id");
         assert_eq!(it.path, "path");
         assert_eq!(it.status, "Finished".into());
+        assert_eq!(it.progress(), progress);
     }
 }
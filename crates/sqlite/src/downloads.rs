@@ -1,6 +1,9 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use mado_engine::{core::Url, path::Utf8PathBuf, DownloadInfo};
+use mado_engine::{core::Url, path::Utf8PathBuf, DownloadInfo, DownloadPriority};
 use rusqlite::{params, Connection, Error};
 
 use crate::{
@@ -12,10 +15,13 @@ use crate::{
 
 pub struct InsertDownload<'a> {
     pub order: usize,
+    pub priority: DownloadPriority,
+    pub start_at: Option<SystemTime>,
     pub title: &'a str,
     pub module_id: &'a i64,
     pub path: &'a str,
     pub url: Option<&'a Url>,
+    pub cover_link: Option<&'a str>,
     pub status: DownloadStatus,
 }
 
@@ -32,15 +38,20 @@ impl DownloadPK {
 
 pub fn insert(conn: &Connection, model: InsertDownload<'_>) -> Result<usize, Error> {
     conn.execute(
-        "INSERT INTO downloads (title, module_id, path, url, status, `order`)
-        VALUES (:title, :module, :path, :url, :status, :order)",
+        "INSERT INTO downloads
+            (title, module_id, path, url, cover_link, status, `order`, priority, start_at)
+        VALUES
+            (:title, :module, :path, :url, :cover_link, :status, :order, :priority, :start_at)",
         rusqlite::named_params! {
             ":title": model.title,
             ":module": model.module_id,
             ":path": model.path,
             ":url": model.url,
+            ":cover_link": model.cover_link,
             ":status": model.status,
-            ":order": model.order
+            ":order": model.order,
+            ":priority": model.priority.as_str(),
+            ":start_at": model.start_at.map(to_unix),
 
         },
     )
@@ -60,10 +71,13 @@ pub fn insert_info(
 
     let model = InsertDownload {
         order: info.order(),
+        priority: info.priority(),
+        start_at: info.start_at(),
         title: info.manga(),
         module_id: &module.id,
         path: info.path().as_str(),
         url: info.url(),
+        cover_link: info.cover_link(),
         status: From::from(&*info.status()),
     };
 
@@ -108,16 +122,20 @@ pub fn insert_info(
 pub struct Download {
     pub pk: DownloadPK,
     pub order: usize,
+    pub priority: DownloadPriority,
+    pub start_at: Option<SystemTime>,
     pub title: String,
     pub module_pk: ModulePK,
     pub path: Utf8PathBuf,
     pub url: Option<Url>,
+    pub cover_link: Option<String>,
     pub status: DownloadStatus,
 }
 
 pub fn load(conn: &Connection) -> Result<Vec<Download>, Error> {
     let mut stmt = conn.prepare(
-        "SELECT id, `order`, title, module_id, path, url, status FROM downloads ORDER BY `order`",
+        "SELECT id, `order`, priority, start_at, title, module_id, path, url, cover_link, status
+            FROM downloads ORDER BY `order`",
     )?;
     let mut rows = stmt.query([])?;
 
@@ -126,6 +144,12 @@ pub fn load(conn: &Connection) -> Result<Vec<Download>, Error> {
     while let Some(row) = rows.next()? {
         let download = Download {
             order: row.get("order")?,
+            // unknown priorities come from a newer version, treat them as normal.
+            priority: row
+                .get::<_, String>("priority")?
+                .parse()
+                .unwrap_or_default(),
+            start_at: row.get::<_, Option<i64>>("start_at")?.map(from_unix),
             pk: DownloadPK::new(row.get("id")?),
             title: row.get("title")?,
             module_pk: ModulePK {
@@ -135,6 +159,7 @@ pub fn load(conn: &Connection) -> Result<Vec<Download>, Error> {
             url: row
                 .get::<_, Option<String>>("url")?
                 .and_then(|it| it.parse().ok()),
+            cover_link: row.get("cover_link")?,
             status: row.get("status")?,
         };
 
@@ -162,6 +187,51 @@ pub fn update_order(conn: &Connection, pk: DownloadPK, order: usize) -> Result<u
     )
 }
 
+pub fn update_priority(
+    conn: &Connection,
+    pk: DownloadPK,
+    priority: DownloadPriority,
+) -> Result<usize, Error> {
+    conn.execute(
+        "UPDATE downloads SET priority = ? WHERE id = ?",
+        params![priority.as_str(), pk.id],
+    )
+}
+
+pub fn update_start_at(
+    conn: &Connection,
+    pk: DownloadPK,
+    start_at: Option<SystemTime>,
+) -> Result<usize, Error> {
+    conn.execute(
+        "UPDATE downloads SET start_at = ? WHERE id = ?",
+        params![start_at.map(to_unix), pk.id],
+    )
+}
+
+/// Seconds since unix epoch, times before it are stored as negative.
+pub(crate) fn to_unix(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(it) => it.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    }
+}
+
+pub(crate) fn from_unix(secs: i64) -> SystemTime {
+    let duration = Duration::from_secs(secs.unsigned_abs());
+
+    if secs < 0 {
+        SystemTime::UNIX_EPOCH - duration
+    } else {
+        SystemTime::UNIX_EPOCH + duration
+    }
+}
+
+/// Delete download, its chapters and images are deleted by the foreign keys.
+pub fn delete(conn: &Connection, pk: DownloadPK) -> Result<usize, Error> {
+    conn.execute("DELETE FROM downloads WHERE id = ?", params![pk.id])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,10 +254,13 @@ mod tests {
             &db,
             InsertDownload {
                 order: 10,
+                priority: DownloadPriority::High,
+                start_at: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(100)),
                 title: "title",
                 module_id: &module_id,
                 path: "path",
                 url: None,
+                cover_link: None,
                 status: "Paused".into(),
             },
         )
@@ -198,20 +271,29 @@ mod tests {
         assert_eq!(vec.len(), 1);
         let it = &vec[0];
         assert_eq!(it.order, 10);
+        assert_eq!(it.priority, DownloadPriority::High);
+        assert_eq!(
+            it.start_at,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(100))
+        );
         assert_eq!(it.title, "title");
         assert_eq!(it.module_pk.id, module_id);
         assert_eq!(it.path, "path");
         assert_eq!(it.url, None);
+        assert_eq!(it.cover_link, None);
         assert_eq!(it.status, "Paused".into());
 
         insert(
             &db,
             InsertDownload {
                 order: 11,
+                priority: Default::default(),
+                start_at: None,
                 title: "title",
                 module_id: &module_id,
                 path: "path",
                 url: Some(&"https://url.com".parse().unwrap()),
+                cover_link: Some("https://url.com/cover.jpg"),
                 status: "Finished".into(),
             },
         )
@@ -225,6 +307,7 @@ mod tests {
         assert_eq!(it.module_pk.id, module_id);
         assert_eq!(it.path, "path");
         assert_eq!(it.url, Some("https://url.com".parse().unwrap()));
+        assert_eq!(it.cover_link.as_deref(), Some("https://url.com/cover.jpg"));
         assert_eq!(it.status, "Finished".into());
     }
 
@@ -288,6 +371,53 @@ mod tests {
         assert_eq!(get_status(1), 10)
     }
 
+    #[test]
+    fn update_priority_test() {
+        let mut db = connection();
+
+        let module = crate::module::insert_pk(
+            &mut db,
+            crate::module::InsertModule {
+                uuid: &Default::default(),
+                name: "Default",
+            },
+        )
+        .unwrap();
+
+        let info = setup_info(1);
+        let insert = crate::downloads::insert_info(&mut db, module, &info).unwrap();
+
+        assert_eq!(load(&db).unwrap()[0].priority, DownloadPriority::Normal);
+        update_priority(&db, insert.pk, DownloadPriority::Low).unwrap();
+        assert_eq!(load(&db).unwrap()[0].priority, DownloadPriority::Low);
+    }
+
+    #[test]
+    fn update_start_at_test() {
+        let mut db = connection();
+
+        let module = crate::module::insert_pk(
+            &mut db,
+            crate::module::InsertModule {
+                uuid: &Default::default(),
+                name: "Default",
+            },
+        )
+        .unwrap();
+
+        let info = setup_info(1);
+        let insert = crate::downloads::insert_info(&mut db, module, &info).unwrap();
+
+        assert_eq!(load(&db).unwrap()[0].start_at, None);
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        update_start_at(&db, insert.pk, Some(time)).unwrap();
+        assert_eq!(load(&db).unwrap()[0].start_at, Some(time));
+
+        update_start_at(&db, insert.pk, None).unwrap();
+        assert_eq!(load(&db).unwrap()[0].start_at, None);
+    }
+
     #[test]
     fn sorted_test() {
         let db = connection();
@@ -303,19 +433,25 @@ mod tests {
 
         let first = InsertDownload {
             order: 2,
+            priority: Default::default(),
+            start_at: None,
             title: "first",
             module_id: &module_id,
             path: "path",
             url: None,
+            cover_link: None,
             status: "Finished".into(),
         };
 
         let second = InsertDownload {
             order: 1,
+            priority: Default::default(),
+            start_at: None,
             title: "second",
             module_id: &module_id,
             path: "path",
             url: None,
+            cover_link: None,
             status: "Finished".into(),
         };
 
@@ -331,4 +467,34 @@ mod tests {
         assert_eq!(vec[1].order, 2);
         assert_eq!(vec[1].title, "first");
     }
+
+    #[test]
+    fn delete_test() {
+        let mut db = connection();
+
+        let module = crate::module::insert_pk(
+            &mut db,
+            crate::module::InsertModule {
+                uuid: &Default::default(),
+                name: "Default",
+            },
+        )
+        .unwrap();
+
+        let info = setup_info(3);
+        let insert = crate::downloads::insert_info(&mut db, module, &info).unwrap();
+
+        let count = |table: &str| {
+            db.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .unwrap()
+        };
+        assert_eq!(count("download_chapters"), 3);
+
+        assert_eq!(delete(&db, insert.pk).unwrap(), 1);
+        assert_eq!(count("downloads"), 0);
+        assert_eq!(count("download_chapters"), 0);
+        assert_eq!(count("download_chapter_images"), 0);
+    }
 }
@@ -10,7 +10,7 @@ pub mod downloads;
 pub mod module;
 
 pub use channel::{channel, Channel, DbMsg, Sender};
-pub use database::Database;
+pub use database::{Database, DEFAULT_READER};
 pub use query::load_download_join;
 pub use schema::{setup_schema, setup_schema_version};
 
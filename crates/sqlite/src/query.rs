@@ -29,7 +29,8 @@ pub struct DownloadChapterImageJoin {
     image: DownloadChapterImage,
 }
 
-pub fn load_download_join(conn: &Connection) -> Result<Vec<DownloadJoin>, Error> {
+/// Load every download, chapters have the read progress of `reader`.
+pub fn load_download_join(conn: &Connection, reader: &str) -> Result<Vec<DownloadJoin>, Error> {
     let downloads = crate::downloads::load(conn)?;
     let mut download_index_map = HashMap::new();
 
@@ -43,7 +44,7 @@ pub fn load_download_join(conn: &Connection) -> Result<Vec<DownloadJoin>, Error>
     }
 
     let mut chapter_index_map = HashMap::new();
-    let chapters = crate::download_chapters::load(conn)?;
+    let chapters = crate::download_chapters::load(conn, reader)?;
 
     for (download_id, it) in chapters {
         for (i, ch) in it.iter().enumerate() {
@@ -102,10 +103,11 @@ pub struct DownloadChapterImageInfoJoin {
 pub fn load_download_info_join(
     conn: &Connection,
     module_map: ArcMadoModuleMap,
+    reader: &str,
 ) -> Result<Vec<DownloadInfoJoin>, Error> {
     let module = crate::module::load_map(conn)?;
 
-    let joins = load_download_join(conn)?;
+    let joins = load_download_join(conn, reader)?;
     let mut downloads = Vec::new();
 
     for join in joins {
@@ -123,6 +125,7 @@ pub fn load_download_info_join(
                 let pk = chapter.chapter.pk;
                 let images = chapter.images;
                 let chapter = chapter.chapter;
+                let progress = chapter.progress();
                 let chapter = Arc::new(DownloadChapterInfo::new(
                     module.clone(),
                     chapter.chapter_id,
@@ -130,6 +133,7 @@ pub fn load_download_info_join(
                     chapter.path,
                     chapter.status.into(),
                 ));
+                chapter.set_progress(progress);
 
                 let images: Vec<_> = images
                     .into_iter()
@@ -163,7 +167,7 @@ pub fn load_download_info_join(
 
         let chapters: Vec<_> = chapters_join.iter().map(|it| it.chapter.clone()).collect();
 
-        let info = Arc::new(DownloadInfo::new(
+        let info = DownloadInfo::new(
             download.order,
             module.clone(),
             download.title,
@@ -171,7 +175,11 @@ pub fn load_download_info_join(
             download.path,
             download.url,
             download.status.into(),
-        ));
+        )
+        .with_cover_link(download.cover_link);
+        let info = Arc::new(info);
+        info.set_priority(download.priority);
+        info.set_start_at(download.start_at);
 
         let join = DownloadInfoJoin {
             pk: dl_pk,
@@ -234,7 +242,7 @@ mod tests {
 
         assert_eq!(count, CHAPTER_LENGTH as i64);
 
-        let downloads = load_download_join(&db).unwrap();
+        let downloads = load_download_join(&db, crate::DEFAULT_READER).unwrap();
         assert_eq!(downloads.len(), 1);
         assert_eq!(downloads[0].chapters.len(), CHAPTER_LENGTH as usize);
     }
@@ -4,7 +4,9 @@
 use rusqlite::{Connection, Error};
 
 type SchemaFn = fn(&rusqlite::Connection) -> Result<(), rusqlite::Error>;
-pub const SCHEMA_FUNCTION: [SchemaFn; 3] = [v1_schema, v2_schema, v3_schema];
+pub const SCHEMA_FUNCTION: [SchemaFn; 7] = [
+    v1_schema, v2_schema, v3_schema, v4_schema, v5_schema, v6_schema, v7_schema,
+];
 
 fn schema_function_with_index() -> impl Iterator<Item = (i64, SchemaFn)> {
     SCHEMA_FUNCTION
@@ -154,6 +156,42 @@ fn v3_add_index_on_donwload_to_status() -> &'static str {
     v2_download_status_index()
 }
 
+fn v4_add_priority_to_downloads() -> &'static str {
+    r"
+        ALTER TABLE downloads ADD COLUMN priority TEXT NOT NULL DEFAULT 'Normal';
+    "
+}
+
+fn v5_add_start_at_to_downloads() -> &'static str {
+    r"
+        ALTER TABLE downloads ADD COLUMN start_at INTEGER;
+    "
+}
+
+fn v6_download_chapter_progress() -> &'static str {
+    r#"
+        CREATE TABLE download_chapter_progress (
+            download_chapter_id INTEGER NOT NULL,
+            reader TEXT NOT NULL,
+            read INTEGER NOT NULL DEFAULT 0,
+            last_page INTEGER NOT NULL DEFAULT 0,
+            updated_at INTEGER,
+
+            PRIMARY KEY (download_chapter_id, reader),
+            FOREIGN KEY (download_chapter_id)
+                REFERENCES download_chapters(id)
+                ON DELETE CASCADE
+                ON UPDATE CASCADE
+        );
+    "#
+}
+
+fn v7_add_cover_link_to_downloads() -> &'static str {
+    r"
+        ALTER TABLE downloads ADD COLUMN cover_link TEXT;
+    "
+}
+
 fn insert_migration_version(conn: &Connection, version: i64) -> Result<usize, Error> {
     conn.execute("INSERT INTO __migration (version) VALUES (?)", [version])
 }
@@ -191,6 +229,38 @@ fn v3_schema(conn: &Connection) -> Result<(), Error> {
     Ok(())
 }
 
+fn v4_schema(conn: &Connection) -> Result<(), Error> {
+    conn.execute(v4_add_priority_to_downloads(), []).unwrap();
+
+    insert_migration_version(conn, 4)?;
+
+    Ok(())
+}
+
+fn v5_schema(conn: &Connection) -> Result<(), Error> {
+    conn.execute(v5_add_start_at_to_downloads(), []).unwrap();
+
+    insert_migration_version(conn, 5)?;
+
+    Ok(())
+}
+
+fn v6_schema(conn: &Connection) -> Result<(), Error> {
+    conn.execute(v6_download_chapter_progress(), []).unwrap();
+
+    insert_migration_version(conn, 6)?;
+
+    Ok(())
+}
+
+fn v7_schema(conn: &Connection) -> Result<(), Error> {
+    conn.execute(v7_add_cover_link_to_downloads(), []).unwrap();
+
+    insert_migration_version(conn, 7)?;
+
+    Ok(())
+}
+
 pub fn setup_schema_version(conn: &Connection, version: i64) -> Result<(), Error> {
     conn.execute("PRAGMA foreign_keys = ON;", []).unwrap();
     create_migration(conn)?;
@@ -290,7 +360,10 @@ mod tests {
         )
         .unwrap();
 
-        v3_schema(&conn).unwrap();
+        // loading expects the latest schema.
+        for schema in &SCHEMA_FUNCTION[2..] {
+            schema(&conn).unwrap();
+        }
 
         let it = crate::downloads::load(&conn).unwrap();
         assert_eq!(it.len(), 1);
@@ -299,8 +372,11 @@ mod tests {
         assert_eq!(it[0].path, "path");
         assert_eq!(it[0].status, "Finished".into());
         assert_eq!(it[0].order, 1);
+        assert_eq!(it[0].priority, mado_engine::DownloadPriority::Normal);
+        assert_eq!(it[0].start_at, None);
 
-        let it = crate::download_chapters::load(&conn).unwrap();
+        let it = crate::download_chapters::load(&conn, crate::DEFAULT_READER).unwrap();
         assert_eq!(it.len(), 1);
+        assert!(!it[&crate::downloads::DownloadPK::new(1)][0].read);
     }
 }
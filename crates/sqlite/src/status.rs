@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use mado_engine::{DownloadProgressStatus, DownloadResumedStatus};
+use mado_engine::{core::ClassifiedError, DownloadProgressStatus, DownloadResumedStatus};
 use rusqlite::{
     types::{FromSql, ToSqlOutput, Value},
     ToSql,
@@ -10,8 +10,9 @@ use rusqlite::{
 pub enum DownloadStatus {
     Resumed,
     Paused,
-    Error(String),
+    Error(ClassifiedError),
     Finished,
+    FinishedWithErrors(usize),
 }
 
 impl DownloadStatus {
@@ -23,7 +24,7 @@ impl DownloadStatus {
         Self::Resumed
     }
 
-    pub fn error<S: Into<String>>(error: S) -> Self {
+    pub fn error<E: Into<ClassifiedError>>(error: E) -> Self {
         Self::Error(error.into())
     }
 
@@ -32,7 +33,7 @@ impl DownloadStatus {
     }
 
     pub fn error_parse<S: std::fmt::Display>(error: S) -> Self {
-        Self::Error(format!("cannot parse status: {}", error))
+        Self::error(format!("cannot parse status: {}", error))
     }
 }
 
@@ -52,16 +53,19 @@ impl From<&str> for DownloadStatus {
                 let last = last.strip_suffix(')')?;
 
                 if first == "Error" {
-                    Some(DownloadProgressStatus::Error(last.to_string()))
+                    // statuses saved before errors were classified only have the message.
+                    let error =
+                        serde_json::from_str(last).unwrap_or_else(|_| ClassifiedError::from(last));
+
+                    Some(Self::Error(error))
+                } else if first == "FinishedWithErrors" {
+                    last.parse().ok().map(Self::FinishedWithErrors)
                 } else {
                     None
                 }
             };
 
-            match fun() {
-                Some(v) => Self::from(mado_engine::DownloadStatus::InProgress(v)),
-                None => Self::error_parse(s),
-            }
+            fun().unwrap_or_else(|| Self::error_parse(s))
         }
     }
 }
@@ -78,9 +82,10 @@ impl From<&mado_engine::DownloadStatus> for DownloadStatus {
             mado_engine::DownloadStatus::InProgress(v) => match v {
                 DownloadProgressStatus::Resumed(_) => Self::Resumed,
                 DownloadProgressStatus::Paused => Self::Paused,
-                DownloadProgressStatus::Error(v) => Self::Error(v.to_string()),
+                DownloadProgressStatus::Error(v) => Self::Error(v.clone()),
             },
             mado_engine::DownloadStatus::Finished => Self::Finished,
+            mado_engine::DownloadStatus::FinishedWithErrors(v) => Self::FinishedWithErrors(*v),
         }
     }
 }
@@ -100,6 +105,9 @@ impl From<DownloadStatus> for mado_engine::DownloadStatus {
             DownloadStatus::Paused => mado_engine::DownloadStatus::paused(),
             DownloadStatus::Error(v) => mado_engine::DownloadStatus::error(v),
             DownloadStatus::Finished => mado_engine::DownloadStatus::finished(),
+            DownloadStatus::FinishedWithErrors(v) => {
+                mado_engine::DownloadStatus::finished_with_errors(v)
+            }
         }
     }
 }
@@ -109,8 +117,13 @@ impl Display for DownloadStatus {
         match self {
             DownloadStatus::Resumed => write!(f, "Resumed"),
             DownloadStatus::Paused => write!(f, "Paused"),
-            DownloadStatus::Error(v) => write!(f, "Error({})", v),
+            DownloadStatus::Error(v) => write!(
+                f,
+                "Error({})",
+                serde_json::to_string(v).map_err(|_| std::fmt::Error)?
+            ),
             DownloadStatus::Finished => write!(f, "Finished"),
+            DownloadStatus::FinishedWithErrors(v) => write!(f, "FinishedWithErrors({})", v),
         }
     }
 }
@@ -145,6 +158,16 @@ mod tests {
 
         assert_eq!(DownloadStatus::from("Finished"), DownloadStatus::Finished);
 
+        assert_eq!(
+            DownloadStatus::from("FinishedWithErrors(2)"),
+            DownloadStatus::FinishedWithErrors(2)
+        );
+
+        assert_eq!(
+            DownloadStatus::from("FinishedWithErrors(a)"),
+            DownloadStatus::error_parse("FinishedWithErrors(a)")
+        );
+
         assert_ne!(DownloadStatus::from("Resumed("), DownloadStatus::resumed());
 
         assert_eq!(
@@ -180,13 +203,35 @@ mod tests {
 
     #[test]
     fn to_str() {
-        assert_eq!(DownloadStatus::from("Error(Foo)").to_string(), "Error(Foo)");
+        assert_eq!(
+            DownloadStatus::from("Error(Foo)").to_string(),
+            r#"Error({"kind":"other","message":"Foo"})"#
+        );
 
         assert_eq!(DownloadStatus::from("Resumed").to_string(), "Resumed");
 
         assert_eq!(DownloadStatus::paused().to_string(), "Paused");
 
         assert_eq!(DownloadStatus::finished().to_string(), "Finished");
+
+        assert_eq!(
+            DownloadStatus::FinishedWithErrors(2).to_string(),
+            "FinishedWithErrors(2)"
+        );
+    }
+
+    #[test]
+    fn error_round_trip() {
+        let status = DownloadStatus::error(mado_engine::core::Error::RateLimited {
+            url: "https://localhost".to_string(),
+            retry_after: Some(std::time::Duration::from_secs(30)),
+        });
+
+        assert_eq!(
+            status.to_string(),
+            r#"Error({"kind":"rateLimited","retryAfter":30,"message":"Rate limited by https://localhost"})"#
+        );
+        assert_eq!(DownloadStatus::from(status.to_string()), status);
     }
 
     #[test]